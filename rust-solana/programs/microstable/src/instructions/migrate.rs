@@ -0,0 +1,54 @@
+// states created before the mint limits were added are too short to load as `State`. migrate_state
+// grows the account in place, the authority paying the extra rent, and zeroes the new fields so the
+// limits start out off until set_mint_limits
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, Transfer};
+use anchor_lang::Discriminator;
+
+use crate::{ErrorCode, State};
+
+// discriminator, min_collat_ratio, weth_mint then shusd_mint come before the authority
+const AUTHORITY_OFFSET: usize = 8 + 8 + 32 + 32;
+
+#[derive(Accounts)]
+pub struct MigrateState<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: deserializing would fail on the old layout, the owner, seeds, discriminator and
+    /// authority are checked by hand
+    #[account(
+        mut,
+        seeds = [b"state".as_ref()],
+        bump,
+        owner = crate::ID
+    )]
+    pub state: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn migrate_state(ctx: Context<MigrateState>) -> Result<()> {
+    let state = ctx.accounts.state.to_account_info();
+    let space = 8 + State::INIT_SPACE;
+    {
+        let data = state.try_borrow_data()?;
+        require!(data.len() >= AUTHORITY_OFFSET + 32 && data[..8] == State::DISCRIMINATOR, ErrorCode::InvalidState);
+        require!(data.len() < space, ErrorCode::AlreadyInitialized);
+        require!(
+            data[AUTHORITY_OFFSET..AUTHORITY_OFFSET + 32] == ctx.accounts.authority.key().to_bytes(),
+            ErrorCode::Unauthorized
+        );
+    }
+
+    let rent = Rent::get()?.minimum_balance(space).saturating_sub(state.lamports());
+    if rent > 0 {
+        let accounts = Transfer {
+            from: ctx.accounts.authority.to_account_info(),
+            to: state.clone(),
+        };
+        system_program::transfer(CpiContext::new(ctx.accounts.system_program.to_account_info(), accounts), rent)?;
+    }
+    state.realloc(space, true)?;
+    Ok(())
+}
@@ -4,4 +4,11 @@ pub use initialize::*;
 
 pub mod transfer;
 
-pub use transfer::*;
\ No newline at end of file
+pub use transfer::*;
+
+pub mod vault;
+
+pub use vault::*;
+pub mod migrate;
+
+pub use migrate::*;
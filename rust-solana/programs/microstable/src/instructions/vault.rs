@@ -0,0 +1,584 @@
+// positions held as nfts. open_vault mints a token-2022 nft (0 decimals, supply of 1, mint authority
+// dropped right after) and a vault_state pda seeded by the nft mint rather than the depositor, so
+// whoever holds the token controls the position and one wallet can hold any number of vaults
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_2022::{spl_token_2022::instruction::AuthorityType, Token2022},
+    token_interface::{self, Mint, MintTo, SetAuthority, TokenAccount, TokenInterface, TransferChecked},
+};
+use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
+
+use crate::{position_ratio, transfer_tokens, ErrorCode, State};
+
+#[account]
+#[derive(InitSpace)]
+pub struct VaultState {
+    pub nft_mint: Pubkey,
+    pub amount_deposited: u64,
+    pub amount_minted: u64,
+    pub bump: u8,
+}
+
+// the bookkeeping of the instructions below, apart from the cpis
+impl VaultState {
+    pub fn add_collateral(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        self.amount_deposited = self.amount_deposited.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    pub fn remove_collateral(&mut self, amount: u64) -> Result<()> {
+        self.amount_deposited = self.amount_deposited.checked_sub(amount).ok_or(ErrorCode::InvalidAmount)?;
+        Ok(())
+    }
+
+    pub fn add_debt(&mut self, amount: u64) -> Result<()> {
+        self.amount_minted = self.amount_minted.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    pub fn repay(&mut self, amount: u64) -> Result<()> {
+        self.amount_minted = self.amount_minted.checked_sub(amount).ok_or(ErrorCode::InvalidAmount)?;
+        Ok(())
+    }
+
+    // empties the vault for a liquidation, returns the debt and the collateral it held
+    pub fn clear(&mut self) -> (u64, u64) {
+        let cleared = (self.amount_minted, self.amount_deposited);
+        self.amount_minted = 0;
+        self.amount_deposited = 0;
+        cleared
+    }
+}
+
+#[derive(Accounts)]
+pub struct OpenVault<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    // a fresh keypair, it signs the transaction once and the mint can never issue a second token
+    #[account(
+        init,
+        payer = owner,
+        mint::decimals = 0,
+        mint::authority = vault_state,
+        mint::token_program = nft_token_program,
+    )]
+    pub nft_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + VaultState::INIT_SPACE,
+        seeds = [b"vault".as_ref(), nft_mint.key().as_ref()],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        init,
+        payer = owner,
+        associated_token::mint = nft_mint,
+        associated_token::authority = owner,
+        associated_token::token_program = nft_token_program,
+    )]
+    pub owner_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = owner,
+        associated_token::mint = weth_mint,
+        associated_token::authority = vault_state,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_weth: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"state".as_ref()],
+        bump = state.bump
+    )]
+    pub state: Account<'info, State>,
+
+    #[account(
+        mint::token_program = token_program,
+        constraint = weth_mint.key() == state.weth_mint
+    )]
+    pub weth_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub nft_token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositVault<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), vault_state.nft_mint.as_ref()],
+        bump = vault_state.bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    // holding the nft is what makes the signer the owner, the account doesn't have to be an ata
+    #[account(
+        token::mint = vault_state.nft_mint,
+        token::authority = owner,
+        token::token_program = nft_token_program,
+        constraint = owner_nft_account.amount == 1 @ ErrorCode::NotVaultOwner,
+    )]
+    pub owner_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = weth_mint,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program,
+    )]
+    pub owner_weth_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = weth_mint,
+        associated_token::authority = vault_state,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_weth: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"state".as_ref()],
+        bump = state.bump
+    )]
+    pub state: Account<'info, State>,
+
+    #[account(
+        mint::token_program = token_program,
+        constraint = weth_mint.key() == state.weth_mint
+    )]
+    pub weth_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub nft_token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct MintVault<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), vault_state.nft_mint.as_ref()],
+        bump = vault_state.bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        token::mint = vault_state.nft_mint,
+        token::authority = owner,
+        token::token_program = nft_token_program,
+        constraint = owner_nft_account.amount == 1 @ ErrorCode::NotVaultOwner,
+    )]
+    pub owner_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = shusd_mint,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program,
+    )]
+    pub owner_shusd_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"mint_authority".as_ref()],
+        bump
+    )]
+    pub mint_authority: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state".as_ref()],
+        bump = state.bump
+    )]
+    pub state: Account<'info, State>,
+
+    pub price_feed: Account<'info, PriceUpdateV2>,
+
+    #[account(
+        mut,
+        mint::token_program = token_program,
+        constraint = shusd_mint.key() == state.shusd_mint
+    )]
+    pub shusd_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub nft_token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BurnVault<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), vault_state.nft_mint.as_ref()],
+        bump = vault_state.bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        token::mint = vault_state.nft_mint,
+        token::authority = owner,
+        token::token_program = nft_token_program,
+        constraint = owner_nft_account.amount == 1 @ ErrorCode::NotVaultOwner,
+    )]
+    pub owner_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = shusd_mint,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program,
+    )]
+    pub owner_shusd_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"state".as_ref()],
+        bump = state.bump
+    )]
+    pub state: Account<'info, State>,
+
+    #[account(
+        mut,
+        mint::token_program = token_program,
+        constraint = shusd_mint.key() == state.shusd_mint
+    )]
+    pub shusd_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub nft_token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawVault<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), vault_state.nft_mint.as_ref()],
+        bump = vault_state.bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        token::mint = vault_state.nft_mint,
+        token::authority = owner,
+        token::token_program = nft_token_program,
+        constraint = owner_nft_account.amount == 1 @ ErrorCode::NotVaultOwner,
+    )]
+    pub owner_nft_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = weth_mint,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program,
+    )]
+    pub owner_weth_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = weth_mint,
+        associated_token::authority = vault_state,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_weth: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"state".as_ref()],
+        bump = state.bump
+    )]
+    pub state: Account<'info, State>,
+
+    pub price_feed: Account<'info, PriceUpdateV2>,
+
+    #[account(
+        mint::token_program = token_program,
+        constraint = weth_mint.key() == state.weth_mint
+    )]
+    pub weth_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub nft_token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+// anyone can liquidate a vault, they burn its whole debt from their own shusd and take the collateral
+#[derive(Accounts)]
+pub struct LiquidateVault<'info> {
+    #[account(mut)]
+    pub liquidator: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault".as_ref(), vault_state.nft_mint.as_ref()],
+        bump = vault_state.bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        associated_token::mint = shusd_mint,
+        associated_token::authority = liquidator,
+        associated_token::token_program = token_program,
+    )]
+    pub liquidator_shusd_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = liquidator,
+        associated_token::mint = weth_mint,
+        associated_token::authority = liquidator,
+        associated_token::token_program = token_program,
+    )]
+    pub liquidator_weth_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = weth_mint,
+        associated_token::authority = vault_state,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_weth: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"state".as_ref()],
+        bump = state.bump
+    )]
+    pub state: Account<'info, State>,
+
+    pub price_feed: Account<'info, PriceUpdateV2>,
+
+    #[account(
+        mut,
+        mint::token_program = token_program,
+        constraint = shusd_mint.key() == state.shusd_mint
+    )]
+    pub shusd_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mint::token_program = token_program,
+        constraint = weth_mint.key() == state.weth_mint
+    )]
+    pub weth_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn open_vault(ctx: Context<OpenVault>) -> Result<()> {
+    let nft_mint = ctx.accounts.nft_mint.key();
+    ctx.accounts.vault_state.set_inner(VaultState {
+        nft_mint,
+        amount_deposited: 0,
+        amount_minted: 0,
+        bump: ctx.bumps.vault_state,
+    });
+
+    let seeds = [b"vault".as_ref(), nft_mint.as_ref(), &[ctx.bumps.vault_state]];
+    let signer = &[&seeds[..]];
+
+    let accounts = MintTo {
+        mint: ctx.accounts.nft_mint.to_account_info(),
+        to: ctx.accounts.owner_nft_account.to_account_info(),
+        authority: ctx.accounts.vault_state.to_account_info(),
+    };
+    let cpi_context = CpiContext::new_with_signer(ctx.accounts.nft_token_program.to_account_info(), accounts, signer);
+    token_interface::mint_to(cpi_context, 1)?;
+
+    // with the mint authority gone the supply stays at exactly one
+    let accounts = SetAuthority {
+        current_authority: ctx.accounts.vault_state.to_account_info(),
+        account_or_mint: ctx.accounts.nft_mint.to_account_info(),
+    };
+    let cpi_context = CpiContext::new_with_signer(ctx.accounts.nft_token_program.to_account_info(), accounts, signer);
+    token_interface::set_authority(cpi_context, AuthorityType::MintTokens, None)?;
+
+    Ok(())
+}
+
+pub fn deposit_vault(ctx: Context<DepositVault>, amount: u64) -> Result<()> {
+    ctx.accounts.vault_state.add_collateral(amount)?;
+    transfer_tokens(
+        &ctx.accounts.owner_weth_account,
+        &ctx.accounts.vault_weth,
+        &amount,
+        &ctx.accounts.weth_mint,
+        &ctx.accounts.owner,
+        &ctx.accounts.token_program,
+    )
+}
+
+pub fn mint_vault(ctx: Context<MintVault>, amount: u64) -> Result<()> {
+    let vault_state = &mut ctx.accounts.vault_state;
+    vault_state.add_debt(amount)?;
+
+    let collateral_ratio = position_ratio(&ctx.accounts.price_feed, vault_state.amount_deposited, vault_state.amount_minted)?;
+    require!(
+        collateral_ratio >= ctx.accounts.state.min_collat_ratio as u128,
+        ErrorCode::CollateralRatioTooLow
+    );
+
+    let clock = Clock::get()?;
+    ctx.accounts.state.consume_mint_capacity(amount, clock.unix_timestamp, clock.slot)?;
+
+    let seeds = [b"mint_authority".as_ref(), &[ctx.bumps.mint_authority]];
+    let signer = &[&seeds[..]];
+
+    let accounts = MintTo {
+        mint: ctx.accounts.shusd_mint.to_account_info(),
+        to: ctx.accounts.owner_shusd_account.to_account_info(),
+        authority: ctx.accounts.mint_authority.to_account_info(),
+    };
+    let cpi_context = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), accounts, signer);
+    token_interface::mint_to(cpi_context, amount)?;
+
+    Ok(())
+}
+
+pub fn burn_vault(ctx: Context<BurnVault>, amount: u64) -> Result<()> {
+    ctx.accounts.vault_state.repay(amount)?;
+    ctx.accounts.state.release_mint_capacity(amount, Clock::get()?.unix_timestamp);
+
+    let accounts = token_interface::Burn {
+        mint: ctx.accounts.shusd_mint.to_account_info(),
+        from: ctx.accounts.owner_shusd_account.to_account_info(),
+        authority: ctx.accounts.owner.to_account_info(),
+    };
+    let cpi_context = CpiContext::new(ctx.accounts.token_program.to_account_info(), accounts);
+    token_interface::burn(cpi_context, amount)?;
+
+    Ok(())
+}
+
+pub fn withdraw_vault(ctx: Context<WithdrawVault>, amount: u64) -> Result<()> {
+    let vault_state = &mut ctx.accounts.vault_state;
+    vault_state.remove_collateral(amount)?;
+
+    let collateral_ratio = position_ratio(&ctx.accounts.price_feed, vault_state.amount_deposited, vault_state.amount_minted)?;
+    require!(
+        collateral_ratio >= ctx.accounts.state.min_collat_ratio as u128,
+        ErrorCode::CollateralRatioTooLow
+    );
+
+    // the vault stays open with whatever is left, the nft can be used again
+    let nft_mint = vault_state.nft_mint;
+    let seeds = [b"vault".as_ref(), nft_mint.as_ref(), &[vault_state.bump]];
+    let signer = &[&seeds[..]];
+
+    let accounts = TransferChecked {
+        from: ctx.accounts.vault_weth.to_account_info(),
+        mint: ctx.accounts.weth_mint.to_account_info(),
+        to: ctx.accounts.owner_weth_account.to_account_info(),
+        authority: ctx.accounts.vault_state.to_account_info(),
+    };
+    let cpi_context = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), accounts, signer);
+    token_interface::transfer_checked(cpi_context, amount, ctx.accounts.weth_mint.decimals)?;
+
+    Ok(())
+}
+
+pub fn liquidate_vault(ctx: Context<LiquidateVault>) -> Result<()> {
+    let vault_state = &ctx.accounts.vault_state;
+    let collateral_ratio = position_ratio(&ctx.accounts.price_feed, vault_state.amount_deposited, vault_state.amount_minted)?;
+    require!(
+        collateral_ratio < ctx.accounts.state.min_collat_ratio as u128,
+        ErrorCode::CannotLiquidate
+    );
+
+    // the holder keeps an empty vault
+    let (amount_to_burn, collateral_to_transfer) = ctx.accounts.vault_state.clear();
+    ctx.accounts.state.release_mint_capacity(amount_to_burn, Clock::get()?.unix_timestamp);
+
+    let accounts = token_interface::Burn {
+        mint: ctx.accounts.shusd_mint.to_account_info(),
+        from: ctx.accounts.liquidator_shusd_account.to_account_info(),
+        authority: ctx.accounts.liquidator.to_account_info(),
+    };
+    let cpi_context = CpiContext::new(ctx.accounts.token_program.to_account_info(), accounts);
+    token_interface::burn(cpi_context, amount_to_burn)?;
+
+    let nft_mint = ctx.accounts.vault_state.nft_mint;
+    let seeds = [b"vault".as_ref(), nft_mint.as_ref(), &[ctx.accounts.vault_state.bump]];
+    let signer = &[&seeds[..]];
+
+    let accounts = TransferChecked {
+        from: ctx.accounts.vault_weth.to_account_info(),
+        mint: ctx.accounts.weth_mint.to_account_info(),
+        to: ctx.accounts.liquidator_weth_account.to_account_info(),
+        authority: ctx.accounts.vault_state.to_account_info(),
+    };
+    let cpi_context = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), accounts, signer);
+    token_interface::transfer_checked(cpi_context, collateral_to_transfer, ctx.accounts.weth_mint.decimals)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ratio_at;
+
+    fn vault() -> VaultState {
+        VaultState { nft_mint: Pubkey::default(), amount_deposited: 0, amount_minted: 0, bump: 0 }
+    }
+
+    #[test]
+    fn vault_bookkeeping_is_checked() {
+        let mut vault = vault();
+        assert!(vault.add_collateral(0).is_err());
+        vault.add_collateral(300).unwrap();
+        assert!(vault.add_collateral(u64::MAX).is_err());
+        vault.add_debt(100).unwrap();
+        assert!(vault.add_debt(u64::MAX).is_err());
+        assert_eq!((vault.amount_deposited, vault.amount_minted), (300, 100));
+
+        // neither more than the debt nor more than the collateral comes back out
+        assert!(vault.repay(101).is_err());
+        vault.repay(40).unwrap();
+        assert!(vault.remove_collateral(301).is_err());
+        vault.remove_collateral(100).unwrap();
+        assert_eq!((vault.amount_deposited, vault.amount_minted), (200, 60));
+
+        assert_eq!(vault.clear(), (60, 200));
+        assert_eq!((vault.amount_deposited, vault.amount_minted), (0, 0));
+    }
+
+    #[test]
+    fn vault_ratio_follows_the_price() {
+        // $2000 with pyth's -8 exponent
+        let price = 2000_00000000;
+        assert_eq!(ratio_at(price, -8, 3, 0).unwrap(), u128::MAX);
+        let ratio = ratio_at(price, -8, 3, 2).unwrap();
+        assert_eq!(ratio, 3 * 2000_00000000 * 100_000_000 / 2);
+        // halving the price halves the ratio, as does doubling the debt
+        assert_eq!(ratio_at(price / 2, -8, 3, 2).unwrap(), ratio / 2);
+        assert_eq!(ratio_at(price, -8, 3, 4).unwrap(), ratio / 2);
+        // the collateral value overflows rather than wrapping
+        assert!(ratio_at(price, -8, u64::MAX, 1).is_err());
+    }
+}
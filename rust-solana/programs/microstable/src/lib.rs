@@ -56,11 +56,54 @@ pub mod manager {
         Ok(())
     }
 
-    pub fn burn(ctx: Context<Liquidate>, amount: u64) -> Result<()> {
-        burn_shusd(ctx, amount)?;
+    pub fn burn(mut ctx: Context<Liquidate>, amount: u64) -> Result<()> {
+        burn_shusd(&mut ctx, amount)?;
         Ok(())
     }
 
+    // caps net new shusd per window and per slot, a limit of zero turns that check off
+    pub fn set_mint_limits(ctx: Context<SetMintLimits>, window_limit: u64, window: i64, slot_limit: u64) -> Result<()> {
+        require!(window >= 0, ErrorCode::InvalidAmount);
+        let clock = Clock::get()?;
+        let state = &mut ctx.accounts.state;
+        state.mint_window_limit = window_limit;
+        state.mint_window = window;
+        state.mint_slot_limit = slot_limit;
+        state.mint_available = window_limit;
+        state.mint_last_update = clock.unix_timestamp;
+        Ok(())
+    }
+
+    // grows a state account created before the mint limits, which start out off
+    pub fn migrate_state(ctx: Context<MigrateState>) -> Result<()> {
+        instructions::migrate_state(ctx)
+    }
+
+    // positions held as nfts, see instructions/vault.rs
+    pub fn open_vault(ctx: Context<OpenVault>) -> Result<()> {
+        instructions::open_vault(ctx)
+    }
+
+    pub fn deposit_vault(ctx: Context<DepositVault>, amount: u64) -> Result<()> {
+        instructions::deposit_vault(ctx, amount)
+    }
+
+    pub fn mint_vault(ctx: Context<MintVault>, amount: u64) -> Result<()> {
+        instructions::mint_vault(ctx, amount)
+    }
+
+    pub fn burn_vault(ctx: Context<BurnVault>, amount: u64) -> Result<()> {
+        instructions::burn_vault(ctx, amount)
+    }
+
+    pub fn withdraw_vault(ctx: Context<WithdrawVault>, amount: u64) -> Result<()> {
+        instructions::withdraw_vault(ctx, amount)
+    }
+
+    pub fn liquidate_vault(ctx: Context<LiquidateVault>) -> Result<()> {
+        instructions::liquidate_vault(ctx)
+    }
+
 }
 
 #[derive(Accounts)]
@@ -334,6 +377,19 @@ pub struct Liquidate<'info> {
     pub associated_token_program: Program<'info, AssociatedToken>,
 }
 
+#[derive(Accounts)]
+pub struct SetMintLimits<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state".as_ref()],
+        bump = state.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub state: Account<'info, State>,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct DepositState {
@@ -352,6 +408,54 @@ pub struct State {
     pub shusd_mint: Pubkey,
     pub authority: Pubkey,  // The admin who can update parameters
     pub bump: u8,
+    // mint rate limiting, see consume_mint_capacity. appended later, older states need migrate_state
+    pub mint_window_limit: u64,
+    pub mint_window: i64,
+    pub mint_slot_limit: u64,
+    pub mint_available: u64,
+    pub mint_last_update: i64,
+    pub mint_last_slot: u64,
+    pub minted_in_slot: u64,
+}
+
+impl State {
+    // window allowance refilled linearly since the last update, u64::MAX when there is no window limit
+    fn window_available(&self, now: i64) -> u64 {
+        if self.mint_window_limit == 0 {
+            return u64::MAX;
+        }
+        let elapsed = now.saturating_sub(self.mint_last_update).max(0) as u128;
+        if self.mint_window == 0 || elapsed >= self.mint_window as u128 {
+            return self.mint_window_limit;
+        }
+        let refill = (self.mint_window_limit as u128) * elapsed / (self.mint_window as u128);
+        (self.mint_available as u128 + refill).min(self.mint_window_limit as u128) as u64
+    }
+
+    pub fn consume_mint_capacity(&mut self, amount: u64, now: i64, slot: u64) -> Result<()> {
+        let window_available = self.window_available(now);
+        require!(amount <= window_available, ErrorCode::MintRateLimited);
+        if self.mint_slot_limit != 0 {
+            let minted = if self.mint_last_slot == slot { self.minted_in_slot } else { 0 };
+            let minted = minted.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+            require!(minted <= self.mint_slot_limit, ErrorCode::MintRateLimited);
+            self.minted_in_slot = minted;
+            self.mint_last_slot = slot;
+        }
+        if self.mint_window_limit != 0 {
+            self.mint_available = window_available - amount;
+            self.mint_last_update = now;
+        }
+        Ok(())
+    }
+
+    pub fn release_mint_capacity(&mut self, amount: u64, now: i64) {
+        if self.mint_window_limit == 0 {
+            return;
+        }
+        self.mint_available = self.window_available(now).saturating_add(amount).min(self.mint_window_limit);
+        self.mint_last_update = now;
+    }
 }
 fn deposit_weth(ctx: Context<DepositWeth>, amount: u64) -> Result<()> {
     // we will transfer tokens from the signer to the vault
@@ -432,6 +536,9 @@ fn mint_to_depositor(ctx: Context<MintShusd>, amount: u64) -> Result<()> {
         ErrorCode::CollateralRatioTooLow
     );
 
+    let clock = Clock::get()?;
+    ctx.accounts.state.consume_mint_capacity(amount, clock.unix_timestamp, clock.slot)?;
+
     mint_shusd(ctx, amount)?;
     Ok(())
 }
@@ -465,17 +572,23 @@ fn mint_shusd(ctx: Context<MintShusd>, amount: u64) -> Result<()> {
 }
 
 fn collateral_ratio(price_feed: &PriceUpdateV2 , deposit_state: &DepositState) -> Result<u128> {
+    position_ratio(price_feed, deposit_state.amount_deposited, deposit_state.amount_minted)
+}
+
+// shared by deposit_state and vault_state positions
+pub(crate) fn position_ratio(price_feed: &PriceUpdateV2, amount_deposited: u64, amount_minted: u64) -> Result<u128> {
     let maximum_age: u64 = 30;
     let feed_id = get_feed_id_from_hex("4TQ1VVWkrYUvyQ6hMmjepwr7swvqssvLi75BiJi13Tf3")?;
     
     let weth_price = price_feed.get_price_no_older_than(&Clock::get()?, maximum_age, &feed_id)?;
-    
-    // adjust for weth_price.exponent
-    let weth_price_adjusted: u128 = (weth_price.price as u128) * 10u128.pow((-weth_price.exponent) as u32);
-    
-    let amount_deposited = deposit_state.amount_deposited;  // Assuming field is called 'amount'
-    let amount_minted = deposit_state.amount_minted;
+    ratio_at(weth_price.price, weth_price.exponent, amount_deposited, amount_minted)
+}
 
+// the ratio at a pyth price and exponent, without the feed so it can be tested
+pub(crate) fn ratio_at(price: i64, exponent: i32, amount_deposited: u64, amount_minted: u64) -> Result<u128> {
+    // adjust for the exponent
+    let weth_price_adjusted: u128 = (price as u128) * 10u128.pow((-exponent) as u32);
+    
     // If nothing minted yet, return max ratio
     if amount_minted == 0 {
         return Ok(u128::MAX);  // Changed to u128::MAX since we're working with u128
@@ -512,12 +625,19 @@ pub enum ErrorCode {
     MathOverflow,
     #[msg("Position is not eligible for liquidation")]
     CannotLiquidate,
+    #[msg("Mint rate limit exceeded")]
+    MintRateLimited,
+    #[msg("Signer does not hold the vault nft")]
+    NotVaultOwner,
+    #[msg("Account is not a state account")]
+    InvalidState,
 }
 
-fn burn_shusd(ctx: Context<Liquidate>, amount: u64) -> Result<()> {
+fn burn_shusd(ctx: &mut Context<Liquidate>, amount: u64) -> Result<()> {
     // decrease the minted amount from deposit state
     let deposit_state = &mut ctx.accounts.deposit_state;
     deposit_state.amount_minted -= amount;
+    ctx.accounts.state.release_mint_capacity(amount, Clock::get()?.unix_timestamp);
 
     let cpi_accounts = token_interface::Burn {
         mint: ctx.accounts.shusd_mint.to_account_info(),
@@ -535,7 +655,7 @@ fn burn_shusd(ctx: Context<Liquidate>, amount: u64) -> Result<()> {
     Ok(())
 }
 
-fn liquidate_user(ctx: Context<Liquidate>) -> Result<()> {
+fn liquidate_user(mut ctx: Context<Liquidate>) -> Result<()> {
     // Check if collateral ratio is below minimum
     let collateral_ratio = collateral_ratio(&ctx.accounts.price_feed, &ctx.accounts.deposit_state)?;
     
@@ -548,8 +668,8 @@ fn liquidate_user(ctx: Context<Liquidate>) -> Result<()> {
     let amount_to_burn = ctx.accounts.deposit_state.amount_minted;
     let collateral_to_transfer = ctx.accounts.deposit_state.amount_deposited;
 
-    // Burn all shUSD tokens
-    burn_shusd(&ctx, amount_to_burn)?;
+    // Burn all shUSD tokens, handing their mint capacity back
+    burn_shusd(&mut ctx, amount_to_burn)?;
 
     // Transfer all WETH to liquidator
     let seeds = [
@@ -579,4 +699,62 @@ fn liquidate_user(ctx: Context<Liquidate>) -> Result<()> {
     ctx.accounts.deposit_state.amount_deposited = 0;
 
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limited(window_limit: u64, window: i64, slot_limit: u64) -> State {
+        State {
+            min_collat_ratio: 150,
+            weth_mint: Pubkey::default(),
+            shusd_mint: Pubkey::default(),
+            authority: Pubkey::default(),
+            bump: 0,
+            mint_window_limit: window_limit,
+            mint_window: window,
+            mint_slot_limit: slot_limit,
+            mint_available: window_limit,
+            mint_last_update: 0,
+            mint_last_slot: 0,
+            minted_in_slot: 0,
+        }
+    }
+
+    #[test]
+    fn window_refills_linearly() {
+        let mut state = limited(1000, 100, 0);
+        state.consume_mint_capacity(1000, 0, 1).unwrap();
+        assert!(state.consume_mint_capacity(1, 0, 2).is_err());
+        // a quarter of the window gives back a quarter of the limit
+        assert_eq!(state.window_available(25), 250);
+        assert!(state.consume_mint_capacity(251, 25, 3).is_err());
+        state.consume_mint_capacity(250, 25, 3).unwrap();
+        assert_eq!(state.window_available(1000), 1000);
+    }
+
+    #[test]
+    fn slot_cap_resets_each_slot() {
+        let mut state = limited(0, 0, 100);
+        state.consume_mint_capacity(60, 0, 1).unwrap();
+        assert!(state.consume_mint_capacity(41, 0, 1).is_err());
+        state.consume_mint_capacity(40, 0, 1).unwrap();
+        state.consume_mint_capacity(100, 0, 2).unwrap();
+        assert_eq!(state.window_available(0), u64::MAX);
+    }
+
+    #[test]
+    fn burns_release_capacity_up_to_the_limit() {
+        let mut state = limited(1000, 100, 0);
+        state.consume_mint_capacity(800, 0, 1).unwrap();
+        state.release_mint_capacity(300, 0);
+        assert_eq!(state.window_available(0), 500);
+        state.release_mint_capacity(u64::MAX, 0);
+        assert_eq!(state.window_available(0), 1000);
+
+        // nothing to track without a window limit
+        let mut state = limited(0, 0, 0);
+        state.release_mint_capacity(300, 0);
+        assert_eq!(state.mint_available, 0);
+    }
+}
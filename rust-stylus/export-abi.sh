@@ -0,0 +1,14 @@
+#!/bin/bash
+# Writes the Solidity interface and JSON ABI of every contract to abi/
+set -e
+
+mkdir -p abi
+for feature in manager sh-usd test-oracle test-weth proxy timelock gov-token governor vault-nft psm test-usdc twap-oracle test-pool forwarder; do
+    echo "📜 Exporting $feature..."
+    cargo run -q --features export-abi,$feature > abi/$feature.sol
+    cargo run -q --features export-abi,$feature -- --json > abi/$feature.json
+done
+echo "✅ ABIs written to abi/"
+
+echo "🤓 Checking the client interfaces against the exported ABIs..."
+cargo test -q --lib --features export-abi,manager client::tests::exported
@@ -0,0 +1,16 @@
+//! Monte Carlo risk simulator of the MicroStable `Manager`.
+//!
+//! `protocol` redoes the Manager's accounting on floats, `population` opens
+//! synthetic positions, `price` draws WETH paths and `sim` runs the
+//! population through them and summarises liquidations, bad debt and
+//! collateralization across paths. Use it to size `MIN_COLLAT_RATIO` and
+//! `LIQUIDATION_PENALTY` before changing them on-chain.
+pub mod population;
+pub mod price;
+pub mod protocol;
+pub mod sim;
+
+pub use population::Population;
+pub use price::Model;
+pub use protocol::{Params, Protocol};
+pub use sim::{Outcome, Report, Scenario};
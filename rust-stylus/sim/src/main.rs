@@ -0,0 +1,111 @@
+//! `cargo run -p microstable-sim --release -- [options]`, `--help` lists them
+use std::process::ExitCode;
+use microstable_sim::price::parse_csv;
+use microstable_sim::sim::Distribution;
+use microstable_sim::{Model, Report, Scenario};
+
+const USAGE: &str = "usage: microstable-sim [options]
+  --paths <n>              price paths to simulate (1000)
+  --seed <n>               rng seed (1)
+  --model <gbm|jump|csv>   price model (gbm)
+  --csv <file>             prices to replay, last column, with --model csv
+  --price <usd>            initial WETH price (2000)
+  --steps <n>              steps per path (365)
+  --dt <years>             length of a step (1/365)
+  --drift <rate>           annual drift (0)
+  --volatility <rate>      annual volatility (0.8)
+  --jumps <n>              jumps a year, with --model jump (4)
+  --jump-mean <log>        mean log size of a jump (-0.1)
+  --jump-volatility <log>  standard deviation of a jump's log size (0.1)
+  --users <n>              positions (1000)
+  --min-ratio <ratio>      ratio users open at, lowest (1.6)
+  --max-ratio <ratio>      ratio users open at, highest (3.0)
+  --collat-ratio <ratio>   MIN_COLLAT_RATIO (1.5)
+  --penalty <share>        LIQUIDATION_PENALTY (0.05)
+  --mint-fee <share>       fee on mints (0)
+  --lag <steps>            steps before liquidators react (0)";
+
+struct Options {
+    scenario: Scenario,
+    paths: u64,
+    seed: u64,
+}
+
+fn parse(args: &[String]) -> Result<Options, String> {
+    let mut options = Options { scenario: Scenario::default(), paths: 1000, seed: 1 };
+    let (mut model, mut csv) = ("gbm".to_string(), None);
+    let (mut drift, mut volatility) = (0.0, 0.8);
+    let (mut jumps, mut jump_mean, mut jump_volatility) = (4.0, -0.1, 0.1);
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        if flag == "--help" || flag == "-h" {
+            return Err(USAGE.into());
+        }
+        let value = args.next().ok_or(format!("{flag} needs a value"))?;
+        let number = || value.parse::<f64>().map_err(|_| format!("{flag}: {value:?} is not a number"));
+        let count = || value.parse::<u64>().map_err(|_| format!("{flag}: {value:?} is not a count"));
+        let scenario = &mut options.scenario;
+        match flag.as_str() {
+            "--paths" => options.paths = count()?,
+            "--seed" => options.seed = count()?,
+            "--model" => model = value.clone(),
+            "--csv" => csv = Some(value.clone()),
+            "--price" => scenario.initial_price = number()?,
+            "--steps" => scenario.steps = count()? as usize,
+            "--dt" => scenario.dt = number()?,
+            "--drift" => drift = number()?,
+            "--volatility" => volatility = number()?,
+            "--jumps" => jumps = number()?,
+            "--jump-mean" => jump_mean = number()?,
+            "--jump-volatility" => jump_volatility = number()?,
+            "--users" => scenario.population.users = count()? as usize,
+            "--min-ratio" => scenario.population.min_ratio = number()?,
+            "--max-ratio" => scenario.population.max_ratio = number()?,
+            "--collat-ratio" => scenario.params.min_collat_ratio = number()?,
+            "--penalty" => scenario.params.liquidation_penalty = number()?,
+            "--mint-fee" => scenario.params.mint_fee = number()?,
+            "--lag" => scenario.lag = count()? as usize,
+            _ => return Err(format!("unknown option {flag}\n{USAGE}")),
+        }
+    }
+    options.scenario.model = match (model.as_str(), csv) {
+        ("gbm", _) => Model::Gbm { drift, volatility },
+        ("jump", _) => Model::JumpDiffusion { drift, volatility, jumps, jump_mean, jump_volatility },
+        ("csv", Some(path)) => {
+            let csv = std::fs::read_to_string(&path).map_err(|error| format!("{path}: {error}"))?;
+            Model::Replay(parse_csv(&csv).map_err(|error| format!("{path}: {error}"))?)
+        }
+        ("csv", None) => return Err("--model csv needs --csv <file>".into()),
+        (model, _) => return Err(format!("unknown model {model}")),
+    };
+    if options.paths == 0 {
+        return Err("--paths must be at least 1".into());
+    }
+    Ok(options)
+}
+
+fn row(name: &str, distribution: &Distribution) {
+    let Distribution { mean, p5, p50, p95, p99, max } = distribution;
+    println!("{name:<16}{mean:>14.2}{p5:>14.2}{p50:>14.2}{p95:>14.2}{p99:>14.2}{max:>14.2}");
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Options { scenario, paths, seed } = match parse(&args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let report = Report::of(&scenario.run_all(seed, paths));
+    println!("{paths} paths of {} steps, {} positions, {}", scenario.steps, scenario.population.users, scenario.model.name());
+    println!("P(bad debt) {:.4}", report.bad_debt_probability);
+    println!("{:<16}{:>14}{:>14}{:>14}{:>14}{:>14}{:>14}", "", "mean", "p5", "p50", "p95", "p99", "max");
+    row("liquidations", &report.liquidations);
+    row("liquidated $", &report.liquidated);
+    row("bad debt $", &report.bad_debt);
+    row("min ratio", &report.min_ratio);
+    row("final ratio", &report.final_ratio);
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,58 @@
+//! Synthetic borrowers.
+use rand::Rng;
+use crate::price::normal;
+use crate::protocol::Protocol;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Population {
+    pub users: usize,
+    /// Median WETH deposited, sizes are lognormal around it
+    pub median_deposit: f64,
+    /// Standard deviation of the log of the deposits
+    pub deposit_spread: f64,
+    /// Collateral ratios users open at are uniform between these two
+    pub min_ratio: f64,
+    pub max_ratio: f64,
+}
+
+impl Default for Population {
+    fn default() -> Self {
+        Self { users: 1000, median_deposit: 5.0, deposit_spread: 1.0, min_ratio: 1.6, max_ratio: 3.0 }
+    }
+}
+
+impl Population {
+    /// Every user deposits and mints up to their ratio at `price`. Ratios at or
+    /// under the protocol's minimum are mints the Manager would refuse, those
+    /// users only deposit.
+    pub fn open<R: Rng>(&self, protocol: &mut Protocol, rng: &mut R, price: f64) {
+        for user in 0..self.users {
+            let deposit = self.median_deposit * (self.deposit_spread * normal(rng)).exp();
+            let ratio = if self.max_ratio > self.min_ratio { rng.gen_range(self.min_ratio..self.max_ratio) } else { self.min_ratio };
+            protocol.deposit(user, deposit);
+            let _ = protocol.mint(user, deposit * price / ratio, price);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::protocol::Params;
+
+    #[test]
+    fn opens_within_the_ratios() {
+        let population = Population { users: 500, ..Population::default() };
+        let mut protocol = Protocol::new(Params::default(), population.users);
+        population.open(&mut protocol, &mut StdRng::seed_from_u64(3), 2000.0);
+        for position in &protocol.positions {
+            let ratio = position.collat_ratio(2000.0);
+            assert!((1.6 - 1e-9..=3.0 + 1e-9).contains(&ratio), "{ratio}");
+        }
+        assert_eq!(protocol.supply, protocol.debt());
+        let ratio = protocol.system_ratio(2000.0);
+        assert!((1.6..3.0).contains(&ratio), "{ratio}");
+    }
+}
@@ -0,0 +1,163 @@
+//! WETH price paths.
+use std::fmt;
+use rand::Rng;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Model {
+    /// Geometric brownian motion, `drift` and `volatility` annualised
+    Gbm { drift: f64, volatility: f64 },
+    /// Merton jump diffusion, GBM plus `jumps` a year of normally distributed log size
+    JumpDiffusion { drift: f64, volatility: f64, jumps: f64, jump_mean: f64, jump_volatility: f64 },
+    /// Historical prices replayed from a random starting point, scaled to start at the initial price
+    Replay(Vec<f64>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CsvError {
+    /// Line number and the field that isn't a positive price
+    BadPrice(usize, String),
+    TooShort,
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::BadPrice(line, field) => write!(f, "line {line}: {field:?} is not a price"),
+            CsvError::TooShort => write!(f, "need at least two prices to replay"),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+/// Reads prices from the last column of a csv, skipping a header if there is one
+pub fn parse_csv(csv: &str) -> Result<Vec<f64>, CsvError> {
+    let mut prices = Vec::new();
+    for (index, line) in csv.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let field = line.rsplit(',').next().unwrap_or_default().trim();
+        match field.parse::<f64>() {
+            Ok(price) if price > 0.0 && price.is_finite() => prices.push(price),
+            Err(_) if index == 0 => continue,
+            _ => return Err(CsvError::BadPrice(index + 1, field.into())),
+        }
+    }
+    if prices.len() < 2 {
+        return Err(CsvError::TooShort);
+    }
+    Ok(prices)
+}
+
+/// A standard normal draw, Box-Muller
+pub fn normal<R: Rng>(rng: &mut R) -> f64 {
+    let u: f64 = 1.0 - rng.gen::<f64>();
+    let v: f64 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+/// A poisson draw by inversion, `mean` is small for daily steps
+pub fn poisson<R: Rng>(rng: &mut R, mean: f64) -> u32 {
+    let limit = (-mean).exp();
+    let mut count = 0;
+    let mut product: f64 = rng.gen();
+    while product > limit {
+        count += 1;
+        product *= rng.gen::<f64>();
+    }
+    count
+}
+
+impl Model {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::Gbm { .. } => "gbm",
+            Model::JumpDiffusion { .. } => "jump diffusion",
+            Model::Replay(_) => "replay",
+        }
+    }
+
+    /// `steps + 1` prices starting at `initial`, `dt` in years
+    pub fn path<R: Rng>(&self, rng: &mut R, initial: f64, steps: usize, dt: f64) -> Vec<f64> {
+        let mut path = Vec::with_capacity(steps + 1);
+        path.push(initial);
+        match self {
+            Model::Gbm { drift, volatility } => {
+                let mut price = initial;
+                for _ in 0..steps {
+                    price *= ((drift - volatility * volatility / 2.0) * dt + volatility * dt.sqrt() * normal(rng)).exp();
+                    path.push(price);
+                }
+            }
+            Model::JumpDiffusion { drift, volatility, jumps, jump_mean, jump_volatility } => {
+                // Compensated so the expected return stays `drift`
+                let compensation = jumps * ((jump_mean + jump_volatility * jump_volatility / 2.0).exp() - 1.0);
+                let mut price = initial;
+                for _ in 0..steps {
+                    let mut log_return = (drift - compensation - volatility * volatility / 2.0) * dt + volatility * dt.sqrt() * normal(rng);
+                    for _ in 0..poisson(rng, jumps * dt) {
+                        log_return += jump_mean + jump_volatility * normal(rng);
+                    }
+                    price *= log_return.exp();
+                    path.push(price);
+                }
+            }
+            Model::Replay(prices) => {
+                // Wraps around the history when the path is longer than what's left of it
+                let start = rng.gen_range(0..prices.len() - 1);
+                let mut price = initial;
+                for step in 0..steps {
+                    let from = (start + step) % (prices.len() - 1);
+                    price *= prices[from + 1] / prices[from];
+                    path.push(price);
+                }
+            }
+        }
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn paths_have_the_expected_moments() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let (drift, volatility) = (0.1, 0.8);
+        let returns: Vec<f64> = (0..20_000)
+            .map(|_| Model::Gbm { drift, volatility }.path(&mut rng, 1.0, 1, 1.0)[1])
+            .collect();
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        assert!((mean - drift.exp()).abs() < 0.03, "{mean}");
+        let logs: Vec<f64> = returns.iter().map(|r| r.ln()).collect();
+        let log_mean = logs.iter().sum::<f64>() / logs.len() as f64;
+        let log_variance = logs.iter().map(|l| (l - log_mean).powi(2)).sum::<f64>() / logs.len() as f64;
+        assert!((log_variance.sqrt() - volatility).abs() < 0.02, "{log_variance}");
+
+        // Jumps are compensated, the mean is still the drift's
+        let model = Model::JumpDiffusion { drift, volatility: 0.5, jumps: 2.0, jump_mean: -0.2, jump_volatility: 0.1 };
+        let returns: Vec<f64> = (0..20_000).map(|_| model.path(&mut rng, 1.0, 10, 0.1)[10]).collect();
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        assert!((mean - drift.exp()).abs() < 0.03, "{mean}");
+    }
+
+    #[test]
+    fn replays_history() {
+        let prices = parse_csv("date,close\n2024-01-01,2000\n2024-01-02,2200\n\n2024-01-03,1100\n").unwrap();
+        assert_eq!(prices, [2000.0, 2200.0, 1100.0]);
+        assert_eq!(parse_csv("2000\nnope\n"), Err(CsvError::BadPrice(2, "nope".into())));
+        assert_eq!(parse_csv("price\n2000\n"), Err(CsvError::TooShort));
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let path = Model::Replay(prices).path(&mut rng, 100.0, 4, 1.0 / 365.0);
+        assert_eq!(path.len(), 5);
+        let mut moves: Vec<f64> = path.windows(2).map(|w| (w[1] / w[0] * 100.0).round() / 100.0).collect();
+        moves.sort_by(f64::total_cmp);
+        assert_eq!(moves, [0.5, 0.5, 1.1, 1.1]);
+    }
+}
@@ -0,0 +1,188 @@
+//! The Manager's state machine on plain floats.
+//!
+//! Amounts are in tokens and prices in dollars per WETH rather than the
+//! contract's fixed point, which is plenty for risk numbers. The rules are the
+//! contract's: mints and withdrawals must leave the position above
+//! `min_collat_ratio`, positions at or below it can be liquidated, and the
+//! liquidator burns the debt plus a penalty capped by what the collateral is
+//! worth, or what the collateral is worth less the penalty when it doesn't
+//! cover the debt.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Params {
+    /// Collateral value over debt a position must stay above, `MIN_COLLAT_RATIO`
+    pub min_collat_ratio: f64,
+    /// Share of the debt the liquidator pays on top, `LIQUIDATION_PENALTY`
+    pub liquidation_penalty: f64,
+    /// Share of each mint taken as surplus, the contract charges none
+    pub mint_fee: f64,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self { min_collat_ratio: 1.5, liquidation_penalty: 0.05, mint_fee: 0.0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Position {
+    /// WETH deposited
+    pub deposited: f64,
+    /// shUSD owed
+    pub minted: f64,
+}
+
+impl Position {
+    /// Collateral value over debt, infinite without debt like `collat_ratio`'s `U256::MAX`
+    pub fn collat_ratio(&self, price: f64) -> f64 {
+        if self.minted == 0.0 {
+            return f64::INFINITY;
+        }
+        self.deposited * price / self.minted
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    Undercollateralized,
+    /// The position is above the minimum ratio
+    NotLiquidatable,
+    CouldNotSub,
+}
+
+/// What a liquidation moved, in dollars
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Liquidation {
+    /// Debt taken off the books
+    pub debt: f64,
+    /// shUSD the liquidator burnt, debt plus penalty or the discounted collateral
+    pub repaid: f64,
+    /// Collateral handed to the liquidator
+    pub collateral: f64,
+    /// Debt the collateral couldn't cover
+    pub bad_debt: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Protocol {
+    pub params: Params,
+    pub positions: Vec<Position>,
+    pub total_deposited: f64,
+    /// shUSD in circulation, debt plus penalties and fees minted to the surplus
+    pub supply: f64,
+    pub surplus: f64,
+    pub bad_debt: f64,
+}
+
+impl Protocol {
+    pub fn new(params: Params, users: usize) -> Self {
+        Self { params, positions: vec![Position::default(); users], ..Self::default() }
+    }
+
+    pub fn deposit(&mut self, user: usize, amount: f64) {
+        self.positions[user].deposited += amount;
+        self.total_deposited += amount;
+    }
+
+    pub fn mint(&mut self, user: usize, amount: f64, price: f64) -> Result<(), Error> {
+        let mut position = self.positions[user];
+        let fee = amount * self.params.mint_fee;
+        position.minted += amount + fee;
+        if position.collat_ratio(price) <= self.params.min_collat_ratio {
+            return Err(Error::Undercollateralized);
+        }
+        self.positions[user] = position;
+        self.supply += amount + fee;
+        self.surplus += fee;
+        Ok(())
+    }
+
+    pub fn burn(&mut self, user: usize, amount: f64) -> Result<(), Error> {
+        let position = &mut self.positions[user];
+        if amount > position.minted {
+            return Err(Error::CouldNotSub);
+        }
+        position.minted -= amount;
+        self.supply -= amount;
+        Ok(())
+    }
+
+    pub fn withdraw(&mut self, user: usize, amount: f64, price: f64) -> Result<(), Error> {
+        let mut position = self.positions[user];
+        if amount > position.deposited {
+            return Err(Error::CouldNotSub);
+        }
+        position.deposited -= amount;
+        if position.collat_ratio(price) <= self.params.min_collat_ratio {
+            return Err(Error::Undercollateralized);
+        }
+        self.positions[user] = position;
+        self.total_deposited -= amount;
+        Ok(())
+    }
+
+    pub fn liquidatable(&self, user: usize, price: f64) -> bool {
+        let position = self.positions[user];
+        position.minted > 0.0 && position.collat_ratio(price) <= self.params.min_collat_ratio
+    }
+
+    pub fn liquidate(&mut self, user: usize, price: f64) -> Result<Liquidation, Error> {
+        if !self.liquidatable(user, price) {
+            return Err(Error::NotLiquidatable);
+        }
+        let position = std::mem::take(&mut self.positions[user]);
+        let value = position.deposited * price;
+        let liquidation = if value < position.minted {
+            let repaid = value / (1.0 + self.params.liquidation_penalty);
+            Liquidation { debt: position.minted, repaid, collateral: value, bad_debt: position.minted - repaid }
+        } else {
+            let penalty = (position.minted * self.params.liquidation_penalty).min(value - position.minted);
+            self.surplus += penalty;
+            Liquidation { debt: position.minted, repaid: position.minted + penalty, collateral: value, bad_debt: 0.0 }
+        };
+        // The penalty is burnt from the liquidator and minted back to the surplus
+        self.supply -= liquidation.repaid.min(position.minted);
+        self.bad_debt += liquidation.bad_debt;
+        self.total_deposited -= position.deposited;
+        Ok(liquidation)
+    }
+
+    /// Debt of all open positions
+    pub fn debt(&self) -> f64 {
+        self.positions.iter().map(|position| position.minted).sum()
+    }
+
+    /// Collateral value over debt across all positions
+    pub fn system_ratio(&self, price: f64) -> f64 {
+        let debt = self.debt();
+        if debt == 0.0 { f64::INFINITY } else { self.total_deposited * price / debt }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_the_manager() {
+        let mut protocol = Protocol::new(Params::default(), 2);
+        protocol.deposit(0, 2.0);
+        assert_eq!(protocol.mint(0, 2400.0, 1800.0), Err(Error::Undercollateralized));
+        protocol.mint(0, 2000.0, 1800.0).unwrap();
+        assert_eq!(protocol.withdraw(0, 1.0, 1800.0), Err(Error::Undercollateralized));
+        assert_eq!(protocol.liquidate(0, 1800.0), Err(Error::NotLiquidatable));
+
+        // $1400 puts it at 1.4, the liquidator burns $2000 of debt and a $100 penalty
+        let liquidation = protocol.liquidate(0, 1400.0).unwrap();
+        assert_eq!(liquidation, Liquidation { debt: 2000.0, repaid: 2100.0, collateral: 2800.0, bad_debt: 0.0 });
+        assert_eq!((protocol.supply, protocol.surplus, protocol.total_deposited), (0.0, 100.0, 0.0));
+
+        // Under water at $840, $1680 of collateral goes for $1600 of the $2000
+        protocol.deposit(1, 2.0);
+        protocol.mint(1, 2000.0, 1800.0).unwrap();
+        let liquidation = protocol.liquidate(1, 840.0).unwrap();
+        assert_eq!((liquidation.repaid, liquidation.bad_debt), (1600.0, 400.0));
+        assert_eq!(protocol.supply, 400.0);
+        assert_eq!(protocol.supply, protocol.debt() + protocol.bad_debt);
+    }
+}
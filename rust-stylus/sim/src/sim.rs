@@ -0,0 +1,198 @@
+//! Runs the population through price paths.
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use crate::population::Population;
+use crate::price::Model;
+use crate::protocol::{Params, Protocol};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub params: Params,
+    pub population: Population,
+    pub model: Model,
+    pub initial_price: f64,
+    pub steps: usize,
+    /// Years per step
+    pub dt: f64,
+    /// Steps between a position becoming liquidatable and a liquidator getting to it
+    pub lag: usize,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            params: Params::default(),
+            population: Population::default(),
+            model: Model::Gbm { drift: 0.0, volatility: 0.8 },
+            initial_price: 2000.0,
+            steps: 365,
+            dt: 1.0 / 365.0,
+            lag: 0,
+        }
+    }
+}
+
+/// What happened along one path, amounts in dollars
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Outcome {
+    pub liquidations: usize,
+    /// Debt liquidated
+    pub liquidated: f64,
+    /// Collateral value handed to liquidators
+    pub seized: f64,
+    pub bad_debt: f64,
+    /// Lowest collateral value over debt across the system
+    pub min_ratio: f64,
+    pub final_ratio: f64,
+    pub final_price: f64,
+}
+
+impl Scenario {
+    /// Simulates path `index`, the same seed and index always give the same outcome
+    pub fn run(&self, seed: u64, index: u64) -> Outcome {
+        let mut rng = StdRng::seed_from_u64(seed ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let mut protocol = Protocol::new(self.params, self.population.users);
+        self.population.open(&mut protocol, &mut rng, self.initial_price);
+        let path = self.model.path(&mut rng, self.initial_price, self.steps, self.dt);
+
+        let mut outcome = Outcome { min_ratio: protocol.system_ratio(self.initial_price), ..Outcome::default() };
+        // When each user was first seen liquidatable
+        let mut flagged: Vec<Option<usize>> = vec![None; self.population.users];
+        for (step, &price) in path.iter().enumerate().skip(1) {
+            for (user, flagged) in flagged.iter_mut().enumerate() {
+                if !protocol.liquidatable(user, price) {
+                    continue;
+                }
+                let since = *flagged.get_or_insert(step);
+                if step - since < self.lag {
+                    continue;
+                }
+                *flagged = None;
+                let liquidation = protocol.liquidate(user, price).expect("checked liquidatable");
+                outcome.liquidations += 1;
+                outcome.liquidated += liquidation.debt;
+                outcome.seized += liquidation.collateral;
+                outcome.bad_debt += liquidation.bad_debt;
+            }
+            outcome.min_ratio = outcome.min_ratio.min(protocol.system_ratio(price));
+        }
+        outcome.final_price = *path.last().expect("paths start at the initial price");
+        outcome.final_ratio = protocol.system_ratio(outcome.final_price);
+        outcome
+    }
+
+    pub fn run_all(&self, seed: u64, paths: u64) -> Vec<Outcome> {
+        (0..paths).map(|index| self.run(seed, index)).collect()
+    }
+}
+
+/// Percentiles of a metric across paths
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Distribution {
+    pub mean: f64,
+    pub p5: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Distribution {
+    /// Nearest rank percentiles, infinite values sort last
+    pub fn of(mut values: Vec<f64>) -> Self {
+        assert!(!values.is_empty(), "no paths");
+        values.sort_by(f64::total_cmp);
+        let rank = |p: f64| values[((p * values.len() as f64).ceil() as usize).clamp(1, values.len()) - 1];
+        Self {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            p5: rank(0.05),
+            p50: rank(0.5),
+            p95: rank(0.95),
+            p99: rank(0.99),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Report {
+    pub paths: usize,
+    /// Share of paths ending with any bad debt
+    pub bad_debt_probability: f64,
+    pub liquidations: Distribution,
+    pub liquidated: Distribution,
+    pub bad_debt: Distribution,
+    pub min_ratio: Distribution,
+    pub final_ratio: Distribution,
+}
+
+impl Report {
+    pub fn of(outcomes: &[Outcome]) -> Self {
+        let metric = |f: fn(&Outcome) -> f64| Distribution::of(outcomes.iter().map(f).collect());
+        Self {
+            paths: outcomes.len(),
+            bad_debt_probability: outcomes.iter().filter(|o| o.bad_debt > 0.0).count() as f64 / outcomes.len() as f64,
+            liquidations: metric(|o| o.liquidations as f64),
+            liquidated: metric(|o| o.liquidated),
+            bad_debt: metric(|o| o.bad_debt),
+            min_ratio: metric(|o| o.min_ratio),
+            final_ratio: metric(|o| o.final_ratio),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcomes_are_reproducible_and_consistent() {
+        let scenario = Scenario { population: Population { users: 200, ..Population::default() }, steps: 90, ..Scenario::default() };
+        let outcomes = scenario.run_all(42, 50);
+        assert_eq!(outcomes, scenario.run_all(42, 50));
+        assert_ne!(outcomes[0], outcomes[1]);
+        for outcome in &outcomes {
+            assert!(outcome.seized >= outcome.liquidated - outcome.bad_debt - 1e-6);
+            assert!(outcome.min_ratio <= outcome.final_ratio);
+        }
+        let report = Report::of(&outcomes);
+        assert_eq!(report.paths, 50);
+        assert!(report.liquidations.p5 <= report.liquidations.p50 && report.liquidations.p50 <= report.liquidations.max);
+    }
+
+    #[test]
+    fn crashes_past_the_debt_leave_bad_debt() {
+        // One step from $2000 to $1200, 40% down
+        let crash = Model::Replay(vec![2000.0, 1200.0]);
+        let scenario = |min_ratio: f64, open: f64| Scenario {
+            params: Params { min_collat_ratio: min_ratio, ..Params::default() },
+            population: Population { users: 10, min_ratio: open, max_ratio: open, ..Population::default() },
+            model: crash.clone(),
+            steps: 1,
+            ..Scenario::default()
+        };
+        // Opened at 1.6, the position is at 0.96 after the crash
+        let outcome = scenario(1.5, 1.6).run(0, 0);
+        assert_eq!(outcome.liquidations, 10);
+        assert!(outcome.bad_debt > 0.0);
+        // Opened at 1.7 it is at 1.02, liquidated with all the debt covered
+        let outcome = scenario(1.5, 1.7).run(0, 0);
+        assert_eq!((outcome.liquidations, outcome.bad_debt), (10, 0.0));
+        assert!(outcome.final_ratio.is_infinite());
+
+        // A liquidator a step late on a path that keeps falling to the same $1200
+        let falling = Model::Replay(vec![2000.0, 1800.0, 1200.0]);
+        let late = Scenario { model: falling, steps: 2, lag: 1, ..scenario(1.5, 1.6) };
+        let outcome = late.run(0, 0);
+        assert_eq!(outcome.final_price.round(), 1200.0);
+        assert_eq!(outcome.liquidations, 10);
+        assert!(outcome.bad_debt > 0.0);
+    }
+
+    #[test]
+    fn percentiles() {
+        let distribution = Distribution::of((1..=100).rev().map(f64::from).collect());
+        assert_eq!((distribution.p5, distribution.p50, distribution.p95, distribution.p99, distribution.max), (5.0, 50.0, 95.0, 99.0, 100.0));
+        assert_eq!(distribution.mean, 50.5);
+    }
+}
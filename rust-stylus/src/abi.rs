@@ -0,0 +1,336 @@
+//! ABI export for the contract selected by the enabled feature.
+//!
+//! Prints the Solidity interface generated by `#[public]` with the contract's
+//! custom errors added, or the equivalent JSON ABI. Only one contract feature
+//! can be enabled per build, `export-abi.sh` runs this for each of them.
+use alloc::format;
+use alloc::string::String;
+#[cfg(feature = "export-abi")]
+use alloc::string::ToString;
+use alloc::vec::Vec;
+#[cfg(feature = "export-abi")]
+use core::{fmt, marker::PhantomData};
+#[cfg(feature = "export-abi")]
+use stylus_sdk::abi::GenerateAbi;
+
+#[cfg(all(feature = "export-abi", not(any(
+    feature = "manager",
+    feature = "sh-usd",
+    feature = "test-oracle",
+    feature = "test-weth",
+    feature = "proxy",
+    feature = "timelock",
+    feature = "gov-token",
+    feature = "governor",
+    feature = "vault-nft",
+    feature = "psm",
+    feature = "test-usdc",
+    feature = "twap-oracle",
+    feature = "test-pool",
+    feature = "forwarder"
+))))]
+compile_error!("export-abi needs one of the contract features");
+
+#[cfg(feature = "export-abi")]
+macro_rules! errors {
+    ($($error:ty),* $(,)?) => {
+        &[$(<$error as alloy_sol_types::SolError>::SIGNATURE),*]
+    };
+}
+
+#[cfg(all(feature = "export-abi", feature = "manager"))]
+mod selected {
+    use crate::access::deployer::OnlyDeployer;
+    use crate::contracts::{calls, manager::*};
+    pub type Contract = Manager;
+    pub const ERRORS: &[&str] = errors![
+        Undercollateralized, MintRateLimited, OnlyGovernance, AlreadyInitialized, CouldNotAdd, CouldNotSub,
+        CouldNotMul, CouldNotDiv, ConversionFailure, NotUpgradeable, OnlyShUSD, InvalidRepayData, PositionOverflow,
+        VaultsDisabled, NotVaultOwner, PriceSuspicious, OnlyGuardian, OnlyDeployer, UnauthorizedCallContext,
+        calls::CouldNotCall, calls::CouldNotUnpackBool,
+    ];
+}
+
+#[cfg(all(feature = "export-abi", feature = "sh-usd"))]
+mod selected {
+    use crate::access::{access_control::AccessControlUnauthorizedAccount, deployer::OnlyDeployer};
+    use crate::contracts::sh_usd::*;
+    use crate::token::erc20::*;
+    pub type Contract = ShUSD;
+    pub const ERRORS: &[&str] = errors![
+        AlreadyInitialized, OnlyDeployer, AccessControlUnauthorizedAccount, MinterCapExceeded, IssuancePaused,
+        ERC20MintError, ERC20BurnError, ERC20TransferError, NotFrozen, OnlyTimelock, InvalidTimelock,
+        InsufficientBalance, InsufficientAllowance, AccountFrozen, BalanceOverflow, SupplyOverflow,
+        SupplyUnderflow, MaxSupplyExceeded, ERC1363InvalidReceiver, ERC1363InvalidSpender,
+    ];
+}
+
+#[cfg(all(feature = "export-abi", feature = "test-oracle"))]
+mod selected {
+    use crate::test::test_oracle::*;
+    pub type Contract = TestOracle;
+    pub const ERRORS: &[&str] = errors![AlreadyInitialized, OnlyOwner, OracleReverted, UnknownFailureMode, PricePathExhausted];
+}
+
+#[cfg(all(feature = "export-abi", feature = "test-weth"))]
+mod selected {
+    use crate::test::test_weth::*;
+    use crate::token::erc20::*;
+    pub type Contract = TestWeth;
+    pub const ERRORS: &[&str] = errors![
+        OnlyManagerCanCall, InsufficientBalance, InsufficientAllowance, AccountFrozen, BalanceOverflow,
+        SupplyOverflow, SupplyUnderflow, MaxSupplyExceeded, ERC1363InvalidReceiver, ERC1363InvalidSpender,
+    ];
+}
+
+#[cfg(all(feature = "export-abi", feature = "proxy"))]
+mod selected {
+    use crate::access::deployer::OnlyDeployer;
+    use crate::contracts::proxy::*;
+    pub type Contract = Erc1967Proxy;
+    pub const ERRORS: &[&str] = errors![ProxyAlreadyInitialized, NoImplementation, OnlyDeployer];
+}
+
+#[cfg(all(feature = "export-abi", feature = "timelock"))]
+mod selected {
+    use crate::access::deployer::OnlyDeployer;
+    use crate::contracts::{calls, timelock::*};
+    pub type Contract = Timelock;
+    pub const ERRORS: &[&str] = errors![
+        AlreadyInitialized, OnlyProposer, OnlyExecutor, OnlyCanceller, DelayTooShort,
+        UnknownOperation, NotQueued, NotReady, Expired, OnlyDeployer, UnsupportedSelfCall, calls::CouldNotCall,
+    ];
+}
+
+#[cfg(all(feature = "export-abi", feature = "gov-token"))]
+mod selected {
+    use crate::access::deployer::OnlyDeployer;
+    use crate::contracts::gov_token::*;
+    use crate::token::{erc20::*, votes::*};
+    pub type Contract = GovToken;
+    pub const ERRORS: &[&str] = errors![
+        AlreadyInitialized, OnlyOwner, OnlyDeployer, InsufficientBalance, InsufficientAllowance, AccountFrozen,
+        BalanceOverflow, SupplyOverflow, SupplyUnderflow, MaxSupplyExceeded, ERC1363InvalidReceiver,
+        ERC1363InvalidSpender, ERC5805FutureLookup, VotesExpiredSignature, InvalidAccountNonce, InvalidSignature,
+    ];
+}
+
+#[cfg(all(feature = "export-abi", feature = "governor"))]
+mod selected {
+    use crate::access::deployer::OnlyDeployer;
+    use crate::contracts::{calls, governor::*};
+    pub type Contract = Governor;
+    pub const ERRORS: &[&str] = errors![
+        AlreadyInitialized, OnlyDeployer, OnlyTimelock, OnlyProposer, BelowProposalThreshold, UnknownProposal,
+        UnexpectedState, AlreadyVoted, InvalidVoteType, InvalidQuorum, InvalidVotingPeriod, CouldNotAdd, CouldNotMul,
+        calls::CouldNotCall,
+    ];
+}
+
+#[cfg(all(feature = "export-abi", feature = "vault-nft"))]
+mod selected {
+    use crate::access::deployer::OnlyDeployer;
+    use crate::contracts::vault_nft::*;
+    use crate::token::erc721::*;
+    pub type Contract = VaultNft;
+    pub const ERRORS: &[&str] = errors![
+        AlreadyInitialized, OnlyManager, OnlyDeployer, ERC721InvalidOwner, ERC721NonexistentToken, ERC721IncorrectOwner,
+        ERC721InsufficientApproval, ERC721InvalidReceiver, ERC721InvalidApprover, ERC721InvalidSender,
+    ];
+}
+
+#[cfg(all(feature = "export-abi", feature = "psm"))]
+mod selected {
+    use crate::access::deployer::OnlyDeployer;
+    use crate::contracts::{calls, psm::*};
+    pub type Contract = Psm;
+    pub const ERRORS: &[&str] = errors![
+        AlreadyInitialized, OnlyDeployer, OnlyGovernance, UnsupportedDecimals, FeeTooHigh, DebtCeilingExceeded,
+        InsufficientReserves, MathOverflow, calls::CouldNotCall, calls::CouldNotUnpackBool,
+    ];
+}
+
+#[cfg(all(feature = "export-abi", feature = "test-usdc"))]
+mod selected {
+    use crate::test::test_usdc::*;
+    use crate::token::erc20::*;
+    pub type Contract = TestUsdc;
+    pub const ERRORS: &[&str] = errors![
+        OnlyMinterCanCall, InsufficientBalance, InsufficientAllowance, AccountFrozen, BalanceOverflow,
+        SupplyOverflow, SupplyUnderflow, MaxSupplyExceeded, ERC1363InvalidReceiver, ERC1363InvalidSpender,
+    ];
+}
+
+#[cfg(all(feature = "export-abi", feature = "twap-oracle"))]
+mod selected {
+    use crate::access::deployer::OnlyDeployer;
+    use crate::contracts::{calls, twap_oracle::*};
+    pub type Contract = TwapOracle;
+    pub const ERRORS: &[&str] = errors![
+        AlreadyInitialized, OnlyDeployer, OnlyGovernance, InvalidWindow, UnsupportedDecimals, TickOutOfRange, MathOverflow,
+        calls::CouldNotCall,
+    ];
+}
+
+#[cfg(all(feature = "export-abi", feature = "test-pool"))]
+mod selected {
+    use crate::test::test_pool::*;
+    pub type Contract = TestPool;
+    pub const ERRORS: &[&str] = errors![NotInitialized, ObservationTooOld];
+}
+
+#[cfg(all(feature = "export-abi", feature = "forwarder"))]
+mod selected {
+    use crate::contracts::forwarder::*;
+    pub type Contract = Forwarder;
+    pub const ERRORS: &[&str] = errors![
+        ERC2771ForwarderExpiredRequest, ERC2771ForwarderInvalidSigner, ERC2771UntrustfulTarget,
+    ];
+}
+
+#[cfg(feature = "export-abi")]
+struct Interface<T: GenerateAbi>(PhantomData<T>);
+
+#[cfg(feature = "export-abi")]
+impl<T: GenerateAbi> fmt::Display for Interface<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        T::fmt_abi(f)
+    }
+}
+
+#[cfg(feature = "export-abi")]
+/// Solidity interfaces of `T` with any of `errors` not declared yet added to the
+/// last one, which is `T`'s own
+pub fn solidity<T: GenerateAbi>(errors: &[&str], license: &str, pragma: &str) -> String {
+    let mut interface = Interface::<T>(PhantomData).to_string();
+    let end = interface.trim_end().len() - 1;
+    // `#[public]` already declares the errors of functions returning a typed error
+    let mut declarations = String::new();
+    for error in errors {
+        if !interface.contains(&format!("error {}", &error[..=error.find('(').unwrap()])) {
+            declarations.push_str(&format!("\n    error {error};\n"));
+        }
+    }
+    interface.insert_str(end, &declarations);
+    format!("// SPDX-License-Identifier: {license}\n{pragma}\n\n{interface}")
+}
+
+/// JSON ABI of the functions and errors declared in `solidity`
+pub fn json(solidity: &str) -> String {
+    let mut entries = Vec::new();
+    for statement in solidity.split(';').map(str::trim) {
+        let statement = statement.rsplit(['{', '}']).next().unwrap_or_default().trim();
+        if let Some(function) = statement.strip_prefix("function ") {
+            let (name, rest) = function.split_once('(').unwrap();
+            let (inputs, rest) = split_group(rest);
+            let mutability = ["view", "pure", "payable"].into_iter()
+                .find(|m| rest.split_whitespace().any(|word| word == *m))
+                .unwrap_or("nonpayable");
+            let outputs = rest.split_once("returns (").map(|(_, outputs)| split_group(outputs).0).unwrap_or_default();
+            entries.push(format!(
+                r#"{{"type":"function","name":"{name}","inputs":[{}],"outputs":[{}],"stateMutability":"{mutability}"}}"#,
+                params(inputs), params(outputs),
+            ));
+        } else if let Some(error) = statement.strip_prefix("error ") {
+            let (name, rest) = error.split_once('(').unwrap();
+            entries.push(format!(r#"{{"type":"error","name":"{name}","inputs":[{}]}}"#, params(split_group(rest).0)));
+        }
+    }
+    // Interfaces repeat the errors they share with those they inherit from
+    let mut seen = Vec::new();
+    entries.retain(|entry| if seen.contains(entry) { false } else { seen.push(entry.clone()); true });
+    format!("[\n  {}\n]", entries.join(",\n  "))
+}
+
+/// Splits `s`, which follows an opening parenthesis, at its closing one
+fn split_group(s: &str) -> (&str, &str) {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return (&s[..i], &s[i + 1..]),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    (s, "")
+}
+
+/// Splits a parameter list at its top level commas
+fn split_params(s: &str) -> Vec<&str> {
+    let (mut depth, mut start, mut params) = (0, 0, Vec::new());
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                params.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    params.push(s[start..].trim());
+    params.retain(|param| !param.is_empty());
+    params
+}
+
+fn params(list: &str) -> String {
+    let params: Vec<String> = split_params(list).into_iter().map(|param| {
+        let (ty, rest) = match param.strip_prefix('(') {
+            Some(tuple) => {
+                let (components, rest) = split_group(tuple);
+                let (array, rest) = rest.split_at(rest.find(' ').unwrap_or(rest.len()));
+                let ty = format!("tuple{array}");
+                return format!(r#"{{"name":"{}","type":"{ty}","components":[{}]}}"#, name(rest), params(components));
+            }
+            None => param.split_at(param.find(' ').unwrap_or(param.len())),
+        };
+        format!(r#"{{"name":"{}","type":"{ty}"}}"#, name(rest))
+    }).collect();
+    params.join(",")
+}
+
+/// The parameter name after the type, skipping data locations
+fn name(rest: &str) -> &str {
+    rest.split_whitespace().find(|word| !matches!(*word, "calldata" | "memory" | "storage")).unwrap_or_default()
+}
+
+#[cfg(feature = "export-abi")]
+/// Prints the ABI of the selected contract, Solidity by default or JSON with
+/// `--json`. `--license` and `--pragma` override the header.
+pub fn print_from_args() {
+    let mut args = std::env::args().skip(1);
+    let (mut as_json, mut license, mut pragma) = (false, String::from("ISC"), String::from("pragma solidity ^0.8.23;"));
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => as_json = true,
+            "--license" => license = args.next().expect("--license needs a value"),
+            "--pragma" => pragma = args.next().expect("--pragma needs a value"),
+            other => panic!("unknown argument {other}"),
+        }
+    }
+    let interface = solidity::<selected::Contract>(selected::ERRORS, &license, &pragma);
+    if as_json {
+        println!("{}", json(&interface));
+    } else {
+        print!("{interface}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_from_solidity() {
+        let solidity = "interface IManager  {\n    function position(address user) external view returns (uint256, uint256);\n\n    function onTransferReceived(address _operator, bytes calldata data) external returns (bytes4);\n\n    error MintRateLimited(uint256,uint256);\n}\n";
+        assert_eq!(json(solidity), concat!(
+            "[\n",
+            r#"  {"type":"function","name":"position","inputs":[{"name":"user","type":"address"}],"outputs":[{"name":"","type":"uint256"},{"name":"","type":"uint256"}],"stateMutability":"view"},"#, "\n",
+            r#"  {"type":"function","name":"onTransferReceived","inputs":[{"name":"_operator","type":"address"},{"name":"data","type":"bytes"}],"outputs":[{"name":"","type":"bytes4"}],"stateMutability":"nonpayable"},"#, "\n",
+            r#"  {"type":"error","name":"MintRateLimited","inputs":[{"name":"","type":"uint256"},{"name":"","type":"uint256"}]}"#, "\n",
+            "]",
+        ));
+    }
+}
@@ -0,0 +1,102 @@
+//! Role based access control
+//!
+//! [`AccessControl`] keeps a set of accounts per role and is intended to be
+//! inherited by other contract types, much like [`crate::token::erc20::Erc20`].
+//! Every role is administered by [`ADMIN_ROLE`], including the admin role itself.
+//!
+//! Note that this code is unaudited and not fit for production use.
+use alloc::vec;
+use alloc::vec::Vec;
+use alloy_primitives::{b256, Address, B256};
+use alloy_sol_types::sol;
+use crate::context;
+use stylus_sdk::prelude::*;
+
+/// keccak256("ADMIN_ROLE")
+pub const ADMIN_ROLE: B256 = b256!("a49807205ce4d355092ef5a8a18f56e8913cf4a201fbe287825b095693c21775");
+/// keccak256("MINTER_ROLE")
+pub const MINTER_ROLE: B256 = b256!("9f2df0fed2c77648de5860a4cc508cd0818c85b8b8a1ab4ceeef8d981c8956a6");
+/// keccak256("BURNER_ROLE")
+pub const BURNER_ROLE: B256 = b256!("3c11d16cbaffd01df69ce1c404f6340ee057498f5f00246190ea54220576a848");
+/// keccak256("PAUSER_ROLE")
+pub const PAUSER_ROLE: B256 = b256!("65d7a28e3265b37a6474929f336521b332c1681b933f6cb9f3376673440d862a");
+/// keccak256("GOVERNANCE_ROLE")
+pub const GOVERNANCE_ROLE: B256 = b256!("71840dc4906352362b0cdaf79870196c8e42acafade72d5d5a6d59291253ceb1");
+/// keccak256("COMPLIANCE_ROLE")
+pub const COMPLIANCE_ROLE: B256 = b256!("442a94f1a1fac79af32856af2a64f63648cfa2ef3b98610a5bb7cbec4cee6985");
+
+sol_storage! {
+    pub struct AccessControl {
+        /// Role => account => whether the account holds it
+        mapping(bytes32 => mapping(address => bool)) roles;
+    }
+}
+
+sol! {
+    event RoleGranted(bytes32 indexed role, address indexed account, address indexed sender);
+    event RoleRevoked(bytes32 indexed role, address indexed account, address indexed sender);
+
+    error AccessControlUnauthorizedAccount(address account, bytes32 needed_role);
+}
+
+#[derive(SolidityError)]
+pub enum AccessControlError {
+    AccessControlUnauthorizedAccount(AccessControlUnauthorizedAccount),
+}
+
+// These methods aren't exposed to other contracts
+impl AccessControl {
+    /// Fails unless the caller holds `role`
+    pub fn only_role(&self, role: B256) -> Result<(), AccessControlError> {
+        let account = context::msg_sender(self.vm());
+        if !self.has_role(role, account) {
+            return Err(AccessControlError::AccessControlUnauthorizedAccount(AccessControlUnauthorizedAccount {
+                account,
+                needed_role: role,
+            }));
+        }
+        Ok(())
+    }
+
+    /// Grants `role` without checking the caller, for use during initialization
+    pub fn _grant_role(&mut self, role: B256, account: Address) {
+        if self.has_role(role, account) {
+            return;
+        }
+        self.roles.setter(role).insert(account, true);
+        log(self.vm(), RoleGranted { role, account, sender: context::msg_sender(self.vm()) });
+    }
+
+    fn _revoke_role(&mut self, role: B256, account: Address) {
+        if !self.has_role(role, account) {
+            return;
+        }
+        self.roles.setter(role).insert(account, false);
+        log(self.vm(), RoleRevoked { role, account, sender: context::msg_sender(self.vm()) });
+    }
+}
+
+// These methods are public to other contracts
+#[public]
+impl AccessControl {
+    pub fn has_role(&self, role: B256, account: Address) -> bool {
+        self.roles.getter(role).get(account)
+    }
+
+    pub fn grant_role(&mut self, role: B256, account: Address) -> Result<(), AccessControlError> {
+        self.only_role(ADMIN_ROLE)?;
+        self._grant_role(role, account);
+        Ok(())
+    }
+
+    pub fn revoke_role(&mut self, role: B256, account: Address) -> Result<(), AccessControlError> {
+        self.only_role(ADMIN_ROLE)?;
+        self._revoke_role(role, account);
+        Ok(())
+    }
+
+    /// Gives up `role` for the caller
+    pub fn renounce_role(&mut self, role: B256) {
+        self._revoke_role(role, context::msg_sender(self.vm()));
+    }
+}
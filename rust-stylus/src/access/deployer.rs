@@ -0,0 +1,89 @@
+//! The account allowed to initialize freshly deployed contracts
+//!
+//! Contracts are deployed and initialized in separate transactions, so anyone
+//! watching the chain could call `init` in between and wire the contract to
+//! themselves. [`DEPLOYER`] is the address in `PUB_KEY` at build time, a
+//! malformed one fails the build. Wasm builds require it, tests without it run
+//! as [`TEST_DEPLOYER`] and other native builds (the ABI export, the tooling)
+//! let anyone initialize.
+use alloy_primitives::{address, Address};
+use alloy_sol_types::sol;
+
+pub const DEPLOYER: Option<Address> = match option_env!("PUB_KEY") {
+    Some(key) => Some(parse(key)),
+    None if cfg!(test) => Some(TEST_DEPLOYER),
+    None => None,
+};
+
+/// The deployer of test builds without `PUB_KEY`, so the tests can show the gates hold
+pub const TEST_DEPLOYER: Address = address!("000000000000000000000000000000000000d001");
+
+#[cfg(target_arch = "wasm32")]
+const _: () = assert!(DEPLOYER.is_some(), "PUB_KEY must be set to the deployer's address when building contracts");
+
+sol! {
+    error OnlyDeployer();
+}
+
+/// Fails unless `sender` is the deployer the contract was built for
+pub fn only_deployer(sender: Address) -> Result<(), OnlyDeployer> {
+    only(DEPLOYER, sender)
+}
+
+fn only(deployer: Option<Address>, sender: Address) -> Result<(), OnlyDeployer> {
+    match deployer {
+        Some(deployer) if deployer != sender => Err(OnlyDeployer {}),
+        _ => Ok(()),
+    }
+}
+
+/// Parses a hex address with or without `0x`, panicking (at compile time for
+/// [`DEPLOYER`]) on anything else
+const fn parse(key: &str) -> Address {
+    let bytes = key.as_bytes();
+    let start = if bytes.len() >= 2 && bytes[0] == b'0' && (bytes[1] == b'x' || bytes[1] == b'X') { 2 } else { 0 };
+    assert!(bytes.len() - start == 40, "PUB_KEY must be a 20 byte hex address");
+    let mut address = [0u8; 20];
+    let mut i = 0;
+    while i < 20 {
+        address[i] = nibble(bytes[start + 2 * i]) << 4 | nibble(bytes[start + 2 * i + 1]);
+        i += 1;
+    }
+    Address::new(address)
+}
+
+const fn nibble(c: u8) -> u8 {
+    match c {
+        b'0'..=b'9' => c - b'0',
+        b'a'..=b'f' => c - b'a' + 10,
+        b'A'..=b'F' => c - b'A' + 10,
+        _ => panic!("PUB_KEY must be a 20 byte hex address"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_addresses_with_and_without_prefix() {
+        let expected = address!("f39fd6e51aad88f6f4ce6ab8827279cfffb92266");
+        assert_eq!(parse("0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"), expected);
+        assert_eq!(parse("f39fd6e51aad88f6f4ce6ab8827279cfffb92266"), expected);
+    }
+
+    #[test]
+    fn only_the_deployer_passes_once_set() {
+        let deployer = address!("f39fd6e51aad88f6f4ce6ab8827279cfffb92266");
+        let other = address!("70997970c51812dc3a010c7d01b50e0d17dc79c8");
+        assert!(only(Some(deployer), deployer).is_ok());
+        assert!(only(Some(deployer), other).is_err());
+        assert!(only(None, other).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "PUB_KEY must be a 20 byte hex address")]
+    fn rejects_malformed_keys() {
+        parse("0xf39fd6e51aad88f6f4ce6ab8827279cfffb9226g");
+    }
+}
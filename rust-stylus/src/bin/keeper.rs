@@ -0,0 +1,5 @@
+/// `cargo run --features keeper --bin microstable-keeper -- <config.json> [--dry-run]`
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    libmicrostable::keeper::run(std::env::args().skip(1).collect()).await
+}
@@ -0,0 +1,284 @@
+//! Client for `Manager`.
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_sol_types::sol;
+use ethers::providers::Middleware;
+use ethers::types::TransactionReceipt;
+use crate::contracts::manager::vault_key;
+use super::{ClientError, Contract, Revert};
+
+sol! {
+    /// What `Manager`'s `#[public]` impl exports
+    interface IManager {
+        function initialize(address weth, address oracle, address sh_usd, address governance) external;
+        function upgradeTo(address new_implementation) external;
+        function proxiableUUID() external view returns (bytes32);
+        function deposit(uint256 amount) external;
+        function burn(uint256 amount) external;
+        function mint(uint256 amount) external;
+        function withdraw(uint256 amount) external;
+        function liquidate(address user) external;
+        function onTransferReceived(address operator, address from, uint256 value, bytes data) external returns (bytes4);
+        function burnRepaid() external returns (uint256);
+        function repaid() external view returns (uint256);
+        function heal(uint256 amount) external;
+        function setMintLimits(uint256 window_limit, uint64 window, uint256 block_limit) external;
+        function mintLimits() external view returns (uint256, uint64, uint256);
+        function availableToMint() external view returns (uint256);
+        function surplus() external view returns (uint256);
+        function badDebt() external view returns (uint256);
+        function setGovernance(address new_governance) external;
+        function governance() external view returns (address);
+        function solvency() external view returns (uint256, uint256);
+        function position(address user) external view returns (uint256, uint256);
+        function collatRatio(address user) external view returns (uint256);
+        function migratePositions(address[] users) external;
+        function positionsPacked() external view returns (bool);
+        function openVault() external returns (uint256);
+        function depositVault(uint256 id, uint256 amount) external;
+        function burnVault(uint256 id, uint256 amount) external;
+        function mintVault(uint256 id, uint256 amount) external;
+        function withdrawVault(uint256 id, uint256 amount) external;
+        function vaultKey(uint256 id) external pure returns (address);
+        function setVaultNft(address vault_nft) external;
+        function vaultNft() external view returns (address);
+        function setPriceGuard(uint256 max_deviation, uint64 window) external;
+        function priceGuard() external view returns (uint256, uint64);
+        function lastGoodPrice() external view returns (uint256, uint64);
+        function breakerTripped() external view returns (bool);
+        function pokePrice() external returns (bool);
+        function confirmPrice() external;
+        function setGuardian(address guardian) external;
+        function guardian() external view returns (address);
+        function setTrustedForwarder(address forwarder) external;
+        function trustedForwarder() external view returns (address);
+        function isTrustedForwarder(address forwarder) external view returns (bool);
+    }
+}
+
+/// A user's position as `Manager` stores it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// WETH deposited as collateral
+    pub deposited: U256,
+    /// shUSD debt
+    pub minted: U256,
+}
+
+/// Mint limits, see `MintLimiter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MintLimits {
+    pub window_limit: U256,
+    pub window: u64,
+    pub block_limit: U256,
+}
+
+pub struct ManagerClient<M> {
+    contract: Contract<M>,
+}
+
+impl<M: Middleware> ManagerClient<M> {
+    pub fn new(address: Address, client: Arc<M>) -> Self {
+        Self { contract: Contract { address, client, decode: Revert::from_manager } }
+    }
+
+    pub fn address(&self) -> Address {
+        self.contract.address
+    }
+
+    pub async fn initialize(&self, weth: Address, oracle: Address, sh_usd: Address, governance: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::initializeCall { weth, oracle, sh_usd, governance }).await
+    }
+
+    pub async fn upgrade_to(&self, new_implementation: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::upgradeToCall { new_implementation }).await
+    }
+
+    pub async fn proxiable_uuid(&self) -> Result<B256, ClientError> {
+        Ok(self.contract.call(IManager::proxiableUUIDCall {}).await?._0)
+    }
+
+    /// Pulls `amount` WETH from the sender, who must have approved the manager
+    pub async fn deposit(&self, amount: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::depositCall { amount }).await
+    }
+
+    pub async fn burn(&self, amount: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::burnCall { amount }).await
+    }
+
+    pub async fn mint(&self, amount: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::mintCall { amount }).await
+    }
+
+    pub async fn withdraw(&self, amount: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::withdrawCall { amount }).await
+    }
+
+    pub async fn liquidate(&self, user: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::liquidateCall { user }).await
+    }
+
+    /// Runs `liquidate` against the latest block without sending it, returns the gas it uses
+    pub async fn simulate_liquidate(&self, user: Address) -> Result<U256, ClientError> {
+        self.contract.estimate(IManager::liquidateCall { user }).await
+    }
+
+    /// Only shUSD may call this, repay through `ShUSDClient::transfer_and_call` instead
+    pub async fn on_transfer_received(&self, operator: Address, from: Address, value: U256, data: Bytes) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::onTransferReceivedCall { operator, from, value, data }).await
+    }
+
+    pub async fn burn_repaid(&self) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::burnRepaidCall {}).await
+    }
+
+    pub async fn repaid(&self) -> Result<U256, ClientError> {
+        Ok(self.contract.call(IManager::repaidCall {}).await?._0)
+    }
+
+    pub async fn heal(&self, amount: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::healCall { amount }).await
+    }
+
+    pub async fn set_mint_limits(&self, limits: MintLimits) -> Result<TransactionReceipt, ClientError> {
+        let MintLimits { window_limit, window, block_limit } = limits;
+        self.contract.send(IManager::setMintLimitsCall { window_limit, window, block_limit }).await
+    }
+
+    pub async fn mint_limits(&self) -> Result<MintLimits, ClientError> {
+        let limits = self.contract.call(IManager::mintLimitsCall {}).await?;
+        Ok(MintLimits { window_limit: limits._0, window: limits._1, block_limit: limits._2 })
+    }
+
+    pub async fn available_to_mint(&self) -> Result<U256, ClientError> {
+        Ok(self.contract.call(IManager::availableToMintCall {}).await?._0)
+    }
+
+    pub async fn surplus(&self) -> Result<U256, ClientError> {
+        Ok(self.contract.call(IManager::surplusCall {}).await?._0)
+    }
+
+    pub async fn bad_debt(&self) -> Result<U256, ClientError> {
+        Ok(self.contract.call(IManager::badDebtCall {}).await?._0)
+    }
+
+    pub async fn set_governance(&self, new_governance: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::setGovernanceCall { new_governance }).await
+    }
+
+    pub async fn governance(&self) -> Result<Address, ClientError> {
+        Ok(self.contract.call(IManager::governanceCall {}).await?._0)
+    }
+
+    /// Value of all deposited collateral and the shUSD supply, in that order
+    pub async fn solvency(&self) -> Result<(U256, U256), ClientError> {
+        let solvency = self.contract.call(IManager::solvencyCall {}).await?;
+        Ok((solvency._0, solvency._1))
+    }
+
+    pub async fn position(&self, user: Address) -> Result<Position, ClientError> {
+        let position = self.contract.call(IManager::positionCall { user }).await?;
+        Ok(Position { deposited: position._0, minted: position._1 })
+    }
+
+    /// Collateral value over debt scaled by 1e18, compare with `MIN_COLLAT_RATIO`
+    pub async fn collat_ratio(&self, user: Address) -> Result<U256, ClientError> {
+        Ok(self.contract.call(IManager::collatRatioCall { user }).await?._0)
+    }
+
+    /// Moves `users`' positions out of the storage used before positions were packed
+    pub async fn migrate_positions(&self, users: Vec<Address>) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::migratePositionsCall { users }).await
+    }
+
+    pub async fn positions_packed(&self) -> Result<bool, ClientError> {
+        Ok(self.contract.call(IManager::positionsPackedCall {}).await?._0)
+    }
+
+    /// Mints a vault NFT to the sender, read its id from the `VaultOpened` log
+    pub async fn open_vault(&self) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::openVaultCall {}).await
+    }
+
+    pub async fn deposit_vault(&self, id: U256, amount: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::depositVaultCall { id, amount }).await
+    }
+
+    pub async fn burn_vault(&self, id: U256, amount: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::burnVaultCall { id, amount }).await
+    }
+
+    pub async fn mint_vault(&self, id: U256, amount: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::mintVaultCall { id, amount }).await
+    }
+
+    pub async fn withdraw_vault(&self, id: U256, amount: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::withdrawVaultCall { id, amount }).await
+    }
+
+    /// Vault `id`'s position, see `vault_key`
+    pub async fn vault_position(&self, id: U256) -> Result<Position, ClientError> {
+        self.position(vault_key(id)).await
+    }
+
+    pub async fn set_vault_nft(&self, vault_nft: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::setVaultNftCall { vault_nft }).await
+    }
+
+    pub async fn vault_nft(&self) -> Result<Address, ClientError> {
+        Ok(self.contract.call(IManager::vaultNftCall {}).await?._0)
+    }
+
+    /// `max_deviation` is a wad fraction, zero disables the price guard
+    pub async fn set_price_guard(&self, max_deviation: U256, window: u64) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::setPriceGuardCall { max_deviation, window }).await
+    }
+
+    /// The maximum deviation and its window
+    pub async fn price_guard(&self) -> Result<(U256, u64), ClientError> {
+        let guard = self.contract.call(IManager::priceGuardCall {}).await?;
+        Ok((guard._0, guard._1))
+    }
+
+    /// The last price the guard accepted and its timestamp
+    pub async fn last_good_price(&self) -> Result<(U256, u64), ClientError> {
+        let last = self.contract.call(IManager::lastGoodPriceCall {}).await?;
+        Ok((last._0, last._1))
+    }
+
+    pub async fn breaker_tripped(&self) -> Result<bool, ClientError> {
+        Ok(self.contract.call(IManager::breakerTrippedCall {}).await?._0)
+    }
+
+    /// Records a trip or reset of the breaker at the current oracle price
+    pub async fn poke_price(&self) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::pokePriceCall {}).await
+    }
+
+    pub async fn confirm_price(&self) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::confirmPriceCall {}).await
+    }
+
+    pub async fn set_guardian(&self, guardian: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::setGuardianCall { guardian }).await
+    }
+
+    pub async fn guardian(&self) -> Result<Address, ClientError> {
+        Ok(self.contract.call(IManager::guardianCall {}).await?._0)
+    }
+
+    pub async fn set_trusted_forwarder(&self, forwarder: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IManager::setTrustedForwarderCall { forwarder }).await
+    }
+
+    pub async fn trusted_forwarder(&self) -> Result<Address, ClientError> {
+        Ok(self.contract.call(IManager::trustedForwarderCall {}).await?._0)
+    }
+
+    pub async fn is_trusted_forwarder(&self, forwarder: Address) -> Result<bool, ClientError> {
+        Ok(self.contract.call(IManager::isTrustedForwarderCall { forwarder }).await?._0)
+    }
+}
+
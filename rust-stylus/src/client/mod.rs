@@ -0,0 +1,340 @@
+//! Typed clients for the deployed `Manager` and `ShUSD`.
+//!
+//! Calls are encoded with the `sol!` interfaces in [`manager`] and [`sh_usd`],
+//! which the tests check against the contracts' routers and exported ABIs, and
+//! reverts are decoded into the contracts' own error enums. Views go through
+//! `eth_call`, everything else is sent through the middleware and awaited
+//! until mined.
+pub mod manager;
+pub mod sh_usd;
+
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use core::fmt;
+use alloy_primitives::{Address, Bytes, U256};
+use alloy_sol_types::{SolCall, SolError};
+use ethers::providers::{Middleware, MiddlewareError};
+use ethers::types::{transaction::eip2718::TypedTransaction, TransactionReceipt, TransactionRequest, TxHash, H160};
+use crate::contracts::calls::{self, CallErrors};
+use crate::contracts::manager::{self as manager_contract, ManagerErrors};
+use crate::contracts::sh_usd::{self as sh_usd_contract, ShUSDErrors};
+use crate::access::{access_control::AccessControlUnauthorizedAccount, deployer::OnlyDeployer};
+use crate::token::erc20::{self, Erc20Error};
+
+pub use manager::{ManagerClient, Position};
+pub use sh_usd::ShUSDClient;
+
+/// Revert data decoded against the errors the contracts declare
+pub enum Revert {
+    Manager(ManagerErrors),
+    ShUSD(ShUSDErrors),
+    Erc20(Erc20Error),
+    Call(CallErrors),
+    /// None of the errors match, a panic or a revert from another contract
+    Unknown(Bytes),
+}
+
+/// `$decode` tries each of `$error` on the revert data and wraps the first that
+/// decodes, `$signature` gives back the Solidity signature of a variant
+macro_rules! decoder {
+    ($decode:ident, $signature:ident, $enum:ident, $wrap:ident { $($variant:ident($error:ty)),* $(,)? }) => {
+        fn $decode(data: &[u8]) -> Option<Revert> {
+            $(if let Ok(error) = <$error as SolError>::abi_decode(data, true) {
+                return Some(Revert::$wrap($enum::$variant(error)));
+            })*
+            None
+        }
+
+        fn $signature(error: &$enum) -> &'static str {
+            match error { $($enum::$variant(_) => <$error as SolError>::SIGNATURE,)* }
+        }
+    };
+}
+
+decoder!(manager_error, manager_signature, ManagerErrors, Manager {
+    Undercollateralized(manager_contract::Undercollateralized),
+    MintRateLimited(manager_contract::MintRateLimited),
+    OnlyGovernance(manager_contract::OnlyGovernance),
+    AlreadyInitialized(manager_contract::AlreadyInitialized),
+    CouldNotAdd(manager_contract::CouldNotAdd),
+    CouldNotSub(manager_contract::CouldNotSub),
+    CouldNotMul(manager_contract::CouldNotMul),
+    CouldNotDiv(manager_contract::CouldNotDiv),
+    ConversionFailure(manager_contract::ConversionFailure),
+    NotUpgradeable(manager_contract::NotUpgradeable),
+    OnlyShUSD(manager_contract::OnlyShUSD),
+    InvalidRepayData(manager_contract::InvalidRepayData),
+    PositionOverflow(manager_contract::PositionOverflow),
+    VaultsDisabled(manager_contract::VaultsDisabled),
+    NotVaultOwner(manager_contract::NotVaultOwner),
+    PriceSuspicious(manager_contract::PriceSuspicious),
+    OnlyGuardian(manager_contract::OnlyGuardian),
+    OnlyDeployer(OnlyDeployer),
+    UnauthorizedCallContext(manager_contract::UnauthorizedCallContext),
+});
+
+decoder!(sh_usd_error, sh_usd_signature, ShUSDErrors, ShUSD {
+    AlreadyInitialized(sh_usd_contract::AlreadyInitialized),
+    OnlyDeployer(OnlyDeployer),
+    Unauthorized(AccessControlUnauthorizedAccount),
+    MinterCapExceeded(sh_usd_contract::MinterCapExceeded),
+    IssuancePaused(sh_usd_contract::IssuancePaused),
+    ERC20MintErr(sh_usd_contract::ERC20MintError),
+    ERC20BurnErr(sh_usd_contract::ERC20BurnError),
+    ERC20TransferErr(sh_usd_contract::ERC20TransferError),
+    NotFrozen(sh_usd_contract::NotFrozen),
+    AccountFrozen(erc20::AccountFrozen),
+    MaxSupplyExceeded(erc20::MaxSupplyExceeded),
+    OnlyTimelock(sh_usd_contract::OnlyTimelock),
+    InvalidTimelock(sh_usd_contract::InvalidTimelock),
+});
+
+decoder!(erc20_error, erc20_signature, Erc20Error, Erc20 {
+    InsufficientBalance(erc20::InsufficientBalance),
+    InsufficientAllowance(erc20::InsufficientAllowance),
+    AccountFrozen(erc20::AccountFrozen),
+    BalanceOverflow(erc20::BalanceOverflow),
+    SupplyOverflow(erc20::SupplyOverflow),
+    SupplyUnderflow(erc20::SupplyUnderflow),
+    MaxSupplyExceeded(erc20::MaxSupplyExceeded),
+    ERC1363InvalidReceiver(erc20::ERC1363InvalidReceiver),
+    ERC1363InvalidSpender(erc20::ERC1363InvalidSpender),
+});
+
+decoder!(call_error, call_signature, CallErrors, Call {
+    CouldNotCall(calls::CouldNotCall),
+    CouldNotUnpackBool(calls::CouldNotUnpackBool),
+});
+
+impl Revert {
+    /// Decodes revert data from `Manager`, which bubbles up the reverts of the
+    /// WETH and shUSD calls it makes
+    pub fn from_manager(data: &[u8]) -> Self {
+        manager_error(data).or_else(|| erc20_error(data)).or_else(|| sh_usd_error(data)).or_else(|| call_error(data))
+            .unwrap_or_else(|| Revert::Unknown(Bytes::copy_from_slice(data)))
+    }
+
+    /// Decodes revert data from `ShUSD`, its own errors first then the inherited ERC-20 ones
+    pub fn from_sh_usd(data: &[u8]) -> Self {
+        sh_usd_error(data).or_else(|| erc20_error(data)).unwrap_or_else(|| Revert::Unknown(Bytes::copy_from_slice(data)))
+    }
+
+    /// Name of the Solidity error, `Unknown` if none matched
+    pub fn name(&self) -> &'static str {
+        let signature = match self {
+            Revert::Manager(error) => manager_signature(error),
+            Revert::ShUSD(error) => sh_usd_signature(error),
+            Revert::Erc20(error) => erc20_signature(error),
+            Revert::Call(error) => call_signature(error),
+            Revert::Unknown(_) => return "Unknown",
+        };
+        &signature[..signature.find('(').unwrap_or(signature.len())]
+    }
+}
+
+impl fmt::Debug for Revert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Revert::Unknown(data) => write!(f, "Unknown({data})"),
+            known => f.write_str(known.name()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The call reverted, or would have when the transaction was estimated
+    Revert(Revert),
+    /// The transaction was mined but reverted, nodes don't return its revert data
+    Reverted(TxHash),
+    /// The transaction left the mempool without being mined
+    Dropped,
+    /// The node or a middleware failed
+    Middleware(String),
+    /// The return data doesn't match the interface
+    Decode(alloy_sol_types::Error),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Revert(revert) => write!(f, "reverted with {revert:?}"),
+            ClientError::Reverted(hash) => write!(f, "transaction {hash:?} reverted"),
+            ClientError::Dropped => f.write_str("transaction dropped"),
+            ClientError::Middleware(message) => f.write_str(message),
+            ClientError::Decode(error) => write!(f, "could not decode the return data: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+pub fn to_ethers(address: Address) -> H160 {
+    H160(address.0 .0)
+}
+
+/// A deployed contract, the calls shared by the typed clients
+struct Contract<M> {
+    address: Address,
+    client: Arc<M>,
+    decode: fn(&[u8]) -> Revert,
+}
+
+impl<M: Middleware> Contract<M> {
+    fn error(&self, error: M::Error) -> ClientError {
+        match error.as_error_response().and_then(|response| response.as_revert_data()) {
+            Some(data) => ClientError::Revert((self.decode)(&data)),
+            None => ClientError::Middleware(error.to_string()),
+        }
+    }
+
+    fn request<C: SolCall>(&self, call: &C) -> TransactionRequest {
+        TransactionRequest::new().to(to_ethers(self.address)).data(call.abi_encode())
+    }
+
+    async fn call<C: SolCall>(&self, call: C) -> Result<C::Return, ClientError> {
+        let tx: TypedTransaction = self.request(&call).into();
+        let data = self.client.call(&tx, None).await.map_err(|error| self.error(error))?;
+        C::abi_decode_returns(&data, true).map_err(ClientError::Decode)
+    }
+
+    async fn estimate<C: SolCall>(&self, call: C) -> Result<U256, ClientError> {
+        let tx: TypedTransaction = self.request(&call).into();
+        let gas = self.client.estimate_gas(&tx, None).await.map_err(|error| self.error(error))?;
+        Ok(U256::from_limbs(gas.0))
+    }
+
+    async fn send<C: SolCall>(&self, call: C) -> Result<TransactionReceipt, ClientError> {
+        let pending = self.client.send_transaction(self.request(&call), None).await.map_err(|error| self.error(error))?;
+        let receipt = pending.await.map_err(|error| ClientError::Middleware(error.to_string()))?.ok_or(ClientError::Dropped)?;
+        match receipt.status.map(|status| status.as_u64()) {
+            Some(1) => Ok(receipt),
+            _ => Err(ClientError::Reverted(receipt.transaction_hash)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use alloy_primitives::address;
+    use ethers::providers::{JsonRpcError, MockResponse, Provider};
+    use stylus_sdk::abi::Router;
+    use stylus_sdk::testing::TestVM;
+    use crate::contracts::{manager::Manager, sh_usd::ShUSD};
+    use manager::IManager::IManagerCalls;
+    use sh_usd::IShUSD::IShUSDCalls;
+
+    /// Every function of the interfaces is routed by the contract, so the
+    /// client can't call something that doesn't exist
+    #[test]
+    fn interfaces_match_the_routers() {
+        let vm = TestVM::new();
+        let mut manager = Manager::from(&vm);
+        for selector in IManagerCalls::SELECTORS {
+            let routed = <Manager as Router<Manager>>::route(&mut manager, u32::from_be_bytes(*selector), &[]);
+            assert!(routed.is_some(), "Manager doesn't route {}", hex::encode(selector));
+        }
+        let mut sh_usd = ShUSD::from(&vm);
+        for selector in IShUSDCalls::SELECTORS {
+            let routed = <ShUSD as Router<ShUSD>>::route(&mut sh_usd, u32::from_be_bytes(*selector), &[]);
+            assert!(routed.is_some(), "ShUSD doesn't route {}", hex::encode(selector));
+        }
+    }
+
+    /// `export-abi` turns on the SDK's debug logging, which panics off chain on
+    /// any call the router rejects, so the rest of the tests run without it.
+    /// `export-abi.sh` runs these.
+    #[cfg(feature = "export-abi")]
+    mod exported {
+        use alloc::collections::BTreeSet;
+        use alloc::string::{String, ToString};
+        use alloc::vec;
+        use alloc::vec::Vec;
+        use core::fmt;
+        use core::marker::PhantomData;
+        use stylus_sdk::abi::GenerateAbi;
+        use crate::contracts::{manager::Manager, sh_usd::ShUSD};
+
+        /// The interface `#[public]` generates for `C`, inherited functions included
+        struct Exported<C>(PhantomData<C>);
+
+        impl<C: GenerateAbi> fmt::Display for Exported<C> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                C::fmt_abi(f)
+            }
+        }
+
+        /// Every function declared in the Solidity `source`, reduced to its name,
+        /// parameter types, mutability and return types
+        fn functions(source: &str) -> BTreeSet<String> {
+            let types = |list: &str| list.split(',').filter_map(|param| param.split_whitespace().next()).collect::<Vec<_>>().join(",");
+            source.lines().filter_map(|line| line.trim().strip_prefix("function ")).map(|function| {
+                let (name, rest) = function.split_once('(').unwrap();
+                let (params, rest) = rest.split_once(')').unwrap();
+                let (modifiers, returns) = rest.trim_end_matches(';').split_once("returns").unwrap_or((rest.trim_end_matches(';'), ""));
+                let returns = returns.trim().trim_start_matches('(').trim_end_matches(')');
+                let mut parts = vec![format!("{name}({})", types(params))];
+                parts.extend(modifiers.split_whitespace().filter(|modifier| *modifier != "external").map(String::from));
+                parts.push(format!("returns ({})", types(returns)));
+                parts.join(" ")
+            }).collect()
+        }
+
+        /// The client's `sol!` interfaces declare exactly what the contracts export,
+        /// with the same mutability and return types
+        #[test]
+        fn interfaces_match_the_exported_abis() {
+            for (contract, client, exported) in [
+                ("Manager", include_str!("manager.rs"), Exported::<Manager>(PhantomData).to_string()),
+                ("ShUSD", include_str!("sh_usd.rs"), Exported::<ShUSD>(PhantomData).to_string()),
+            ] {
+                let (client, exported) = (functions(client), functions(&exported));
+                assert!(!exported.is_empty());
+                let missing: Vec<_> = exported.difference(&client).collect();
+                let unknown: Vec<_> = client.difference(&exported).collect();
+                assert!(missing.is_empty() && unknown.is_empty(), "{contract} exports {missing:?} but the client declares {unknown:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_reverts_per_contract() {
+        let data: Vec<u8> = ManagerErrors::Undercollateralized(manager_contract::Undercollateralized {}).into();
+        assert!(matches!(Revert::from_manager(&data), Revert::Manager(ManagerErrors::Undercollateralized(_))));
+
+        // Bubbled up from the WETH transfer
+        let insufficient = erc20::InsufficientBalance { from: Address::ZERO, have: U256::ZERO, want: U256::from(1) };
+        let data: Vec<u8> = Erc20Error::InsufficientBalance(insufficient).into();
+        let revert = Revert::from_manager(&data);
+        assert_eq!(revert.name(), "InsufficientBalance");
+        assert!(matches!(revert, Revert::Erc20(Erc20Error::InsufficientBalance(error)) if error.want == U256::from(1)));
+
+        // Both declare `AlreadyInitialized()`, the contract called decides
+        let data: Vec<u8> = ManagerErrors::AlreadyInitialized(manager_contract::AlreadyInitialized {}).into();
+        assert!(matches!(Revert::from_sh_usd(&data), Revert::ShUSD(ShUSDErrors::AlreadyInitialized(_))));
+
+        assert!(matches!(Revert::from_sh_usd(&[0xde, 0xad, 0xbe, 0xef]), Revert::Unknown(data) if data.len() == 4));
+    }
+
+    #[tokio::test]
+    async fn views_and_reverts_through_the_provider() {
+        let (provider, mock) = Provider::mocked();
+        let manager = ManagerClient::new(address!("000000000000000000000000000000000000a001"), Arc::new(provider));
+        let user = address!("000000000000000000000000000000000000c001");
+
+        let returns = [U256::from(3), U256::from(4)].map(|word| word.to_be_bytes::<32>()).concat();
+        mock.push::<ethers::types::Bytes, _>(ethers::types::Bytes::from(returns)).unwrap();
+        assert_eq!(manager.position(user).await.unwrap(), Position { deposited: U256::from(3), minted: U256::from(4) });
+
+        let data: Vec<u8> = ManagerErrors::Undercollateralized(manager_contract::Undercollateralized {}).into();
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted".into(),
+            data: Some(serde_json::Value::String(alloc::format!("0x{}", hex::encode(data)))),
+        }));
+        let error = manager.collat_ratio(user).await.unwrap_err();
+        assert_eq!(error.to_string(), "reverted with Undercollateralized");
+    }
+}
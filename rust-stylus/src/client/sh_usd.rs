@@ -0,0 +1,206 @@
+//! Client for `ShUSD`, including what it inherits from `Erc20` and `AccessControl`.
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_sol_types::sol;
+use ethers::providers::Middleware;
+use ethers::types::TransactionReceipt;
+use super::{ClientError, Contract, Revert};
+
+sol! {
+    /// What `ShUSD`'s `#[public]` impl exports, inherited functions included
+    interface IShUSD {
+        function init(address manager) external;
+        function mint(address to, uint256 amount) external;
+        function burn(address from, uint256 amount) external;
+        function setMinterCap(address minter, uint256 cap) external;
+        function pause() external;
+        function unpause() external;
+        function minterCap(address minter) external view returns (uint256);
+        function mintedBy(address minter) external view returns (uint256);
+        function paused() external view returns (bool);
+        function freeze(address account) external;
+        function unfreeze(address account) external;
+        function seize(address from, address to) external returns (uint256);
+        function wipeFrozen(address account) external returns (uint256);
+        function isFrozen(address account) external view returns (bool);
+        function setTimelock(address timelock) external;
+        function timelock() external view returns (address);
+        function setTrustedForwarder(address forwarder) external;
+        function trustedForwarder() external view returns (address);
+        function isTrustedForwarder(address forwarder) external view returns (bool);
+
+        function name() external pure returns (string);
+        function symbol() external pure returns (string);
+        function decimals() external pure returns (uint8);
+        function totalSupply() external view returns (uint256);
+        function balanceOf(address owner) external view returns (uint256);
+        function transfer(address to, uint256 value) external returns (bool);
+        function transferFrom(address from, address to, uint256 value) external returns (bool);
+        function approve(address spender, uint256 value) external returns (bool);
+        function allowance(address owner, address spender) external view returns (uint256);
+        function transferAndCall(address to, uint256 value, bytes data) external returns (bool);
+        function transferFromAndCall(address from, address to, uint256 value, bytes data) external returns (bool);
+        function approveAndCall(address spender, uint256 value, bytes data) external returns (bool);
+
+        function hasRole(bytes32 role, address account) external view returns (bool);
+        function grantRole(bytes32 role, address account) external;
+        function revokeRole(bytes32 role, address account) external;
+        function renounceRole(bytes32 role) external;
+    }
+}
+
+pub struct ShUSDClient<M> {
+    contract: Contract<M>,
+}
+
+impl<M: Middleware> ShUSDClient<M> {
+    pub fn new(address: Address, client: Arc<M>) -> Self {
+        Self { contract: Contract { address, client, decode: Revert::from_sh_usd } }
+    }
+
+    pub fn address(&self) -> Address {
+        self.contract.address
+    }
+
+    pub async fn init(&self, manager: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::initCall { manager }).await
+    }
+
+    pub async fn mint(&self, to: Address, amount: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::mintCall { to, amount }).await
+    }
+
+    pub async fn burn(&self, from: Address, amount: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::burnCall { from, amount }).await
+    }
+
+    pub async fn set_minter_cap(&self, minter: Address, cap: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::setMinterCapCall { minter, cap }).await
+    }
+
+    pub async fn pause(&self) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::pauseCall {}).await
+    }
+
+    pub async fn unpause(&self) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::unpauseCall {}).await
+    }
+
+    pub async fn minter_cap(&self, minter: Address) -> Result<U256, ClientError> {
+        Ok(self.contract.call(IShUSD::minterCapCall { minter }).await?._0)
+    }
+
+    pub async fn minted_by(&self, minter: Address) -> Result<U256, ClientError> {
+        Ok(self.contract.call(IShUSD::mintedByCall { minter }).await?._0)
+    }
+
+    pub async fn paused(&self) -> Result<bool, ClientError> {
+        Ok(self.contract.call(IShUSD::pausedCall {}).await?._0)
+    }
+
+    pub async fn freeze(&self, account: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::freezeCall { account }).await
+    }
+
+    pub async fn unfreeze(&self, account: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::unfreezeCall { account }).await
+    }
+
+    pub async fn seize(&self, from: Address, to: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::seizeCall { from, to }).await
+    }
+
+    pub async fn wipe_frozen(&self, account: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::wipeFrozenCall { account }).await
+    }
+
+    pub async fn is_frozen(&self, account: Address) -> Result<bool, ClientError> {
+        Ok(self.contract.call(IShUSD::isFrozenCall { account }).await?._0)
+    }
+
+    pub async fn set_timelock(&self, timelock: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::setTimelockCall { timelock }).await
+    }
+
+    pub async fn timelock(&self) -> Result<Address, ClientError> {
+        Ok(self.contract.call(IShUSD::timelockCall {}).await?._0)
+    }
+
+    pub async fn set_trusted_forwarder(&self, forwarder: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::setTrustedForwarderCall { forwarder }).await
+    }
+
+    pub async fn trusted_forwarder(&self) -> Result<Address, ClientError> {
+        Ok(self.contract.call(IShUSD::trustedForwarderCall {}).await?._0)
+    }
+
+    pub async fn is_trusted_forwarder(&self, forwarder: Address) -> Result<bool, ClientError> {
+        Ok(self.contract.call(IShUSD::isTrustedForwarderCall { forwarder }).await?._0)
+    }
+
+    pub async fn name(&self) -> Result<String, ClientError> {
+        Ok(self.contract.call(IShUSD::nameCall {}).await?._0)
+    }
+
+    pub async fn symbol(&self) -> Result<String, ClientError> {
+        Ok(self.contract.call(IShUSD::symbolCall {}).await?._0)
+    }
+
+    pub async fn decimals(&self) -> Result<u8, ClientError> {
+        Ok(self.contract.call(IShUSD::decimalsCall {}).await?._0)
+    }
+
+    pub async fn total_supply(&self) -> Result<U256, ClientError> {
+        Ok(self.contract.call(IShUSD::totalSupplyCall {}).await?._0)
+    }
+
+    pub async fn balance_of(&self, owner: Address) -> Result<U256, ClientError> {
+        Ok(self.contract.call(IShUSD::balanceOfCall { owner }).await?._0)
+    }
+
+    pub async fn transfer(&self, to: Address, value: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::transferCall { to, value }).await
+    }
+
+    pub async fn transfer_from(&self, from: Address, to: Address, value: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::transferFromCall { from, to, value }).await
+    }
+
+    pub async fn approve(&self, spender: Address, value: U256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::approveCall { spender, value }).await
+    }
+
+    pub async fn allowance(&self, owner: Address, spender: Address) -> Result<U256, ClientError> {
+        Ok(self.contract.call(IShUSD::allowanceCall { owner, spender }).await?._0)
+    }
+
+    /// Repays debt when `to` is the manager, `data` is empty or the abi encoded user to repay for
+    pub async fn transfer_and_call(&self, to: Address, value: U256, data: Bytes) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::transferAndCallCall { to, value, data }).await
+    }
+
+    pub async fn transfer_from_and_call(&self, from: Address, to: Address, value: U256, data: Bytes) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::transferFromAndCallCall { from, to, value, data }).await
+    }
+
+    pub async fn approve_and_call(&self, spender: Address, value: U256, data: Bytes) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::approveAndCallCall { spender, value, data }).await
+    }
+
+    pub async fn has_role(&self, role: B256, account: Address) -> Result<bool, ClientError> {
+        Ok(self.contract.call(IShUSD::hasRoleCall { role, account }).await?._0)
+    }
+
+    pub async fn grant_role(&self, role: B256, account: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::grantRoleCall { role, account }).await
+    }
+
+    pub async fn revoke_role(&self, role: B256, account: Address) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::revokeRoleCall { role, account }).await
+    }
+
+    pub async fn renounce_role(&self, role: B256) -> Result<TransactionReceipt, ClientError> {
+        self.contract.send(IShUSD::renounceRoleCall { role }).await
+    }
+}
@@ -0,0 +1,76 @@
+//! EIP-2771 meta-transactions, the sender of calls relayed by a trusted forwarder.
+//!
+//! The forwarder appends the address that signed a request to the calldata of
+//! the call it relays. Stylus' router rejects calldata longer than the
+//! arguments and `#[public]` methods never see it whole, so contracts taking
+//! relayed calls use [`route`] as their entrypoint. It takes the sender off
+//! calls from their forwarder before routing them, and [`msg_sender`] returns
+//! it in place of the forwarder for the rest of the call.
+use alloc::vec::Vec;
+use core::borrow::BorrowMut;
+use core::cell::RefCell;
+use alloy_primitives::{Address, U256};
+use stylus_sdk::abi::{router_entrypoint, Router};
+use stylus_sdk::host::VM;
+use stylus_sdk::prelude::*;
+use stylus_sdk::storage::StorageAddress;
+use stylus_sdk::stylus_core::ValueDenier;
+use stylus_sdk::ArbResult;
+
+/// Abi encoded calls are a selector and whole words, the 20 bytes the forwarder
+/// appends tell relayed calls apart from its own such as `isTrustedForwarder`
+fn is_relayed(input: &[u8]) -> bool {
+    input.len() >= 4 + 20 && (input.len() - 4) % 32 == 20
+}
+
+/// Contracts in a relayed call and the sender they were relayed for. A wasm
+/// instance only ever runs one call, the tests run a whole chain per thread.
+#[cfg(target_arch = "wasm32")]
+static RELAYED: Relayed = Relayed(RefCell::new(Vec::new()));
+
+#[cfg(target_arch = "wasm32")]
+struct Relayed(RefCell<Vec<(Address, Address)>>);
+
+// Wasm contracts are single threaded
+#[cfg(target_arch = "wasm32")]
+unsafe impl Sync for Relayed {}
+
+#[cfg(not(target_arch = "wasm32"))]
+std::thread_local! {
+    static RELAYED: RefCell<Vec<(Address, Address)>> = const { RefCell::new(Vec::new()) };
+}
+
+fn with_relayed<R>(f: impl FnOnce(&mut Vec<(Address, Address)>) -> R) -> R {
+    #[cfg(target_arch = "wasm32")]
+    return f(&mut RELAYED.0.borrow_mut());
+    #[cfg(not(target_arch = "wasm32"))]
+    RELAYED.with(|relayed| f(&mut relayed.borrow_mut()))
+}
+
+/// The caller, or the account the trusted forwarder relayed this call for
+pub fn msg_sender(host: &dyn Host) -> Address {
+    let this = host.contract_address();
+    with_relayed(|relayed| relayed.iter().rev().find(|(to, _)| *to == this).map(|(_, from)| *from))
+        .unwrap_or_else(|| host.msg_sender())
+}
+
+/// Routes `input` to `C` like `#[entrypoint]` does. Calls relayed by the
+/// forwarder stored at `slot` and `offset` lose their last 20 bytes, which are
+/// the sender while they run.
+pub fn route<C>(mut input: Vec<u8>, host: VM, slot: U256, offset: u8) -> ArbResult
+where
+    C: Router<C, Storage = C> + StorageType + TopLevelStorage + BorrowMut<C> + ValueDenier,
+{
+    let forwarder = unsafe { StorageAddress::new(slot, offset, host.clone()) };
+    let trusted = forwarder.get();
+    if trusted.is_zero() || forwarder.vm().msg_sender() != trusted || !is_relayed(&input) {
+        return router_entrypoint::<C, C>(input, host);
+    }
+    let sender = Address::from_slice(&input[input.len() - 20..]);
+    input.truncate(input.len() - 20);
+    let this = forwarder.vm().contract_address();
+    with_relayed(|relayed| relayed.push((this, sender)));
+    let result = router_entrypoint::<C, C>(input, host);
+    with_relayed(|relayed| relayed.pop());
+    result
+}
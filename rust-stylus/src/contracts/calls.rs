@@ -1,6 +1,7 @@
 use alloc::vec::Vec;
 use alloy_primitives::Address;
-use stylus_sdk::{prelude::*, alloy_primitives::{I256, U256}, call::RawCall, alloy_sol_types::{sol, SolCall}};
+use stylus_sdk::{prelude::*, alloy_primitives::{B256, I256, U256}, alloy_sol_types::{sol, SolCall}};
+use stylus_sdk::stylus_core::{calls::context::Call, Host};
 
 sol! {
     error CouldNotCall();
@@ -19,39 +20,132 @@ sol! {
     function transfer(address to, uint256 value) external returns (bool);
     function burn(address from, uint256 amount) external;
     function mint(address to, uint256 amount) external;
+    function totalSupply() external view returns (uint256);
+    function decimals() external view returns (uint8);
+    function proxiableUUID() external view returns (bytes32);
+    function getPastVotes(address account, uint256 timepoint) external view returns (uint256);
+    function getPastTotalSupply(uint256 timepoint) external view returns (uint256);
+    function minDelay() external view returns (uint64);
+    function queue(address target, bytes data, uint64 eta) external returns (uint256);
+    function execute(uint256 id) external returns (bytes);
+    function isTrustedForwarder(address forwarder) external view returns (bool);
+    function observe(uint32[] secondsAgos) external view returns (int56[] tickCumulatives, uint160[] secondsPerLiquidityCumulativeX128s);
+
+    interface IVaultNft {
+        function mint(address to) external returns (uint256);
+        function ownerOf(uint256 id) external view returns (address);
+    }
+}
+
+// Every call goes through the contract's host rather than raw hostios, so the
+// contracts can be wired together on a test VM.
+
+fn call(host: &dyn Host, to: Address, data: &[u8]) -> Result<Vec<u8>, Vec<u8>> {
+    Ok(host.call(&Call::new(), to, data)?)
+}
+
+fn static_call(host: &dyn Host, to: Address, data: &[u8]) -> Result<Vec<u8>, Vec<u8>> {
+    Ok(host.static_call(&Call::new(), to, data)?)
+}
+
+pub fn latest_answer_call(host: &dyn Host, oracle: Address) -> Result<I256, Vec<u8>> {
+    I256::try_from_be_slice(&static_call(host, oracle, &latestAnswerCall {}.abi_encode())?).ok_or(CallErrors::CouldNotCall(CouldNotCall {}).into())
+}
+
+/// Tick cumulatives of a Uniswap V3 style `pool`, one per entry of `seconds_agos`
+pub fn observe_call(host: &dyn Host, pool: Address, seconds_agos: Vec<u32>) -> Result<Vec<i64>, Vec<u8>> {
+    let data = static_call(host, pool, &observeCall { secondsAgos: seconds_agos.clone() }.abi_encode())?;
+    let cumulatives = observeCall::abi_decode_returns(&data, true)
+        .map_err(|_| CallErrors::CouldNotCall(CouldNotCall {}))?.tickCumulatives;
+    assert_or!(cumulatives.len() == seconds_agos.len(), CallErrors::CouldNotCall(CouldNotCall {}));
+    Ok(cumulatives.into_iter().map(|cumulative| cumulative.as_i64()).collect())
+}
+
+/// Whether `target` takes calls from `forwarder` as relayed, false if it can't tell
+pub fn is_trusted_forwarder_call(host: &dyn Host, target: Address, forwarder: Address) -> bool {
+    static_call(host, target, &isTrustedForwarderCall { forwarder }.abi_encode()).ok()
+        .and_then(|data| isTrustedForwarderCall::abi_decode_returns(&data, true).ok())
+        .is_some_and(|r| r._0)
+}
+
+pub fn total_supply_call(host: &dyn Host, token: Address) -> Result<U256, Vec<u8>> {
+    U256::try_from_be_slice(&static_call(host, token, &totalSupplyCall {}.abi_encode())?).ok_or(CallErrors::CouldNotCall(CouldNotCall {}).into())
+}
+
+pub fn decimals_call(host: &dyn Host, token: Address) -> Result<u8, Vec<u8>> {
+    let data = static_call(host, token, &decimalsCall {}.abi_encode())?;
+    decimalsCall::abi_decode_returns(&data, true).map(|r| r._0).map_err(|_| CallErrors::CouldNotCall(CouldNotCall {}).into())
+}
+
+pub fn proxiable_uuid_call(host: &dyn Host, implementation: Address) -> Result<B256, Vec<u8>> {
+    let data = static_call(host, implementation, &proxiableUUIDCall {}.abi_encode());
+    data.ok().filter(|data| data.len() == 32).map(|data| B256::from_slice(&data)).ok_or(CallErrors::CouldNotCall(CouldNotCall {}).into())
+}
+
+pub fn get_past_votes_call(host: &dyn Host, token: Address, account: Address, timepoint: U256) -> Result<U256, Vec<u8>> {
+    let data = static_call(host, token, &getPastVotesCall { account, timepoint }.abi_encode())?;
+    getPastVotesCall::abi_decode_returns(&data, true).map(|r| r._0).map_err(|_| CallErrors::CouldNotCall(CouldNotCall {}).into())
+}
+
+pub fn get_past_total_supply_call(host: &dyn Host, token: Address, timepoint: U256) -> Result<U256, Vec<u8>> {
+    let data = static_call(host, token, &getPastTotalSupplyCall { timepoint }.abi_encode())?;
+    getPastTotalSupplyCall::abi_decode_returns(&data, true).map(|r| r._0).map_err(|_| CallErrors::CouldNotCall(CouldNotCall {}).into())
+}
+
+pub fn min_delay_call(host: &dyn Host, timelock: Address) -> Result<u64, Vec<u8>> {
+    let data = static_call(host, timelock, &minDelayCall {}.abi_encode())?;
+    minDelayCall::abi_decode_returns(&data, true).map(|r| r._0).map_err(|_| CallErrors::CouldNotCall(CouldNotCall {}).into())
+}
+
+/// Queues `data` on `target` in the timelock, returns the operation id
+pub fn queue_call(host: &dyn Host, timelock: Address, target: Address, data: &[u8], eta: u64) -> Result<U256, Vec<u8>> {
+    let data = call(host, timelock, &queueCall { target, data: data.to_vec().into(), eta }.abi_encode())?;
+    queueCall::abi_decode_returns(&data, true).map(|r| r._0).map_err(|_| CallErrors::CouldNotCall(CouldNotCall {}).into())
 }
 
-pub fn latest_answer_call(oracle: Address) -> Result<I256, Vec<u8>> {
-    I256::try_from_be_slice(unsafe { &RawCall::new().call(oracle, &latestAnswerCall {}.abi_encode()).unwrap()}).ok_or(CallErrors::CouldNotCall(CouldNotCall {}).into())
+pub fn execute_call(host: &dyn Host, timelock: Address, id: U256) -> Result<Vec<u8>, Vec<u8>> {
+    let data = call(host, timelock, &executeCall { id }.abi_encode())?;
+    executeCall::abi_decode_returns(&data, true).map(|r| r._0.to_vec()).map_err(|_| CallErrors::CouldNotCall(CouldNotCall {}).into())
 }
 
-pub fn transfer_from_call(token: Address, from: Address, to: Address, value: U256) -> Result<(), Vec<u8>> {
-    unpack_bool_safe(unsafe { &RawCall::new().call(token, &transferFromCall {
+pub fn transfer_from_call(host: &dyn Host, token: Address, from: Address, to: Address, value: U256) -> Result<(), Vec<u8>> {
+    unpack_bool_safe(&call(host, token, &transferFromCall {
         from,
         to,
         value,
-    }.abi_encode()).unwrap()})
+    }.abi_encode())?)
 }
 
-pub fn transfer_call(token: Address, to: Address, value: U256) -> Result<(), Vec<u8>> {
-    unpack_bool_safe(unsafe { &RawCall::new().call(token, &transferCall {
+pub fn transfer_call(host: &dyn Host, token: Address, to: Address, value: U256) -> Result<(), Vec<u8>> {
+    unpack_bool_safe(&call(host, token, &transferCall {
         to,
         value,
-    }.abi_encode()).unwrap()})
+    }.abi_encode())?)
 }
 
-pub fn mint_call(token: Address, to: Address, amount: U256) -> Result<(), Vec<u8>> {
-    unpack_bool_safe(unsafe { &RawCall::new().call(token, &mintCall {
+pub fn mint_call(host: &dyn Host, token: Address, to: Address, amount: U256) -> Result<(), Vec<u8>> {
+    unpack_bool_safe(&call(host, token, &mintCall {
         to,
         amount,
-    }.abi_encode()).unwrap()})
+    }.abi_encode())?)
 }
 
-pub fn burn_call(token: Address, from: Address, amount: U256) -> Result<(), Vec<u8>> {
-    unpack_bool_safe(unsafe { &RawCall::new().call(token, &burnCall {
+pub fn burn_call(host: &dyn Host, token: Address, from: Address, amount: U256) -> Result<(), Vec<u8>> {
+    unpack_bool_safe(&call(host, token, &burnCall {
         from,
         amount,
-    }.abi_encode()).unwrap()})
+    }.abi_encode())?)
+}
+
+/// Mints the next vault NFT to `to`, returns its id
+pub fn mint_vault_call(host: &dyn Host, vault_nft: Address, to: Address) -> Result<U256, Vec<u8>> {
+    let data = call(host, vault_nft, &IVaultNft::mintCall { to }.abi_encode())?;
+    IVaultNft::mintCall::abi_decode_returns(&data, true).map(|r| r._0).map_err(|_| CallErrors::CouldNotCall(CouldNotCall {}).into())
+}
+
+pub fn owner_of_call(host: &dyn Host, vault_nft: Address, id: U256) -> Result<Address, Vec<u8>> {
+    let data = static_call(host, vault_nft, &IVaultNft::ownerOfCall { id }.abi_encode())?;
+    IVaultNft::ownerOfCall::abi_decode_returns(&data, true).map(|r| r._0).map_err(|_| CallErrors::CouldNotCall(CouldNotCall {}).into())
 }
 
 pub fn unpack_bool_safe(data: &[u8]) -> Result<(), Vec<u8>> {
@@ -59,4 +153,4 @@ pub fn unpack_bool_safe(data: &[u8]) -> Result<(), Vec<u8>> {
         None | Some(1) => Ok(()),
         _ => Err(CallErrors::CouldNotUnpackBool(CouldNotUnpackBool {}).into()),
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,121 @@
+//! EIP-2771 forwarder relaying EIP-712 signed calls.
+//!
+//! Anyone holding a `ForwardRequest` signed by `from` can have it executed,
+//! paying the gas for them. The call reaches `to` with `from` appended to the
+//! calldata, which contracts trusting this forwarder take as the sender. Each
+//! request is signed over the signer's current nonce, so it runs at most once,
+//! and only before its deadline.
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use alloy_primitives::{address, Address, B256, U256};
+use alloy_sol_types::{sol, Eip712Domain, SolStruct, SolValue};
+use stylus_sdk::{abi::Bytes, prelude::*};
+use stylus_sdk::storage::{StorageMap, StorageU256};
+use stylus_sdk::stylus_core::calls::context::Call;
+use crate::contracts::calls;
+
+/// The ecrecover precompile
+const ECRECOVER: Address = address!("0000000000000000000000000000000000000001");
+pub const NAME: &str = "Microstable Forwarder";
+
+sol! {
+    event ExecutedForwardRequest(address indexed signer, address indexed target, uint256 nonce);
+
+    error ERC2771ForwarderExpiredRequest(uint256 deadline);
+    error ERC2771ForwarderInvalidSigner(address signer, address from);
+    error ERC2771UntrustfulTarget(address target, address forwarder);
+
+    struct ForwardRequest {
+        address from;
+        address to;
+        uint256 nonce;
+        uint256 deadline;
+        bytes data;
+    }
+}
+
+#[derive(SolidityError)]
+pub enum ForwarderErrors {
+    ERC2771ForwarderExpiredRequest(ERC2771ForwarderExpiredRequest),
+    ERC2771ForwarderInvalidSigner(ERC2771ForwarderInvalidSigner),
+    ERC2771UntrustfulTarget(ERC2771UntrustfulTarget),
+}
+
+#[cfg_attr(feature = "forwarder", stylus_sdk::prelude::entrypoint)]
+#[storage]
+pub struct Forwarder {
+    nonces: StorageMap<Address, StorageU256>,
+}
+
+#[cfg_attr(any(feature = "forwarder", test), stylus_sdk::prelude::public)]
+#[cfg(any(feature = "forwarder", test))]
+impl Forwarder {
+    /// Calls `to` with `data` on behalf of `from`, who signed the request over their
+    /// current nonce. Returns what `to` returned and bubbles up its reverts.
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute(&mut self, from: Address, to: Address, deadline: U256, data: Bytes, v: u8, r: B256, s: B256) -> Result<Bytes, Vec<u8>> {
+        let nonce = self.nonces.get(from);
+        self.check(&ForwardRequest { from, to, nonce, deadline, data: data.0.clone().into() }, v, r, s)?;
+        self.nonces.insert(from, nonce + U256::from(1));
+        let mut calldata = data.0;
+        calldata.extend_from_slice(from.as_slice());
+        let output = self.vm().call(&Call::new(), to, &calldata)?;
+        log(self.vm(), ExecutedForwardRequest { signer: from, target: to, nonce });
+        Ok(output.into())
+    }
+
+    /// Whether `execute` would accept the request right now
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify(&self, from: Address, to: Address, deadline: U256, data: Bytes, v: u8, r: B256, s: B256) -> bool {
+        let nonce = self.nonces.get(from);
+        self.check(&ForwardRequest { from, to, nonce, deadline, data: data.0.into() }, v, r, s).is_ok()
+    }
+
+    /// The nonce `from`'s next request must be signed over
+    pub fn nonces(&self, owner: Address) -> U256 {
+        self.nonces.get(owner)
+    }
+
+    #[selector(name = "DOMAIN_SEPARATOR")]
+    pub fn domain_separator(&self) -> B256 {
+        self.domain().separator()
+    }
+}
+
+#[cfg(any(feature = "forwarder", test))]
+impl Forwarder {
+    fn check(&self, request: &ForwardRequest, v: u8, r: B256, s: B256) -> Result<(), ForwarderErrors> {
+        let ForwardRequest { from, to, deadline, .. } = *request;
+        if U256::from(self.vm().block_timestamp()) > deadline {
+            return Err(ForwarderErrors::ERC2771ForwarderExpiredRequest(ERC2771ForwarderExpiredRequest { deadline }));
+        }
+        let signer = self.recover(request.eip712_signing_hash(&self.domain()), v, r, s);
+        if signer.is_zero() || signer != from {
+            return Err(ForwarderErrors::ERC2771ForwarderInvalidSigner(ERC2771ForwarderInvalidSigner { signer, from }));
+        }
+        let this = self.vm().contract_address();
+        if !calls::is_trusted_forwarder_call(self.vm(), to, this) {
+            return Err(ForwarderErrors::ERC2771UntrustfulTarget(ERC2771UntrustfulTarget { target: to, forwarder: this }));
+        }
+        Ok(())
+    }
+
+    /// Signer of `digest`, zero for an invalid signature
+    fn recover(&self, digest: B256, v: u8, r: B256, s: B256) -> Address {
+        let input = (digest, U256::from(v), r, s).abi_encode();
+        match self.vm().static_call(&Call::new(), ECRECOVER, &input) {
+            Ok(output) if output.len() == 32 => Address::from_word(B256::from_slice(&output)),
+            _ => Address::ZERO,
+        }
+    }
+
+    fn domain(&self) -> Eip712Domain {
+        Eip712Domain::new(
+            Some(Cow::Borrowed(NAME)),
+            Some(Cow::Borrowed("1")),
+            Some(U256::from(self.vm().chain_id())),
+            Some(self.vm().contract_address()),
+            None,
+        )
+    }
+}
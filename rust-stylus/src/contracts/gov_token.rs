@@ -0,0 +1,148 @@
+//! Governance token, an [`Erc20`] with checkpointed voting power.
+//!
+//! [`GovParams`] hooks [`Votes`] into the token's balance changes, so every
+//! transfer, mint and burn moves voting power whichever entrypoint made it. The
+//! votes live at [`VOTES_SLOT`] where the hook can find them.
+use alloc::vec;
+use alloc::vec::Vec;
+use alloc::borrow::Cow;
+use crate::access::deployer::{self, OnlyDeployer};
+use crate::token::erc20::{self, Erc20, Erc20Error};
+use crate::token::votes::{Delegation, Votes, VOTES_SLOT};
+use alloy_sol_types::{sol, Eip712Domain};
+use alloy_primitives::{Address, B256};
+use stylus_sdk::{alloy_primitives::U256, prelude::*};
+
+pub struct GovParams;
+
+impl erc20::Erc20Params for GovParams {
+    const NAME: &'static str = "Shafu Governance";
+    const SYMBOL: &'static str = "shGOV";
+    const DECIMALS: u8 = 18;
+
+    fn after_update(token: &mut Erc20<Self>, from: Address, to: Address, value: U256) -> Result<(), Erc20Error> {
+        votes(token).transfer_voting_units(from, to, value)
+    }
+}
+
+fn votes(token: &Erc20<GovParams>) -> Votes {
+    // Nothing else of the token's contract uses the slot
+    unsafe { token.storage_at(VOTES_SLOT) }
+}
+
+sol_storage! {
+    #[cfg_attr(feature = "gov-token", stylus_sdk::prelude::entrypoint)]
+    pub struct GovToken {
+        #[borrow]
+        Erc20<GovParams> erc20;
+        address owner;
+        bool is_initialized;
+    }
+}
+
+sol! {
+    error AlreadyInitialized();
+    error OnlyOwner();
+}
+
+#[derive(SolidityError)]
+pub enum GovTokenErrors {
+    AlreadyInitialized(AlreadyInitialized),
+    OnlyOwner(OnlyOwner),
+    OnlyDeployer(OnlyDeployer),
+}
+
+#[cfg_attr(any(feature = "gov-token", test), stylus_sdk::prelude::public, inherit(Erc20::<GovParams>))]
+#[cfg(any(feature = "gov-token", test))]
+impl GovToken {
+    /// Deployer only. `owner` can mint, it should be handed to the timelock once the supply is out
+    pub fn init(&mut self, owner: Address) -> Result<(), Vec<u8>> {
+        assert_or!(!self.is_initialized.get(), GovTokenErrors::AlreadyInitialized(AlreadyInitialized {}));
+        deployer::only_deployer(self.vm().msg_sender()).map_err(GovTokenErrors::OnlyDeployer)?;
+        self.owner.set(owner);
+        self.is_initialized.set(true);
+        Ok(())
+    }
+
+    pub fn set_owner(&mut self, owner: Address) -> Result<(), Vec<u8>> {
+        assert_or!(self.vm().msg_sender() == self.owner.get(), GovTokenErrors::OnlyOwner(OnlyOwner {}));
+        self.owner.set(owner);
+        Ok(())
+    }
+
+    pub fn mint(&mut self, to: Address, amount: U256) -> Result<(), Vec<u8>> {
+        assert_or!(self.vm().msg_sender() == self.owner.get(), GovTokenErrors::OnlyOwner(OnlyOwner {}));
+        Ok(self.erc20.mint(to, amount)?)
+    }
+
+    pub fn burn(&mut self, amount: U256) -> Result<(), Vec<u8>> {
+        let sender = self.vm().msg_sender();
+        Ok(self.erc20.burn(sender, amount)?)
+    }
+
+    pub fn delegate(&mut self, delegatee: Address) -> Result<(), Vec<u8>> {
+        let sender = self.vm().msg_sender();
+        let balance = self.erc20.balance_of(sender);
+        Ok(votes(&self.erc20).delegate(sender, delegatee, balance)?)
+    }
+
+    pub fn delegate_by_sig(&mut self, delegatee: Address, nonce: U256, expiry: U256, v: u8, r: B256, s: B256) -> Result<(), Vec<u8>> {
+        let domain = self.domain();
+        let delegation = Delegation { delegatee, nonce, expiry };
+        let mut votes = votes(&self.erc20);
+        let signer = votes.recover_delegation(&domain, &delegation, v, r, s)?;
+        let balance = self.erc20.balance_of(signer);
+        Ok(votes.delegate(signer, delegatee, balance)?)
+    }
+
+    pub fn delegates(&self, account: Address) -> Address {
+        votes(&self.erc20).delegates(account)
+    }
+
+    pub fn nonces(&self, account: Address) -> U256 {
+        votes(&self.erc20).nonces(account)
+    }
+
+    pub fn get_votes(&self, account: Address) -> U256 {
+        votes(&self.erc20).get_votes(account)
+    }
+
+    pub fn get_past_votes(&self, account: Address, timepoint: U256) -> Result<U256, Vec<u8>> {
+        Ok(votes(&self.erc20).get_past_votes(account, timepoint)?)
+    }
+
+    pub fn get_past_total_supply(&self, timepoint: U256) -> Result<U256, Vec<u8>> {
+        Ok(votes(&self.erc20).get_past_total_supply(timepoint)?)
+    }
+
+    pub fn num_checkpoints(&self, account: Address) -> u32 {
+        votes(&self.erc20).num_checkpoints(account)
+    }
+
+    /// Votes are keyed by block number
+    pub fn clock(&self) -> u64 {
+        self.vm().block_number()
+    }
+
+    #[selector(name = "CLOCK_MODE")]
+    pub fn clock_mode(&self) -> alloc::string::String {
+        "mode=blocknumber&from=default".into()
+    }
+
+    pub fn owner(&self) -> Address {
+        self.owner.get()
+    }
+}
+
+#[cfg(any(feature = "gov-token", test))]
+impl GovToken {
+    fn domain(&self) -> Eip712Domain {
+        Eip712Domain::new(
+            Some(Cow::Borrowed(<GovParams as erc20::Erc20Params>::NAME)),
+            Some(Cow::Borrowed("1")),
+            Some(U256::from(self.vm().chain_id())),
+            Some(self.vm().contract_address()),
+            None,
+        )
+    }
+}
@@ -0,0 +1,332 @@
+//! Token-weighted governor in front of the timelock.
+//!
+//! Anyone holding at least `proposal_threshold` votes can propose a single call,
+//! usually a risk parameter change on `Manager`, `ShUSD` or an oracle adapter.
+//! Voting opens `voting_delay` blocks later and lasts `voting_period` blocks,
+//! weighted by the votes each account had when it opened. A proposal passes when
+//! more votes are for than against and for plus abstain reach the quorum, a
+//! fraction of the total supply at that block. Passed proposals are queued into
+//! the timelock, for which the governor has to be both proposer and executor.
+//! Stylus contracts aren't reentrant, so proposals calling the governor itself
+//! have to be executed on the timelock by another executor.
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use alloy_sol_types::sol;
+use alloy_primitives::{Address, U64};
+use crate::access::deployer::{self, OnlyDeployer};
+use crate::contracts::calls;
+use stylus_sdk::{abi::Bytes, alloy_primitives::U256, prelude::*};
+use stylus_sdk::storage::{StorageAddress, StorageBool, StorageBytes, StorageMap, StorageU256, StorageU64, StorageVec};
+
+/// Quorum is expressed in basis points of the past total supply
+const QUORUM_DENOMINATOR: u64 = 10_000;
+
+const AGAINST: u8 = 0;
+const FOR: u8 = 1;
+const ABSTAIN: u8 = 2;
+
+/// Proposal states, as returned by `state`
+pub const PENDING: u8 = 0;
+pub const ACTIVE: u8 = 1;
+pub const CANCELED: u8 = 2;
+pub const DEFEATED: u8 = 3;
+pub const SUCCEEDED: u8 = 4;
+pub const QUEUED: u8 = 5;
+pub const EXECUTED: u8 = 6;
+
+sol! {
+    event ProposalCreated(uint256 indexed id, address indexed proposer, address target, bytes data, uint64 snapshot, uint64 deadline, string description);
+    event VoteCast(address indexed voter, uint256 indexed id, uint8 support, uint256 weight);
+    event ProposalQueued(uint256 indexed id, uint256 timelock_id, uint64 eta);
+    event ProposalExecuted(uint256 indexed id);
+    event ProposalCanceled(uint256 indexed id);
+    event VotingDelaySet(uint64 voting_delay);
+    event VotingPeriodSet(uint64 voting_period);
+    event ProposalThresholdSet(uint256 proposal_threshold);
+    event QuorumSet(uint256 quorum_bps);
+
+    error AlreadyInitialized();
+    error OnlyTimelock();
+    error OnlyProposer();
+    error BelowProposalThreshold(uint256 votes, uint256 threshold);
+    error UnknownProposal();
+    error UnexpectedState(uint8 state);
+    error AlreadyVoted();
+    error InvalidVoteType();
+    error InvalidQuorum();
+    error InvalidVotingPeriod();
+    error CouldNotAdd();
+    error CouldNotMul();
+}
+
+#[derive(SolidityError)]
+pub enum GovernorErrors {
+    AlreadyInitialized(AlreadyInitialized),
+    OnlyDeployer(OnlyDeployer),
+    OnlyTimelock(OnlyTimelock),
+    OnlyProposer(OnlyProposer),
+    BelowProposalThreshold(BelowProposalThreshold),
+    UnknownProposal(UnknownProposal),
+    UnexpectedState(UnexpectedState),
+    AlreadyVoted(AlreadyVoted),
+    InvalidVoteType(InvalidVoteType),
+    InvalidQuorum(InvalidQuorum),
+    InvalidVotingPeriod(InvalidVotingPeriod),
+    CouldNotAdd(CouldNotAdd),
+    CouldNotMul(CouldNotMul)
+}
+
+#[storage]
+pub struct Proposal {
+    proposer: StorageAddress,
+    target: StorageAddress,
+    data: StorageBytes,
+    snapshot: StorageU64,
+    deadline: StorageU64,
+    against_votes: StorageU256,
+    for_votes: StorageU256,
+    abstain_votes: StorageU256,
+    canceled: StorageBool,
+    queued: StorageBool,
+    executed: StorageBool,
+    timelock_id: StorageU256
+}
+
+#[cfg_attr(feature = "governor", stylus_sdk::prelude::entrypoint)]
+#[storage]
+pub struct Governor {
+    token: StorageAddress,
+    timelock: StorageAddress,
+    voting_delay: StorageU64,
+    voting_period: StorageU64,
+    proposal_threshold: StorageU256,
+    quorum_bps: StorageU256,
+    proposals: StorageVec<Proposal>,
+    has_voted: StorageMap<U256, StorageMap<Address, StorageBool>>,
+    is_initialized: StorageBool
+}
+
+#[cfg_attr(any(feature = "governor", test), stylus_sdk::prelude::public)]
+#[cfg(any(feature = "governor", test))]
+impl Governor {
+    /// Deployer only
+    pub fn init(&mut self, token: Address, timelock: Address, voting_delay: u64, voting_period: u64, proposal_threshold: U256, quorum_bps: U256) -> Result<(), Vec<u8>> {
+        assert_or!(!self.is_initialized.get(), GovernorErrors::AlreadyInitialized(AlreadyInitialized {}));
+        deployer::only_deployer(self.vm().msg_sender()).map_err(GovernorErrors::OnlyDeployer)?;
+        assert_or!(voting_period > 0, GovernorErrors::InvalidVotingPeriod(InvalidVotingPeriod {}));
+        assert_or!(quorum_bps <= U256::from(QUORUM_DENOMINATOR), GovernorErrors::InvalidQuorum(InvalidQuorum {}));
+        self.token.set(token);
+        self.timelock.set(timelock);
+        self.voting_delay.set(U64::from(voting_delay));
+        self.voting_period.set(U64::from(voting_period));
+        self.proposal_threshold.set(proposal_threshold);
+        self.quorum_bps.set(quorum_bps);
+        self.is_initialized.set(true);
+        Ok(())
+    }
+
+    /// Proposes calling `target` with `data`, returns the proposal id.
+    pub fn propose(&mut self, target: Address, data: Bytes, description: String) -> Result<U256, Vec<u8>> {
+        let proposer = self.vm().msg_sender();
+        let now = self.vm().block_number();
+        let votes = calls::get_past_votes_call(self.vm(), self.token.get(), proposer, U256::from(now.saturating_sub(1)))?;
+        let threshold = self.proposal_threshold.get();
+        assert_or!(votes >= threshold, GovernorErrors::BelowProposalThreshold(BelowProposalThreshold { votes, threshold }));
+
+        let snapshot = now.checked_add(self.voting_delay.get().to())
+            .ok_or(GovernorErrors::CouldNotAdd(CouldNotAdd {}))?;
+        let deadline = snapshot.checked_add(self.voting_period.get().to())
+            .ok_or(GovernorErrors::CouldNotAdd(CouldNotAdd {}))?;
+        let id = U256::from(self.proposals.len());
+        let mut proposal = self.proposals.grow();
+        proposal.proposer.set(proposer);
+        proposal.target.set(target);
+        proposal.data.set_bytes(&data);
+        proposal.snapshot.set(U64::from(snapshot));
+        proposal.deadline.set(U64::from(deadline));
+        log(self.vm(), ProposalCreated { id, proposer, target, data: data.0.into(), snapshot, deadline, description });
+        Ok(id)
+    }
+
+    /// Votes against (0), for (1) or abstains (2) with the votes held at the snapshot.
+    pub fn cast_vote(&mut self, id: U256, support: u8) -> Result<U256, Vec<u8>> {
+        self.expect_state(id, ACTIVE)?;
+        assert_or!(support <= ABSTAIN, GovernorErrors::InvalidVoteType(InvalidVoteType {}));
+        let voter = self.vm().msg_sender();
+        assert_or!(!self.has_voted.getter(id).get(voter), GovernorErrors::AlreadyVoted(AlreadyVoted {}));
+        self.has_voted.setter(id).insert(voter, true);
+
+        let snapshot = self.proposals.getter(id).map(|p| p.snapshot.get()).unwrap_or_default();
+        let weight = calls::get_past_votes_call(self.vm(), self.token.get(), voter, U256::from(snapshot))?;
+        let mut proposal = self.proposals.setter(id).ok_or(GovernorErrors::UnknownProposal(UnknownProposal {}))?;
+        let tally = match support {
+            AGAINST => &mut proposal.against_votes,
+            FOR => &mut proposal.for_votes,
+            _ => &mut proposal.abstain_votes,
+        };
+        tally.set(tally.get().checked_add(weight).ok_or(GovernorErrors::CouldNotAdd(CouldNotAdd {}))?);
+        log(self.vm(), VoteCast { voter, id, support, weight });
+        Ok(weight)
+    }
+
+    /// Queues a succeeded proposal into the timelock, executable after its minimum delay.
+    pub fn queue(&mut self, id: U256) -> Result<U256, Vec<u8>> {
+        self.expect_state(id, SUCCEEDED)?;
+        let timelock = self.timelock.get();
+        let eta = self.vm().block_timestamp().checked_add(calls::min_delay_call(self.vm(), timelock)?)
+            .ok_or(GovernorErrors::CouldNotAdd(CouldNotAdd {}))?;
+        let (target, data) = self.proposals.getter(id).map(|p| (p.target.get(), p.data.get_bytes())).unwrap_or_default();
+        let timelock_id = calls::queue_call(self.vm(), timelock, target, &data, eta)?;
+        let mut proposal = self.proposals.setter(id).ok_or(GovernorErrors::UnknownProposal(UnknownProposal {}))?;
+        proposal.queued.set(true);
+        proposal.timelock_id.set(timelock_id);
+        log(self.vm(), ProposalQueued { id, timelock_id, eta });
+        Ok(timelock_id)
+    }
+
+    /// Executes a queued proposal through the timelock once its delay has passed.
+    pub fn execute(&mut self, id: U256) -> Result<Bytes, Vec<u8>> {
+        self.expect_state(id, QUEUED)?;
+        let timelock = self.timelock.get();
+        let mut proposal = self.proposals.setter(id).ok_or(GovernorErrors::UnknownProposal(UnknownProposal {}))?;
+        proposal.executed.set(true);
+        let timelock_id = proposal.timelock_id.get();
+        let result = calls::execute_call(self.vm(), timelock, timelock_id)?;
+        log(self.vm(), ProposalExecuted { id });
+        Ok(result.into())
+    }
+
+    /// The proposer can withdraw a proposal until voting opens.
+    pub fn cancel(&mut self, id: U256) -> Result<(), Vec<u8>> {
+        self.expect_state(id, PENDING)?;
+        let sender = self.vm().msg_sender();
+        let mut proposal = self.proposals.setter(id).ok_or(GovernorErrors::UnknownProposal(UnknownProposal {}))?;
+        assert_or!(proposal.proposer.get() == sender, GovernorErrors::OnlyProposer(OnlyProposer {}));
+        proposal.canceled.set(true);
+        log(self.vm(), ProposalCanceled { id });
+        Ok(())
+    }
+
+    pub fn set_voting_delay(&mut self, voting_delay: u64) -> Result<(), Vec<u8>> {
+        self.only_timelock()?;
+        self.voting_delay.set(U64::from(voting_delay));
+        log(self.vm(), VotingDelaySet { voting_delay });
+        Ok(())
+    }
+
+    pub fn set_voting_period(&mut self, voting_period: u64) -> Result<(), Vec<u8>> {
+        self.only_timelock()?;
+        assert_or!(voting_period > 0, GovernorErrors::InvalidVotingPeriod(InvalidVotingPeriod {}));
+        self.voting_period.set(U64::from(voting_period));
+        log(self.vm(), VotingPeriodSet { voting_period });
+        Ok(())
+    }
+
+    pub fn set_proposal_threshold(&mut self, proposal_threshold: U256) -> Result<(), Vec<u8>> {
+        self.only_timelock()?;
+        self.proposal_threshold.set(proposal_threshold);
+        log(self.vm(), ProposalThresholdSet { proposal_threshold });
+        Ok(())
+    }
+
+    pub fn set_quorum(&mut self, quorum_bps: U256) -> Result<(), Vec<u8>> {
+        self.only_timelock()?;
+        assert_or!(quorum_bps <= U256::from(QUORUM_DENOMINATOR), GovernorErrors::InvalidQuorum(InvalidQuorum {}));
+        self.quorum_bps.set(quorum_bps);
+        log(self.vm(), QuorumSet { quorum_bps });
+        Ok(())
+    }
+
+    /// Returns pending (0), active, canceled, defeated, succeeded, queued or executed (6)
+    pub fn state(&self, id: U256) -> Result<u8, Vec<u8>> {
+        let proposal = self.proposals.getter(id).ok_or(GovernorErrors::UnknownProposal(UnknownProposal {}))?;
+        if proposal.executed.get() {
+            return Ok(EXECUTED);
+        }
+        if proposal.canceled.get() {
+            return Ok(CANCELED);
+        }
+        if proposal.queued.get() {
+            return Ok(QUEUED);
+        }
+        let now = self.vm().block_number();
+        let snapshot: u64 = proposal.snapshot.get().to();
+        if now <= snapshot {
+            return Ok(PENDING);
+        }
+        if now <= proposal.deadline.get().to::<u64>() {
+            return Ok(ACTIVE);
+        }
+        let for_votes = proposal.for_votes.get();
+        let quorum = self.quorum(U256::from(snapshot))?;
+        let counted = for_votes.checked_add(proposal.abstain_votes.get()).ok_or(GovernorErrors::CouldNotAdd(CouldNotAdd {}))?;
+        if for_votes > proposal.against_votes.get() && counted >= quorum {
+            Ok(SUCCEEDED)
+        } else {
+            Ok(DEFEATED)
+        }
+    }
+
+    /// Votes needed for a proposal snapshotted at `block` to pass
+    pub fn quorum(&self, block: U256) -> Result<U256, Vec<u8>> {
+        let supply = calls::get_past_total_supply_call(self.vm(), self.token.get(), block)?;
+        let votes = supply.checked_mul(self.quorum_bps.get()).ok_or(GovernorErrors::CouldNotMul(CouldNotMul {}))?;
+        Ok(votes / U256::from(QUORUM_DENOMINATOR))
+    }
+
+    /// Returns proposer, target, calldata, snapshot and deadline of a proposal
+    pub fn proposal(&self, id: U256) -> Result<(Address, Address, Bytes, u64, u64), Vec<u8>> {
+        let proposal = self.proposals.getter(id).ok_or(GovernorErrors::UnknownProposal(UnknownProposal {}))?;
+        Ok((proposal.proposer.get(), proposal.target.get(), proposal.data.get_bytes().into(), proposal.snapshot.get().to(), proposal.deadline.get().to()))
+    }
+
+    /// Returns against, for and abstain votes of a proposal
+    pub fn proposal_votes(&self, id: U256) -> Result<(U256, U256, U256), Vec<u8>> {
+        let proposal = self.proposals.getter(id).ok_or(GovernorErrors::UnknownProposal(UnknownProposal {}))?;
+        Ok((proposal.against_votes.get(), proposal.for_votes.get(), proposal.abstain_votes.get()))
+    }
+
+    pub fn has_voted(&self, id: U256, account: Address) -> bool {
+        self.has_voted.getter(id).get(account)
+    }
+
+    pub fn proposal_count(&self) -> U256 {
+        U256::from(self.proposals.len())
+    }
+
+    pub fn voting_delay(&self) -> u64 {
+        self.voting_delay.get().to()
+    }
+
+    pub fn voting_period(&self) -> u64 {
+        self.voting_period.get().to()
+    }
+
+    pub fn proposal_threshold(&self) -> U256 {
+        self.proposal_threshold.get()
+    }
+
+    pub fn token(&self) -> Address {
+        self.token.get()
+    }
+
+    pub fn timelock(&self) -> Address {
+        self.timelock.get()
+    }
+}
+
+#[cfg(any(feature = "governor", test))]
+impl Governor {
+    fn expect_state(&self, id: U256, expected: u8) -> Result<(), Vec<u8>> {
+        let state = self.state(id)?;
+        assert_or!(state == expected, GovernorErrors::UnexpectedState(UnexpectedState { state }));
+        Ok(())
+    }
+
+    /// Governor parameters are themselves changed by proposals
+    fn only_timelock(&self) -> Result<(), Vec<u8>> {
+        assert_or!(self.vm().msg_sender() == self.timelock.get(), GovernorErrors::OnlyTimelock(OnlyTimelock {}));
+        Ok(())
+    }
+}
@@ -1,46 +1,93 @@
 use alloc::vec;
 use alloc::vec::Vec;
-use core::str::FromStr;
 use alloy_sol_types::sol;
-use crate::contracts::calls;
-use alloy_primitives::Address;
-use crate::alloc::string::ToString;
-use stylus_sdk::{alloy_primitives::U256, prelude::*};
+use crate::access::deployer::{self, OnlyDeployer};
+use crate::context;
+use crate::contracts::{calls, price_guard::PriceGuard, proxy, rate_limit::MintLimiter};
+use crate::contracts::position::{Position, MIGRATED};
+use crate::math::{self, MathError, Ray, Rounding, Wad};
+use alloy_primitives::{keccak256, Address};
+use stylus_sdk::{abi::Bytes, alloy_primitives::{FixedBytes, B256, I256, U256}, prelude::*};
 use stylus_sdk::storage::{StorageAddress, StorageMap, StorageU256, StorageBool};
 
-const MIN_COLLAT_RATIO: u128 = 1_500_000_000_000_000_000; // 1.5e18
+pub const MIN_COLLAT_RATIO: u128 = 1_500_000_000_000_000_000; // 1.5e18
+pub const LIQUIDATION_PENALTY: u128 = 50_000_000_000_000_000; // 0.05e18
+/// Decimals of the oracle's `latestAnswer`, Chainlink's usd feeds have 8
+pub const ORACLE_DECIMALS: u8 = 8;
+/// No stability fee accrues yet, debt is normalized by one
+const DEBT_RATE: Ray = Ray::ONE;
 
 sol! {
+    event SurplusAccrued(uint256 amount);
+    event BadDebtRecorded(address indexed user, uint256 amount);
+    event BadDebtHealed(uint256 amount);
+    event GovernanceTransferred(address indexed previous, address indexed governance);
+    event MintLimitsSet(uint256 window_limit, uint64 window, uint256 block_limit);
+    event Repaid(address indexed user, address indexed from, uint256 amount);
+    event PositionUpdated(address indexed user, uint256 deposited, uint256 minted);
+    event PositionsMigrated();
+    event VaultNftSet(address indexed vault_nft);
+    event VaultOpened(uint256 indexed id, address indexed owner, address key);
+    event PriceGuardSet(uint256 max_deviation, uint64 window);
+    event GuardianSet(address indexed guardian);
+    event TrustedForwarderSet(address indexed forwarder);
+
     error Undercollateralized();
+    error MintRateLimited(uint256 available, uint256 requested);
+    error OnlyGovernance();
     error AlreadyInitialized();
     error CouldNotAdd();
     error CouldNotSub();
     error CouldNotMul();
     error CouldNotDiv();
     error ConversionFailure();
+    error NotUpgradeable();
+    error OnlyShUSD();
+    error InvalidRepayData();
+    error PositionOverflow();
+    error VaultsDisabled();
+    error NotVaultOwner(uint256 id);
+    error PriceSuspicious();
+    error OnlyGuardian();
+    error UnauthorizedCallContext();
 }
 
+/// `onTransferReceived(address,address,uint256,bytes)`, returned to accept an ERC-1363 transfer
+const ON_TRANSFER_RECEIVED: [u8; 4] = [0x88, 0xa7, 0xca, 0x5c];
+
 #[derive(SolidityError)]
 pub enum ManagerErrors {
     Undercollateralized(Undercollateralized),
+    MintRateLimited(MintRateLimited),
+    OnlyGovernance(OnlyGovernance),
     AlreadyInitialized(AlreadyInitialized),
     CouldNotAdd(CouldNotAdd),
     CouldNotSub(CouldNotSub),
     CouldNotMul(CouldNotMul),
     CouldNotDiv(CouldNotDiv),
-    ConversionFailure(ConversionFailure)
+    ConversionFailure(ConversionFailure),
+    NotUpgradeable(NotUpgradeable),
+    OnlyShUSD(OnlyShUSD),
+    InvalidRepayData(InvalidRepayData),
+    PositionOverflow(PositionOverflow),
+    VaultsDisabled(VaultsDisabled),
+    NotVaultOwner(NotVaultOwner),
+    PriceSuspicious(PriceSuspicious),
+    OnlyGuardian(OnlyGuardian),
+    OnlyDeployer(OnlyDeployer),
+    UnauthorizedCallContext(UnauthorizedCallContext),
 }
 
-#[macro_export]
-macro_rules! assert_or {
-    ($cond:expr, $err:expr) => {
-        if !($cond) {
-            Err($err)?;
+impl From<MathError> for ManagerErrors {
+    fn from(error: MathError) -> Self {
+        match error {
+            MathError::Overflow => ManagerErrors::CouldNotMul(CouldNotMul {}),
+            MathError::DivisionByZero => ManagerErrors::CouldNotDiv(CouldNotDiv {}),
+            MathError::Negative => ManagerErrors::ConversionFailure(ConversionFailure {}),
         }
-    };
+    }
 }
 
-#[cfg_attr(feature = "manager", stylus_sdk::prelude::entrypoint)]
 #[storage]
 pub struct Manager {
     sh_usd: StorageAddress,
@@ -48,85 +95,683 @@ pub struct Manager {
     oracle: StorageAddress,
     address_2deposit: StorageMap<Address, StorageU256>,
     address_2minted: StorageMap<Address, StorageU256>,
-    is_initialized: StorageBool
+    is_initialized: StorageBool,
+    governance: StorageAddress,
+    surplus: StorageU256,
+    bad_debt: StorageU256,
+    total_deposited: StorageU256,
+    mint_limiter: MintLimiter,
+    repaid: StorageU256,
+    positions: StorageMap<Address, Position>,
+    /// Collateral still held in `address_2deposit`, counts down while positions are migrated
+    legacy_deposited: StorageU256,
+    /// Set once no position is left in `address_2deposit` and `address_2minted`
+    positions_packed: StorageBool,
+    /// Opens vaults, whose owner controls the position at `vault_key(id)`
+    vault_nft: StorageAddress,
+    /// Stops mints and withdrawals while the oracle price jumps
+    price_guard: PriceGuard,
+    /// Can confirm a price the guard holds back
+    guardian: StorageAddress,
+    /// Relays meta-transactions, the sender is the last 20 bytes of its calls
+    trusted_forwarder: StorageAddress,
 }
 
-#[cfg_attr(feature = "manager", stylus_sdk::prelude::public)]
+/// Slot and offset, counted from the left of the word, of `trusted_forwarder`, read before the call is routed
+const TRUSTED_FORWARDER_SLOT: (u64, u8) = (24, 12);
+
 #[cfg(feature = "manager")]
+unsafe impl TopLevelStorage for Manager {}
+
+/// Routes calls like `#[entrypoint]` on the struct would, taking the sender off
+/// those relayed by the trusted forwarder
+#[cfg_attr(feature = "manager", stylus_sdk::prelude::entrypoint)]
+#[cfg(any(feature = "manager", test))]
+pub fn route(input: Vec<u8>, host: stylus_sdk::host::VM) -> stylus_sdk::ArbResult {
+    let (slot, offset) = TRUSTED_FORWARDER_SLOT;
+    context::route::<Manager>(input, host, U256::from(slot), offset)
+}
+
+#[cfg_attr(any(feature = "manager", test), stylus_sdk::prelude::public)]
+#[cfg(any(feature = "manager", test))]
 impl Manager {
-    pub fn init(&mut self, weth_address: Address, oracle_address: Address, sh_usd_address: Address) -> Result<(), Vec<u8>> {
+    /// Deployer only, also through the proxy's `init_proxy` which keeps the sender
+    pub fn initialize(&mut self, weth_address: Address, oracle_address: Address, sh_usd_address: Address, governance_address: Address) -> Result<(), Vec<u8>> {
         assert_or!(!self.is_initialized.get(), ManagerErrors::AlreadyInitialized(AlreadyInitialized {}));
+        deployer::only_deployer(context::msg_sender(self.vm())).map_err(ManagerErrors::OnlyDeployer)?;
         self.weth.set(weth_address);
         self.oracle.set(oracle_address);
         self.sh_usd.set(sh_usd_address);
+        self.governance.set(governance_address);
         self.is_initialized.set(true);
+        // A new manager has nothing to migrate
+        self.positions_packed.set(true);
         Ok(())
     }
 
-    pub fn deposit(&mut self, amount: U256) -> Result<(), Vec<u8>> {
-        let sender = self.vm().msg_sender();
-        let this = self.vm().contract_address();
-        calls::transfer_from_call(self.weth.get(), sender, this, amount)?;
-        let previus_balance = self.address_2deposit.get(sender);
-        self.address_2deposit.insert(sender, previus_balance.checked_add(amount)
-            .ok_or(ManagerErrors::CouldNotAdd(CouldNotAdd {}))?);
+    /// Points the proxy this manager runs behind at `new_implementation`. Only
+    /// works through a proxy, the slot of a bare manager points nowhere.
+    pub fn upgrade_to(&mut self, new_implementation: Address) -> Result<(), Vec<u8>> {
+        assert_or!(!proxy::implementation(self.vm()).is_zero(), ManagerErrors::UnauthorizedCallContext(UnauthorizedCallContext {}));
+        assert_or!(context::msg_sender(self.vm()) == self.governance.get(), ManagerErrors::OnlyGovernance(OnlyGovernance {}));
+        let uuid = calls::proxiable_uuid_call(self.vm(), new_implementation)
+            .map_err(|_| ManagerErrors::NotUpgradeable(NotUpgradeable {}))?;
+        assert_or!(uuid == proxy::proxiable_uuid(), ManagerErrors::NotUpgradeable(NotUpgradeable {}));
+        proxy::set_implementation(self.vm(), new_implementation);
         Ok(())
     }
 
+    /// Reverts through a proxy, so a proxy can't be upgraded to another proxy
+    #[selector(name = "proxiableUUID")]
+    pub fn proxiable_uuid(&self) -> Result<B256, Vec<u8>> {
+        assert_or!(proxy::implementation(self.vm()).is_zero(), ManagerErrors::UnauthorizedCallContext(UnauthorizedCallContext {}));
+        Ok(proxy::proxiable_uuid())
+    }
+
+    pub fn deposit(&mut self, amount: U256) -> Result<(), Vec<u8>> {
+        let sender = context::msg_sender(self.vm());
+        self.deposit_into(sender, sender, amount)
+    }
+
     pub fn burn(&mut self, amount: U256) -> Result<(), Vec<u8>> {
-        let sender = self.vm().msg_sender();
-        let previous_balance = self.address_2minted.get(sender);
-        self.address_2minted.insert(sender, previous_balance.checked_sub(amount)
+        let sender = context::msg_sender(self.vm());
+        self.burn_from(sender, sender, amount)
+    }
+
+    pub fn mint(&mut self, amount: U256) -> Result<(), Vec<u8>> {
+        let sender = context::msg_sender(self.vm());
+        self.mint_from(sender, sender, amount)
+    }
+
+    pub fn withdraw(&mut self, amount: U256) -> Result<(), Vec<u8>> {
+        let sender = context::msg_sender(self.vm());
+        self.withdraw_from(sender, sender, amount)
+    }
+
+    /// Mints a vault NFT to the sender, returns its id. The vault's position is
+    /// isolated from the sender's own and from their other vaults.
+    pub fn open_vault(&mut self) -> Result<U256, Vec<u8>> {
+        let vault_nft = self.vault_nft.get();
+        assert_or!(!vault_nft.is_zero(), ManagerErrors::VaultsDisabled(VaultsDisabled {}));
+        let owner = context::msg_sender(self.vm());
+        let id = calls::mint_vault_call(self.vm(), vault_nft, owner)?;
+        log(self.vm(), VaultOpened { id, owner, key: vault_key(id) });
+        Ok(id)
+    }
+
+    /// `deposit` into vault `id`, which the sender must own
+    pub fn deposit_vault(&mut self, id: U256, amount: U256) -> Result<(), Vec<u8>> {
+        let owner = self.vault_owner(id)?;
+        self.deposit_into(vault_key(id), owner, amount)
+    }
+
+    pub fn burn_vault(&mut self, id: U256, amount: U256) -> Result<(), Vec<u8>> {
+        let owner = self.vault_owner(id)?;
+        self.burn_from(vault_key(id), owner, amount)
+    }
+
+    pub fn mint_vault(&mut self, id: U256, amount: U256) -> Result<(), Vec<u8>> {
+        let owner = self.vault_owner(id)?;
+        self.mint_from(vault_key(id), owner, amount)
+    }
+
+    pub fn withdraw_vault(&mut self, id: U256, amount: U256) -> Result<(), Vec<u8>> {
+        let owner = self.vault_owner(id)?;
+        self.withdraw_from(vault_key(id), owner, amount)
+    }
+
+    /// Where vault `id`'s position is kept, pass it to `position`, `liquidate` or
+    /// as the repay data of `transferAndCall`
+    pub fn vault_key(id: U256) -> Address {
+        vault_key(id)
+    }
+
+    /// Hands the collateral of a position at or below the minimum ratio to the
+    /// sender, who repays its debt with their own shUSD. The user keeps whatever
+    /// shUSD they hold. Solvent positions cost the debt plus the penalty, which
+    /// accrues to surplus. Under water ones cost the collateral's value less the
+    /// penalty as a discount, the rest of the debt is recorded as bad debt.
+    /// The price guard doesn't apply, a crash must not keep positions from
+    /// being liquidated until the guardian confirms it.
+    pub fn liquidate(&mut self, user: Address) -> Result<(), Vec<u8>> {
+        let (amount_deposited, amount_minted) = self.take_position(user)?;
+        let answer = calls::latest_answer_call(self.vm(), self.oracle.get())?;
+        let result = Self::ratio_at(amount_deposited, amount_minted, answer)?;
+        assert_or!(result <= U256::from(MIN_COLLAT_RATIO), ManagerErrors::Undercollateralized(Undercollateralized {}));
+        let sender = context::msg_sender(self.vm());
+        let value = collateral_value(amount_deposited, answer).map_err(ManagerErrors::from)?;
+        if value < amount_minted {
+            // The collateral can't cover the debt, the liquidator repays what it is
+            // worth less the penalty and whatever is left stays in circulation as
+            // bad debt.
+            let repaid = underwater_repayment(value).map_err(ManagerErrors::from)?;
+            calls::burn_call(self.vm(), self.sh_usd.get(), sender, repaid)?;
+            self.mint_limiter.release(repaid);
+            let uncovered = amount_minted - repaid;
+            self.bad_debt.set(self.bad_debt.get().checked_add(uncovered)
+                .ok_or(ManagerErrors::CouldNotAdd(CouldNotAdd {}))?);
+            log(self.vm(), BadDebtRecorded { user, amount: uncovered });
+        } else {
+            let penalty = Wad(amount_minted).mul(Wad(U256::from(LIQUIDATION_PENALTY)), Rounding::Down)
+                .map_err(ManagerErrors::from)?.0
+                .min(value - amount_minted);
+            calls::burn_call(self.vm(), self.sh_usd.get(), sender, amount_minted.checked_add(penalty)
+                .ok_or(ManagerErrors::CouldNotAdd(CouldNotAdd {}))?)?;
+            // The penalty is minted straight back, only the debt leaves the supply
+            self.mint_limiter.release(amount_minted);
+            if !penalty.is_zero() {
+                let this = self.vm().contract_address();
+                calls::mint_call(self.vm(), self.sh_usd.get(), this, penalty)?;
+                self.surplus.set(self.surplus.get().checked_add(penalty)
+                    .ok_or(ManagerErrors::CouldNotAdd(CouldNotAdd {}))?);
+                log(self.vm(), SurplusAccrued { amount: penalty });
+            }
+        }
+        calls::transfer_call(self.vm(), self.weth.get(), sender, amount_deposited)?;
+        self.total_deposited.set(self.total_deposited.get().checked_sub(amount_deposited)
             .ok_or(ManagerErrors::CouldNotSub(CouldNotSub {}))?);
-        calls::burn_call(self.sh_usd.get(), sender, amount)?;
+        self.positions.setter(user).clear();
+        self.log_position(user);
         Ok(())
     }
 
-    pub fn mint(&mut self, amount: U256) -> Result<(), Vec<u8>> {
-        let sender = self.vm().msg_sender();
-        let previous_balance = self.address_2minted.get(sender);
-        self.address_2minted.insert(sender, previous_balance.checked_add(amount)
+    /// ERC-1363 hook, repays debt with shUSD sent through `transferAndCall`. `data` is
+    /// either empty to repay the sender's own debt or the abi encoded user to repay for.
+    /// The token can't be reentered here, the shUSD is burnt later by `burn_repaid`.
+    pub fn on_transfer_received(&mut self, _operator: Address, from: Address, value: U256, data: Bytes) -> Result<FixedBytes<4>, Vec<u8>> {
+        assert_or!(context::msg_sender(self.vm()) == self.sh_usd.get(), ManagerErrors::OnlyShUSD(OnlyShUSD {}));
+        let user = match data.len() {
+            0 => from,
+            32 => Address::from_word(B256::from_slice(&data)),
+            _ => return Err(ManagerErrors::InvalidRepayData(InvalidRepayData {}).into()),
+        };
+        let (deposited, minted) = self.take_position(user)?;
+        self.store_position(user, deposited, minted.checked_sub(value)
+            .ok_or(ManagerErrors::CouldNotSub(CouldNotSub {}))?)?;
+        self.repaid.set(self.repaid.get().checked_add(value)
             .ok_or(ManagerErrors::CouldNotAdd(CouldNotAdd {}))?);
-        let ratio = self.collat_ratio(sender)?;
+        self.mint_limiter.release(value);
+        log(self.vm(), Repaid { user, from, amount: value });
+        self.log_position(user);
+        Ok(FixedBytes(ON_TRANSFER_RECEIVED))
+    }
+
+    /// Burns the shUSD repaid through `on_transfer_received`, callable by anyone.
+    pub fn burn_repaid(&mut self) -> Result<U256, Vec<u8>> {
+        let amount = self.repaid.get();
+        self.repaid.set(U256::ZERO);
+        let this = self.vm().contract_address();
+        calls::burn_call(self.vm(), self.sh_usd.get(), this, amount)?;
+        Ok(amount)
+    }
+
+    pub fn repaid(&self) -> U256 {
+        self.repaid.get()
+    }
+
+    /// Burns `amount` of the surplus held by the manager against recorded bad debt.
+    pub fn heal(&mut self, amount: U256) -> Result<(), Vec<u8>> {
+        assert_or!(context::msg_sender(self.vm()) == self.governance.get(), ManagerErrors::OnlyGovernance(OnlyGovernance {}));
+        self.surplus.set(self.surplus.get().checked_sub(amount)
+            .ok_or(ManagerErrors::CouldNotSub(CouldNotSub {}))?);
+        self.bad_debt.set(self.bad_debt.get().checked_sub(amount)
+            .ok_or(ManagerErrors::CouldNotSub(CouldNotSub {}))?);
+        let this = self.vm().contract_address();
+        calls::burn_call(self.vm(), self.sh_usd.get(), this, amount)?;
+        log(self.vm(), BadDebtHealed { amount });
+        Ok(())
+    }
+
+    /// Caps net new shUSD at `window_limit` per `window` seconds and `block_limit` per block, zero disables a cap.
+    pub fn set_mint_limits(&mut self, window_limit: U256, window: u64, block_limit: U256) -> Result<(), Vec<u8>> {
+        assert_or!(context::msg_sender(self.vm()) == self.governance.get(), ManagerErrors::OnlyGovernance(OnlyGovernance {}));
+        self.mint_limiter.configure(window_limit, window, block_limit);
+        log(self.vm(), MintLimitsSet { window_limit, window, block_limit });
+        Ok(())
+    }
+
+    pub fn mint_limits(&self) -> (U256, u64, U256) {
+        self.mint_limiter.limits()
+    }
+
+    pub fn available_to_mint(&self) -> U256 {
+        self.mint_limiter.available()
+    }
+
+    pub fn surplus(&self) -> U256 {
+        self.surplus.get()
+    }
+
+    pub fn bad_debt(&self) -> U256 {
+        self.bad_debt.get()
+    }
+
+    /// Hands every governance-only function over to `new_governance`, usually the timelock.
+    pub fn set_governance(&mut self, new_governance: Address) -> Result<(), Vec<u8>> {
+        let previous = self.governance.get();
+        assert_or!(context::msg_sender(self.vm()) == previous, ManagerErrors::OnlyGovernance(OnlyGovernance {}));
+        self.governance.set(new_governance);
+        log(self.vm(), GovernanceTransferred { previous, governance: new_governance });
+        Ok(())
+    }
+
+    pub fn governance(&self) -> Address {
+        self.governance.get()
+    }
+
+    /// Returns the value of all deposited collateral and the total shUSD supply,
+    /// leaving out repayments waiting to be burnt.
+    pub fn solvency(&self) -> Result<(U256, U256), Vec<u8>> {
+        let collateral_value = self.collateral_value(self.total_deposited.get())?;
+        let supply = calls::total_supply_call(self.vm(), self.sh_usd.get())?.saturating_sub(self.repaid.get());
+        Ok((collateral_value, supply))
+    }
+
+    /// Returns the weth deposited and shUSD minted by `user`
+    pub fn position(&self, user: Address) -> Result<(U256, U256), Vec<u8>> {
+        self.load_position(user)
+    }
+
+    /// Collateral value over debt, scaled by 1e18
+    pub fn collat_ratio(&self, user: Address) -> Result<U256, Vec<u8>> {
+        let (deposited, minted) = self.load_position(user)?;
+        self.ratio(deposited, minted)
+    }
+
+    /// Moves `users`' positions out of `address_2deposit` and `address_2minted`,
+    /// callable by anyone. Positions are also moved the first time they change.
+    /// Once the last one is moved `PositionsMigrated` is emitted and the old maps
+    /// are never read again.
+    pub fn migrate_positions(&mut self, users: Vec<Address>) -> Result<(), Vec<u8>> {
+        for user in users {
+            self.take_position(user)?;
+        }
+        Ok(())
+    }
+
+    pub fn positions_packed(&self) -> bool {
+        self.positions_packed.get()
+    }
+
+    /// Enables vaults, can only be set once as the NFT owners control the positions
+    pub fn set_vault_nft(&mut self, vault_nft: Address) -> Result<(), Vec<u8>> {
+        assert_or!(context::msg_sender(self.vm()) == self.governance.get(), ManagerErrors::OnlyGovernance(OnlyGovernance {}));
+        assert_or!(self.vault_nft.get().is_zero(), ManagerErrors::AlreadyInitialized(AlreadyInitialized {}));
+        self.vault_nft.set(vault_nft);
+        log(self.vm(), VaultNftSet { vault_nft });
+        Ok(())
+    }
+
+    pub fn vault_nft(&self) -> Address {
+        self.vault_nft.get()
+    }
+
+    /// Holds back prices more than `max_deviation` (a wad fraction) away from the last
+    /// good one within `window` seconds of it, zero disables the guard.
+    pub fn set_price_guard(&mut self, max_deviation: U256, window: u64) -> Result<(), Vec<u8>> {
+        assert_or!(context::msg_sender(self.vm()) == self.governance.get(), ManagerErrors::OnlyGovernance(OnlyGovernance {}));
+        self.price_guard.configure(max_deviation, window);
+        log(self.vm(), PriceGuardSet { max_deviation, window });
+        Ok(())
+    }
+
+    pub fn price_guard(&self) -> (U256, u64) {
+        self.price_guard.limits()
+    }
+
+    /// The last price the guard accepted and when
+    pub fn last_good_price(&self) -> (U256, u64) {
+        self.price_guard.last_price()
+    }
+
+    pub fn breaker_tripped(&self) -> bool {
+        self.price_guard.tripped()
+    }
+
+    /// Checks the current oracle answer and returns whether it is accepted. Callable by
+    /// anyone to record a trip, which a reverted mint or withdrawal rolls back.
+    pub fn poke_price(&mut self) -> Result<bool, Vec<u8>> {
+        let answer = calls::latest_answer_call(self.vm(), self.oracle.get())?;
+        let price = math::to_unsigned(answer).map_err(ManagerErrors::from)?;
+        Ok(self.price_guard.observe(price))
+    }
+
+    /// Takes the current oracle answer as good and resets the breaker
+    pub fn confirm_price(&mut self) -> Result<(), Vec<u8>> {
+        let sender = context::msg_sender(self.vm());
+        assert_or!(sender == self.guardian.get(), ManagerErrors::OnlyGuardian(OnlyGuardian {}));
+        let answer = calls::latest_answer_call(self.vm(), self.oracle.get())?;
+        let price = math::to_unsigned(answer).map_err(ManagerErrors::from)?;
+        self.price_guard.reset(price, sender);
+        Ok(())
+    }
+
+    pub fn set_guardian(&mut self, guardian: Address) -> Result<(), Vec<u8>> {
+        assert_or!(context::msg_sender(self.vm()) == self.governance.get(), ManagerErrors::OnlyGovernance(OnlyGovernance {}));
+        self.guardian.set(guardian);
+        log(self.vm(), GuardianSet { guardian });
+        Ok(())
+    }
+
+    pub fn guardian(&self) -> Address {
+        self.guardian.get()
+    }
+
+    /// Trusts `forwarder` to relay calls for the address it appends, zero stops relaying
+    pub fn set_trusted_forwarder(&mut self, forwarder: Address) -> Result<(), Vec<u8>> {
+        assert_or!(context::msg_sender(self.vm()) == self.governance.get(), ManagerErrors::OnlyGovernance(OnlyGovernance {}));
+        self.trusted_forwarder.set(forwarder);
+        log(self.vm(), TrustedForwarderSet { forwarder });
+        Ok(())
+    }
+
+    pub fn trusted_forwarder(&self) -> Address {
+        self.trusted_forwarder.get()
+    }
+
+    /// EIP-2771, whether calls from `forwarder` are taken to be relayed
+    pub fn is_trusted_forwarder(&self, forwarder: Address) -> bool {
+        !forwarder.is_zero() && forwarder == self.trusted_forwarder.get()
+    }
+}
+
+#[cfg(any(feature = "manager", test))]
+impl Manager {
+    /// Pulls `amount` WETH from `account` into the position at `key`
+    fn deposit_into(&mut self, key: Address, account: Address, amount: U256) -> Result<(), Vec<u8>> {
+        let this = self.vm().contract_address();
+        calls::transfer_from_call(self.vm(), self.weth.get(), account, this, amount)?;
+        let (deposited, minted) = self.take_position(key)?;
+        self.store_position(key, deposited.checked_add(amount)
+            .ok_or(ManagerErrors::CouldNotAdd(CouldNotAdd {}))?, minted)?;
+        self.total_deposited.set(self.total_deposited.get().checked_add(amount)
+            .ok_or(ManagerErrors::CouldNotAdd(CouldNotAdd {}))?);
+        self.log_position(key);
+        Ok(())
+    }
+
+    /// Burns `amount` shUSD held by `account` against the debt at `key`
+    fn burn_from(&mut self, key: Address, account: Address, amount: U256) -> Result<(), Vec<u8>> {
+        let (deposited, minted) = self.take_position(key)?;
+        self.store_position(key, deposited, minted.checked_sub(amount)
+            .ok_or(ManagerErrors::CouldNotSub(CouldNotSub {}))?)?;
+        calls::burn_call(self.vm(), self.sh_usd.get(), account, amount)?;
+        self.mint_limiter.release(amount);
+        self.log_position(key);
+        Ok(())
+    }
+
+    /// Mints `amount` shUSD to `account` against the collateral at `key`
+    fn mint_from(&mut self, key: Address, account: Address, amount: U256) -> Result<(), Vec<u8>> {
+        let (deposited, minted) = self.take_position(key)?;
+        let minted = minted.checked_add(amount).ok_or(ManagerErrors::CouldNotAdd(CouldNotAdd {}))?;
+        self.store_position(key, deposited, minted)?;
+        let ratio = self.guarded_ratio(deposited, minted)?;
         assert_or!(ratio > U256::from(MIN_COLLAT_RATIO), ManagerErrors::Undercollateralized(Undercollateralized {}));
-        calls::mint_call(self.sh_usd.get(), sender, amount)?;
+        if !self.mint_limiter.consume(amount) {
+            let available = self.mint_limiter.available();
+            return Err(ManagerErrors::MintRateLimited(MintRateLimited { available, requested: amount }).into());
+        }
+        calls::mint_call(self.vm(), self.sh_usd.get(), account, amount)?;
+        self.log_position(key);
         Ok(())
     }
 
-    pub fn withdraw(&mut self, amount: U256) -> Result<(), Vec<u8>> {
-        let sender = self.vm().msg_sender();
-        let previous_deposit = self.address_2deposit.get(sender);
-        self.address_2deposit.insert(sender, previous_deposit.checked_sub(amount)
+    /// Sends `amount` WETH from the position at `key` to `account`
+    fn withdraw_from(&mut self, key: Address, account: Address, amount: U256) -> Result<(), Vec<u8>> {
+        let (deposited, minted) = self.take_position(key)?;
+        let deposited = deposited.checked_sub(amount).ok_or(ManagerErrors::CouldNotSub(CouldNotSub {}))?;
+        self.store_position(key, deposited, minted)?;
+        self.total_deposited.set(self.total_deposited.get().checked_sub(amount)
             .ok_or(ManagerErrors::CouldNotSub(CouldNotSub {}))?);
-        let ratio = self.collat_ratio(sender)?;
+        let ratio = self.guarded_ratio(deposited, minted)?;
         assert_or!(ratio > U256::from(MIN_COLLAT_RATIO), ManagerErrors::Undercollateralized(Undercollateralized {}));
-        calls::transfer_call(self.weth.get(), sender, amount)?;
+        calls::transfer_call(self.vm(), self.weth.get(), account, amount)?;
+        self.log_position(key);
         Ok(())
     }
 
-    pub fn liquidate(&mut self, user: Address) -> Result<(), Vec<u8>> {
-        let result = self.collat_ratio(user)?;
-        assert_or!(result <= U256::from(MIN_COLLAT_RATIO), ManagerErrors::Undercollateralized(Undercollateralized {}));
-        let sender = self.vm().msg_sender();
-        let amount_minted = self.address_2minted.get(user);
-        calls::burn_call(self.sh_usd.get(), user, amount_minted)?;
-        let amount_deposited = self.address_2deposit.get(user);
-        calls::transfer_call(self.weth.get(), sender, amount_deposited)?;
-        self.address_2deposit.insert(user, U256::ZERO);
-        self.address_2minted.insert(user, U256::ZERO);
+    /// The sender, if they own vault `id`
+    fn vault_owner(&self, id: U256) -> Result<Address, Vec<u8>> {
+        let vault_nft = self.vault_nft.get();
+        assert_or!(!vault_nft.is_zero(), ManagerErrors::VaultsDisabled(VaultsDisabled {}));
+        let sender = context::msg_sender(self.vm());
+        // `ownerOf` reverts for vaults that were never opened
+        let owner = calls::owner_of_call(self.vm(), vault_nft, id)
+            .map_err(|_| ManagerErrors::NotVaultOwner(NotVaultOwner { id }))?;
+        assert_or!(owner == sender, ManagerErrors::NotVaultOwner(NotVaultOwner { id }));
+        Ok(owner)
+    }
+
+    /// Lets keepers and indexers follow positions without reading storage
+    fn log_position(&self, user: Address) {
+        let (deposited, minted) = self.load_position(user).unwrap_or_default();
+        log(self.vm(), PositionUpdated { user, deposited, minted });
+    }
+
+    /// `user`'s collateral and debt, from the old maps while they may still hold it
+    fn load_position(&self, user: Address) -> Result<(U256, U256), Vec<u8>> {
+        let position = self.positions.getter(user);
+        if position.is_empty() && !self.positions_packed.get() {
+            return Ok((self.address_2deposit.get(user), self.address_2minted.get(user)));
+        }
+        Ok((position.collateral(), position.debt(DEBT_RATE).map_err(ManagerErrors::from)?))
+    }
+
+    /// Loads `user`'s position for an update, moving it out of the old maps first
+    fn take_position(&mut self, user: Address) -> Result<(U256, U256), Vec<u8>> {
+        if !self.positions.getter(user).is_empty() || self.positions_packed.get() {
+            return self.load_position(user);
+        }
+        // Everything deposited before the upgrade is still to be moved
+        let mut legacy = self.legacy_deposited.get();
+        if legacy.is_zero() {
+            legacy = self.total_deposited.get();
+        }
+        let (deposited, minted) = (self.address_2deposit.get(user), self.address_2minted.get(user));
+        if !deposited.is_zero() || !minted.is_zero() {
+            self.store_position(user, deposited, minted)?;
+            self.positions.setter(user).set_flags(MIGRATED);
+            self.address_2deposit.insert(user, U256::ZERO);
+            self.address_2minted.insert(user, U256::ZERO);
+            legacy = legacy.checked_sub(deposited).ok_or(ManagerErrors::CouldNotSub(CouldNotSub {}))?;
+        }
+        self.legacy_deposited.set(legacy);
+        if legacy.is_zero() {
+            self.positions_packed.set(true);
+            log(self.vm(), PositionsMigrated {});
+        }
+        Ok((deposited, minted))
+    }
+
+    fn store_position(&mut self, user: Address, deposited: U256, minted: U256) -> Result<(), Vec<u8>> {
+        let mut position = self.positions.setter(user);
+        position.set_collateral(deposited)
+            .and_then(|_| position.set_debt(minted, DEBT_RATE))
+            .map_err(|_| ManagerErrors::PositionOverflow(PositionOverflow {}))?;
         Ok(())
     }
 
-    pub fn collat_ratio(&self, user: Address) -> Result<U256, Vec<u8>> {
-        let minted = self.address_2minted.get(user);
+    /// Collateral value over debt, scaled by 1e18
+    fn ratio(&self, deposited: U256, minted: U256) -> Result<U256, Vec<u8>> {
+        if minted.is_zero() { return Ok(U256::MAX); }
+        let answer = calls::latest_answer_call(self.vm(), self.oracle.get())?;
+        Self::ratio_at(deposited, minted, answer)
+    }
+
+    /// Like `ratio`, at a price the guard accepts. Positions without debt need no price.
+    fn guarded_ratio(&mut self, deposited: U256, minted: U256) -> Result<U256, Vec<u8>> {
         if minted.is_zero() { return Ok(U256::MAX); }
-        let deposited = self.address_2deposit.get(user);
-        let price = calls::latest_answer_call(self.oracle.get())?;
-        let value = deposited.checked_mul(U256::from_str(&price.to_string()).unwrap()
-            .checked_mul(U256::from(1e10 as u64))
-            .ok_or(ManagerErrors::CouldNotMul(CouldNotMul {}))?)
-        .ok_or(ManagerErrors::CouldNotMul(CouldNotMul {}))?;
-        let value_scaled = value.checked_div(U256::from(1e18 as u64)).ok_or(ManagerErrors::CouldNotDiv(CouldNotDiv {}))?;
-        Ok(value_scaled.checked_div(minted).ok_or(ManagerErrors::CouldNotDiv(CouldNotDiv {}))?)
+        let answer = self.guarded_answer()?;
+        Self::ratio_at(deposited, minted, answer)
+    }
+
+    fn ratio_at(deposited: U256, minted: U256, answer: I256) -> Result<U256, Vec<u8>> {
+        if minted.is_zero() { return Ok(U256::MAX); }
+        let value = collateral_value(deposited, answer).map_err(ManagerErrors::from)?;
+        Ok(Wad(value).div(Wad(minted), Rounding::Down).map_err(ManagerErrors::from)?.0)
+    }
+
+    /// The oracle answer, unless the price guard holds it back
+    fn guarded_answer(&mut self) -> Result<I256, Vec<u8>> {
+        let answer = calls::latest_answer_call(self.vm(), self.oracle.get())?;
+        let price = math::to_unsigned(answer).map_err(ManagerErrors::from)?;
+        assert_or!(self.price_guard.observe(price), ManagerErrors::PriceSuspicious(PriceSuspicious {}));
+        Ok(answer)
+    }
+
+    /// Usd value of `deposited` weth, scaled to 18 decimals
+    fn collateral_value(&self, deposited: U256) -> Result<U256, Vec<u8>> {
+        let answer = calls::latest_answer_call(self.vm(), self.oracle.get())?;
+        Ok(collateral_value(deposited, answer).map_err(ManagerErrors::from)?)
+    }
+}
+
+/// The address vault `id`'s position is kept under in place of a user's, the
+/// last 20 bytes of `keccak256("microstable.vault" ++ id)`. Nobody holds its key.
+pub fn vault_key(id: U256) -> Address {
+    let mut preimage = [0u8; 49];
+    preimage[..17].copy_from_slice(b"microstable.vault");
+    preimage[17..].copy_from_slice(&id.to_be_bytes::<32>());
+    Address::from_word(keccak256(preimage))
+}
+
+/// Usd value of `deposited` weth at an oracle `answer` with `ORACLE_DECIMALS`,
+/// scaled to 18 decimals and rounded down. Negative answers are rejected.
+pub fn collateral_value(deposited: U256, answer: I256) -> math::Result<U256> {
+    let price = Wad::from_decimals(math::to_unsigned(answer)?, ORACLE_DECIMALS, Rounding::Down)?;
+    Ok(Wad(deposited).mul(price, Rounding::Down)?.0)
+}
+
+/// shUSD a liquidator repays for under water collateral worth `value`, which
+/// it gets at `LIQUIDATION_PENALTY` below its value. Rounded up.
+pub fn underwater_repayment(value: U256) -> math::Result<U256> {
+    Ok(Wad(value).div(Wad(math::WAD + U256::from(LIQUIDATION_PENALTY)), Rounding::Up)?.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, keccak256};
+    use alloc::boxed::Box;
+    use stylus_sdk::host::VM;
+    use stylus_sdk::testing::TestVM;
+
+    fn map_slot(key: Address, slot: u64) -> U256 {
+        let mut preimage = [0u8; 64];
+        preimage[12..32].copy_from_slice(key.as_slice());
+        preimage[32..].copy_from_slice(&U256::from(slot).to_be_bytes::<32>());
+        U256::from_be_bytes(keccak256(preimage).0)
+    }
+
+    /// Fields may only ever be appended to `Manager`, a proxy upgrade would
+    /// otherwise read existing positions from the wrong slots.
+    #[test]
+    fn storage_layout_is_append_only() {
+        let vm = TestVM::new();
+        let mut manager = Manager::from(&vm);
+        let user = address!("00000000000000000000000000000000000000aa");
+
+        manager.sh_usd.set(address!("0000000000000000000000000000000000000001"));
+        manager.weth.set(address!("0000000000000000000000000000000000000002"));
+        manager.oracle.set(address!("0000000000000000000000000000000000000003"));
+        manager.address_2deposit.insert(user, U256::from(4));
+        manager.address_2minted.insert(user, U256::from(5));
+        manager.is_initialized.set(true);
+        manager.governance.set(address!("0000000000000000000000000000000000000006"));
+        manager.surplus.set(U256::from(7));
+        manager.bad_debt.set(U256::from(8));
+        manager.total_deposited.set(U256::from(9));
+        manager.mint_limiter.configure(U256::from(10), 0, U256::ZERO);
+        manager.repaid.set(U256::from(11));
+        let mut position = manager.positions.setter(user);
+        position.set_collateral(U256::from(12)).unwrap();
+        position.set_debt(U256::from(13), Ray::ONE).unwrap();
+        position.set_flags(MIGRATED);
+        manager.legacy_deposited.set(U256::from(14));
+        manager.positions_packed.set(true);
+        manager.vault_nft.set(address!("0000000000000000000000000000000000000013"));
+        manager.price_guard.configure(U256::from(20), 0);
+        manager.guardian.set(address!("0000000000000000000000000000000000000024"));
+        manager.trusted_forwarder.set(address!("0000000000000000000000000000000000000025"));
+
+        let word = |slot: U256| U256::from_be_bytes(vm.get_storage(slot).0);
+        assert_eq!(word(U256::from(0)), U256::from(1));
+        assert_eq!(word(U256::from(1)), U256::from(2));
+        assert_eq!(word(U256::from(2)), U256::from(3));
+        assert_eq!(word(map_slot(user, 3)), U256::from(4));
+        assert_eq!(word(map_slot(user, 4)), U256::from(5));
+        // is_initialized and governance share slot 5
+        assert_eq!(word(U256::from(5)), (U256::from(6) << 8) | U256::from(1));
+        assert_eq!(word(U256::from(6)), U256::from(7));
+        assert_eq!(word(U256::from(7)), U256::from(8));
+        assert_eq!(word(U256::from(8)), U256::from(9));
+        assert_eq!(word(U256::from(9)), U256::from(10));
+        // the mint limiter takes slots 9 to 14
+        assert_eq!(word(U256::from(15)), U256::from(11));
+        // collateral and debt share a slot, the rate and flags take the next
+        assert_eq!(word(map_slot(user, 16)), (U256::from(13) << 128) | U256::from(12));
+        assert_eq!(word(map_slot(user, 16) + U256::from(1)), (U256::from(MIGRATED) << 128) | math::RAY);
+        assert_eq!(word(U256::from(17)), U256::from(14));
+        // positions_packed and vault_nft share slot 18
+        assert_eq!(word(U256::from(18)), (U256::from(0x13) << 8) | U256::from(1));
+        assert_eq!(word(U256::from(19)), U256::from(20));
+        // the price guard takes slots 19 to 22
+        assert_eq!(word(U256::from(23)), U256::from(0x24));
+        assert_eq!(word(U256::from(24)), U256::from(0x25));
+        let (slot, offset) = TRUSTED_FORWARDER_SLOT;
+        let forwarder = unsafe { StorageAddress::new(U256::from(slot), offset, VM { host: Box::new(vm.clone()) }) };
+        assert_eq!(forwarder.get(), address!("0000000000000000000000000000000000000025"));
+    }
+
+    /// A manager upgraded from the two maps layout moves positions over one by one
+    #[test]
+    fn positions_migrate_from_the_old_maps() {
+        let vm = TestVM::new();
+        let mut manager = Manager::from(&vm);
+        let [alice, bob, carol] = [0xa1, 0xb0, 0xc0].map(Address::with_last_byte);
+        manager.is_initialized.set(true);
+        manager.address_2deposit.insert(alice, U256::from(30));
+        manager.address_2minted.insert(alice, U256::from(10));
+        manager.address_2deposit.insert(bob, U256::from(20));
+        manager.total_deposited.set(U256::from(50));
+
+        assert_eq!(manager.position(alice), Ok((U256::from(30), U256::from(10))));
+        assert!(!manager.positions_packed());
+        // Carol has nothing to move, alice's position moves with its debt
+        manager.migrate_positions(vec![carol, alice]).unwrap();
+        assert_eq!(manager.position(alice), Ok((U256::from(30), U256::from(10))));
+        assert_eq!((manager.address_2deposit.get(alice), manager.address_2minted.get(alice)), (U256::ZERO, U256::ZERO));
+        assert_eq!(manager.positions.getter(alice).flags(), MIGRATED);
+        assert_eq!(manager.legacy_deposited.get(), U256::from(20));
+        assert!(!manager.positions_packed());
+        assert!(vm.get_emitted_logs().is_empty());
+
+        // Moving a position twice changes nothing
+        manager.migrate_positions(vec![alice, bob]).unwrap();
+        assert_eq!(manager.position(alice), Ok((U256::from(30), U256::from(10))));
+        assert_eq!(manager.position(bob), Ok((U256::from(20), U256::ZERO)));
+        assert!(manager.positions_packed());
+        let logs = vm.get_emitted_logs();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].0[0], <PositionsMigrated as alloy_sol_types::SolEvent>::SIGNATURE_HASH);
+
+        // The old maps are no longer read
+        manager.address_2deposit.insert(carol, U256::from(1));
+        assert_eq!(manager.position(carol), Ok((U256::ZERO, U256::ZERO)));
+    }
+
+    #[test]
+    fn collateral_value_scales_the_oracle_answer() {
+        let ether = |amount: u64| U256::from(amount) * math::WAD;
+        let answer = |price: i64| I256::try_from(price).unwrap();
+        // 1.5 WETH at $1757.65550000
+        assert_eq!(collateral_value(ether(3) / U256::from(2), answer(1757_6555_0000)), Ok(U256::from(2_636_483_250_000_000_000_000u128)));
+        // A wei at $0.5 is worth half a wei, rounded down
+        assert_eq!(collateral_value(U256::from(1), answer(5000_0000)), Ok(U256::ZERO));
+        assert_eq!(collateral_value(ether(1), I256::ZERO), Ok(U256::ZERO));
+        assert_eq!(collateral_value(ether(1), answer(-1)), Err(MathError::Negative));
+        assert_eq!(collateral_value(U256::MAX, answer(2_0000_0000)), Err(MathError::Overflow));
+        assert!(matches!(ManagerErrors::from(MathError::Negative), ManagerErrors::ConversionFailure(_)));
     }
 }
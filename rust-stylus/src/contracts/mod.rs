@@ -1,6 +1,29 @@
-#[cfg(feature = "sh-usd")]
+#[cfg(any(feature = "sh-usd", feature = "client", test))]
 pub mod sh_usd;
-#[cfg(feature = "manager")]
+#[cfg(any(feature = "manager", feature = "client", test))]
+// The client only needs the errors, the contract itself is left out
+#[cfg_attr(not(any(feature = "manager", test)), allow(dead_code, unused_imports))]
 pub mod manager;
 
-pub mod calls;
\ No newline at end of file
+#[cfg(any(feature = "timelock", feature = "sh-usd", feature = "client", test))]
+// shUSD only needs the delay floor to check its timelock
+#[cfg_attr(not(any(feature = "timelock", test)), allow(dead_code, unused_imports))]
+pub mod timelock;
+#[cfg(any(feature = "gov-token", test))]
+pub mod gov_token;
+#[cfg(any(feature = "governor", test))]
+pub mod governor;
+#[cfg(any(feature = "vault-nft", test))]
+pub mod vault_nft;
+#[cfg(any(feature = "psm", test))]
+pub mod psm;
+#[cfg(any(feature = "twap-oracle", test))]
+pub mod twap_oracle;
+#[cfg(any(feature = "forwarder", test))]
+pub mod forwarder;
+
+pub mod calls;
+pub mod rate_limit;
+pub mod price_guard;
+pub mod position;
+pub mod proxy;
\ No newline at end of file
@@ -0,0 +1,84 @@
+//! A user's position packed into two storage slots.
+//!
+//! Collateral and normalized debt share the first slot, so checking a ratio
+//! costs a single SLOAD. The second holds the debt rate the position was last
+//! written at and flags, with room left for whatever positions need next.
+use alloy_primitives::{U128, U256, U8};
+use stylus_sdk::prelude::*;
+use stylus_sdk::storage::{StorageU128, StorageU8};
+use crate::math::{self, MathError, Ray, Rounding};
+
+/// The position was moved over from `Manager`'s `address_2deposit` and `address_2minted`
+pub const MIGRATED: u8 = 1;
+
+#[storage]
+pub struct Position {
+    collateral: StorageU128,
+    normalized_debt: StorageU128,
+    rate: StorageU128,
+    flags: StorageU8,
+}
+
+impl Position {
+    pub fn collateral(&self) -> U256 {
+        U256::from(self.collateral.get())
+    }
+
+    pub fn normalized_debt(&self) -> U256 {
+        U256::from(self.normalized_debt.get())
+    }
+
+    /// Debt at `rate`, rounded up
+    pub fn debt(&self, rate: Ray) -> math::Result<U256> {
+        rate.apply(self.normalized_debt(), Rounding::Up)
+    }
+
+    /// The rate the debt was last written at, zero for a position that never had any
+    pub fn rate(&self) -> Ray {
+        Ray(U256::from(self.rate.get()))
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags.get().to()
+    }
+
+    /// Only reads the first slot
+    pub fn is_empty(&self) -> bool {
+        self.collateral.get().is_zero() && self.normalized_debt.get().is_zero()
+    }
+
+    pub fn set_collateral(&mut self, amount: U256) -> math::Result<()> {
+        self.collateral.set(narrow(amount)?);
+        Ok(())
+    }
+
+    /// Stores `debt` normalized by `rate`, rounded up so the position never owes less
+    pub fn set_debt(&mut self, debt: U256, rate: Ray) -> math::Result<()> {
+        let normalized = math::mul_div(debt, math::RAY, rate.0, Rounding::Up)?;
+        self.normalized_debt.set(narrow(normalized)?);
+        // Saves writing the second slot while the rate doesn't move
+        if self.rate() != rate {
+            self.rate.set(narrow(rate.0)?);
+        }
+        Ok(())
+    }
+
+    pub fn set_flags(&mut self, flags: u8) {
+        self.flags.set(U8::from(flags));
+    }
+
+    /// Zeroes both slots for the refund
+    pub fn clear(&mut self) {
+        self.collateral.set(U128::ZERO);
+        self.normalized_debt.set(U128::ZERO);
+        self.rate.set(U128::ZERO);
+        self.flags.set(U8::ZERO);
+    }
+}
+
+fn narrow(value: U256) -> math::Result<U128> {
+    if value.bit_len() > 128 {
+        return Err(MathError::Overflow);
+    }
+    Ok(value.to())
+}
@@ -0,0 +1,107 @@
+//! Circuit breaker on the oracle price.
+//!
+//! A price further than `max_deviation` (a wad fraction) from the last good one
+//! within `window` seconds of it trips the breaker. While tripped no price is
+//! good until it either comes back near the last good one, holds near the
+//! suspicious one for a whole `window`, or the guardian confirms it. A zero
+//! `max_deviation` turns the breaker off.
+//!
+//! A call that reverts on a suspicious price also rolls back the trip, anyone
+//! can persist it with the manager's `pokePrice`.
+use alloy_primitives::{Address, U256, U64};
+use alloy_sol_types::sol;
+use stylus_sdk::prelude::*;
+use stylus_sdk::storage::{StorageBool, StorageU256, StorageU64};
+use crate::math::{self, Rounding, WAD};
+
+sol! {
+    event BreakerTripped(uint256 last_price, uint256 price);
+    /// `by` is zero when the price stabilised on its own
+    event BreakerReset(uint256 price, address indexed by);
+}
+
+#[storage]
+pub struct PriceGuard {
+    max_deviation: StorageU256,
+    last_price: StorageU256,
+    pending_price: StorageU256,
+    window: StorageU64,
+    last_updated: StorageU64,
+    pending_since: StorageU64,
+    tripped: StorageBool
+}
+
+impl PriceGuard {
+    pub fn configure(&mut self, max_deviation: U256, window: u64) {
+        self.max_deviation.set(max_deviation);
+        self.window.set(U64::from(window));
+    }
+
+    pub fn limits(&self) -> (U256, u64) {
+        (self.max_deviation.get(), self.window.get().to())
+    }
+
+    /// The last good price and when it was seen
+    pub fn last_price(&self) -> (U256, u64) {
+        (self.last_price.get(), self.last_updated.get().to())
+    }
+
+    pub fn tripped(&self) -> bool {
+        self.tripped.get()
+    }
+
+    /// Checks `price` against the last good one, returns whether it can be used.
+    pub fn observe(&mut self, price: U256) -> bool {
+        let now = self.vm().block_timestamp();
+        let last_price = self.last_price.get();
+        if self.max_deviation.get().is_zero() || last_price.is_zero() {
+            self.accept(price, now);
+            return true;
+        }
+        let window: u64 = self.window.get().to();
+        if self.tripped.get() {
+            let pending_price = self.pending_price.get();
+            let stable = self.near(price, pending_price) && now.saturating_sub(self.pending_since.get().to()) >= window;
+            if self.near(price, last_price) || stable {
+                self.reset(price, Address::ZERO);
+                return true;
+            }
+            if !self.near(price, pending_price) {
+                self.pending_price.set(price);
+                self.pending_since.set(U64::from(now));
+            }
+            return false;
+        }
+        if self.near(price, last_price) || now.saturating_sub(self.last_updated.get().to()) > window {
+            self.accept(price, now);
+            return true;
+        }
+        self.tripped.set(true);
+        self.pending_price.set(price);
+        self.pending_since.set(U64::from(now));
+        log(self.vm(), BreakerTripped { last_price, price });
+        false
+    }
+
+    /// Takes `price` as good and closes the breaker, `by` confirmed it
+    pub fn reset(&mut self, price: U256, by: Address) {
+        self.tripped.set(false);
+        self.accept(price, self.vm().block_timestamp());
+        log(self.vm(), BreakerReset { price, by });
+    }
+
+    fn accept(&mut self, price: U256, now: u64) {
+        self.last_price.set(price);
+        self.last_updated.set(U64::from(now));
+    }
+
+    /// Whether `price` is within `max_deviation` of `reference`
+    fn near(&self, price: U256, reference: U256) -> bool {
+        if reference.is_zero() {
+            return price.is_zero();
+        }
+        let difference = price.abs_diff(reference);
+        math::mul_div(difference, WAD, reference, Rounding::Up)
+            .is_ok_and(|deviation| deviation <= self.max_deviation.get())
+    }
+}
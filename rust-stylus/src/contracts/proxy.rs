@@ -0,0 +1,87 @@
+//! Minimal ERC-1967 proxy.
+//!
+//! The proxy keeps no state of its own apart from the implementation address,
+//! which lives in the ERC-1967 slot so it can never collide with the
+//! sequential `#[storage]` layout of the implementation. Upgrades are UUPS
+//! style: the implementation decides who may call `upgrade_to`. Only the
+//! deployer can point a fresh proxy at its implementation.
+use alloc::vec;
+use alloc::vec::Vec;
+use alloy_sol_types::sol;
+use alloy_primitives::{uint, Address, B256, U256};
+use stylus_sdk::prelude::*;
+use stylus_sdk::stylus_core::Host;
+use crate::access::deployer::OnlyDeployer;
+
+/// keccak256("eip1967.proxy.implementation") - 1
+pub const IMPLEMENTATION_SLOT: U256 = uint!(0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc_U256);
+
+sol! {
+    event Upgraded(address indexed implementation);
+
+    error ProxyAlreadyInitialized();
+    error NoImplementation();
+}
+
+#[derive(SolidityError)]
+pub enum ProxyErrors {
+    ProxyAlreadyInitialized(ProxyAlreadyInitialized),
+    NoImplementation(NoImplementation),
+    OnlyDeployer(OnlyDeployer),
+}
+
+/// Reads the implementation address out of the ERC-1967 slot
+pub fn implementation(host: &dyn Host) -> Address {
+    Address::from_word(host.storage_load_bytes32(IMPLEMENTATION_SLOT))
+}
+
+/// Writes `new_implementation` into the ERC-1967 slot and emits `Upgraded`
+pub fn set_implementation(host: &dyn Host, new_implementation: Address) {
+    unsafe { host.storage_cache_bytes32(IMPLEMENTATION_SLOT, new_implementation.into_word()) };
+    host.flush_cache(false);
+    log(host, Upgraded { implementation: new_implementation });
+}
+
+#[cfg_attr(feature = "proxy", stylus_sdk::prelude::entrypoint)]
+#[storage]
+pub struct Erc1967Proxy {}
+
+#[cfg_attr(any(feature = "proxy", test), stylus_sdk::prelude::public)]
+#[cfg(any(feature = "proxy", test))]
+impl Erc1967Proxy {
+    /// Points the proxy at `implementation` and delegates `data` to it,
+    /// which should be the implementation's `initialize` call. Deployer only.
+    pub fn init_proxy(&mut self, implementation_address: Address, data: stylus_sdk::abi::Bytes) -> Result<(), Vec<u8>> {
+        if implementation(self.vm()) != Address::ZERO {
+            return Err(ProxyErrors::ProxyAlreadyInitialized(ProxyAlreadyInitialized {}).into());
+        }
+        crate::access::deployer::only_deployer(self.vm().msg_sender()).map_err(ProxyErrors::OnlyDeployer)?;
+        set_implementation(self.vm(), implementation_address);
+        if !data.is_empty() {
+            self.delegate(implementation_address, &data)?;
+        }
+        Ok(())
+    }
+
+    #[fallback]
+    pub fn fallback(&mut self, calldata: &[u8]) -> stylus_sdk::ArbResult {
+        let implementation_address = implementation(self.vm());
+        if implementation_address == Address::ZERO {
+            return Err(ProxyErrors::NoImplementation(NoImplementation {}).into());
+        }
+        self.delegate(implementation_address, calldata)
+    }
+}
+
+#[cfg(any(feature = "proxy", test))]
+impl Erc1967Proxy {
+    fn delegate(&mut self, implementation_address: Address, calldata: &[u8]) -> stylus_sdk::ArbResult {
+        let context = stylus_sdk::stylus_core::calls::context::Call::new();
+        Ok(unsafe { self.vm().delegate_call(&context, implementation_address, calldata) }?)
+    }
+}
+
+/// Value `proxiable_uuid` must return for an implementation to be upgradeable to
+pub fn proxiable_uuid() -> B256 {
+    IMPLEMENTATION_SLOT.into()
+}
@@ -0,0 +1,221 @@
+//! Peg stability module, swaps USDC and shUSD 1:1 less a fee.
+//!
+//! `sellUsdc` takes USDC into the reserves and mints the same dollar amount of
+//! shUSD, `buyUsdc` burns shUSD and pays USDC out of them. The shUSD minted
+//! against the reserves is capped by `debt_ceiling`. `tin` and `tout` are wad
+//! fractions charged in shUSD on the way in and out, kept by the PSM until
+//! governance collects them. Needs the minter and burner roles on `ShUSD`.
+use alloc::vec;
+use alloc::vec::Vec;
+use alloy_sol_types::sol;
+use alloy_primitives::{Address, U8};
+use crate::access::deployer::{self, OnlyDeployer};
+use crate::contracts::calls;
+use crate::math::{self, MathError, Rounding, Wad};
+use stylus_sdk::{alloy_primitives::U256, prelude::*};
+use stylus_sdk::storage::{StorageAddress, StorageBool, StorageU256, StorageU8};
+
+sol! {
+    event SoldUsdc(address indexed user, uint256 usdc, uint256 sh_usd, uint256 fee);
+    event BoughtUsdc(address indexed user, uint256 usdc, uint256 sh_usd, uint256 fee);
+    event FeesSet(uint256 tin, uint256 tout);
+    event DebtCeilingSet(uint256 debt_ceiling);
+    event FeesCollected(address indexed to, uint256 amount);
+    event GovernanceTransferred(address indexed previous, address indexed governance);
+
+    error AlreadyInitialized();
+    error OnlyGovernance();
+    error UnsupportedDecimals(uint8 decimals);
+    error FeeTooHigh();
+    error DebtCeilingExceeded(uint256 debt_ceiling, uint256 debt);
+    error InsufficientReserves(uint256 reserves, uint256 requested);
+    error MathOverflow();
+}
+
+#[derive(SolidityError)]
+pub enum PsmErrors {
+    AlreadyInitialized(AlreadyInitialized),
+    OnlyDeployer(OnlyDeployer),
+    OnlyGovernance(OnlyGovernance),
+    UnsupportedDecimals(UnsupportedDecimals),
+    FeeTooHigh(FeeTooHigh),
+    DebtCeilingExceeded(DebtCeilingExceeded),
+    InsufficientReserves(InsufficientReserves),
+    MathOverflow(MathOverflow),
+}
+
+impl From<MathError> for PsmErrors {
+    fn from(_: MathError) -> Self {
+        PsmErrors::MathOverflow(MathOverflow {})
+    }
+}
+
+#[cfg_attr(feature = "psm", stylus_sdk::prelude::entrypoint)]
+#[storage]
+pub struct Psm {
+    usdc: StorageAddress,
+    sh_usd: StorageAddress,
+    governance: StorageAddress,
+    usdc_decimals: StorageU8,
+    is_initialized: StorageBool,
+    /// Fee on `sellUsdc`, a wad fraction of the shUSD minted
+    tin: StorageU256,
+    /// Fee on `buyUsdc`, a wad fraction of the shUSD burnt
+    tout: StorageU256,
+    /// Most shUSD that may be minted against the reserves
+    debt_ceiling: StorageU256,
+    /// USDC held, in its own decimals. Tracked rather than read from the
+    /// balance so donations don't count against the ceiling.
+    reserves: StorageU256,
+    /// shUSD taken as fees and not collected yet
+    fees: StorageU256,
+}
+
+#[cfg_attr(any(feature = "psm", test), stylus_sdk::prelude::public)]
+#[cfg(any(feature = "psm", test))]
+impl Psm {
+    /// Deployer only. Starts without fees and with a zero debt ceiling, governance opens it
+    pub fn init(&mut self, usdc: Address, sh_usd: Address, governance: Address) -> Result<(), Vec<u8>> {
+        assert_or!(!self.is_initialized.get(), PsmErrors::AlreadyInitialized(AlreadyInitialized {}));
+        deployer::only_deployer(self.vm().msg_sender()).map_err(PsmErrors::OnlyDeployer)?;
+        let decimals = calls::decimals_call(self.vm(), usdc)?;
+        assert_or!(decimals <= 18, PsmErrors::UnsupportedDecimals(UnsupportedDecimals { decimals }));
+        self.usdc.set(usdc);
+        self.sh_usd.set(sh_usd);
+        self.governance.set(governance);
+        self.usdc_decimals.set(U8::from(decimals));
+        self.is_initialized.set(true);
+        Ok(())
+    }
+
+    /// Swaps `amount` USDC for shUSD, returns the shUSD received after `tin`
+    pub fn sell_usdc(&mut self, amount: U256) -> Result<U256, Vec<u8>> {
+        let value = self.to_sh_usd(amount)?;
+        let reserves = self.reserves.get().checked_add(amount).ok_or(PsmErrors::MathOverflow(MathOverflow {}))?;
+        let debt = self.to_sh_usd(reserves)?;
+        let debt_ceiling = self.debt_ceiling.get();
+        assert_or!(debt <= debt_ceiling, PsmErrors::DebtCeilingExceeded(DebtCeilingExceeded { debt_ceiling, debt }));
+        let fee = Wad(value).mul(Wad(self.tin.get()), Rounding::Up).map_err(PsmErrors::from)?.0;
+
+        let sender = self.vm().msg_sender();
+        let this = self.vm().contract_address();
+        calls::transfer_from_call(self.vm(), self.usdc.get(), sender, this, amount)?;
+        self.reserves.set(reserves);
+        let out = value - fee;
+        calls::mint_call(self.vm(), self.sh_usd.get(), sender, out)?;
+        if !fee.is_zero() {
+            calls::mint_call(self.vm(), self.sh_usd.get(), this, fee)?;
+            self.fees.set(self.fees.get().checked_add(fee).ok_or(PsmErrors::MathOverflow(MathOverflow {}))?);
+        }
+        log(self.vm(), SoldUsdc { user: sender, usdc: amount, sh_usd: out, fee });
+        Ok(out)
+    }
+
+    /// Swaps shUSD for `amount` USDC, returns the shUSD paid including `tout`.
+    /// The PSM must be approved for it.
+    pub fn buy_usdc(&mut self, amount: U256) -> Result<U256, Vec<u8>> {
+        let reserves = self.reserves.get();
+        assert_or!(amount <= reserves, PsmErrors::InsufficientReserves(InsufficientReserves { reserves, requested: amount }));
+        let value = self.to_sh_usd(amount)?;
+        let fee = Wad(value).mul(Wad(self.tout.get()), Rounding::Up).map_err(PsmErrors::from)?.0;
+        let paid = value.checked_add(fee).ok_or(PsmErrors::MathOverflow(MathOverflow {}))?;
+
+        let sender = self.vm().msg_sender();
+        let this = self.vm().contract_address();
+        calls::transfer_from_call(self.vm(), self.sh_usd.get(), sender, this, paid)?;
+        calls::burn_call(self.vm(), self.sh_usd.get(), this, value)?;
+        self.fees.set(self.fees.get().checked_add(fee).ok_or(PsmErrors::MathOverflow(MathOverflow {}))?);
+        self.reserves.set(reserves - amount);
+        calls::transfer_call(self.vm(), self.usdc.get(), sender, amount)?;
+        log(self.vm(), BoughtUsdc { user: sender, usdc: amount, sh_usd: paid, fee });
+        Ok(paid)
+    }
+
+    /// Both are wad fractions of at most one
+    pub fn set_fees(&mut self, tin: U256, tout: U256) -> Result<(), Vec<u8>> {
+        self.only_governance()?;
+        assert_or!(tin <= Wad::ONE.0 && tout <= Wad::ONE.0, PsmErrors::FeeTooHigh(FeeTooHigh {}));
+        self.tin.set(tin);
+        self.tout.set(tout);
+        log(self.vm(), FeesSet { tin, tout });
+        Ok(())
+    }
+
+    /// Lowering the ceiling below the debt only stops `sellUsdc`
+    pub fn set_debt_ceiling(&mut self, debt_ceiling: U256) -> Result<(), Vec<u8>> {
+        self.only_governance()?;
+        self.debt_ceiling.set(debt_ceiling);
+        log(self.vm(), DebtCeilingSet { debt_ceiling });
+        Ok(())
+    }
+
+    /// Sends the shUSD taken as fees to `to`, returns the amount
+    pub fn collect_fees(&mut self, to: Address) -> Result<U256, Vec<u8>> {
+        self.only_governance()?;
+        let amount = self.fees.get();
+        self.fees.set(U256::ZERO);
+        calls::transfer_call(self.vm(), self.sh_usd.get(), to, amount)?;
+        log(self.vm(), FeesCollected { to, amount });
+        Ok(amount)
+    }
+
+    pub fn set_governance(&mut self, new_governance: Address) -> Result<(), Vec<u8>> {
+        let previous = self.governance.get();
+        self.only_governance()?;
+        self.governance.set(new_governance);
+        log(self.vm(), GovernanceTransferred { previous, governance: new_governance });
+        Ok(())
+    }
+
+    /// USDC held, in its own decimals
+    pub fn reserves(&self) -> U256 {
+        self.reserves.get()
+    }
+
+    /// shUSD minted against the reserves, the reserves scaled to 18 decimals
+    pub fn debt(&self) -> Result<U256, Vec<u8>> {
+        self.to_sh_usd(self.reserves.get())
+    }
+
+    pub fn debt_ceiling(&self) -> U256 {
+        self.debt_ceiling.get()
+    }
+
+    pub fn fees(&self) -> U256 {
+        self.fees.get()
+    }
+
+    pub fn tin(&self) -> U256 {
+        self.tin.get()
+    }
+
+    pub fn tout(&self) -> U256 {
+        self.tout.get()
+    }
+
+    pub fn usdc(&self) -> Address {
+        self.usdc.get()
+    }
+
+    pub fn sh_usd(&self) -> Address {
+        self.sh_usd.get()
+    }
+
+    pub fn governance(&self) -> Address {
+        self.governance.get()
+    }
+}
+
+#[cfg(any(feature = "psm", test))]
+impl Psm {
+    fn only_governance(&self) -> Result<(), Vec<u8>> {
+        assert_or!(self.vm().msg_sender() == self.governance.get(), PsmErrors::OnlyGovernance(OnlyGovernance {}));
+        Ok(())
+    }
+
+    /// `amount` USDC as 18 decimals shUSD, exact since USDC has at most 18
+    fn to_sh_usd(&self, amount: U256) -> Result<U256, Vec<u8>> {
+        let decimals = self.usdc_decimals.get().to::<u8>();
+        Ok(math::rescale(amount, decimals, 18, Rounding::Down).map_err(PsmErrors::from)?)
+    }
+}
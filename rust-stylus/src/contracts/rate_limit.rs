@@ -0,0 +1,88 @@
+//! Rolling window limit on net new shUSD.
+//!
+//! The window allowance refills linearly, `window_limit` per `window` seconds,
+//! and burns hand capacity back. On top of that at most `block_limit` can be
+//! minted within a single block. A zero limit disables that check.
+use alloy_primitives::{U256, U64};
+use stylus_sdk::prelude::*;
+use stylus_sdk::storage::{StorageU256, StorageU64};
+
+#[storage]
+pub struct MintLimiter {
+    window_limit: StorageU256,
+    window: StorageU64,
+    block_limit: StorageU256,
+    available: StorageU256,
+    last_update: StorageU64,
+    last_block: StorageU64,
+    minted_in_block: StorageU256
+}
+
+impl MintLimiter {
+    pub fn configure(&mut self, window_limit: U256, window: u64, block_limit: U256) {
+        self.window_limit.set(window_limit);
+        self.window.set(U64::from(window));
+        self.block_limit.set(block_limit);
+        self.available.set(window_limit);
+        self.last_update.set(U64::from(self.vm().block_timestamp()));
+    }
+
+    pub fn limits(&self) -> (U256, u64, U256) {
+        (self.window_limit.get(), self.window.get().to(), self.block_limit.get())
+    }
+
+    /// Amount that can be minted right now, `U256::MAX` when unlimited
+    pub fn available(&self) -> U256 {
+        let mut available = self.window_available();
+        let block_limit = self.block_limit.get();
+        if !block_limit.is_zero() {
+            let minted = if self.last_block.get() == U64::from(self.vm().block_number()) { self.minted_in_block.get() } else { U256::ZERO };
+            available = available.min(block_limit.saturating_sub(minted));
+        }
+        available
+    }
+
+    /// Uses up `amount` of the allowance, returns false if there isn't enough left.
+    pub fn consume(&mut self, amount: U256) -> bool {
+        if amount > self.available() {
+            return false;
+        }
+        if !self.window_limit.get().is_zero() {
+            let available = self.window_available();
+            self.available.set(available - amount);
+            self.last_update.set(U64::from(self.vm().block_timestamp()));
+        }
+        if !self.block_limit.get().is_zero() {
+            let block = U64::from(self.vm().block_number());
+            let minted = if self.last_block.get() == block { self.minted_in_block.get() } else { U256::ZERO };
+            self.minted_in_block.set(minted + amount);
+            self.last_block.set(block);
+        }
+        true
+    }
+
+    /// Gives `amount` of the window allowance back after a burn.
+    pub fn release(&mut self, amount: U256) {
+        let window_limit = self.window_limit.get();
+        if window_limit.is_zero() {
+            return;
+        }
+        let available = self.window_available();
+        self.available.set(available.saturating_add(amount).min(window_limit));
+        self.last_update.set(U64::from(self.vm().block_timestamp()));
+    }
+
+    fn window_available(&self) -> U256 {
+        let window_limit = self.window_limit.get();
+        if window_limit.is_zero() {
+            return U256::MAX;
+        }
+        let window: u64 = self.window.get().to();
+        let elapsed = self.vm().block_timestamp().saturating_sub(self.last_update.get().to());
+        if window == 0 || elapsed >= window {
+            return window_limit;
+        }
+        let refill = window_limit.saturating_mul(U256::from(elapsed)) / U256::from(window);
+        self.available.get().saturating_add(refill).min(window_limit)
+    }
+}
@@ -1,6 +1,12 @@
 use alloc::vec;
 extern crate alloc;
 use alloc::vec::Vec;
+use crate::context;
+use crate::access::access_control::{
+    self, AccessControl, ADMIN_ROLE, BURNER_ROLE, COMPLIANCE_ROLE, GOVERNANCE_ROLE, MINTER_ROLE, PAUSER_ROLE,
+};
+use crate::access::deployer::{self, OnlyDeployer};
+use crate::contracts::{calls, timelock};
 use crate::token::erc20;
 use alloy_sol_types::sol;
 use alloy_primitives::Address;
@@ -12,53 +18,336 @@ impl erc20::Erc20Params for MicroParams {
     const NAME: &'static str = "Shafu USD";
     const SYMBOL: &'static str = "shUSD";
     const DECIMALS: u8 = 18;
+
+    /// Frozen accounts can neither send, receive, mint nor burn
+    fn before_transfer(token: &erc20::Erc20<Self>, from: Address, to: Address, _value: U256) -> Result<(), erc20::Erc20Error> {
+        for account in [from, to] {
+            if account != Address::ZERO && token.is_frozen(account) {
+                return Err(erc20::Erc20Error::AccountFrozen(erc20::AccountFrozen { account }));
+            }
+        }
+        Ok(())
+    }
 }
 
 sol_storage! {
-    #[cfg_attr(any(feature = "sh-usd"), stylus_sdk::prelude::entrypoint)]
     pub struct ShUSD {
         #[borrow]
         erc20::Erc20<MicroParams> erc20;
         address manager;
+        #[borrow]
+        AccessControl access;
+        /// Most shUSD a minter may have outstanding, zero means uncapped
+        mapping(address => uint256) minter_caps;
+        mapping(address => uint256) minted;
+        bool paused;
+        bool is_initialized;
+        /// Relays meta-transactions, the sender is the last 20 bytes of its calls
+        address trusted_forwarder;
+        /// Seizes and wipes frozen balances, along with the governance role
+        address timelock;
     }
 }
 
+/// `trusted_forwarder` shares slot 7 with `paused` and `is_initialized`
+#[cfg(any(feature = "sh-usd", test))]
+const TRUSTED_FORWARDER_SLOT: (u64, u8) = (7, 10);
+
+#[cfg(feature = "sh-usd")]
+unsafe impl TopLevelStorage for ShUSD {}
+
+/// Routes calls like `#[entrypoint]` on the struct would, taking the sender off
+/// those relayed by the trusted forwarder
+#[cfg_attr(feature = "sh-usd", stylus_sdk::prelude::entrypoint)]
+#[cfg(any(feature = "sh-usd", test))]
+pub fn route(input: Vec<u8>, host: stylus_sdk::host::VM) -> stylus_sdk::ArbResult {
+    let (slot, offset) = TRUSTED_FORWARDER_SLOT;
+    context::route::<ShUSD>(input, host, U256::from(slot), offset)
+}
+
 sol! {
-    error OnlyManagerCanCall();
+    event Frozen(address indexed account, address indexed by);
+    event Unfrozen(address indexed account, address indexed by);
+    event Seized(address indexed from, address indexed to, uint256 amount);
+    event Wiped(address indexed account, uint256 amount);
+    event TrustedForwarderSet(address indexed forwarder);
+    event TimelockSet(address indexed timelock);
+    event MinterCapSet(address indexed minter, uint256 cap);
+    event Paused(address indexed by);
+    event Unpaused(address indexed by);
+
+    error AlreadyInitialized();
+    error MinterCapExceeded(address minter, uint256 cap, uint256 requested);
+    error IssuancePaused();
     error ERC20MintError();
     error ERC20BurnError();
+    error ERC20TransferError();
+    error NotFrozen(address account);
+    error OnlyTimelock();
+    error InvalidTimelock(address timelock);
 }
 
 #[derive(SolidityError)]
 pub enum ShUSDErrors {
-    OnlyManagerCanCall(OnlyManagerCanCall),
+    AlreadyInitialized(AlreadyInitialized),
+    OnlyDeployer(OnlyDeployer),
+    Unauthorized(access_control::AccessControlUnauthorizedAccount),
+    MinterCapExceeded(MinterCapExceeded),
+    IssuancePaused(IssuancePaused),
     ERC20MintErr(ERC20MintError),
-    ERC20BurnErr(ERC20BurnError)
+    ERC20BurnErr(ERC20BurnError),
+    ERC20TransferErr(ERC20TransferError),
+    NotFrozen(NotFrozen),
+    AccountFrozen(erc20::AccountFrozen),
+    MaxSupplyExceeded(erc20::MaxSupplyExceeded),
+    OnlyTimelock(OnlyTimelock),
+    InvalidTimelock(InvalidTimelock)
 }
 
-#[cfg_attr(feature = "sh-usd", stylus_sdk::prelude::public, inherit(erc20::Erc20::<MicroParams>))]
+impl From<access_control::AccessControlError> for ShUSDErrors {
+    fn from(err: access_control::AccessControlError) -> Self {
+        match err {
+            access_control::AccessControlError::AccessControlUnauthorizedAccount(err) => ShUSDErrors::Unauthorized(err),
+        }
+    }
+}
+
+#[cfg_attr(any(feature = "sh-usd", test), stylus_sdk::prelude::public, inherit(erc20::Erc20::<MicroParams>, AccessControl))]
 impl ShUSD {
-    pub fn init(&mut self, manager_address: Address) {
+    /// Gives the caller the admin, governance and compliance roles and lets the
+    /// manager mint and burn. Can only run once and only from the deployer.
+    pub fn init(&mut self, manager_address: Address) -> Result<(), ShUSDErrors> {
+        if self.is_initialized.get() {
+            return Err(ShUSDErrors::AlreadyInitialized(AlreadyInitialized {}));
+        }
+        let sender = context::msg_sender(self.vm());
+        deployer::only_deployer(sender).map_err(ShUSDErrors::OnlyDeployer)?;
+        self.is_initialized.set(true);
         self.manager.set(manager_address);
+        self.access._grant_role(ADMIN_ROLE, sender);
+        self.access._grant_role(GOVERNANCE_ROLE, sender);
+        self.access._grant_role(COMPLIANCE_ROLE, sender);
+        self.access._grant_role(MINTER_ROLE, manager_address);
+        self.access._grant_role(BURNER_ROLE, manager_address);
+        Ok(())
     }
 
     pub fn mint(&mut self, to: Address, amount: U256) -> Result<(), ShUSDErrors> {
-        if self.vm().msg_sender() != self.manager.get() {
-            return Err(ShUSDErrors::OnlyManagerCanCall(OnlyManagerCanCall {}));
+        self.access.only_role(MINTER_ROLE)?;
+        self.when_not_paused()?;
+        let minter = context::msg_sender(self.vm());
+        let cap = self.minter_caps.get(minter);
+        let minted = self.minted.get(minter).saturating_add(amount);
+        if !cap.is_zero() && minted > cap {
+            return Err(ShUSDErrors::MinterCapExceeded(MinterCapExceeded { minter, cap, requested: amount }));
         }
+        self.minted.insert(minter, minted);
         self.erc20
             .mint(to, amount)
-            .map_err(|_| ShUSDErrors::ERC20MintErr(ERC20MintError{}))?;
+            .map_err(|err| match err {
+                erc20::Erc20Error::AccountFrozen(err) => ShUSDErrors::AccountFrozen(err),
+                erc20::Erc20Error::MaxSupplyExceeded(err) => ShUSDErrors::MaxSupplyExceeded(err),
+                _ => ShUSDErrors::ERC20MintErr(ERC20MintError{}),
+            })?;
         Ok(())
     }
 
+    /// Burning frees up the caller's cap if it is also a minter
     pub fn burn(&mut self, from: Address, amount: U256) -> Result<(), ShUSDErrors> {
-        if self.vm().msg_sender() != self.manager.get() {
-            return Err(ShUSDErrors::OnlyManagerCanCall(OnlyManagerCanCall {}));
-        }
+        self.access.only_role(BURNER_ROLE)?;
+        let burner = context::msg_sender(self.vm());
+        let minted = self.minted.get(burner);
+        self.minted.insert(burner, minted.saturating_sub(amount));
         self.erc20
             .burn(from, amount)
+            .map_err(|err| match err {
+                erc20::Erc20Error::AccountFrozen(err) => ShUSDErrors::AccountFrozen(err),
+                _ => ShUSDErrors::ERC20BurnErr(ERC20BurnError{}),
+            })?;
+        Ok(())
+    }
+
+    pub fn set_minter_cap(&mut self, minter: Address, cap: U256) -> Result<(), ShUSDErrors> {
+        self.access.only_role(ADMIN_ROLE)?;
+        self.minter_caps.insert(minter, cap);
+        log(self.vm(), MinterCapSet { minter, cap });
+        Ok(())
+    }
+
+    /// Stops minting and burning, transfers keep working
+    pub fn pause(&mut self) -> Result<(), ShUSDErrors> {
+        self.access.only_role(PAUSER_ROLE)?;
+        self.paused.set(true);
+        log(self.vm(), Paused { by: context::msg_sender(self.vm()) });
+        Ok(())
+    }
+
+    pub fn unpause(&mut self) -> Result<(), ShUSDErrors> {
+        self.access.only_role(PAUSER_ROLE)?;
+        self.paused.set(false);
+        log(self.vm(), Unpaused { by: context::msg_sender(self.vm()) });
+        Ok(())
+    }
+
+    pub fn minter_cap(&self, minter: Address) -> U256 {
+        self.minter_caps.get(minter)
+    }
+
+    pub fn minted_by(&self, minter: Address) -> U256 {
+        self.minted.get(minter)
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused.get()
+    }
+
+    pub fn freeze(&mut self, account: Address) -> Result<(), ShUSDErrors> {
+        self.access.only_role(COMPLIANCE_ROLE)?;
+        self.erc20.set_frozen(account, true);
+        log(self.vm(), Frozen { account, by: context::msg_sender(self.vm()) });
+        Ok(())
+    }
+
+    pub fn unfreeze(&mut self, account: Address) -> Result<(), ShUSDErrors> {
+        self.access.only_role(COMPLIANCE_ROLE)?;
+        self.erc20.set_frozen(account, false);
+        log(self.vm(), Unfrozen { account, by: context::msg_sender(self.vm()) });
+        Ok(())
+    }
+
+    /// Moves the whole balance of a frozen account to `to`, the timelock only
+    /// and only while it holds the governance role
+    pub fn seize(&mut self, from: Address, to: Address) -> Result<U256, ShUSDErrors> {
+        self.only_governing_timelock()?;
+        self.only_frozen(from)?;
+        let amount = self.erc20.balance_of(from);
+        self.erc20
+            .force_transfer(from, to, amount)
+            .map_err(|_| ShUSDErrors::ERC20TransferErr(ERC20TransferError{}))?;
+        log(self.vm(), Seized { from, to, amount });
+        Ok(amount)
+    }
+
+    /// Burns the whole balance of a frozen account, the timelock only and only
+    /// while it holds the governance role
+    pub fn wipe_frozen(&mut self, account: Address) -> Result<U256, ShUSDErrors> {
+        self.only_governing_timelock()?;
+        self.only_frozen(account)?;
+        let amount = self.erc20.balance_of(account);
+        self.erc20
+            .force_burn(account, amount)
             .map_err(|_| ShUSDErrors::ERC20BurnErr(ERC20BurnError{}))?;
+        log(self.vm(), Wiped { account, amount });
+        Ok(amount)
+    }
+
+    pub fn is_frozen(&self, account: Address) -> bool {
+        self.erc20.is_frozen(account)
+    }
+
+    /// Sets the timelock that can seize and wipe, admin only. It has to report a
+    /// `minDelay` of at least the timelock's floor. The timelock can't check that
+    /// when it is the caller, Stylus contracts aren't reentrant.
+    pub fn set_timelock(&mut self, timelock: Address) -> Result<(), ShUSDErrors> {
+        self.access.only_role(ADMIN_ROLE)?;
+        match calls::min_delay_call(self.vm(), timelock) {
+            Ok(min_delay) if min_delay >= timelock::MIN_DELAY => {}
+            _ => return Err(ShUSDErrors::InvalidTimelock(InvalidTimelock { timelock })),
+        }
+        self.timelock.set(timelock);
+        log(self.vm(), TimelockSet { timelock });
+        Ok(())
+    }
+
+    pub fn timelock(&self) -> Address {
+        self.timelock.get()
+    }
+
+    /// Trusts `forwarder` to relay calls for the address it appends, zero stops relaying
+    pub fn set_trusted_forwarder(&mut self, forwarder: Address) -> Result<(), ShUSDErrors> {
+        self.access.only_role(GOVERNANCE_ROLE)?;
+        self.trusted_forwarder.set(forwarder);
+        log(self.vm(), TrustedForwarderSet { forwarder });
+        Ok(())
+    }
+
+    pub fn trusted_forwarder(&self) -> Address {
+        self.trusted_forwarder.get()
+    }
+
+    /// EIP-2771, whether calls from `forwarder` are taken to be relayed
+    pub fn is_trusted_forwarder(&self, forwarder: Address) -> bool {
+        !forwarder.is_zero() && forwarder == self.trusted_forwarder.get()
+    }
+}
+
+impl ShUSD {
+    fn when_not_paused(&self) -> Result<(), ShUSDErrors> {
+        if self.paused.get() {
+            return Err(ShUSDErrors::IssuancePaused(IssuancePaused {}));
+        }
+        Ok(())
+    }
+
+    fn only_governing_timelock(&self) -> Result<(), ShUSDErrors> {
+        self.access.only_role(GOVERNANCE_ROLE)?;
+        let sender = context::msg_sender(self.vm());
+        if sender.is_zero() || sender != self.timelock.get() {
+            return Err(ShUSDErrors::OnlyTimelock(OnlyTimelock {}));
+        }
+        Ok(())
+    }
+
+    fn only_frozen(&self, account: Address) -> Result<(), ShUSDErrors> {
+        if !self.erc20.is_frozen(account) {
+            return Err(ShUSDErrors::NotFrozen(NotFrozen { account }));
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, keccak256};
+    use alloc::boxed::Box;
+    use stylus_sdk::host::VM;
+    use stylus_sdk::storage::StorageAddress;
+    use stylus_sdk::testing::TestVM;
+
+    fn map_slot(key: Address, slot: u64) -> U256 {
+        let mut preimage = [0u8; 64];
+        preimage[12..32].copy_from_slice(key.as_slice());
+        preimage[32..].copy_from_slice(&U256::from(slot).to_be_bytes::<32>());
+        U256::from_be_bytes(keccak256(preimage).0)
+    }
+
+    /// Fields may only ever be appended to `ShUSD`, the embedded `Erc20` takes
+    /// the first three slots.
+    #[test]
+    fn storage_layout_is_append_only() {
+        let vm = TestVM::new();
+        let mut sh_usd = ShUSD::from(&vm);
+
+        sh_usd.manager.set(address!("0000000000000000000000000000000000000001"));
+        let minter = address!("0000000000000000000000000000000000000004");
+        sh_usd.minter_caps.insert(minter, U256::from(5));
+        sh_usd.minted.insert(minter, U256::from(6));
+        sh_usd.paused.set(true);
+        sh_usd.is_initialized.set(true);
+        sh_usd.trusted_forwarder.set(address!("000000000000000000000000000000000000000a"));
+        sh_usd.timelock.set(address!("000000000000000000000000000000000000000b"));
+
+        let word = |slot: U256| U256::from_be_bytes(vm.get_storage(slot).0);
+        assert_eq!(word(U256::from(3)), U256::from(1));
+        // access control roles are a nested mapping rooted at slot 4
+        assert_eq!(word(map_slot(minter, 5)), U256::from(5));
+        assert_eq!(word(map_slot(minter, 6)), U256::from(6));
+        // paused, is_initialized and trusted_forwarder share slot 7
+        assert_eq!(word(U256::from(7)), (U256::from(0xa) << 16) | (U256::from(1) << 8) | U256::from(1));
+        let (slot, offset) = TRUSTED_FORWARDER_SLOT;
+        let forwarder = unsafe { StorageAddress::new(U256::from(slot), offset, VM { host: Box::new(vm.clone()) }) };
+        assert_eq!(forwarder.get(), address!("000000000000000000000000000000000000000a"));
+        assert_eq!(word(U256::from(8)), U256::from(0xb));
+    }
+}
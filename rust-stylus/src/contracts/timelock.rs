@@ -0,0 +1,214 @@
+//! Timelock that owns every governance-only function of the protocol.
+//!
+//! Proposers queue calls which executors may run once `min_delay` has passed,
+//! the guardian (or a proposer) can cancel them before that. Every queued call
+//! is kept in `operations` so the full queue can be read on-chain. The delay
+//! can't go below [`MIN_DELAY`] and only the deployer can initialize.
+//! Stylus contracts aren't reentrant, so the timelock has no public setters:
+//! parameter changes are queued with the timelock as target and decoded by
+//! [`ITimelock`] when executed.
+use alloc::vec;
+use alloc::vec::Vec;
+use alloy_sol_types::{sol, SolInterface};
+use alloy_primitives::{Address, U64, U8};
+use stylus_sdk::{abi::Bytes, alloy_primitives::U256, prelude::*};
+use stylus_sdk::stylus_core::calls::context::Call;
+use stylus_sdk::storage::{StorageAddress, StorageBool, StorageBytes, StorageMap, StorageU64, StorageU8, StorageVec};
+use crate::access::deployer::{self, OnlyDeployer};
+
+/// Time an operation stays executable after its eta
+pub const GRACE_PERIOD: u64 = 14 * 24 * 60 * 60;
+/// Shortest `min_delay` the timelock accepts, a day for anyone to react
+pub const MIN_DELAY: u64 = 24 * 60 * 60;
+
+const QUEUED: u8 = 1;
+const EXECUTED: u8 = 2;
+const CANCELLED: u8 = 3;
+
+sol! {
+    event CallQueued(uint256 indexed id, address indexed target, bytes data, uint64 eta);
+    event CallExecuted(uint256 indexed id);
+    event CallCancelled(uint256 indexed id);
+    event MinDelayChanged(uint64 min_delay);
+    event ProposerSet(address indexed account, bool allowed);
+    event ExecutorSet(address indexed account, bool allowed);
+    event GuardianSet(address indexed guardian);
+
+    error AlreadyInitialized();
+    error OnlyProposer();
+    error OnlyExecutor();
+    error OnlyCanceller();
+    error DelayTooShort();
+    error UnknownOperation();
+    error NotQueued();
+    error NotReady();
+    error Expired();
+    error UnsupportedSelfCall();
+
+    interface ITimelock {
+        function setMinDelay(uint64 min_delay) external;
+        function setProposer(address account, bool allowed) external;
+        function setExecutor(address account, bool allowed) external;
+        function setGuardian(address guardian) external;
+    }
+}
+
+#[derive(SolidityError)]
+pub enum TimelockErrors {
+    AlreadyInitialized(AlreadyInitialized),
+    OnlyProposer(OnlyProposer),
+    OnlyExecutor(OnlyExecutor),
+    OnlyCanceller(OnlyCanceller),
+    DelayTooShort(DelayTooShort),
+    UnknownOperation(UnknownOperation),
+    NotQueued(NotQueued),
+    NotReady(NotReady),
+    Expired(Expired),
+    OnlyDeployer(OnlyDeployer),
+    UnsupportedSelfCall(UnsupportedSelfCall)
+}
+
+#[storage]
+pub struct Operation {
+    target: StorageAddress,
+    data: StorageBytes,
+    eta: StorageU64,
+    status: StorageU8
+}
+
+#[cfg_attr(feature = "timelock", stylus_sdk::prelude::entrypoint)]
+#[storage]
+pub struct Timelock {
+    min_delay: StorageU64,
+    proposers: StorageMap<Address, StorageBool>,
+    executors: StorageMap<Address, StorageBool>,
+    guardian: StorageAddress,
+    operations: StorageVec<Operation>,
+    is_initialized: StorageBool
+}
+
+#[cfg_attr(any(feature = "timelock", test), stylus_sdk::prelude::public)]
+#[cfg(any(feature = "timelock", test))]
+impl Timelock {
+    /// Deployer only, `min_delay` is at least [`MIN_DELAY`]
+    pub fn init(&mut self, min_delay: u64, proposer: Address, executor: Address, guardian: Address) -> Result<(), Vec<u8>> {
+        assert_or!(!self.is_initialized.get(), TimelockErrors::AlreadyInitialized(AlreadyInitialized {}));
+        deployer::only_deployer(self.vm().msg_sender()).map_err(TimelockErrors::OnlyDeployer)?;
+        assert_or!(min_delay >= MIN_DELAY, TimelockErrors::DelayTooShort(DelayTooShort {}));
+        self.min_delay.set(U64::from(min_delay));
+        self.proposers.insert(proposer, true);
+        self.executors.insert(executor, true);
+        self.guardian.set(guardian);
+        self.is_initialized.set(true);
+        Ok(())
+    }
+
+    /// Queues a call of `data` on `target`, executable from `eta` on. Returns the operation id.
+    pub fn queue(&mut self, target: Address, data: Bytes, eta: u64) -> Result<U256, Vec<u8>> {
+        assert_or!(self.proposers.get(self.vm().msg_sender()), TimelockErrors::OnlyProposer(OnlyProposer {}));
+        assert_or!(eta >= self.vm().block_timestamp().saturating_add(self.min_delay.get().to()), TimelockErrors::DelayTooShort(DelayTooShort {}));
+        let id = U256::from(self.operations.len());
+        let mut operation = self.operations.grow();
+        operation.target.set(target);
+        operation.data.set_bytes(&data);
+        operation.eta.set(U64::from(eta));
+        operation.status.set(U8::from(QUEUED));
+        log(self.vm(), CallQueued { id, target, data: data.0.into(), eta });
+        Ok(id)
+    }
+
+    /// Runs a queued operation, bubbling up the revert data of the target if it fails.
+    pub fn execute(&mut self, id: U256) -> Result<Bytes, Vec<u8>> {
+        assert_or!(self.executors.get(self.vm().msg_sender()), TimelockErrors::OnlyExecutor(OnlyExecutor {}));
+        let now = self.vm().block_timestamp();
+        let mut operation = self.operations.setter(id).ok_or(TimelockErrors::UnknownOperation(UnknownOperation {}))?;
+        assert_or!(operation.status.get() == U8::from(QUEUED), TimelockErrors::NotQueued(NotQueued {}));
+        let eta: u64 = operation.eta.get().to();
+        assert_or!(now >= eta, TimelockErrors::NotReady(NotReady {}));
+        assert_or!(now <= eta.saturating_add(GRACE_PERIOD), TimelockErrors::Expired(Expired {}));
+        operation.status.set(U8::from(EXECUTED));
+        let target = operation.target.get();
+        let data = operation.data.get_bytes();
+        let result = if target == self.vm().contract_address() {
+            self.execute_self(&data)?
+        } else {
+            self.vm().call(&Call::new(), target, &data)?
+        };
+        log(self.vm(), CallExecuted { id });
+        Ok(result.into())
+    }
+
+    pub fn cancel(&mut self, id: U256) -> Result<(), Vec<u8>> {
+        let sender = self.vm().msg_sender();
+        assert_or!(sender == self.guardian.get() || self.proposers.get(sender), TimelockErrors::OnlyCanceller(OnlyCanceller {}));
+        let mut operation = self.operations.setter(id).ok_or(TimelockErrors::UnknownOperation(UnknownOperation {}))?;
+        assert_or!(operation.status.get() == U8::from(QUEUED), TimelockErrors::NotQueued(NotQueued {}));
+        operation.status.set(U8::from(CANCELLED));
+        log(self.vm(), CallCancelled { id });
+        Ok(())
+    }
+
+    pub fn min_delay(&self) -> u64 {
+        self.min_delay.get().to()
+    }
+
+    pub fn guardian(&self) -> Address {
+        self.guardian.get()
+    }
+
+    pub fn is_proposer(&self, account: Address) -> bool {
+        self.proposers.get(account)
+    }
+
+    pub fn is_executor(&self, account: Address) -> bool {
+        self.executors.get(account)
+    }
+
+    pub fn operation_count(&self) -> U256 {
+        U256::from(self.operations.len())
+    }
+
+    /// Returns target, calldata, eta and status (1 queued, 2 executed, 3 cancelled) of an operation
+    pub fn operation(&self, id: U256) -> Result<(Address, Bytes, u64, u8), Vec<u8>> {
+        let operation = self.operations.getter(id).ok_or(TimelockErrors::UnknownOperation(UnknownOperation {}))?;
+        Ok((operation.target.get(), operation.data.get_bytes().into(), operation.eta.get().to(), operation.status.get().to()))
+    }
+}
+
+#[cfg(any(feature = "timelock", test))]
+impl Timelock {
+    /// Runs an operation queued on the timelock itself, one of the setters
+    fn execute_self(&mut self, data: &[u8]) -> Result<Vec<u8>, Vec<u8>> {
+        let call = ITimelock::ITimelockCalls::abi_decode(data, true)
+            .map_err(|_| TimelockErrors::UnsupportedSelfCall(UnsupportedSelfCall {}))?;
+        match call {
+            ITimelock::ITimelockCalls::setMinDelay(call) => self.update_min_delay(call.min_delay)?,
+            ITimelock::ITimelockCalls::setProposer(call) => self.update_proposer(call.account, call.allowed),
+            ITimelock::ITimelockCalls::setExecutor(call) => self.update_executor(call.account, call.allowed),
+            ITimelock::ITimelockCalls::setGuardian(call) => self.update_guardian(call.guardian),
+        }
+        Ok(Vec::new())
+    }
+
+    fn update_min_delay(&mut self, min_delay: u64) -> Result<(), Vec<u8>> {
+        assert_or!(min_delay >= MIN_DELAY, TimelockErrors::DelayTooShort(DelayTooShort {}));
+        self.min_delay.set(U64::from(min_delay));
+        log(self.vm(), MinDelayChanged { min_delay });
+        Ok(())
+    }
+
+    fn update_proposer(&mut self, account: Address, allowed: bool) {
+        self.proposers.insert(account, allowed);
+        log(self.vm(), ProposerSet { account, allowed });
+    }
+
+    fn update_executor(&mut self, account: Address, allowed: bool) {
+        self.executors.insert(account, allowed);
+        log(self.vm(), ExecutorSet { account, allowed });
+    }
+
+    fn update_guardian(&mut self, guardian: Address) {
+        self.guardian.set(guardian);
+        log(self.vm(), GuardianSet { guardian });
+    }
+}
@@ -0,0 +1,272 @@
+//! Oracle adapter averaging a Uniswap V3 style pool's price over time.
+//!
+//! `latestAnswer` reads the pool's tick cumulatives for the last `window`
+//! seconds through `observe`, takes the arithmetic mean tick and quotes one
+//! `base` token in `quote` at it with 8 decimals, the same answer the Manager
+//! reads from a Chainlink feed. The pool must keep enough observations to
+//! cover the window, otherwise `observe` reverts.
+use alloc::vec;
+use alloc::vec::Vec;
+use alloy_primitives::{Address, U256, U32, U8};
+use alloy_sol_types::sol;
+use stylus_sdk::{alloy_primitives::I256, prelude::*};
+use stylus_sdk::storage::{StorageAddress, StorageBool, StorageU32, StorageU8};
+use crate::access::deployer::{self, OnlyDeployer};
+use crate::contracts::calls;
+use crate::math::{self, MathError, Rounding};
+
+/// Decimals of `latestAnswer`, those of Chainlink's usd feeds
+pub const DECIMALS: u8 = 8;
+pub const MIN_TICK: i32 = -887272;
+pub const MAX_TICK: i32 = 887272;
+
+sol! {
+    event WindowSet(uint32 window);
+    event GovernanceTransferred(address indexed previous, address indexed governance);
+
+    error AlreadyInitialized();
+    error OnlyGovernance();
+    error InvalidWindow();
+    error UnsupportedDecimals(uint8 decimals);
+    error TickOutOfRange(int32 tick);
+    error MathOverflow();
+}
+
+#[derive(SolidityError)]
+pub enum TwapOracleErrors {
+    AlreadyInitialized(AlreadyInitialized),
+    OnlyDeployer(OnlyDeployer),
+    OnlyGovernance(OnlyGovernance),
+    InvalidWindow(InvalidWindow),
+    UnsupportedDecimals(UnsupportedDecimals),
+    TickOutOfRange(TickOutOfRange),
+    MathOverflow(MathOverflow),
+}
+
+impl From<MathError> for TwapOracleErrors {
+    fn from(_: MathError) -> Self {
+        TwapOracleErrors::MathOverflow(MathOverflow {})
+    }
+}
+
+#[cfg_attr(feature = "twap-oracle", stylus_sdk::prelude::entrypoint)]
+#[storage]
+pub struct TwapOracle {
+    pool: StorageAddress,
+    base: StorageAddress,
+    quote: StorageAddress,
+    base_decimals: StorageU8,
+    quote_decimals: StorageU8,
+    /// Seconds averaged over
+    window: StorageU32,
+    governance: StorageAddress,
+    is_initialized: StorageBool,
+}
+
+#[cfg_attr(any(feature = "twap-oracle", test), stylus_sdk::prelude::public)]
+#[cfg(any(feature = "twap-oracle", test))]
+impl TwapOracle {
+    /// Prices `base` in `quote`, the two tokens of `pool`. Deployer only.
+    pub fn init(&mut self, pool: Address, base: Address, quote: Address, window: u32, governance: Address) -> Result<(), Vec<u8>> {
+        assert_or!(!self.is_initialized.get(), TwapOracleErrors::AlreadyInitialized(AlreadyInitialized {}));
+        deployer::only_deployer(self.vm().msg_sender()).map_err(TwapOracleErrors::OnlyDeployer)?;
+        assert_or!(window > 0, TwapOracleErrors::InvalidWindow(InvalidWindow {}));
+        let base_decimals = calls::decimals_call(self.vm(), base)?;
+        let quote_decimals = calls::decimals_call(self.vm(), quote)?;
+        // 10^77 is the largest power of ten a uint256 holds
+        for decimals in [base_decimals, quote_decimals] {
+            assert_or!(decimals <= 77, TwapOracleErrors::UnsupportedDecimals(UnsupportedDecimals { decimals }));
+        }
+        self.base_decimals.set(U8::from(base_decimals));
+        self.quote_decimals.set(U8::from(quote_decimals));
+        self.pool.set(pool);
+        self.base.set(base);
+        self.quote.set(quote);
+        self.window.set(U32::from(window));
+        self.governance.set(governance);
+        self.is_initialized.set(true);
+        Ok(())
+    }
+
+    /// Price of one `base` in `quote` over the window, with `DECIMALS`
+    pub fn latest_answer(&self) -> Result<I256, Vec<u8>> {
+        let tick = self.mean_tick()?;
+        let base_unit = U256::from(10).pow(U256::from(self.base_decimals.get().to::<u8>()));
+        let base_is_token0 = self.base.get() < self.quote.get();
+        let quote = quote_at_tick(tick, base_unit, base_is_token0).map_err(TwapOracleErrors::from)?;
+        let answer = math::rescale(quote, self.quote_decimals.get().to(), DECIMALS, Rounding::Down)
+            .map_err(TwapOracleErrors::from)?;
+        Ok(I256::try_from(answer).map_err(|_| TwapOracleErrors::MathOverflow(MathOverflow {}))?)
+    }
+
+    pub fn decimals(&self) -> u8 {
+        DECIMALS
+    }
+
+    /// Arithmetic mean of the pool's tick over the window
+    pub fn mean_tick(&self) -> Result<i32, Vec<u8>> {
+        let window = self.window.get().to::<u32>();
+        let cumulatives = calls::observe_call(self.vm(), self.pool.get(), vec![window, 0])?;
+        let tick = mean_tick(cumulatives[0], cumulatives[1], window);
+        assert_or!((MIN_TICK..=MAX_TICK).contains(&tick), TwapOracleErrors::TickOutOfRange(TickOutOfRange { tick }));
+        Ok(tick)
+    }
+
+    /// Longer windows are harder to move but slower to follow the market
+    pub fn set_window(&mut self, window: u32) -> Result<(), Vec<u8>> {
+        self.only_governance()?;
+        assert_or!(window > 0, TwapOracleErrors::InvalidWindow(InvalidWindow {}));
+        self.window.set(U32::from(window));
+        log(self.vm(), WindowSet { window });
+        Ok(())
+    }
+
+    pub fn set_governance(&mut self, new_governance: Address) -> Result<(), Vec<u8>> {
+        let previous = self.governance.get();
+        self.only_governance()?;
+        self.governance.set(new_governance);
+        log(self.vm(), GovernanceTransferred { previous, governance: new_governance });
+        Ok(())
+    }
+
+    pub fn window(&self) -> u32 {
+        self.window.get().to()
+    }
+
+    pub fn pool(&self) -> Address {
+        self.pool.get()
+    }
+
+    pub fn base(&self) -> Address {
+        self.base.get()
+    }
+
+    pub fn quote(&self) -> Address {
+        self.quote.get()
+    }
+
+    pub fn governance(&self) -> Address {
+        self.governance.get()
+    }
+}
+
+#[cfg(any(feature = "twap-oracle", test))]
+impl TwapOracle {
+    fn only_governance(&self) -> Result<(), Vec<u8>> {
+        assert_or!(self.vm().msg_sender() == self.governance.get(), TwapOracleErrors::OnlyGovernance(OnlyGovernance {}));
+        Ok(())
+    }
+}
+
+/// Mean tick between two tick cumulatives `window` seconds apart, rounded
+/// towards negative infinity like Uniswap's `OracleLibrary.consult`
+pub fn mean_tick(from: i64, to: i64, window: u32) -> i32 {
+    let delta = to - from;
+    let window = i64::from(window);
+    let mut tick = delta / window;
+    if delta < 0 && delta % window != 0 {
+        tick -= 1;
+    }
+    tick.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
+}
+
+/// `sqrt(1.0001^tick)` as a Q64.96, Uniswap's `TickMath.getSqrtRatioAtTick`
+pub fn sqrt_ratio_at_tick(tick: i32) -> Result<U256, MathError> {
+    const FACTORS: [u128; 19] = [
+        0xfff97272373d413259a46990580e213a,
+        0xfff2e50f5f656932ef12357cf3c7fdcc,
+        0xffe5caca7e10e4e61c3624eaa0941cd0,
+        0xffcb9843d60f6159c9db58835c926644,
+        0xff973b41fa98c081472e6896dfb254c0,
+        0xff2ea16466c96a3843ec78b326b52861,
+        0xfe5dee046a99a2a811c461f1969c3053,
+        0xfcbe86c7900a88aedcffc83b479aa3a4,
+        0xf987a7253ac413176f2b074cf7815e54,
+        0xf3392b0822b70005940c7a398e4b70f3,
+        0xe7159475a2c29b7443b29c7fa6e889d9,
+        0xd097f3bdfd2022b8845ad8f792aa5825,
+        0xa9f746462d870fdf8a65dc1f90e061e5,
+        0x70d869a156d2a1b890bb3df62baf32f7,
+        0x31be135f97d08fd981231505542fcfa6,
+        0x9aa508b5b7a84e1c677de54f3e99bc9,
+        0x5d6af8dedb81196699c329225ee604,
+        0x2216e584f5fa1ea926041bedfe98,
+        0x48a170391f7dc42444e8fa2,
+    ];
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(MathError::Overflow);
+    }
+    let abs_tick = tick.unsigned_abs();
+    let mut ratio = if abs_tick & 1 != 0 {
+        U256::from(0xfffcb933bd6fad37aa2d162d1a594001u128)
+    } else {
+        U256::from(1) << 128
+    };
+    for (bit, factor) in FACTORS.iter().enumerate() {
+        if abs_tick & (2 << bit) != 0 {
+            ratio = (ratio * U256::from(*factor)) >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+    // Back from Q128.128 to Q64.96, rounding up
+    let rounded = !(ratio & U256::from(u32::MAX)).is_zero();
+    Ok((ratio >> 32) + U256::from(rounded))
+}
+
+/// `base_amount` of one token priced in the other at `tick`, in raw units.
+/// Uniswap's `OracleLibrary.getQuoteAtTick`, the pool prices token0 in token1.
+pub fn quote_at_tick(tick: i32, base_amount: U256, base_is_token0: bool) -> Result<U256, MathError> {
+    let sqrt_ratio = sqrt_ratio_at_tick(tick)?;
+    // Squaring a Q64.96 over 128 bits would overflow, drop 64 bits of it first
+    let (ratio, one) = if sqrt_ratio <= U256::from(u128::MAX) {
+        (sqrt_ratio * sqrt_ratio, U256::from(1) << 192)
+    } else {
+        (math::mul_div(sqrt_ratio, sqrt_ratio, U256::from(1) << 64, Rounding::Down)?, U256::from(1) << 128)
+    };
+    if base_is_token0 {
+        math::mul_div(ratio, base_amount, one, Rounding::Down)
+    } else {
+        math::mul_div(one, base_amount, ratio, Rounding::Down)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sqrt_ratio_matches_tick_math() {
+        assert_eq!(sqrt_ratio_at_tick(0).ok(), Some(U256::from(1) << 96));
+        assert_eq!(sqrt_ratio_at_tick(MIN_TICK).ok(), Some(U256::from(4295128739u64)));
+        assert_eq!(sqrt_ratio_at_tick(MAX_TICK).ok(), "1461446703485210103287273052203988822378723970342".parse().ok());
+        assert!(sqrt_ratio_at_tick(MAX_TICK + 1).is_err());
+        // Every factor against floating point
+        for bit in 0..20 {
+            for tick in [1i32 << bit, -(1 << bit)] {
+                let expected = 1.0001f64.powf(f64::from(tick) / 2.0) * 2f64.powi(96);
+                let actual: f64 = sqrt_ratio_at_tick(tick).unwrap().to_string().parse().unwrap();
+                assert!((actual / expected - 1.0).abs() < 1e-9, "tick {tick}");
+            }
+        }
+    }
+
+    #[test]
+    fn mean_tick_rounds_down() {
+        assert_eq!(mean_tick(0, 3600 * 10, 3600), 10);
+        assert_eq!(mean_tick(100, 100 - 7, 2), -4);
+        assert_eq!(mean_tick(0, -8, 2), -4);
+    }
+
+    #[test]
+    fn quotes_both_ways() {
+        let ether = U256::from(10).pow(U256::from(18));
+        assert_eq!(quote_at_tick(0, ether, true).ok(), Some(ether));
+        // 1.0001^23027 is 9.9999
+        let ten = quote_at_tick(23027, ether, true).unwrap();
+        assert_eq!(ten / U256::from(10).pow(U256::from(14)), U256::from(99999));
+        let tenth = quote_at_tick(23027, ether, false).unwrap();
+        assert_eq!(tenth / U256::from(10).pow(U256::from(13)), U256::from(10000));
+    }
+}
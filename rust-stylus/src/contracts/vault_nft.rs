@@ -0,0 +1,73 @@
+//! Vault NFTs, an [`Erc721`] where each token is a `Manager` position.
+//!
+//! Only the manager mints, from `openVault`. The position is stored under
+//! `vaultKey(id)` and whoever holds the token controls it, so positions can be
+//! sold, moved to a new wallet or held several at a time. Tokens are never
+//! burnt, an emptied vault can be used again.
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::access::deployer::{self, OnlyDeployer};
+use crate::token::erc721::{Erc721, Erc721Params};
+use alloy_sol_types::sol;
+use alloy_primitives::Address;
+use stylus_sdk::{alloy_primitives::U256, prelude::*};
+
+pub struct VaultParams;
+
+impl Erc721Params for VaultParams {
+    const NAME: &'static str = "Microstable Vault";
+    const SYMBOL: &'static str = "msVAULT";
+}
+
+sol_storage! {
+    #[cfg_attr(feature = "vault-nft", stylus_sdk::prelude::entrypoint)]
+    pub struct VaultNft {
+        #[borrow]
+        Erc721<VaultParams> erc721;
+        address manager;
+        uint256 total_supply;
+        bool is_initialized;
+    }
+}
+
+sol! {
+    error AlreadyInitialized();
+    error OnlyManager();
+}
+
+#[derive(SolidityError)]
+pub enum VaultNftErrors {
+    AlreadyInitialized(AlreadyInitialized),
+    OnlyManager(OnlyManager),
+    OnlyDeployer(OnlyDeployer),
+}
+
+#[cfg_attr(any(feature = "vault-nft", test), stylus_sdk::prelude::public, inherit(Erc721::<VaultParams>))]
+#[cfg(any(feature = "vault-nft", test))]
+impl VaultNft {
+    /// Deployer only
+    pub fn init(&mut self, manager: Address) -> Result<(), Vec<u8>> {
+        assert_or!(!self.is_initialized.get(), VaultNftErrors::AlreadyInitialized(AlreadyInitialized {}));
+        deployer::only_deployer(self.vm().msg_sender()).map_err(VaultNftErrors::OnlyDeployer)?;
+        self.manager.set(manager);
+        self.is_initialized.set(true);
+        Ok(())
+    }
+
+    /// Mints the next vault to `to`, ids start at one
+    pub fn mint(&mut self, to: Address) -> Result<U256, Vec<u8>> {
+        assert_or!(self.vm().msg_sender() == self.manager.get(), VaultNftErrors::OnlyManager(OnlyManager {}));
+        let id = self.total_supply.get() + U256::from(1);
+        self.erc721.mint(to, id)?;
+        self.total_supply.set(id);
+        Ok(id)
+    }
+
+    pub fn total_supply(&self) -> U256 {
+        self.total_supply.get()
+    }
+
+    pub fn manager(&self) -> Address {
+        self.manager.get()
+    }
+}
@@ -0,0 +1,122 @@
+//! Deployment manifest and the record written once a deployment is done.
+use std::path::{Path, PathBuf};
+use ethers::types::Address;
+use eyre::WrapErr;
+use serde::{Deserialize, Serialize};
+
+/// Where a contract of the system comes from
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    /// Build with this contract feature, deploy, activate and initialize it
+    Deploy(String),
+    /// Use the contract already at this address as is
+    At(Address),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Contracts {
+    pub sh_usd: Source,
+    pub weth: Source,
+    pub oracle: Source,
+    pub manager: Source,
+}
+
+/// Everything `deploy` needs besides the `PRIVATE_KEY`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Manifest {
+    pub rpc_url: String,
+    /// Manager governance, the deployer if left out
+    #[serde(default)]
+    pub governance: Option<Address>,
+    /// Who may mint a deployed `TestWeth`, the deployer if left out
+    #[serde(default)]
+    pub weth_minter: Option<Address>,
+    /// Where the deployed addresses are recorded
+    #[serde(default = "default_output")]
+    pub output: PathBuf,
+    pub contracts: Contracts,
+}
+
+fn default_output() -> PathBuf {
+    PathBuf::from("deployment.json")
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        let json = std::fs::read_to_string(path).wrap_err_with(|| format!("reading {}", path.display()))?;
+        Self::parse(&json)
+    }
+
+    pub fn parse(json: &str) -> eyre::Result<Self> {
+        let manifest: Self = serde_json::from_str(json).wrap_err("parsing the manifest")?;
+        let contracts = &manifest.contracts;
+        for (source, expected) in [&contracts.sh_usd, &contracts.weth, &contracts.oracle, &contracts.manager].into_iter().zip(FEATURES) {
+            if let Source::Deploy(feature) = source {
+                eyre::ensure!(feature == expected, "{expected} can't be deployed with the {feature} feature");
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+/// Features building each of `Contracts`, in field order
+pub const FEATURES: [&str; 4] = ["sh-usd", "test-weth", "test-oracle", "manager"];
+
+/// Addresses of a finished deployment
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Deployment {
+    pub chain_id: u64,
+    pub deployer: Address,
+    pub governance: Address,
+    pub sh_usd: Address,
+    pub weth: Address,
+    pub oracle: Address,
+    /// The proxy `Manager` is used through
+    pub manager: Address,
+    /// Code behind the `manager` proxy, when deployed by this run
+    #[serde(default)]
+    pub manager_implementation: Option<Address>,
+    /// Contracts deployed by this run rather than taken from the manifest
+    pub deployed: Vec<String>,
+}
+
+impl Deployment {
+    pub fn save(&self, path: &Path) -> eyre::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n").wrap_err_with(|| format!("writing {}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        let json = std::fs::read_to_string(path).wrap_err_with(|| format!("reading {}", path.display()))?;
+        serde_json::from_str(&json).wrap_err("parsing the deployment")
+    }
+
+    pub fn was_deployed(&self, contract: &str) -> bool {
+        self.deployed.iter().any(|deployed| deployed == contract)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sources() {
+        let manifest = Manifest::parse(r#"{
+            "rpc_url": "http://localhost:8547",
+            "contracts": {
+                "sh_usd": { "deploy": "sh-usd" },
+                "weth": { "at": "0x82af49447d8a07e3bd95bd0d56f35241523fbab1" },
+                "oracle": { "deploy": "test-oracle" },
+                "manager": { "deploy": "manager" }
+            }
+        }"#).unwrap();
+        assert_eq!(manifest.contracts.sh_usd, Source::Deploy("sh-usd".into()));
+        assert_eq!(manifest.contracts.weth, Source::At("0x82af49447d8a07e3bd95bd0d56f35241523fbab1".parse().unwrap()));
+        assert_eq!(manifest.output, PathBuf::from("deployment.json"));
+        assert_eq!(manifest.governance, None);
+
+        let unknown = r#"{"rpc_url": "", "contracts": {"sh_usd": {"deploy": "proxy"}, "weth": {"deploy": "test-weth"}, "oracle": {"deploy": "test-oracle"}, "manager": {"deploy": "manager"}}}"#;
+        assert!(Manifest::parse(unknown).is_err());
+    }
+}
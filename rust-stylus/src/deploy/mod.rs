@@ -0,0 +1,294 @@
+//! Deploys and wires the system from a manifest.
+//!
+//! `microstable-stylus deploy <manifest.json>` builds, deploys and activates
+//! every contract the manifest asks for through `cargo stylus deploy`, calls
+//! the `init` functions in order, records the addresses and then reads the
+//! storage back to check the wiring. `Manager` goes behind an ERC-1967 proxy
+//! and is initialized through it. `microstable-stylus verify <manifest.json>`
+//! runs the checks again against the recorded deployment. The deployer key is
+//! read from `PRIVATE_KEY`, a `.env` file works as for `test.sh`, and handed to
+//! `cargo stylus` in a file readable only by the current user.
+//!
+//! Every contract is built with `PUB_KEY` set to the deployer's address, so the
+//! `init` calls sent after deploying only succeed from the deployer and anyone
+//! getting in between reverts with `OnlyDeployer`.
+pub mod manifest;
+pub mod wiring;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use alloy_sol_types::{sol, SolCall};
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use eyre::{bail, ensure, eyre, WrapErr};
+use manifest::{Deployment, Manifest, Source, FEATURES};
+
+sol! {
+    interface IShUSD {
+        function init(address manager) external;
+    }
+
+    interface ITestWeth {
+        function init(address manager) external;
+    }
+
+    interface ITestOracle {
+        function init() external;
+    }
+
+    interface IManager {
+        function initialize(address weth, address oracle, address sh_usd, address governance) external;
+    }
+
+    interface IProxy {
+        function initProxy(address implementation, bytes data) external;
+    }
+
+    interface IArbWasm {
+        function programVersion(address program) external view returns (uint16);
+    }
+}
+
+const WASM: &str = "target/wasm32-unknown-unknown/release/libmicrostable.wasm";
+/// ArbWasm precompile, which activates Stylus programs
+const ARB_WASM: Address = H160([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x71]);
+
+pub async fn run(args: Vec<String>) -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["deploy", manifest] => deploy(Path::new(manifest)).await,
+        ["verify", manifest] => verify(Path::new(manifest)).await,
+        _ => bail!("usage: microstable-stylus deploy|verify <manifest.json>"),
+    }
+}
+
+async fn deploy(path: &Path) -> eyre::Result<()> {
+    let manifest = Manifest::load(path)?;
+    let key = std::env::var("PRIVATE_KEY").wrap_err("PRIVATE_KEY is not set")?;
+    let provider = Provider::<Http>::try_from(manifest.rpc_url.as_str())?;
+    let chain_id = provider.get_chainid().await?.as_u64();
+    let wallet = key.parse::<LocalWallet>()?.with_chain_id(chain_id);
+    let deployer = wallet.address();
+    let client = SignerMiddleware::new(provider, wallet);
+
+    // Everything is deployed before anything is initialized, `init` needs the manager's address
+    let contracts = &manifest.contracts;
+    let key_file = KeyFile::new(&key)?;
+    let mut deployed = Vec::new();
+    let mut addresses = Vec::new();
+    for (source, feature) in [&contracts.sh_usd, &contracts.weth, &contracts.oracle, &contracts.manager].into_iter().zip(FEATURES) {
+        addresses.push(match source {
+            Source::At(address) => *address,
+            Source::Deploy(_) => {
+                let address = build_and_deploy(feature, &manifest.rpc_url, &key_file, deployer)?;
+                deployed.push(feature.to_string());
+                address
+            }
+        });
+    }
+    // The manager's address is the proxy's, the implementation only holds the code
+    let manager_implementation = match contracts.manager {
+        Source::At(_) => None,
+        Source::Deploy(_) => {
+            let proxy = build_and_deploy("proxy", &manifest.rpc_url, &key_file, deployer)?;
+            Some(std::mem::replace(&mut addresses[3], proxy))
+        }
+    };
+    drop(key_file);
+    let deployment = Deployment {
+        chain_id,
+        deployer,
+        governance: manifest.governance.unwrap_or(deployer),
+        sh_usd: addresses[0],
+        weth: addresses[1],
+        oracle: addresses[2],
+        manager: addresses[3],
+        manager_implementation,
+        deployed,
+    };
+    deployment.save(&manifest.output)?;
+
+    // Only the deployer can call `init` on a fresh contract, a revert here most
+    // likely means it was built for another `PUB_KEY` and has to be deployed again.
+    let weth_minter = manifest.weth_minter.unwrap_or(deployer);
+    if deployment.was_deployed("sh-usd") {
+        send(&client, "sh-usd", deployment.sh_usd, IShUSD::initCall { manager: wiring::alloy(deployment.manager) }.abi_encode()).await?;
+    }
+    if deployment.was_deployed("test-weth") {
+        send(&client, "test-weth", deployment.weth, ITestWeth::initCall { manager: wiring::alloy(weth_minter) }.abi_encode()).await?;
+    }
+    if deployment.was_deployed("test-oracle") {
+        send(&client, "test-oracle", deployment.oracle, ITestOracle::initCall {}.abi_encode()).await?;
+    }
+    if let Some(implementation) = deployment.manager_implementation {
+        let initialize = IManager::initializeCall {
+            weth: wiring::alloy(deployment.weth),
+            oracle: wiring::alloy(deployment.oracle),
+            sh_usd: wiring::alloy(deployment.sh_usd),
+            governance: wiring::alloy(deployment.governance),
+        };
+        let call = IProxy::initProxyCall { implementation: wiring::alloy(implementation), data: initialize.abi_encode().into() };
+        send(&client, "manager", deployment.manager, call.abi_encode()).await?;
+    }
+    println!("📝 Addresses written to {}", manifest.output.display());
+
+    check(client.provider(), &deployment, weth_minter).await
+}
+
+async fn verify(path: &Path) -> eyre::Result<()> {
+    let manifest = Manifest::load(path)?;
+    let deployment = Deployment::load(&manifest.output)?;
+    let provider = Provider::<Http>::try_from(manifest.rpc_url.as_str())?;
+    check(&provider, &deployment, manifest.weth_minter.unwrap_or(deployment.deployer)).await
+}
+
+/// Builds `feature` and deploys it with `cargo stylus`, which also activates it
+fn build_and_deploy(feature: &str, rpc_url: &str, key_file: &KeyFile, deployer: Address) -> eyre::Result<Address> {
+    println!("🚀 Deploying {feature}...");
+    let status = build(feature, deployer).status()?;
+    ensure!(status.success(), "building {feature} failed");
+
+    let output = Command::new("cargo")
+        .args(["stylus", "deploy", "--endpoint", rpc_url, "--wasm-file", WASM, "--no-verify"])
+        .arg("--private-key-path")
+        .arg(&key_file.0)
+        .output()?;
+    let stdout = strip_ansi(&String::from_utf8_lossy(&output.stdout));
+    ensure!(output.status.success(), "deploying {feature} failed:\n{stdout}{}", String::from_utf8_lossy(&output.stderr));
+    let address = deployed_address(&stdout).ok_or_else(|| eyre!("no address in the output of cargo stylus deploy:\n{stdout}"))?;
+    println!("✅ {feature} deployed at {address:?}");
+    Ok(address)
+}
+
+/// The wasm build of `feature`, whose `init` only accepts `deployer`
+fn build(feature: &str, deployer: Address) -> Command {
+    let mut command = Command::new("cargo");
+    command
+        .args(["build", "--release", "--target", "wasm32-unknown-unknown", "--features", feature])
+        .env("PUB_KEY", format!("{deployer:?}"));
+    command
+}
+
+/// The deployer key written out for `cargo stylus --private-key-path`, which
+/// keeps it off the command line. Removed once dropped.
+struct KeyFile(PathBuf);
+
+impl KeyFile {
+    fn new(key: &str) -> eyre::Result<Self> {
+        let path = std::env::temp_dir().join(format!("microstable-deployer-{}", std::process::id()));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&path).wrap_err_with(|| format!("creating {}", path.display()))?;
+        let key_file = Self(path);
+        file.write_all(key.trim().as_bytes())?;
+        Ok(key_file)
+    }
+}
+
+impl Drop for KeyFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Picks the address out of `cargo stylus deploy`'s "deployed code at address: 0x.."
+fn deployed_address(output: &str) -> Option<Address> {
+    let line = output.lines().find(|line| line.contains("deployed code at address"))?;
+    line.split_whitespace().last()?.parse().ok()
+}
+
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+async fn send<M: Middleware + 'static>(client: &M, contract: &str, to: Address, data: Vec<u8>) -> eyre::Result<()> {
+    println!("🔌 Initializing {contract}...");
+    let tx = TransactionRequest::new().to(to).data(data);
+    let pending = client.send_transaction(tx, None).await.map_err(|err| eyre!("initializing {contract}: {err}"))?;
+    let receipt = pending.await?.ok_or_else(|| eyre!("initializing {contract}: transaction dropped"))?;
+    ensure!(receipt.status == Some(1u64.into()), "initializing {contract} reverted, was it initialized by someone else?");
+    Ok(())
+}
+
+/// Reads back every storage word the wiring should have set and checks the
+/// deployed contracts are activated
+async fn check<M: Middleware + 'static>(provider: &M, deployment: &Deployment, weth_minter: Address) -> eyre::Result<()> {
+    println!("🤓 Checking the wiring...");
+    let mut failed = 0;
+    for check in wiring::checks(deployment, wiring::alloy(weth_minter)) {
+        let word = provider.get_storage_at(H160(check.address.0 .0), H256(check.slot.to_be_bytes()), None).await
+            .map_err(|err| eyre!("reading {} storage: {err}", check.contract))?;
+        if word.0 == check.expected.0 {
+            println!("✅ {} {}", check.contract, check.what);
+        } else {
+            println!("❌ {} {}: expected {} in slot {}, found {word:?}", check.contract, check.what, check.expected, check.slot);
+            failed += 1;
+        }
+    }
+
+    if provider.get_code(ARB_WASM, None).await.map_err(|err| eyre!("{err}"))?.is_empty() {
+        println!("⚠️ No ArbWasm precompile, not a Stylus chain, skipping the activation checks");
+    } else {
+        let programs = FEATURES.into_iter().zip([deployment.sh_usd, deployment.weth, deployment.oracle, deployment.manager])
+            .filter(|(feature, _)| deployment.was_deployed(feature))
+            .chain(deployment.manager_implementation.map(|implementation| ("manager implementation", implementation)));
+        for (feature, address) in programs {
+            let call = IArbWasm::programVersionCall { program: wiring::alloy(address) };
+            let tx: TypedTransaction = TransactionRequest::new().to(ARB_WASM).data(call.abi_encode()).into();
+            match provider.call(&tx, None).await {
+                Ok(_) => println!("✅ {feature} activated"),
+                Err(_) => {
+                    println!("❌ {feature} is not activated");
+                    failed += 1;
+                }
+            }
+        }
+    }
+    ensure!(failed == 0, "{failed} checks failed");
+    println!("🦀 Deployment verified");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cargo_stylus_output() {
+        let output = "\u{1b}[1;92mdeployed code at address: \u{1b}[0m0x33f54de59419570a9442e788f5dd5cf635b3c7ac\n\u{1b}[1;92mdeployment tx hash: \u{1b}[0m0xabc\n";
+        assert_eq!(deployed_address(&strip_ansi(output)), Some("0x33f54de59419570a9442e788f5dd5cf635b3c7ac".parse().unwrap()));
+        assert_eq!(deployed_address("error: insufficient funds"), None);
+    }
+
+    #[test]
+    fn builds_for_the_deployer() {
+        let deployer: Address = "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266".parse().unwrap();
+        let build = build("manager", deployer);
+        let pub_key = build.get_envs().find(|(name, _)| *name == "PUB_KEY").and_then(|(_, value)| value);
+        assert_eq!(pub_key.and_then(|value| value.to_str()), Some("0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266"));
+    }
+
+    #[test]
+    fn key_file_is_private_and_removed() {
+        let key_file = KeyFile::new("0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80\n").unwrap();
+        let path = key_file.0.clone();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80");
+        #[cfg(unix)]
+        assert_eq!(std::os::unix::fs::PermissionsExt::mode(&std::fs::metadata(&path).unwrap().permissions()) & 0o777, 0o600);
+        drop(key_file);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,134 @@
+//! Storage words a correctly wired deployment must hold.
+//!
+//! The slots follow the layouts pinned by the `storage_layout_is_append_only`
+//! tests, reading them back catches an `init` someone else got in first.
+use alloc::vec::Vec;
+use alloy_primitives::{keccak256, Address, B256, U256};
+use crate::access::access_control::{BURNER_ROLE, MINTER_ROLE};
+use crate::contracts::proxy::IMPLEMENTATION_SLOT;
+use super::manifest::Deployment;
+
+pub struct Check {
+    pub contract: &'static str,
+    pub what: &'static str,
+    pub address: Address,
+    pub slot: U256,
+    pub expected: B256,
+}
+
+pub fn alloy(address: ethers::types::Address) -> Address {
+    Address::from(address.0)
+}
+
+fn word(value: U256) -> B256 {
+    B256::from(value.to_be_bytes::<32>())
+}
+
+fn address_word(address: Address, offset: usize) -> B256 {
+    word(U256::from_be_slice(address.as_slice()) << (offset * 8))
+}
+
+fn map_slot(key: B256, slot: U256) -> U256 {
+    let mut preimage = [0u8; 64];
+    preimage[..32].copy_from_slice(key.as_slice());
+    preimage[32..].copy_from_slice(&slot.to_be_bytes::<32>());
+    U256::from_be_bytes(keccak256(preimage).0)
+}
+
+/// Slot of `roles[role][account]` in `ShUSD`'s `AccessControl`
+fn role_slot(role: B256, account: Address) -> U256 {
+    map_slot(account.into_word(), map_slot(role, U256::from(4)))
+}
+
+/// What the contracts deployed in `deployment` must hold once initialized
+pub fn checks(deployment: &Deployment, weth_minter: Address) -> Vec<Check> {
+    let [sh_usd, weth, oracle, manager] = [deployment.sh_usd, deployment.weth, deployment.oracle, deployment.manager].map(alloy);
+    let deployer = alloy(deployment.deployer);
+    let mut checks = Vec::new();
+    let mut check = |contract, what, address, slot: u64, expected| {
+        checks.push(Check { contract, what, address, slot: U256::from(slot), expected })
+    };
+
+    if deployment.was_deployed("manager") {
+        check("manager", "shUSD", manager, 0, address_word(sh_usd, 0));
+        check("manager", "WETH", manager, 1, address_word(weth, 0));
+        check("manager", "oracle", manager, 2, address_word(oracle, 0));
+        // is_initialized and governance share a slot
+        check("manager", "initialized with governance", manager, 5, word(U256::from_be_slice(address_word(alloy(deployment.governance), 1).as_slice()) | U256::from(1)));
+    }
+    if deployment.was_deployed("sh-usd") {
+        check("sh-usd", "manager", sh_usd, 3, address_word(manager, 0));
+        // paused and is_initialized share a slot
+        check("sh-usd", "initialized and not paused", sh_usd, 7, word(U256::from(1) << 8));
+    }
+    if deployment.was_deployed("test-weth") {
+        check("test-weth", "minter", weth, 3, address_word(weth_minter, 0));
+    }
+    if deployment.was_deployed("test-oracle") {
+        // is_rekt, is_initialized and owner share a slot
+        check("test-oracle", "initialized with owner", oracle, 0, word(U256::from_be_slice(address_word(deployer, 2).as_slice()) | (U256::from(1) << 8)));
+    }
+
+    if let Some(implementation) = deployment.manager_implementation {
+        checks.push(Check { contract: "manager", what: "proxy implementation", address: manager, slot: IMPLEMENTATION_SLOT, expected: address_word(alloy(implementation), 0) });
+    }
+    if deployment.was_deployed("sh-usd") {
+        for (what, role) in [("manager can mint", MINTER_ROLE), ("manager can burn", BURNER_ROLE)] {
+            checks.push(Check { contract: "sh-usd", what, address: sh_usd, slot: role_slot(role, manager), expected: word(U256::from(1)) });
+        }
+    }
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+    use stylus_sdk::testing::TestVM;
+    use crate::access::deployer::DEPLOYER;
+    use crate::contracts::{manager::Manager, proxy, sh_usd::ShUSD};
+    use crate::test::{test_oracle::TestOracle, test_weth::TestWeth};
+
+    fn ethers(address: Address) -> ethers::types::Address {
+        ethers::types::Address::from(address.0 .0)
+    }
+
+    /// Wires the real contracts on test VMs and expects every check to hold
+    #[test]
+    fn checks_match_initialized_contracts() {
+        let deployer = DEPLOYER.unwrap_or(address!("000000000000000000000000000000000000d001"));
+        let governance = address!("000000000000000000000000000000000000d002");
+        let [sh_usd, weth, oracle, manager, implementation] = [1u8, 2, 3, 4, 5].map(Address::with_last_byte);
+        let deployment = Deployment {
+            chain_id: 412346,
+            deployer: ethers(deployer),
+            governance: ethers(governance),
+            sh_usd: ethers(sh_usd),
+            weth: ethers(weth),
+            oracle: ethers(oracle),
+            manager: ethers(manager),
+            manager_implementation: Some(ethers(implementation)),
+            deployed: ["sh-usd", "test-weth", "test-oracle", "manager"].map(Into::into).to_vec(),
+        };
+
+        let vms = [sh_usd, weth, oracle, manager].map(|address| {
+            let vm = TestVM::new();
+            vm.set_contract_address(address);
+            vm.set_sender(deployer);
+            (address, vm)
+        });
+        ShUSD::from(&vms[0].1).init(manager).ok().unwrap();
+        TestWeth::from(&vms[1].1).init(deployer);
+        TestOracle::from(&vms[2].1).init().ok().unwrap();
+        // `manager` is the proxy, which runs `initialize` on its own storage
+        proxy::set_implementation(&vms[3].1, implementation);
+        Manager::from(&vms[3].1).initialize(weth, oracle, sh_usd, governance).ok().unwrap();
+
+        let checks = checks(&deployment, deployer);
+        assert_eq!(checks.len(), 11);
+        for check in checks {
+            let vm = &vms.iter().find(|(address, _)| *address == check.address).unwrap().1;
+            assert_eq!(vm.get_storage(check.slot), check.expected, "{} {}", check.contract, check.what);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Positions followed from `PositionUpdated` and the Manager's math redone off-chain.
+//!
+//! Everything here mirrors `Manager::collat_ratio` and `Manager::liquidate`
+//! exactly, the tests in `test::keeper` compare both on the same positions.
+use alloc::vec::Vec;
+use std::collections::HashMap;
+use alloy_primitives::{Address, I256, U256};
+use crate::client::Position;
+use crate::contracts::manager::{underwater_repayment, PositionUpdated, LIQUIDATION_PENALTY, MIN_COLLAT_RATIO, ORACLE_DECIMALS};
+use crate::math::{Rounding, Wad};
+
+#[derive(Default)]
+pub struct Book {
+    positions: HashMap<Address, Position>,
+}
+
+/// A position `Manager` would let be liquidated
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub user: Address,
+    pub position: Position,
+    pub ratio: U256,
+    /// What the liquidator gains before gas, in USD scaled by 1e18
+    pub proceeds: U256,
+}
+
+impl Book {
+    pub fn apply(&mut self, event: &PositionUpdated) {
+        let position = Position { deposited: event.deposited, minted: event.minted };
+        if position.deposited.is_zero() && position.minted.is_zero() {
+            self.positions.remove(&event.user);
+        } else {
+            self.positions.insert(event.user, position);
+        }
+    }
+
+    pub fn get(&self, user: Address) -> Option<Position> {
+        self.positions.get(&user).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Positions at or below `MIN_COLLAT_RATIO` at `price`, the riskiest first
+    pub fn liquidatable(&self, price: U256) -> Vec<Candidate> {
+        let mut candidates: Vec<Candidate> = self.positions.iter()
+            .filter(|(_, position)| !position.minted.is_zero())
+            .map(|(&user, &position)| Candidate { user, position, ratio: collat_ratio(&position, price), proceeds: proceeds(&position, price) })
+            .filter(|candidate| candidate.ratio <= U256::from(MIN_COLLAT_RATIO))
+            .collect();
+        candidates.sort_by(|a, b| a.ratio.cmp(&b.ratio).then(a.user.cmp(&b.user)));
+        candidates
+    }
+}
+
+/// The oracle answer as `Manager` reads it, `None` where it would revert
+pub fn price(answer: I256) -> Option<U256> {
+    answer.is_positive().then(|| answer.into_raw())
+}
+
+/// Usd value of `deposited` WETH, scaled to 18 decimals, `U256::MAX` where `Manager` would overflow
+pub fn collateral_value(deposited: U256, price: U256) -> U256 {
+    Wad::from_decimals(price, ORACLE_DECIMALS, Rounding::Down)
+        .and_then(|price| Wad(deposited).mul(price, Rounding::Down))
+        .map_or(U256::MAX, |value| value.0)
+}
+
+/// Collateral value over debt, scaled by 1e18
+pub fn collat_ratio(position: &Position, price: U256) -> U256 {
+    if position.minted.is_zero() {
+        return U256::MAX;
+    }
+    Wad(collateral_value(position.deposited, price)).div(Wad(position.minted), Rounding::Down).map_or(U256::MAX, |ratio| ratio.0)
+}
+
+/// WETH value received minus shUSD burnt when liquidating `position`. Under
+/// water positions burn what the collateral is worth less the penalty.
+pub fn proceeds(position: &Position, price: U256) -> U256 {
+    let value = collateral_value(position.deposited, price);
+    if value < position.minted {
+        return underwater_repayment(value).map_or(U256::ZERO, |repaid| value.saturating_sub(repaid));
+    }
+    let penalty = Wad(position.minted).mul(Wad(U256::from(LIQUIDATION_PENALTY)), Rounding::Down)
+        .map_or(U256::MAX, |penalty| penalty.0)
+        .min(value - position.minted);
+    value - position.minted - penalty
+}
+
+/// Usd cost of `gas` at `gas_price` wei, gas being paid in ETH
+pub fn gas_cost(gas: U256, gas_price: U256, price: U256) -> U256 {
+    collateral_value(gas.saturating_mul(gas_price), price)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::WAD;
+
+    fn ether(amount: u64) -> U256 {
+        U256::from(amount) * WAD
+    }
+
+    #[test]
+    fn follows_events_and_ranks_by_ratio() {
+        let [alice, bob, carol] = [0xc1, 0xc2, 0xc3].map(Address::with_last_byte);
+        let mut book = Book::default();
+        book.apply(&PositionUpdated { user: alice, deposited: ether(1), minted: ether(1000) });
+        book.apply(&PositionUpdated { user: bob, deposited: ether(1), minted: ether(1200) });
+        book.apply(&PositionUpdated { user: carol, deposited: ether(1), minted: U256::ZERO });
+        assert_eq!(book.len(), 3);
+
+        // $1800: alice at 1.8 is safe, bob at 1.5 can be liquidated
+        let usd_1800 = U256::from(1800_0000_0000u64);
+        let candidates = book.liquidatable(usd_1800);
+        assert_eq!(candidates.iter().map(|c| c.user).collect::<Vec<_>>(), [bob]);
+        assert_eq!(candidates[0].ratio, U256::from(MIN_COLLAT_RATIO));
+        // $1800 of WETH for $1200 of debt and a $60 penalty
+        assert_eq!(candidates[0].proceeds, ether(540));
+
+        let candidates = book.liquidatable(usd_1800 / U256::from(2));
+        assert_eq!(candidates.iter().map(|c| c.user).collect::<Vec<_>>(), [bob, alice]);
+        // Under water, $900 of collateral goes for $900 / 1.05 of the debt
+        assert_eq!(candidates[0].proceeds, U256::from(42_857_142_857_142_857_142u128));
+
+        book.apply(&PositionUpdated { user: bob, deposited: U256::ZERO, minted: U256::ZERO });
+        assert_eq!(book.get(bob), None);
+        assert_eq!(price(I256::MINUS_ONE), None);
+        assert_eq!(gas_cost(U256::from(1_000_000), U256::from(100_000_000), U256::from(2000_0000_0000u64)), ether(2) / U256::from(10));
+    }
+}
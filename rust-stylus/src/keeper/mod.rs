@@ -0,0 +1,303 @@
+//! Liquidation keeper for the Stylus deployment.
+//!
+//! `microstable-keeper <config.json> [--dry-run]` follows positions from the
+//! Manager's `PositionUpdated` events and polls the oracle. When either changes
+//! every ratio is recomputed off-chain (see [`book`]). Positions at or below
+//! `MIN_COLLAT_RATIO` get `liquidate` simulated with `eth_estimateGas`, and it is
+//! sent only if the proceeds pay for the gas with `min_profit_usd` left over.
+//! Up to `concurrency` liquidations are in flight at once. [`Nonces`] hands out
+//! the nonces so a failed send never leaves a gap.
+//!
+//! The keeper repays debt with its own shUSD and keeps the WETH it receives.
+//! Its key is read from `PRIVATE_KEY` or `.env`, as for `deploy`.
+pub mod book;
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use alloy_primitives::{B256, U256};
+use alloy_sol_types::{SolCall, SolEvent};
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use eyre::{bail, eyre, WrapErr};
+use serde::Deserialize;
+use tokio::sync::Semaphore;
+use crate::client::{manager::IManager, to_ethers, ClientError, ManagerClient};
+use crate::contracts::{calls::latestAnswerCall, manager::PositionUpdated};
+use book::{Book, Candidate};
+
+/// Blocks per `eth_getLogs` request while catching up
+const LOG_RANGE: u64 = 10_000;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    pub rpc_url: String,
+    pub manager: Address,
+    pub oracle: Address,
+    /// Block to start following positions from, the manager's deployment block
+    #[serde(default)]
+    pub from_block: u64,
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    /// Liquidations simulated or sent at the same time
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// Least profit after gas worth sending a liquidation for, in whole dollars
+    #[serde(default)]
+    pub min_profit_usd: u64,
+    /// Simulates and reports liquidations without sending them
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+fn default_poll_interval_ms() -> u64 {
+    1000
+}
+
+fn default_concurrency() -> usize {
+    4
+}
+
+impl Config {
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        let json = std::fs::read_to_string(path).wrap_err_with(|| format!("reading {}", path.display()))?;
+        serde_json::from_str(&json).wrap_err("parsing the keeper config")
+    }
+}
+
+pub async fn run(args: Vec<String>) -> eyre::Result<()> {
+    dotenv::dotenv().ok();
+    let (path, dry_run) = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [path] => (path, false),
+        [path, "--dry-run"] | ["--dry-run", path] => (path, true),
+        _ => bail!("usage: microstable-keeper <config.json> [--dry-run]"),
+    };
+    let mut config = Config::load(Path::new(path))?;
+    config.dry_run |= dry_run;
+
+    let provider = Provider::<Http>::try_from(config.rpc_url.as_str())?;
+    let chain_id = provider.get_chainid().await?.as_u64();
+    let key = std::env::var("PRIVATE_KEY").wrap_err("PRIVATE_KEY is not set")?;
+    let wallet = key.parse::<LocalWallet>()?.with_chain_id(chain_id);
+    let mut keeper = Keeper::new(config, Arc::new(SignerMiddleware::new(provider, wallet)));
+    println!("🤖 Keeping {:?}{}", keeper.shared.config.manager, if keeper.shared.config.dry_run { " (dry run)" } else { "" });
+
+    let mut interval = tokio::time::interval(Duration::from_millis(keeper.shared.config.poll_interval_ms));
+    loop {
+        interval.tick().await;
+        if let Err(error) = keeper.tick().await {
+            eprintln!("⚠️ {error:#}");
+        }
+    }
+}
+
+/// Hands out the keeper's nonces. A nonce is only used up once the node
+/// accepted the transaction, any failure resyncs with the node.
+#[derive(Default)]
+pub struct Nonces {
+    next: tokio::sync::Mutex<Option<U256>>,
+}
+
+impl Nonces {
+    pub async fn send<M: Middleware>(&self, client: &M, mut tx: TypedTransaction) -> Result<TxHash, M::Error> {
+        let mut next = self.next.lock().await;
+        let nonce = match *next {
+            Some(nonce) => nonce,
+            None => {
+                let sender = client.default_sender().unwrap_or_default();
+                let count = client.get_transaction_count(sender, Some(BlockNumber::Pending.into())).await?;
+                U256::from_limbs(count.0)
+            }
+        };
+        tx.set_nonce(ethers::types::U256(nonce.into_limbs()));
+        match client.send_transaction(tx, None).await {
+            Ok(pending) => {
+                *next = Some(nonce + U256::from(1));
+                Ok(pending.tx_hash())
+            }
+            Err(error) => {
+                *next = None;
+                Err(error)
+            }
+        }
+    }
+}
+
+/// What the liquidation tasks share with the keeper
+struct Shared<M> {
+    config: Config,
+    client: Arc<M>,
+    manager: ManagerClient<M>,
+    nonces: Nonces,
+    in_flight: Mutex<HashSet<alloy_primitives::Address>>,
+    permits: Semaphore,
+}
+
+pub struct Keeper<M> {
+    shared: Arc<Shared<M>>,
+    book: Book,
+    next_block: u64,
+    price: Option<U256>,
+    candidates: Vec<Candidate>,
+}
+
+impl<M: Middleware + 'static> Keeper<M> {
+    pub fn new(config: Config, client: Arc<M>) -> Self {
+        let manager = ManagerClient::new(config.manager.0.into(), client.clone());
+        let (next_block, permits) = (config.from_block, Semaphore::new(config.concurrency.max(1)));
+        let shared = Shared { config, client, manager, nonces: Nonces::default(), in_flight: Mutex::default(), permits };
+        Self { shared: Arc::new(shared), book: Book::default(), next_block, price: None, candidates: Vec::new() }
+    }
+
+    /// Catches up on positions and the price, then tries every liquidatable
+    /// position that isn't already being liquidated
+    pub async fn tick(&mut self) -> eyre::Result<()> {
+        let positions_changed = self.sync_positions().await?;
+        let price_changed = self.sync_price().await?;
+        let Some(price) = self.price else { return Ok(()) };
+        if positions_changed || price_changed {
+            self.candidates = self.book.liquidatable(price);
+            println!("📈 ${} per WETH, {} positions, {} liquidatable", wad(price * U256::from(10_000_000_000u64)), self.book.len(), self.candidates.len());
+        }
+        for candidate in &self.candidates {
+            if !self.shared.in_flight.lock().unwrap().insert(candidate.user) {
+                continue;
+            }
+            let (shared, candidate) = (self.shared.clone(), candidate.clone());
+            tokio::spawn(async move {
+                let _permit = shared.permits.acquire().await;
+                if let Err(error) = liquidate(&shared, &candidate, price).await {
+                    eprintln!("❌ liquidating {}: {error:#}", candidate.user);
+                }
+                shared.in_flight.lock().unwrap().remove(&candidate.user);
+            });
+        }
+        Ok(())
+    }
+
+    async fn sync_positions(&mut self) -> eyre::Result<bool> {
+        let head = self.shared.client.get_block_number().await.map_err(|error| eyre!("{error}"))?.as_u64();
+        let mut changed = false;
+        while self.next_block <= head {
+            let to = (self.next_block + LOG_RANGE - 1).min(head);
+            let filter = Filter::new()
+                .address(self.shared.config.manager)
+                .topic0(H256(PositionUpdated::SIGNATURE_HASH.0))
+                .from_block(self.next_block)
+                .to_block(to);
+            for log in self.shared.client.get_logs(&filter).await.map_err(|error| eyre!("{error}"))? {
+                let topics = log.topics.iter().map(|topic| B256::from(topic.0));
+                let event = PositionUpdated::decode_raw_log(topics, &log.data, true)?;
+                self.book.apply(&event);
+                changed = true;
+            }
+            self.next_block = to + 1;
+        }
+        Ok(changed)
+    }
+
+    async fn sync_price(&mut self) -> eyre::Result<bool> {
+        let tx: TypedTransaction = TransactionRequest::new().to(self.shared.config.oracle).data(latestAnswerCall {}.abi_encode()).into();
+        let data = self.shared.client.call(&tx, None).await.map_err(|error| eyre!("reading the oracle: {error}"))?;
+        let price = book::price(latestAnswerCall::abi_decode_returns(&data, true)?._0);
+        if price.is_none() {
+            eprintln!("⚠️ The oracle answer isn't positive, the manager can't liquidate");
+        }
+        let changed = price != self.price;
+        self.price = price;
+        Ok(changed)
+    }
+}
+
+/// Simulates liquidating `candidate` and sends it if it is worth the gas
+async fn liquidate<M: Middleware + 'static>(shared: &Shared<M>, candidate: &Candidate, price: U256) -> eyre::Result<()> {
+    let user = candidate.user;
+    let gas = match shared.manager.simulate_liquidate(user).await {
+        Ok(gas) => gas,
+        // Repaid, topped up or liquidated by someone else since the last event
+        Err(ClientError::Revert(revert)) => {
+            println!("⏭️ {user} can't be liquidated: {revert:?}");
+            return Ok(());
+        }
+        Err(error) => return Err(error.into()),
+    };
+    let gas_price = shared.client.get_gas_price().await.map_err(|error| eyre!("{error}"))?;
+    let cost = book::gas_cost(gas, U256::from_limbs(gas_price.0), price);
+    let min_profit = U256::from(shared.config.min_profit_usd) * U256::from(10u64.pow(18));
+    if candidate.proceeds < cost + min_profit {
+        println!("⏭️ {user} isn't worth it: ${} proceeds for ${} of gas", wad(candidate.proceeds), wad(cost));
+        return Ok(());
+    }
+    let profit = wad(candidate.proceeds - cost);
+    if shared.config.dry_run {
+        println!("🧪 Would liquidate {user} at ratio {} for ${profit}", wad(candidate.ratio));
+        return Ok(());
+    }
+
+    // Some headroom over the estimate, the price may move before inclusion
+    let tx = TransactionRequest::new()
+        .to(to_ethers(shared.manager.address()))
+        .data(IManager::liquidateCall { user }.abi_encode())
+        .gas(ethers::types::U256((gas * U256::from(6) / U256::from(5)).into_limbs()))
+        .gas_price(gas_price);
+    let hash = shared.nonces.send(&*shared.client, tx.into()).await.map_err(|error| eyre!("{error}"))?;
+    let receipt = PendingTransaction::new(hash, shared.client.provider()).await?.ok_or_else(|| eyre!("{hash:?} dropped"))?;
+    if receipt.status != Some(1u64.into()) {
+        bail!("{hash:?} reverted");
+    }
+    println!("✅ Liquidated {user} for ${profit} in {hash:?}");
+    Ok(())
+}
+
+/// A value scaled by 1e18 with two decimals
+fn wad(value: U256) -> String {
+    let hundredths = value / U256::from(10u64.pow(16));
+    format!("{}.{:02}", hundredths / U256::from(100), hundredths % U256::from(100))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::MockProvider;
+
+    #[tokio::test]
+    async fn nonces_are_only_used_up_by_accepted_transactions() {
+        let (provider, mock) = Provider::mocked();
+        let nonces = Nonces::default();
+        let tx = || -> TypedTransaction {
+            TransactionRequest::new().from(Address::repeat_byte(1)).to(Address::repeat_byte(2)).gas(21_000).gas_price(1).into()
+        };
+        let sent = |mock: &MockProvider, nonce: u64| {
+            mock.assert_request("eth_sendTransaction", [tx().set_nonce(nonce).clone()]).unwrap();
+        };
+
+        // Responses are popped from the back
+        mock.push(TxHash::repeat_byte(7)).unwrap();
+        mock.push(ethers::types::U256::from(5)).unwrap();
+        nonces.send(&provider, tx()).await.unwrap();
+        mock.assert_request("eth_getTransactionCount", (Address::zero(), "pending")).unwrap();
+        sent(&mock, 5);
+
+        mock.push(TxHash::repeat_byte(8)).unwrap();
+        nonces.send(&provider, tx()).await.unwrap();
+        sent(&mock, 6);
+
+        // A rejected transaction leaves the nonce to whatever the node says next
+        mock.push_response(ethers::providers::MockResponse::Error(ethers::providers::JsonRpcError {
+            code: -32000,
+            message: "nonce too low".into(),
+            data: None,
+        }));
+        assert!(nonces.send(&provider, tx()).await.is_err());
+        sent(&mock, 7);
+        mock.push(TxHash::repeat_byte(9)).unwrap();
+        mock.push(ethers::types::U256::from(9)).unwrap();
+        nonces.send(&provider, tx()).await.unwrap();
+        mock.assert_request("eth_getTransactionCount", (Address::zero(), "pending")).unwrap();
+        sent(&mock, 9);
+    }
+}
@@ -2,9 +2,30 @@
 #![cfg_attr(target_arch = "wasm32", no_std, no_main)]
 
 extern crate alloc;
+
+#[macro_export]
+macro_rules! assert_or {
+    ($cond:expr, $err:expr) => {
+        if !($cond) {
+            Err($err)?;
+        }
+    };
+}
+
+pub mod access;
+pub mod math;
+pub mod context;
 pub mod token;
 pub mod contracts;
 pub mod test;
+#[cfg(any(feature = "export-abi", test))]
+pub mod abi;
+#[cfg(any(feature = "deploy", test))]
+pub mod deploy;
+#[cfg(any(feature = "client", test))]
+pub mod client;
+#[cfg(any(feature = "keeper", test))]
+pub mod keeper;
 
 #[cfg(all(
     target_arch = "wasm32",
@@ -12,7 +33,17 @@ pub mod test;
         feature = "manager",
         feature = "sh-usd",
         feature = "test-oracle",
-        feature = "test-weth"
+        feature = "test-weth",
+        feature = "proxy",
+        feature = "timelock",
+        feature = "gov-token",
+        feature = "governor",
+        feature = "vault-nft",
+        feature = "psm",
+        feature = "test-usdc",
+        feature = "twap-oracle",
+        feature = "test-pool",
+        feature = "forwarder"
     ))
 ))]
 compile_error!("one of the contract-* features must be enabled!");
\ No newline at end of file
@@ -6,6 +6,19 @@ fn panic(_: &core::panic::PanicInfo) -> ! {
     core::arch::wasm32::unreachable()
 }
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "export-abi"), not(feature = "deploy")))]
 #[doc(hidden)]
 fn main() {}
+
+/// `cargo run --features export-abi,<contract> -- [--json]` prints the contract's ABI
+#[cfg(all(not(target_arch = "wasm32"), feature = "export-abi"))]
+fn main() {
+    libmicrostable::abi::print_from_args();
+}
+
+/// `cargo run --features deploy -- deploy|verify <manifest.json>` deploys and wires the contracts
+#[cfg(all(not(target_arch = "wasm32"), feature = "deploy", not(feature = "export-abi")))]
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    libmicrostable::deploy::run(std::env::args().skip(1).collect()).await
+}
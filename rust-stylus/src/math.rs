@@ -0,0 +1,263 @@
+//! Fixed point money math.
+//!
+//! `Wad` has 18 decimals like shUSD and WETH amounts, `Ray` 27 for rates
+//! that get compounded. Every product goes through `mul_div`, which keeps the
+//! full 512 bit intermediate so `a * b / c` only fails when the result itself
+//! doesn't fit, and makes the caller pick which way to round.
+use alloy_primitives::{I256, U256, U512};
+
+/// 1e18
+pub const WAD: U256 = U256::from_limbs([1_000_000_000_000_000_000, 0, 0, 0]);
+/// 1e27
+pub const RAY: U256 = U256::from_limbs([0x9fd0_803c_e800_0000, 0x033b_2e3c, 0, 0]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// Towards zero, what the protocol pays out
+    Down,
+    /// Away from zero, what the protocol is owed
+    Up,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathError {
+    /// The result doesn't fit in 256 bits
+    Overflow,
+    DivisionByZero,
+    /// A signed value that had to be positive, such as an oracle answer
+    Negative,
+}
+
+pub type Result<T> = core::result::Result<T, MathError>;
+
+/// `a * b / denominator` rounded as asked
+pub fn mul_div(a: U256, b: U256, denominator: U256, rounding: Rounding) -> Result<U256> {
+    if denominator.is_zero() {
+        return Err(MathError::DivisionByZero);
+    }
+    let product: U512 = a.widening_mul(b);
+    let (quotient, remainder) = product.div_rem(U512::from(denominator));
+    if quotient.bit_len() > 256 {
+        return Err(MathError::Overflow);
+    }
+    let mut result: U256 = quotient.wrapping_to();
+    if rounding == Rounding::Up && !remainder.is_zero() {
+        result = result.checked_add(U256::from(1)).ok_or(MathError::Overflow)?;
+    }
+    Ok(result)
+}
+
+/// `amount` with `from` decimals expressed with `to` decimals
+pub fn rescale(amount: U256, from: u8, to: u8, rounding: Rounding) -> Result<U256> {
+    let ten = U256::from(10);
+    if to >= from {
+        amount.checked_mul(ten.pow(U256::from(to - from))).ok_or(MathError::Overflow)
+    } else {
+        mul_div(amount, U256::from(1), ten.pow(U256::from(from - to)), rounding)
+    }
+}
+
+/// A signed value that must not be negative, zero is let through
+pub fn to_unsigned(value: I256) -> Result<U256> {
+    if value.is_negative() {
+        return Err(MathError::Negative);
+    }
+    Ok(value.into_raw())
+}
+
+/// An amount with 18 decimals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Wad(pub U256);
+
+/// A rate with 27 decimals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Ray(pub U256);
+
+impl Wad {
+    pub const ZERO: Wad = Wad(U256::ZERO);
+    pub const ONE: Wad = Wad(WAD);
+
+    /// `amount` with `decimals` decimals, such as an 8 decimals oracle answer
+    pub fn from_decimals(amount: U256, decimals: u8, rounding: Rounding) -> Result<Wad> {
+        rescale(amount, decimals, 18, rounding).map(Wad)
+    }
+
+    pub fn mul(self, other: Wad, rounding: Rounding) -> Result<Wad> {
+        mul_div(self.0, other.0, WAD, rounding).map(Wad)
+    }
+
+    pub fn div(self, other: Wad, rounding: Rounding) -> Result<Wad> {
+        mul_div(self.0, WAD, other.0, rounding).map(Wad)
+    }
+
+    pub fn to_ray(self) -> Result<Ray> {
+        rescale(self.0, 18, 27, Rounding::Down).map(Ray)
+    }
+}
+
+impl Ray {
+    pub const ZERO: Ray = Ray(U256::ZERO);
+    pub const ONE: Ray = Ray(RAY);
+
+    pub fn mul(self, other: Ray, rounding: Rounding) -> Result<Ray> {
+        mul_div(self.0, other.0, RAY, rounding).map(Ray)
+    }
+
+    pub fn div(self, other: Ray, rounding: Rounding) -> Result<Ray> {
+        mul_div(self.0, RAY, other.0, rounding).map(Ray)
+    }
+
+    /// `self` to the power of `exponent` by squaring, every product rounded
+    /// the same way. Compounds a per second rate over `exponent` seconds.
+    pub fn pow(self, mut exponent: u64, rounding: Rounding) -> Result<Ray> {
+        let (mut base, mut result) = (self, Ray::ONE);
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(base, rounding)?;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base.mul(base, rounding)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Scales `amount` by this rate, e.g. normalized debt to debt
+    pub fn apply(self, amount: U256, rounding: Rounding) -> Result<U256> {
+        mul_div(amount, self.0, RAY, rounding)
+    }
+
+    pub fn to_wad(self, rounding: Rounding) -> Result<Wad> {
+        rescale(self.0, 27, 18, rounding).map(Wad)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Rounding::{Down, Up};
+
+    fn u(value: u128) -> U256 {
+        U256::from(value)
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(WAD, U256::from(10).pow(U256::from(18)));
+        assert_eq!(RAY, U256::from(10).pow(U256::from(27)));
+    }
+
+    /// Every small triple against u128 arithmetic, both roundings
+    #[test]
+    fn mul_div_matches_integer_division() {
+        for a in 0..40u128 {
+            for b in 0..40u128 {
+                assert_eq!(mul_div(u(a), u(b), U256::ZERO, Down), Err(MathError::DivisionByZero));
+                for d in 1..40u128 {
+                    assert_eq!(mul_div(u(a), u(b), u(d), Down), Ok(u(a * b / d)), "{a} * {b} / {d}");
+                    assert_eq!(mul_div(u(a), u(b), u(d), Up), Ok(u((a * b).div_ceil(d))), "{a} * {b} / {d} up");
+                }
+            }
+        }
+        // Same around the top of u64, where products need all of u128
+        let edges = [1, 2, 3, u64::MAX as u128 - 1, u64::MAX as u128, 1 << 63, 999_999_999_999_999_999];
+        for a in edges {
+            for b in edges {
+                for d in edges {
+                    assert_eq!(mul_div(u(a), u(b), u(d), Down), Ok(u(a * b / d)));
+                    assert_eq!(mul_div(u(a), u(b), u(d), Up), Ok(u((a * b).div_ceil(d))));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mul_div_keeps_512_bits() {
+        let max = U256::MAX;
+        assert_eq!(mul_div(max, max, max, Down), Ok(max));
+        assert_eq!(mul_div(max, max, max, Up), Ok(max));
+        assert_eq!(mul_div(max, u(2), u(4), Down), Ok(max >> 1));
+        assert_eq!(mul_div(max, u(2), u(4), Up), Ok((max >> 1) + u(1)));
+        assert_eq!(mul_div(max, max - u(1), max, Down), Ok(max - u(1)));
+        // max * (max - 1) / max is max - 1 exactly, one less divides with a remainder
+        assert_eq!(mul_div(max - u(1), max - u(1), max, Down), Ok(max - u(2)));
+        assert_eq!(mul_div(max - u(1), max - u(1), max, Up), Ok(max - u(1)));
+
+        assert_eq!(mul_div(max, u(2), u(1), Down), Err(MathError::Overflow));
+        assert_eq!(mul_div(max, max, max - u(1), Down), Err(MathError::Overflow));
+        // Fits rounded down, not rounded up
+        assert_eq!(mul_div(max, u(3), u(3), Up), Ok(max));
+        assert_eq!(mul_div(max, max, max - u(1), Up), Err(MathError::Overflow));
+    }
+
+    #[test]
+    fn wad_and_ray() {
+        let half = Wad(WAD / u(2));
+        let third = Wad::ONE.div(Wad(u(3) * WAD), Down).unwrap();
+        assert_eq!(third, Wad(u(333_333_333_333_333_333)));
+        assert_eq!(Wad::ONE.div(Wad(u(3) * WAD), Up), Ok(Wad(u(333_333_333_333_333_334))));
+        assert_eq!(half.mul(half, Down), Ok(Wad(WAD / u(4))));
+        assert_eq!(third.mul(Wad(u(3)), Down), Ok(Wad::ZERO));
+        assert_eq!(third.mul(Wad(u(3)), Up), Ok(Wad(u(1))));
+        assert_eq!(Wad::ONE.div(Wad::ZERO, Down), Err(MathError::DivisionByZero));
+        assert_eq!(Wad(U256::MAX).mul(Wad(u(2) * WAD), Down), Err(MathError::Overflow));
+
+        assert_eq!(Wad::ONE.to_ray(), Ok(Ray::ONE));
+        assert_eq!(Ray(RAY + u(1)).to_wad(Down), Ok(Wad::ONE));
+        assert_eq!(Ray(RAY + u(1)).to_wad(Up), Ok(Wad(WAD + u(1))));
+        assert_eq!(Wad(U256::MAX).to_ray(), Err(MathError::Overflow));
+        assert_eq!(Ray(u(3) * RAY).div(Ray(u(2) * RAY), Down), Ok(Ray(RAY + RAY / u(2))));
+        assert_eq!(Ray(u(3) * RAY).apply(u(10), Down), Ok(u(30)));
+        assert_eq!(Ray(RAY / u(3)).apply(u(10), Down), Ok(u(3)));
+        assert_eq!(Ray(RAY / u(3)).apply(u(10), Up), Ok(u(4)));
+    }
+
+    #[test]
+    fn pow_compounds_rates() {
+        let rate = Ray(RAY + u(1_000_000_000_000_000_000_000_000));
+        assert_eq!(rate.pow(0, Down), Ok(Ray::ONE));
+        assert_eq!(rate.pow(1, Down), Ok(rate));
+        assert_eq!(Ray::ONE.pow(u64::MAX, Down), Ok(Ray::ONE));
+        assert_eq!(Ray::ZERO.pow(5, Down), Ok(Ray::ZERO));
+        // 1.1 ^ 2 and 1.1 ^ 3 are exact in 27 decimals
+        let ten_percent = Ray(RAY + RAY / u(10));
+        assert_eq!(ten_percent.pow(2, Down), Ok(Ray(u(1_210_000_000_000_000_000_000_000_000))));
+        assert_eq!(ten_percent.pow(3, Down), Ok(Ray(u(1_331_000_000_000_000_000_000_000_000))));
+
+        // Squaring agrees with multiplying one step at a time
+        let per_second = Ray(u(1_000_000_001_547_125_957_863_212_449));
+        let mut stepped = Ray::ONE;
+        for exponent in 0..=300u64 {
+            let squared = per_second.pow(exponent, Down).unwrap();
+            let difference = squared.0.abs_diff(stepped.0);
+            assert!(difference <= u(exponent as u128), "{exponent}: {squared:?} {stepped:?}");
+            assert!(per_second.pow(exponent, Up).unwrap() >= squared);
+            stepped = stepped.mul(per_second, Down).unwrap();
+        }
+        // That rate compounds to 5% a year, within a millionth of a percent
+        let year = per_second.pow(365 * 24 * 60 * 60, Down).unwrap();
+        let five_percent = Ray(RAY + RAY / u(20));
+        assert!(year.0.abs_diff(five_percent.0) < RAY / u(100_000_000), "{year:?}");
+
+        assert_eq!(Ray(u(2) * RAY).pow(256, Down), Err(MathError::Overflow));
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(to_unsigned(I256::try_from(1234).unwrap()), Ok(u(1234)));
+        assert_eq!(to_unsigned(I256::ZERO), Ok(U256::ZERO));
+        assert_eq!(to_unsigned(I256::MAX), Ok(U256::MAX >> 1));
+        assert_eq!(to_unsigned(I256::MINUS_ONE), Err(MathError::Negative));
+        assert_eq!(to_unsigned(I256::MIN), Err(MathError::Negative));
+
+        // An 8 decimals oracle answer
+        assert_eq!(Wad::from_decimals(u(1800_0000_0000), 8, Down), Ok(Wad(u(1800) * WAD)));
+        // 6 decimals USDC both ways
+        assert_eq!(rescale(u(1_500_000), 6, 18, Down), Ok(u(1_500_000_000_000_000_000)));
+        assert_eq!(rescale(u(1_500_000_000_000_000_001), 18, 6, Down), Ok(u(1_500_000)));
+        assert_eq!(rescale(u(1_500_000_000_000_000_001), 18, 6, Up), Ok(u(1_500_001)));
+        assert_eq!(rescale(u(7), 18, 18, Up), Ok(u(7)));
+        assert_eq!(rescale(U256::MAX, 6, 18, Down), Err(MathError::Overflow));
+    }
+}
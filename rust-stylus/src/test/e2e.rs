@@ -0,0 +1,509 @@
+//! End to end tests of the `Manager` against the real `ShUSD`, `TestWeth` and
+//! `TestOracle`, wired together on a [`Chain`].
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use alloy_primitives::{address, Address, B256, I256, U256};
+use alloy_sol_types::{sol, SolCall, SolError, SolEvent};
+use stylus_sdk::stylus_core::storage::TopLevelStorage;
+use crate::contracts::manager::{self, AlreadyInitialized, BadDebtRecorded, ConversionFailure, MintRateLimited, NotVaultOwner, OnlyGovernance, OnlyGuardian, PriceSuspicious, SurplusAccrued, Undercollateralized};
+use crate::contracts::price_guard::{BreakerReset, BreakerTripped};
+use crate::contracts::forwarder::Forwarder;
+use crate::contracts::sh_usd;
+use crate::access::access_control::{BURNER_ROLE, MINTER_ROLE};
+use crate::access::deployer;
+use crate::contracts::psm::Psm;
+use crate::contracts::vault_nft::{OnlyManager, VaultNft};
+pub(super) use crate::client::manager::IManager;
+use crate::client::sh_usd::IShUSD;
+use crate::test::harness::Chain;
+pub(super) use crate::test::psm::IPsm;
+use crate::test::test_oracle::{self, OracleReverted, TestOracle};
+use crate::test::test_usdc::TestUsdc;
+use crate::test::test_weth::TestWeth;
+
+// Only `#[entrypoint]` marks a contract as top level storage, the chain needs
+// every contract to be one.
+#[cfg(not(feature = "manager"))]
+unsafe impl TopLevelStorage for manager::Manager {}
+#[cfg(not(feature = "sh-usd"))]
+unsafe impl TopLevelStorage for sh_usd::ShUSD {}
+#[cfg(not(feature = "test-weth"))]
+unsafe impl TopLevelStorage for TestWeth {}
+#[cfg(not(feature = "test-oracle"))]
+unsafe impl TopLevelStorage for TestOracle {}
+#[cfg(not(feature = "vault-nft"))]
+unsafe impl TopLevelStorage for VaultNft {}
+#[cfg(not(feature = "test-usdc"))]
+unsafe impl TopLevelStorage for TestUsdc {}
+#[cfg(not(feature = "psm"))]
+unsafe impl TopLevelStorage for Psm {}
+#[cfg(not(feature = "forwarder"))]
+unsafe impl TopLevelStorage for Forwarder {}
+
+sol! {
+    interface IToken {
+        function init(address manager) external;
+        function mint(address to, uint256 amount) external;
+        function approve(address spender, uint256 value) external returns (bool);
+        function balanceOf(address account) external view returns (uint256);
+        function totalSupply() external view returns (uint256);
+        function grantRole(bytes32 role, address account) external;
+        function setTrustedForwarder(address forwarder) external;
+    }
+
+    interface IOracle {
+        function init() external;
+        function setPrice(int256 price) external;
+        function setFailureMode(uint8 mode) external;
+    }
+
+    interface IVaultNft {
+        function mint(address to) external returns (uint256);
+        function ownerOf(uint256 id) external view returns (address);
+        function transferFrom(address from, address to, uint256 id) external;
+    }
+}
+
+pub(super) const MANAGER: Address = address!("000000000000000000000000000000000000a001");
+pub(super) const SH_USD: Address = address!("000000000000000000000000000000000000a002");
+pub(super) const WETH: Address = address!("000000000000000000000000000000000000a003");
+pub(super) const ORACLE: Address = address!("000000000000000000000000000000000000a004");
+pub(super) const VAULT_NFT: Address = address!("000000000000000000000000000000000000a005");
+pub(super) const USDC: Address = address!("000000000000000000000000000000000000a006");
+pub(super) const PSM: Address = address!("000000000000000000000000000000000000a007");
+pub(super) const FORWARDER: Address = address!("000000000000000000000000000000000000a008");
+pub(super) const GOVERNANCE: Address = address!("000000000000000000000000000000000000b001");
+/// `init` is restricted to `PUB_KEY` when it is set at build time
+pub(super) const DEPLOYER: Address = match deployer::DEPLOYER {
+    Some(deployer) => deployer,
+    None => GOVERNANCE,
+};
+pub(super) const ALICE: Address = address!("000000000000000000000000000000000000c001");
+pub(super) const BOB: Address = address!("000000000000000000000000000000000000c002");
+pub(super) const CAROL: Address = address!("000000000000000000000000000000000000c003");
+pub(super) const USERS: [Address; 3] = [ALICE, BOB, CAROL];
+
+pub(super) fn ether(amount: u64) -> U256 {
+    U256::from(amount) * U256::from(10u64.pow(18))
+}
+
+/// Oracle price with 8 decimals
+pub(super) fn usd(amount: i64) -> I256 {
+    I256::try_from(amount * 100_000_000).unwrap()
+}
+
+pub(super) struct World {
+    chain: Rc<Chain>,
+}
+
+impl World {
+    /// Deploys and initializes everything, each of `USERS` starts with 10 WETH
+    /// and has approved the manager. The PSM starts closed with a zero ceiling. The oracle starts at ~$1757.
+    /// The manager and shUSD trust the forwarder.
+    pub(super) fn new() -> Self {
+        let chain = Chain::new();
+        chain.set_block(1, 1_700_000_000);
+        chain.deploy_with(MANAGER, manager::route);
+        chain.deploy_with(SH_USD, sh_usd::route);
+        chain.deploy::<TestWeth>(WETH);
+        chain.deploy::<TestOracle>(ORACLE);
+        chain.deploy::<VaultNft>(VAULT_NFT);
+        chain.deploy::<TestUsdc>(USDC);
+        chain.deploy::<Psm>(PSM);
+        chain.deploy::<Forwarder>(FORWARDER);
+        let world = Self { chain };
+
+        world.call(DEPLOYER, SH_USD, IToken::initCall { manager: MANAGER }).unwrap();
+        world.call(GOVERNANCE, WETH, IToken::initCall { manager: MANAGER }).unwrap();
+        world.call(GOVERNANCE, ORACLE, IOracle::initCall {}).unwrap();
+        world.call(DEPLOYER, MANAGER, IManager::initializeCall { weth: WETH, oracle: ORACLE, sh_usd: SH_USD, governance: GOVERNANCE }).unwrap();
+        world.call(DEPLOYER, VAULT_NFT, IToken::initCall { manager: MANAGER }).unwrap();
+        world.call(GOVERNANCE, MANAGER, IManager::setVaultNftCall { vault_nft: VAULT_NFT }).unwrap();
+        world.call(GOVERNANCE, USDC, IToken::initCall { manager: GOVERNANCE }).unwrap();
+        world.call(DEPLOYER, PSM, IPsm::initCall { usdc: USDC, sh_usd: SH_USD, governance: GOVERNANCE }).unwrap();
+        for role in [MINTER_ROLE, BURNER_ROLE] {
+            world.call(DEPLOYER, SH_USD, IToken::grantRoleCall { role, account: PSM }).unwrap();
+        }
+        world.call(GOVERNANCE, MANAGER, IManager::setTrustedForwarderCall { forwarder: FORWARDER }).unwrap();
+        world.call(DEPLOYER, SH_USD, IToken::setTrustedForwarderCall { forwarder: FORWARDER }).unwrap();
+
+        for user in USERS {
+            world.call(MANAGER, WETH, IToken::mintCall { to: user, amount: ether(10) }).unwrap();
+            world.call(user, WETH, IToken::approveCall { spender: MANAGER, value: U256::MAX }).unwrap();
+        }
+        world
+    }
+
+    /// To deploy more contracts next to the world's
+    pub(super) fn chain(&self) -> &Rc<Chain> {
+        &self.chain
+    }
+
+    pub(super) fn call<C: SolCall>(&self, from: Address, to: Address, call: C) -> Result<Vec<u8>, Vec<u8>> {
+        self.call_raw(from, to, &call.abi_encode())
+    }
+
+    pub(super) fn call_raw(&self, from: Address, to: Address, data: &[u8]) -> Result<Vec<u8>, Vec<u8>> {
+        self.chain.call(from, to, data)
+    }
+
+    pub(super) fn view<C: SolCall<Return = R>, R>(&self, to: Address, call: C) -> R {
+        let data = self.call(Address::ZERO, to, call).unwrap();
+        C::abi_decode_returns(&data, true).unwrap()
+    }
+
+    pub(super) fn balance(&self, token: Address, account: Address) -> U256 {
+        self.view(token, IToken::balanceOfCall { account })._0
+    }
+
+    /// Logs emitted by the contract at `address` so far, as `(topics, data)`
+    pub(super) fn logs(&self, address: Address) -> Vec<(Vec<B256>, Vec<u8>)> {
+        self.chain.host(address).vm().get_emitted_logs()
+    }
+
+    pub(super) fn ratio(&self, user: Address) -> U256 {
+        self.view(MANAGER, IManager::collatRatioCall { user })._0
+    }
+
+    /// Moves to the next block, `seconds` later
+    pub(super) fn advance(&self, seconds: u64) {
+        let (number, timestamp) = self.chain.block();
+        self.chain.set_block(number + 1, timestamp + seconds);
+    }
+
+    pub(super) fn set_price(&self, price: I256) {
+        self.call(GOVERNANCE, ORACLE, IOracle::setPriceCall { price }).unwrap();
+    }
+
+    pub(super) fn deposit(&self, user: Address, amount: U256) -> Result<Vec<u8>, Vec<u8>> {
+        self.call(user, MANAGER, IManager::depositCall { amount })
+    }
+
+    pub(super) fn mint(&self, user: Address, amount: U256) -> Result<Vec<u8>, Vec<u8>> {
+        self.call(user, MANAGER, IManager::mintCall { amount })
+    }
+}
+
+#[test]
+fn deposit_mint_burn_withdraw() {
+    let world = World::new();
+
+    world.deposit(ALICE, ether(1)).unwrap();
+    assert_eq!(world.balance(WETH, ALICE), ether(9));
+    assert_eq!(world.balance(WETH, MANAGER), ether(1));
+    assert_eq!(world.ratio(ALICE), U256::MAX);
+
+    world.mint(ALICE, ether(1000)).unwrap();
+    assert_eq!(world.balance(SH_USD, ALICE), ether(1000));
+    assert_eq!(world.view(SH_USD, IToken::totalSupplyCall {})._0, ether(1000));
+    // 1757.6555 / 1000, scaled by 1e18
+    assert_eq!(world.ratio(ALICE), U256::from(1_757_655_500_000_000_000u64));
+
+    world.call(ALICE, MANAGER, IManager::burnCall { amount: ether(500) }).unwrap();
+    assert_eq!(world.balance(SH_USD, ALICE), ether(500));
+
+    world.call(ALICE, MANAGER, IManager::withdrawCall { amount: ether(1) / U256::from(2) }).unwrap();
+    assert_eq!(world.balance(WETH, ALICE), ether(9) + ether(1) / U256::from(2));
+    assert_eq!(world.balance(WETH, MANAGER), ether(1) / U256::from(2));
+}
+
+#[test]
+fn undercollateralized_positions_revert() {
+    let world = World::new();
+    world.deposit(ALICE, ether(1)).unwrap();
+    world.mint(ALICE, ether(1000)).unwrap();
+
+    let err = world.mint(ALICE, ether(200)).unwrap_err();
+    assert_eq!(err, Undercollateralized {}.abi_encode());
+    let err = world.call(ALICE, MANAGER, IManager::withdrawCall { amount: ether(1) / U256::from(2) }).unwrap_err();
+    assert_eq!(err, Undercollateralized {}.abi_encode());
+
+    // Both reverts are rolled back on every contract
+    assert_eq!(world.balance(SH_USD, ALICE), ether(1000));
+    assert_eq!(world.balance(WETH, MANAGER), ether(1));
+    assert_eq!(world.ratio(ALICE), U256::from(1_757_655_500_000_000_000u64));
+
+    // Healthy positions can't be liquidated
+    let err = world.call(BOB, MANAGER, IManager::liquidateCall { user: ALICE }).unwrap_err();
+    assert_eq!(err, Undercollateralized {}.abi_encode());
+
+    // Repaying isn't possible without the shUSD
+    assert!(world.call(BOB, MANAGER, IManager::burnCall { amount: ether(1) }).is_err());
+}
+
+#[test]
+fn liquidate_with_penalty() {
+    let world = World::new();
+    world.deposit(ALICE, ether(1)).unwrap();
+    world.mint(ALICE, ether(1000)).unwrap();
+    world.deposit(BOB, ether(10)).unwrap();
+    world.mint(BOB, ether(1500)).unwrap();
+
+    world.set_price(usd(1400));
+    world.call(BOB, MANAGER, IManager::liquidateCall { user: ALICE }).unwrap();
+
+    // Bob repays the debt plus the 5% penalty and takes the collateral
+    assert_eq!(world.balance(SH_USD, BOB), ether(450));
+    assert_eq!(world.balance(WETH, BOB), ether(1));
+    assert_eq!(world.balance(SH_USD, MANAGER), ether(50));
+    assert_eq!(world.view(MANAGER, IManager::surplusCall {})._0, ether(50));
+    assert_eq!(world.ratio(ALICE), U256::MAX);
+    assert_eq!(world.balance(WETH, MANAGER), ether(10));
+}
+
+#[test]
+fn liquidate_into_bad_debt() {
+    let world = World::new();
+    world.deposit(ALICE, ether(1)).unwrap();
+    world.mint(ALICE, ether(1000)).unwrap();
+    world.deposit(BOB, ether(10)).unwrap();
+    world.mint(BOB, ether(1500)).unwrap();
+
+    world.set_price(usd(800));
+    world.call(BOB, MANAGER, IManager::liquidateCall { user: ALICE }).unwrap();
+
+    // $800 of collateral goes for $800 / 1.05 of the $1000 debt, the rest is bad debt
+    let repaid = U256::from(761_904_761_904_761_904_762u128);
+    assert_eq!(world.balance(SH_USD, BOB), ether(1500) - repaid);
+    assert_eq!(world.balance(WETH, BOB), ether(1));
+    assert_eq!(world.view(MANAGER, IManager::badDebtCall {})._0, ether(1000) - repaid);
+    assert_eq!(world.view(MANAGER, IManager::surplusCall {})._0, U256::ZERO);
+    let bad_debt = BadDebtRecorded { user: ALICE, amount: ether(1000) - repaid };
+    assert!(world.logs(MANAGER).iter().any(|(topics, data)| topics[0] == BadDebtRecorded::SIGNATURE_HASH && *data == bad_debt.encode_data()));
+}
+
+#[test]
+fn liquidators_repay_with_their_own_shusd() {
+    let world = World::new();
+    world.deposit(ALICE, ether(1)).unwrap();
+    world.mint(ALICE, ether(1000)).unwrap();
+    world.deposit(BOB, ether(10)).unwrap();
+    world.mint(BOB, ether(500)).unwrap();
+    world.set_price(usd(1400));
+
+    // Carol holds no shUSD and Bob too little for the debt and penalty
+    assert!(world.call(CAROL, MANAGER, IManager::liquidateCall { user: ALICE }).is_err());
+    assert!(world.call(BOB, MANAGER, IManager::liquidateCall { user: ALICE }).is_err());
+    assert_eq!(world.view(MANAGER, IManager::positionCall { user: ALICE })._1, ether(1000));
+
+    let weth = world.balance(WETH, CAROL);
+    world.call(ALICE, SH_USD, IShUSD::transferCall { to: CAROL, value: ether(1000) }).unwrap();
+    world.call(BOB, SH_USD, IShUSD::transferCall { to: CAROL, value: ether(50) }).unwrap();
+    world.call(CAROL, MANAGER, IManager::liquidateCall { user: ALICE }).unwrap();
+    assert_eq!(world.balance(SH_USD, CAROL), U256::ZERO);
+    assert_eq!(world.balance(WETH, CAROL), weth + ether(1));
+    assert!(world.logs(MANAGER).iter().any(|(topics, data)| topics[0] == SurplusAccrued::SIGNATURE_HASH && *data == SurplusAccrued { amount: ether(50) }.encode_data()));
+
+    // Liquidating leaves the user's own shUSD alone
+    world.deposit(ALICE, ether(1)).unwrap();
+    world.mint(ALICE, ether(900)).unwrap();
+    world.set_price(usd(1300));
+    world.mint(BOB, ether(1000)).unwrap();
+    world.call(BOB, SH_USD, IShUSD::transferCall { to: CAROL, value: ether(945) }).unwrap();
+    world.call(CAROL, MANAGER, IManager::liquidateCall { user: ALICE }).unwrap();
+    assert_eq!(world.balance(SH_USD, ALICE), ether(900));
+    assert_eq!(world.view(MANAGER, IManager::positionCall { user: ALICE })._1, U256::ZERO);
+}
+
+#[test]
+fn mints_are_rate_limited() {
+    let world = World::new();
+    let available = || world.view(MANAGER, IManager::availableToMintCall {})._0;
+    let limits = IManager::setMintLimitsCall { window_limit: ether(1000), window: 100, block_limit: ether(600) };
+    assert_eq!(world.call(ALICE, MANAGER, limits.clone()).unwrap_err(), OnlyGovernance {}.abi_encode());
+    world.call(GOVERNANCE, MANAGER, limits).unwrap();
+    world.deposit(ALICE, ether(10)).unwrap();
+
+    // At most 600 within a block
+    world.mint(ALICE, ether(600)).unwrap();
+    let err = world.mint(ALICE, ether(1)).unwrap_err();
+    assert_eq!(err, MintRateLimited { available: U256::ZERO, requested: ether(1) }.abi_encode());
+
+    // A tenth of the window refills a tenth of the limit
+    world.advance(10);
+    assert_eq!(available(), ether(500));
+    let err = world.mint(ALICE, ether(501)).unwrap_err();
+    assert_eq!(err, MintRateLimited { available: ether(500), requested: ether(501) }.abi_encode());
+    world.mint(ALICE, ether(500)).unwrap();
+
+    // Burns hand window capacity back but don't lift the block cap
+    world.call(ALICE, MANAGER, IManager::burnCall { amount: ether(300) }).unwrap();
+    assert_eq!(available(), ether(100));
+    world.advance(0);
+    assert_eq!(available(), ether(300));
+    world.advance(100);
+    assert_eq!(available(), ether(600));
+}
+
+#[test]
+fn liquidations_release_mint_capacity() {
+    let world = World::new();
+    world.deposit(ALICE, ether(1)).unwrap();
+    world.mint(ALICE, ether(1000)).unwrap();
+    world.deposit(BOB, ether(10)).unwrap();
+    world.mint(BOB, ether(1500)).unwrap();
+    let limits = IManager::setMintLimitsCall { window_limit: ether(1000), window: 100, block_limit: U256::ZERO };
+    world.call(GOVERNANCE, MANAGER, limits).unwrap();
+    world.deposit(CAROL, ether(1)).unwrap();
+    world.mint(CAROL, ether(1000)).unwrap();
+    assert_eq!(world.view(MANAGER, IManager::availableToMintCall {})._0, U256::ZERO);
+
+    // The penalty is minted back to the surplus, only the debt counts
+    world.set_price(usd(1400));
+    world.call(BOB, MANAGER, IManager::liquidateCall { user: ALICE }).unwrap();
+    assert_eq!(world.view(MANAGER, IManager::availableToMintCall {})._0, ether(1000));
+}
+
+#[test]
+fn oracle_crashes() {
+    let world = World::new();
+    world.deposit(ALICE, ether(1)).unwrap();
+    world.mint(ALICE, ether(100)).unwrap();
+
+    let set_mode = |mode| world.call(GOVERNANCE, ORACLE, IOracle::setFailureModeCall { mode }).unwrap();
+
+    // The oracle's revert reaches the caller untouched
+    set_mode(test_oracle::REVERT);
+    assert_eq!(world.mint(ALICE, ether(1)).unwrap_err(), OracleReverted {}.abi_encode());
+    assert_eq!(world.call(ALICE, MANAGER, IManager::withdrawCall { amount: U256::from(1) }).unwrap_err(), OracleReverted {}.abi_encode());
+    assert!(world.call(BOB, MANAGER, IManager::liquidateCall { user: ALICE }).is_err());
+
+    set_mode(test_oracle::NEGATIVE);
+    assert_eq!(world.mint(ALICE, ether(1)).unwrap_err(), ConversionFailure {}.abi_encode());
+
+    // A zero price leaves every position worthless
+    set_mode(test_oracle::ZERO);
+    assert_eq!(world.mint(ALICE, ether(1)).unwrap_err(), Undercollateralized {}.abi_encode());
+    assert_eq!(world.ratio(ALICE), U256::ZERO);
+
+    // Deposits don't read the price
+    world.deposit(ALICE, ether(1)).unwrap();
+
+    set_mode(test_oracle::HEALTHY);
+    world.mint(ALICE, ether(1)).unwrap();
+    assert_eq!(world.balance(SH_USD, ALICE), ether(101));
+}
+
+#[test]
+fn vaults_follow_their_nft() {
+    let world = World::new();
+    let open = |user| IManager::openVaultCall::abi_decode_returns(&world.call(user, MANAGER, IManager::openVaultCall {}).unwrap(), true).unwrap()._0;
+    let (first, second) = (open(ALICE), open(ALICE));
+    assert_eq!((first, second), (U256::from(1), U256::from(2)));
+    let (key, key2) = (manager::vault_key(first), manager::vault_key(second));
+
+    world.call(ALICE, MANAGER, IManager::depositVaultCall { id: first, amount: ether(1) }).unwrap();
+    world.call(ALICE, MANAGER, IManager::mintVaultCall { id: first, amount: ether(1000) }).unwrap();
+    assert_eq!(world.balance(SH_USD, ALICE), ether(1000));
+    assert_eq!(world.balance(WETH, ALICE), ether(9));
+    // Each vault is isolated from the owner's own position and the other vaults
+    assert_eq!(world.ratio(key), U256::from(1_757_655_500_000_000_000u64));
+    assert_eq!(world.ratio(ALICE), U256::MAX);
+    assert_eq!(world.ratio(key2), U256::MAX);
+    let err = world.call(ALICE, MANAGER, IManager::withdrawVaultCall { id: second, amount: U256::from(1) }).unwrap_err();
+    assert_eq!(err, manager::CouldNotSub {}.abi_encode());
+
+    let not_owner = |id| NotVaultOwner { id }.abi_encode();
+    assert_eq!(world.call(BOB, MANAGER, IManager::mintVaultCall { id: first, amount: ether(1) }).unwrap_err(), not_owner(first));
+    assert_eq!(world.call(BOB, MANAGER, IManager::depositVaultCall { id: U256::from(3), amount: ether(1) }).unwrap_err(), not_owner(U256::from(3)));
+
+    // The position moves with the NFT
+    world.call(ALICE, VAULT_NFT, IVaultNft::transferFromCall { from: ALICE, to: BOB, id: first }).unwrap();
+    assert_eq!(world.view(VAULT_NFT, IVaultNft::ownerOfCall { id: first })._0, BOB);
+    assert_eq!(world.call(ALICE, MANAGER, IManager::withdrawVaultCall { id: first, amount: U256::from(1) }).unwrap_err(), not_owner(first));
+    world.call(BOB, MANAGER, IManager::withdrawVaultCall { id: first, amount: ether(1) / U256::from(10) }).unwrap();
+    world.call(BOB, MANAGER, IManager::mintVaultCall { id: first, amount: ether(50) }).unwrap();
+    assert_eq!(world.balance(WETH, BOB), ether(10) + ether(1) / U256::from(10));
+    assert_eq!(world.balance(SH_USD, BOB), ether(50));
+    world.call(BOB, MANAGER, IManager::burnVaultCall { id: first, amount: ether(50) }).unwrap();
+
+    // Vault positions are liquidated by their key
+    world.deposit(CAROL, ether(10)).unwrap();
+    world.mint(CAROL, ether(2000)).unwrap();
+    world.set_price(usd(1400));
+    world.call(CAROL, MANAGER, IManager::liquidateCall { user: key }).unwrap();
+    assert_eq!(world.view(MANAGER, IManager::positionCall { user: key })._0, U256::ZERO);
+    assert_eq!(world.balance(WETH, CAROL), ether(9) / U256::from(10));
+
+    // Only the manager opens vaults, and the NFT can't be swapped
+    assert_eq!(world.call(ALICE, VAULT_NFT, IVaultNft::mintCall { to: ALICE }).unwrap_err(), OnlyManager {}.abi_encode());
+    let err = world.call(GOVERNANCE, MANAGER, IManager::setVaultNftCall { vault_nft: ALICE }).unwrap_err();
+    assert_eq!(err, AlreadyInitialized {}.abi_encode());
+}
+
+#[test]
+fn price_breaker_holds_back_jumps() {
+    let world = World::new();
+    let withdraw = |amount| world.call(ALICE, MANAGER, IManager::withdrawCall { amount });
+    let tripped = || world.view(MANAGER, IManager::breakerTrippedCall {})._0;
+    let has_log = |topic: B256| world.logs(MANAGER).iter().any(|(topics, _)| topics[0] == topic);
+    // 10% within an hour
+    world.call(GOVERNANCE, MANAGER, IManager::setPriceGuardCall { max_deviation: ether(1) / U256::from(10), window: 3600 }).unwrap();
+    world.call(GOVERNANCE, MANAGER, IManager::setGuardianCall { guardian: CAROL }).unwrap();
+    world.deposit(ALICE, ether(2)).unwrap();
+    world.mint(ALICE, ether(1000)).unwrap();
+    let last_good = world.view(MANAGER, IManager::lastGoodPriceCall {});
+    assert_eq!((last_good._0, last_good._1), (U256::from(175_765_550_000u64), 1_700_000_000));
+
+    // A 15% drop blocks mints and withdrawals but not deposits and repayments.
+    // Liquidations go by the oracle alone, ALICE is still safe at the new price.
+    world.set_price(usd(1500));
+    assert_eq!(world.mint(ALICE, ether(1)).unwrap_err(), PriceSuspicious {}.abi_encode());
+    assert_eq!(withdraw(U256::from(1)).unwrap_err(), PriceSuspicious {}.abi_encode());
+    assert_eq!(world.call(BOB, MANAGER, IManager::liquidateCall { user: ALICE }).unwrap_err(), Undercollateralized {}.abi_encode());
+    world.deposit(ALICE, ether(1)).unwrap();
+    world.call(ALICE, MANAGER, IManager::burnCall { amount: ether(100) }).unwrap();
+    // The reverts rolled the trip back, poking records it
+    assert!(!tripped());
+    let accepted = world.call(BOB, MANAGER, IManager::pokePriceCall {}).unwrap();
+    assert!(!IManager::pokePriceCall::abi_decode_returns(&accepted, true).unwrap()._0);
+    assert!(tripped());
+    assert!(has_log(BreakerTripped::SIGNATURE_HASH));
+
+    // Coming back near the last good price resets it
+    world.set_price(usd(1700));
+    world.mint(ALICE, ether(1)).unwrap();
+    assert!(!tripped());
+    assert!(has_log(BreakerReset::SIGNATURE_HASH));
+
+    // So does holding the new price for a whole window
+    world.set_price(usd(1500));
+    world.call(BOB, MANAGER, IManager::pokePriceCall {}).unwrap();
+    world.advance(1800);
+    assert_eq!(world.mint(ALICE, ether(1)).unwrap_err(), PriceSuspicious {}.abi_encode());
+    world.advance(1800);
+    world.mint(ALICE, ether(1)).unwrap();
+    assert_eq!(world.view(MANAGER, IManager::lastGoodPriceCall {})._0, U256::from(150_000_000_000u64));
+
+    // Or the guardian confirming it
+    world.set_price(usd(1000));
+    world.call(BOB, MANAGER, IManager::pokePriceCall {}).unwrap();
+    assert_eq!(world.call(BOB, MANAGER, IManager::confirmPriceCall {}).unwrap_err(), OnlyGuardian {}.abi_encode());
+    world.call(CAROL, MANAGER, IManager::confirmPriceCall {}).unwrap();
+    assert!(!tripped());
+    withdraw(ether(1) / U256::from(10)).unwrap();
+
+    // Moves spread over more than a window don't trip it
+    world.advance(3601);
+    world.set_price(usd(1200));
+    world.mint(ALICE, ether(1)).unwrap();
+}
+
+#[test]
+fn liquidations_ignore_the_price_breaker() {
+    let world = World::new();
+    world.call(GOVERNANCE, MANAGER, IManager::setPriceGuardCall { max_deviation: ether(1) / U256::from(10), window: 3600 }).unwrap();
+    world.deposit(ALICE, ether(2)).unwrap();
+    world.mint(ALICE, ether(1000)).unwrap();
+    world.deposit(BOB, ether(10)).unwrap();
+    world.mint(BOB, ether(2000)).unwrap();
+
+    // The crash trips the breaker for BOB's mint, ALICE at 140% is liquidated all the same
+    world.set_price(usd(700));
+    assert_eq!(world.mint(BOB, ether(1)).unwrap_err(), PriceSuspicious {}.abi_encode());
+    world.call(BOB, MANAGER, IManager::liquidateCall { user: ALICE }).unwrap();
+    let position = world.view(MANAGER, IManager::positionCall { user: ALICE });
+    assert_eq!((position._0, position._1), (U256::ZERO, U256::ZERO));
+    assert_eq!(world.balance(WETH, BOB), ether(2));
+    assert_eq!(world.balance(SH_USD, BOB), ether(950));
+}
@@ -0,0 +1,129 @@
+//! Meta-transactions relayed by the `Forwarder` to the `Manager` and `ShUSD` of a [`World`].
+use alloc::vec::Vec;
+use alloy_primitives::{keccak256, Address, B256, U256};
+use alloy_sol_types::{sol, SolCall, SolError, SolStruct};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::H256;
+use crate::client::sh_usd::IShUSD;
+use crate::contracts::forwarder::{
+    ERC2771ForwarderExpiredRequest, ERC2771ForwarderInvalidSigner, ERC2771UntrustfulTarget, ForwardRequest,
+};
+use super::e2e::{ether, IManager, IToken, World, ALICE, BOB, CAROL, FORWARDER, GOVERNANCE, MANAGER, SH_USD, WETH};
+
+sol! {
+    interface IForwarder {
+        function execute(address from, address to, uint256 deadline, bytes data, uint8 v, bytes32 r, bytes32 s) external returns (bytes);
+        function verify(address from, address to, uint256 deadline, bytes data, uint8 v, bytes32 r, bytes32 s) external view returns (bool);
+        function nonces(address owner) external view returns (uint256);
+        function DOMAIN_SEPARATOR() external view returns (bytes32);
+    }
+}
+
+/// A day after the world starts
+const DEADLINE: u64 = 1_700_086_400;
+
+/// An account without ether, all it does is sign
+fn wallet() -> LocalWallet {
+    "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318".parse().unwrap()
+}
+
+fn address(wallet: &LocalWallet) -> Address {
+    Address::from(wallet.address().0)
+}
+
+/// `wallet`'s request to call `to` on behalf of `from`, over `from`'s current nonce
+fn sign<C: SolCall>(world: &World, wallet: &LocalWallet, from: Address, to: Address, deadline: u64, call: C) -> IForwarder::executeCall {
+    let data = call.abi_encode();
+    let nonce = world.view(FORWARDER, IForwarder::noncesCall { owner: from })._0;
+    let request = ForwardRequest { from, to, nonce, deadline: U256::from(deadline), data: data.clone().into() };
+    let separator = world.view(FORWARDER, IForwarder::DOMAIN_SEPARATORCall {})._0;
+    let digest = keccak256([&[0x19, 0x01], separator.as_slice(), request.eip712_hash_struct().as_slice()].concat());
+    let signature = wallet.sign_hash(H256(digest.0)).unwrap();
+    IForwarder::executeCall {
+        from,
+        to,
+        deadline: U256::from(deadline),
+        data: data.into(),
+        v: signature.v as u8,
+        r: B256::from(<[u8; 32]>::from(signature.r)),
+        s: B256::from(<[u8; 32]>::from(signature.s)),
+    }
+}
+
+/// BOB relays the request and pays for it
+fn relay(world: &World, request: IForwarder::executeCall) -> Result<Vec<u8>, Vec<u8>> {
+    world.call(BOB, FORWARDER, request)
+}
+
+#[test]
+fn relays_signed_calls_for_their_signer() {
+    let world = World::new();
+    let wallet = wallet();
+    let user = address(&wallet);
+    world.call(MANAGER, WETH, IToken::mintCall { to: user, amount: ether(1) }).unwrap();
+    world.call(user, WETH, IToken::approveCall { spender: MANAGER, value: U256::MAX }).unwrap();
+
+    let deposit = sign(&world, &wallet, user, MANAGER, DEADLINE, IManager::depositCall { amount: ether(1) });
+    let verify = IForwarder::verifyCall { from: user, to: MANAGER, deadline: deposit.deadline, data: deposit.data.clone(), v: deposit.v, r: deposit.r, s: deposit.s };
+    assert!(world.view(FORWARDER, verify)._0);
+    relay(&world, deposit.clone()).unwrap();
+    relay(&world, sign(&world, &wallet, user, MANAGER, DEADLINE, IManager::mintCall { amount: ether(1000) })).unwrap();
+    relay(&world, sign(&world, &wallet, user, MANAGER, DEADLINE, IManager::burnCall { amount: ether(400) })).unwrap();
+    relay(&world, sign(&world, &wallet, user, SH_USD, DEADLINE, IShUSD::transferCall { to: CAROL, value: ether(100) })).unwrap();
+
+    // The position, shUSD and WETH are the signer's, the relayer only paid for gas
+    let position = world.view(MANAGER, IManager::positionCall { user });
+    assert_eq!((position._0, position._1), (ether(1), ether(600)));
+    assert_eq!(world.balance(SH_USD, user), ether(500));
+    assert_eq!(world.balance(SH_USD, CAROL), ether(100));
+    assert_eq!(world.balance(WETH, user), U256::ZERO);
+    assert_eq!(world.balance(SH_USD, BOB), U256::ZERO);
+    assert_eq!(world.view(FORWARDER, IForwarder::noncesCall { owner: user })._0, U256::from(4));
+
+    // A request only runs once, its nonce is used up
+    let err = relay(&world, deposit).unwrap_err();
+    assert_eq!(err[..4], ERC2771ForwarderInvalidSigner::SELECTOR);
+}
+
+#[test]
+fn rejects_bad_requests() {
+    let world = World::new();
+    let wallet = wallet();
+    let user = address(&wallet);
+
+    let expired = sign(&world, &wallet, user, MANAGER, 1_699_999_999, IManager::mintCall { amount: ether(1) });
+    assert_eq!(relay(&world, expired).unwrap_err(), ERC2771ForwarderExpiredRequest { deadline: U256::from(1_699_999_999) }.abi_encode());
+
+    // Signing for someone else
+    let forged = sign(&world, &wallet, ALICE, MANAGER, DEADLINE, IManager::withdrawCall { amount: ether(1) });
+    assert_eq!(relay(&world, forged).unwrap_err(), ERC2771ForwarderInvalidSigner { signer: user, from: ALICE }.abi_encode());
+
+    // WETH doesn't trust the forwarder and would take it as the sender
+    let untrusted = sign(&world, &wallet, user, WETH, DEADLINE, IToken::approveCall { spender: BOB, value: U256::MAX });
+    assert_eq!(relay(&world, untrusted).unwrap_err(), ERC2771UntrustfulTarget { target: WETH, forwarder: FORWARDER }.abi_encode());
+    assert_eq!(world.view(FORWARDER, IForwarder::noncesCall { owner: user })._0, U256::ZERO);
+}
+
+#[test]
+fn only_the_forwarder_appends_senders() {
+    let world = World::new();
+    world.deposit(ALICE, ether(1)).unwrap();
+    world.mint(ALICE, ether(1000)).unwrap();
+
+    // BOB can't pass for ALICE by appending her address himself
+    let mut data = IShUSD::transferCall { to: BOB, value: ether(1000) }.abi_encode();
+    data.extend_from_slice(ALICE.as_slice());
+    assert!(world.call_raw(BOB, SH_USD, &data).is_err());
+    let mut data = IManager::withdrawCall { amount: ether(1) }.abi_encode();
+    data.extend_from_slice(ALICE.as_slice());
+    assert!(world.call_raw(BOB, MANAGER, &data).is_err());
+    assert_eq!(world.balance(SH_USD, ALICE), ether(1000));
+
+    // Once the manager stops trusting the forwarder it is an ordinary caller
+    let wallet = wallet();
+    let user = address(&wallet);
+    world.call(GOVERNANCE, MANAGER, IManager::setTrustedForwarderCall { forwarder: Address::ZERO }).unwrap();
+    assert!(!world.view(MANAGER, IManager::isTrustedForwarderCall { forwarder: FORWARDER })._0);
+    let request = sign(&world, &wallet, user, MANAGER, DEADLINE, IManager::depositCall { amount: U256::ZERO });
+    assert_eq!(relay(&world, request).unwrap_err(), ERC2771UntrustfulTarget { target: MANAGER, forwarder: FORWARDER }.abi_encode());
+}
@@ -0,0 +1,166 @@
+//! `GovToken` voting power on the chain of a [`World`].
+use alloc::borrow::Cow;
+use alloy_primitives::{address, Address, B256, U256};
+use alloy_sol_types::{sol, Eip712Domain, SolError, SolStruct};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::H256;
+use stylus_sdk::stylus_core::ChainAccess;
+use crate::access::deployer::OnlyDeployer;
+use crate::contracts::gov_token::{GovToken, OnlyOwner};
+use crate::token::erc20::InsufficientBalance;
+use crate::token::votes::{Delegation, ERC5805FutureLookup, InvalidAccountNonce, VotesExpiredSignature};
+use super::e2e::{ether, World, ALICE, BOB, CAROL, DEPLOYER, GOVERNANCE};
+
+// Only `#[entrypoint]` marks the token as top level storage
+#[cfg(not(feature = "gov-token"))]
+unsafe impl stylus_sdk::stylus_core::storage::TopLevelStorage for GovToken {}
+
+sol! {
+    interface IGovToken {
+        function init(address owner) external;
+        function mint(address to, uint256 amount) external;
+        function burn(uint256 amount) external;
+        function transfer(address to, uint256 value) external returns (bool);
+        function transferFrom(address from, address to, uint256 value) external returns (bool);
+        function approve(address spender, uint256 value) external returns (bool);
+        function delegate(address delegatee) external;
+        function delegateBySig(address delegatee, uint256 nonce, uint256 expiry, uint8 v, bytes32 r, bytes32 s) external;
+        function delegates(address account) external view returns (address);
+        function nonces(address account) external view returns (uint256);
+        function getVotes(address account) external view returns (uint256);
+        function getPastVotes(address account, uint256 timepoint) external view returns (uint256);
+        function getPastTotalSupply(uint256 timepoint) external view returns (uint256);
+        function numCheckpoints(address account) external view returns (uint32);
+    }
+}
+
+pub(super) const GOV_TOKEN: Address = address!("000000000000000000000000000000000000a00e");
+
+/// Deploys the token owned by GOVERNANCE
+pub(super) fn deploy(world: &World) {
+    world.chain().deploy::<GovToken>(GOV_TOKEN);
+    world.call(DEPLOYER, GOV_TOKEN, IGovToken::initCall { owner: GOVERNANCE }).unwrap();
+}
+
+/// Mints `amount` to `account`, who delegates to themselves
+pub(super) fn mint_votes(world: &World, account: Address, amount: U256) {
+    world.call(GOVERNANCE, GOV_TOKEN, IGovToken::mintCall { to: account, amount }).unwrap();
+    world.call(account, GOV_TOKEN, IGovToken::delegateCall { delegatee: account }).unwrap();
+}
+
+fn votes(world: &World, account: Address) -> U256 {
+    world.view(GOV_TOKEN, IGovToken::getVotesCall { account })._0
+}
+
+fn block(world: &World) -> u64 {
+    world.chain().block().0
+}
+
+/// An account without ether, all it does is sign
+fn wallet() -> LocalWallet {
+    "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318".parse().unwrap()
+}
+
+/// `wallet`'s signature delegating to `delegatee`
+fn sign(world: &World, wallet: &LocalWallet, delegatee: Address, nonce: U256, expiry: u64) -> IGovToken::delegateBySigCall {
+    let delegation = Delegation { delegatee, nonce, expiry: U256::from(expiry) };
+    let domain = Eip712Domain::new(
+        Some(Cow::Borrowed("Shafu Governance")),
+        Some(Cow::Borrowed("1")),
+        Some(U256::from(world.chain().host(GOV_TOKEN).vm().chain_id())),
+        Some(GOV_TOKEN),
+        None,
+    );
+    let signature = wallet.sign_hash(H256(delegation.eip712_signing_hash(&domain).0)).unwrap();
+    IGovToken::delegateBySigCall {
+        delegatee,
+        nonce,
+        expiry: U256::from(expiry),
+        v: signature.v as u8,
+        r: B256::from(<[u8; 32]>::from(signature.r)),
+        s: B256::from(<[u8; 32]>::from(signature.s)),
+    }
+}
+
+#[test]
+fn only_the_deployer_initializes() {
+    let world = World::new();
+    world.chain().deploy::<GovToken>(GOV_TOKEN);
+    let init = IGovToken::initCall { owner: ALICE };
+    assert_eq!(world.call(ALICE, GOV_TOKEN, init).unwrap_err(), OnlyDeployer {}.abi_encode());
+    let mint = IGovToken::mintCall { to: ALICE, amount: ether(100) };
+    assert_eq!(world.call(ALICE, GOV_TOKEN, mint).unwrap_err(), OnlyOwner {}.abi_encode());
+}
+
+#[test]
+fn votes_follow_every_balance_change() {
+    let world = World::new();
+    deploy(&world);
+    let mint = IGovToken::mintCall { to: ALICE, amount: ether(100) };
+    assert_eq!(world.call(ALICE, GOV_TOKEN, mint.clone()).unwrap_err(), OnlyOwner {}.abi_encode());
+    world.call(GOVERNANCE, GOV_TOKEN, mint).unwrap();
+    world.call(GOVERNANCE, GOV_TOKEN, IGovToken::mintCall { to: BOB, amount: ether(50) }).unwrap();
+    // Tokens only count once delegated
+    assert_eq!(votes(&world, ALICE), U256::ZERO);
+    world.call(ALICE, GOV_TOKEN, IGovToken::delegateCall { delegatee: ALICE }).unwrap();
+    assert_eq!(votes(&world, ALICE), ether(100));
+    let minted = block(&world);
+
+    // Transfers through the inherited entrypoints move votes too
+    world.advance(12);
+    world.call(ALICE, GOV_TOKEN, IGovToken::transferCall { to: BOB, value: ether(30) }).unwrap();
+    assert_eq!(votes(&world, ALICE), ether(70));
+    world.call(BOB, GOV_TOKEN, IGovToken::delegateCall { delegatee: CAROL }).unwrap();
+    assert_eq!(world.view(GOV_TOKEN, IGovToken::delegatesCall { account: BOB })._0, CAROL);
+    assert_eq!((votes(&world, BOB), votes(&world, CAROL)), (U256::ZERO, ether(80)));
+    world.call(BOB, GOV_TOKEN, IGovToken::approveCall { spender: ALICE, value: ether(20) }).unwrap();
+    world.call(ALICE, GOV_TOKEN, IGovToken::transferFromCall { from: BOB, to: ALICE, value: ether(20) }).unwrap();
+    assert_eq!((votes(&world, ALICE), votes(&world, CAROL)), (ether(90), ether(60)));
+    world.call(BOB, GOV_TOKEN, IGovToken::burnCall { amount: ether(10) }).unwrap();
+    assert_eq!(votes(&world, CAROL), ether(50));
+    let moved = block(&world);
+
+    // Voting power more than the delegator holds can't move
+    let err = world.call(BOB, GOV_TOKEN, IGovToken::transferCall { to: ALICE, value: ether(51) }).unwrap_err();
+    assert_eq!(err, InsufficientBalance { from: BOB, have: ether(50), want: ether(51) }.abi_encode());
+
+    let past = |account, timepoint: u64| world.view(GOV_TOKEN, IGovToken::getPastVotesCall { account, timepoint: U256::from(timepoint) })._0;
+    let supply = |timepoint: u64| world.view(GOV_TOKEN, IGovToken::getPastTotalSupplyCall { timepoint: U256::from(timepoint) })._0;
+    let err = world.call(ALICE, GOV_TOKEN, IGovToken::getPastVotesCall { account: ALICE, timepoint: U256::from(moved) }).unwrap_err();
+    assert_eq!(err, ERC5805FutureLookup { timepoint: U256::from(moved), clock: moved }.abi_encode());
+    world.advance(12);
+    assert_eq!((past(ALICE, minted), past(ALICE, moved)), (ether(100), ether(90)));
+    assert_eq!((past(CAROL, minted), past(CAROL, moved)), (U256::ZERO, ether(50)));
+    assert_eq!((supply(minted), supply(moved)), (ether(150), ether(140)));
+    assert_eq!(world.view(GOV_TOKEN, IGovToken::numCheckpointsCall { account: ALICE })._0, 2);
+}
+
+#[test]
+fn delegates_by_signature() {
+    let world = World::new();
+    deploy(&world);
+    let wallet = wallet();
+    let signer = Address::from(wallet.address().0);
+    world.call(GOVERNANCE, GOV_TOKEN, IGovToken::mintCall { to: signer, amount: ether(100) }).unwrap();
+    let expiry = world.chain().block().1 + 3600;
+
+    // Anyone can submit the signature, once
+    let delegation = sign(&world, &wallet, CAROL, U256::ZERO, expiry);
+    world.call(BOB, GOV_TOKEN, delegation.clone()).unwrap();
+    assert_eq!(world.view(GOV_TOKEN, IGovToken::delegatesCall { account: signer })._0, CAROL);
+    assert_eq!(votes(&world, CAROL), ether(100));
+    assert_eq!(world.view(GOV_TOKEN, IGovToken::noncesCall { account: signer })._0, U256::from(1));
+    let err = world.call(BOB, GOV_TOKEN, delegation).unwrap_err();
+    assert_eq!(err, InvalidAccountNonce { account: signer, current_nonce: U256::from(1) }.abi_encode());
+
+    // A tampered delegation recovers someone else, with no nonce to match
+    let mut tampered = sign(&world, &wallet, BOB, U256::from(1), expiry);
+    tampered.delegatee = ALICE;
+    assert!(world.call(BOB, GOV_TOKEN, tampered).is_err());
+    assert_eq!(votes(&world, ALICE), U256::ZERO);
+
+    world.advance(3601);
+    let expired = sign(&world, &wallet, BOB, U256::from(1), expiry);
+    assert_eq!(world.call(BOB, GOV_TOKEN, expired).unwrap_err(), VotesExpiredSignature { expiry: U256::from(expiry) }.abi_encode());
+    assert_eq!(votes(&world, CAROL), ether(100));
+}
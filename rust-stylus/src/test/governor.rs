@@ -0,0 +1,170 @@
+//! `Governor` proposals voted with `GovToken` and run by the `Timelock` of a [`World`].
+use alloy_primitives::{address, Address, U256};
+use alloy_sol_types::{sol, SolCall, SolError};
+use crate::contracts::governor::{
+    AlreadyInitialized, AlreadyVoted, BelowProposalThreshold, CouldNotAdd, Governor, InvalidVoteType, OnlyTimelock, UnexpectedState, ACTIVE,
+    DEFEATED, EXECUTED, PENDING, QUEUED, SUCCEEDED,
+};
+use crate::access::deployer::OnlyDeployer;
+use crate::contracts::timelock::{NotReady, Timelock, MIN_DELAY};
+use super::e2e::{ether, IManager, World, ALICE, BOB, CAROL, DEPLOYER, GOVERNANCE, MANAGER};
+use super::gov_token::{self, IGovToken, GOV_TOKEN};
+use super::timelock::{ITimelock, TIMELOCK};
+
+// Only `#[entrypoint]` marks the governor as top level storage
+#[cfg(not(feature = "governor"))]
+unsafe impl stylus_sdk::stylus_core::storage::TopLevelStorage for Governor {}
+
+sol! {
+    interface IGovernor {
+        function init(address token, address timelock, uint64 voting_delay, uint64 voting_period, uint256 proposal_threshold, uint256 quorum_bps) external;
+        function propose(address target, bytes data, string description) external returns (uint256);
+        function castVote(uint256 id, uint8 support) external returns (uint256);
+        function queue(uint256 id) external returns (uint256);
+        function execute(uint256 id) external returns (bytes);
+        function setVotingDelay(uint64 voting_delay) external;
+        function state(uint256 id) external view returns (uint8);
+        function quorum(uint256 block) external view returns (uint256);
+        function proposalVotes(uint256 id) external view returns (uint256, uint256, uint256);
+    }
+}
+
+const GOVERNOR: Address = address!("000000000000000000000000000000000000a00f");
+const VOTING_DELAY: u64 = 1;
+const VOTING_PERIOD: u64 = 10;
+const AGAINST: u8 = 0;
+const FOR: u8 = 1;
+const ABSTAIN: u8 = 2;
+
+/// ALICE, BOB and CAROL hold 60, 30 and 10 votes. Proposing takes 20 and the quorum
+/// is 40%, the governor proposes and executes on the timelock owning the manager.
+fn deploy(world: &World) {
+    gov_token::deploy(world);
+    for (account, votes) in [(ALICE, 60), (BOB, 30), (CAROL, 10)] {
+        gov_token::mint_votes(world, account, ether(votes));
+    }
+    world.chain().deploy::<Governor>(GOVERNOR);
+    world.call(DEPLOYER, GOVERNOR, init(GOV_TOKEN)).unwrap();
+    world.chain().deploy::<Timelock>(TIMELOCK);
+    let init = ITimelock::initCall { min_delay: MIN_DELAY, proposer: GOVERNOR, executor: GOVERNOR, guardian: GOVERNANCE };
+    world.call(DEPLOYER, TIMELOCK, init).unwrap();
+    world.call(GOVERNANCE, MANAGER, IManager::setGovernanceCall { new_governance: TIMELOCK }).unwrap();
+    world.advance(12);
+}
+
+fn init(token: Address) -> IGovernor::initCall {
+    IGovernor::initCall {
+        token,
+        timelock: TIMELOCK,
+        voting_delay: VOTING_DELAY,
+        voting_period: VOTING_PERIOD,
+        proposal_threshold: ether(20),
+        quorum_bps: U256::from(4000),
+    }
+}
+
+/// Moves `blocks` blocks ahead, 12 seconds each
+fn mine(world: &World, blocks: u64) {
+    for _ in 0..blocks {
+        world.advance(12);
+    }
+}
+
+fn propose(world: &World, proposer: Address, guardian: Address) -> Result<U256, Vec<u8>> {
+    let data = IManager::setGuardianCall { guardian }.abi_encode();
+    let propose = IGovernor::proposeCall { target: MANAGER, data: data.into(), description: "guardian".into() };
+    let id = world.call(proposer, GOVERNOR, propose)?;
+    Ok(IGovernor::proposeCall::abi_decode_returns(&id, true).unwrap()._0)
+}
+
+fn vote(world: &World, voter: Address, id: U256, support: u8) -> Result<Vec<u8>, Vec<u8>> {
+    world.call(voter, GOVERNOR, IGovernor::castVoteCall { id, support })
+}
+
+fn state(world: &World, id: U256) -> u8 {
+    world.view(GOVERNOR, IGovernor::stateCall { id })._0
+}
+
+fn unexpected(state: u8) -> Vec<u8> {
+    UnexpectedState { state }.abi_encode()
+}
+
+#[test]
+fn only_the_deployer_initializes() {
+    let world = World::new();
+    world.chain().deploy::<Governor>(GOVERNOR);
+    // Someone else's token would let them pass anything
+    assert_eq!(world.call(ALICE, GOVERNOR, init(ALICE)).unwrap_err(), OnlyDeployer {}.abi_encode());
+    world.call(DEPLOYER, GOVERNOR, init(GOV_TOKEN)).unwrap();
+    assert_eq!(world.call(DEPLOYER, GOVERNOR, init(GOV_TOKEN)).unwrap_err(), AlreadyInitialized {}.abi_encode());
+}
+
+#[test]
+fn proposals_run_through_the_timelock() {
+    let world = World::new();
+    deploy(&world);
+    let err = propose(&world, CAROL, CAROL).unwrap_err();
+    assert_eq!(err, BelowProposalThreshold { votes: ether(10), threshold: ether(20) }.abi_encode());
+    let id = propose(&world, ALICE, CAROL).unwrap();
+    assert_eq!(state(&world, id), PENDING);
+    assert_eq!(vote(&world, ALICE, id, FOR).unwrap_err(), unexpected(PENDING));
+
+    mine(&world, VOTING_DELAY + 1);
+    assert_eq!(state(&world, id), ACTIVE);
+    vote(&world, ALICE, id, FOR).unwrap();
+    assert_eq!(vote(&world, ALICE, id, AGAINST).unwrap_err(), AlreadyVoted {}.abi_encode());
+    assert_eq!(vote(&world, BOB, id, 3).unwrap_err(), InvalidVoteType {}.abi_encode());
+    vote(&world, BOB, id, AGAINST).unwrap();
+    assert_eq!(world.call(ALICE, GOVERNOR, IGovernor::queueCall { id }).unwrap_err(), unexpected(ACTIVE));
+
+    mine(&world, VOTING_PERIOD);
+    assert_eq!(state(&world, id), SUCCEEDED);
+    assert_eq!(world.call(ALICE, GOVERNOR, IGovernor::executeCall { id }).unwrap_err(), unexpected(SUCCEEDED));
+    world.call(CAROL, GOVERNOR, IGovernor::queueCall { id }).unwrap();
+    assert_eq!(state(&world, id), QUEUED);
+    assert_eq!(world.call(CAROL, GOVERNOR, IGovernor::executeCall { id }).unwrap_err(), NotReady {}.abi_encode());
+
+    world.advance(MIN_DELAY);
+    world.call(CAROL, GOVERNOR, IGovernor::executeCall { id }).unwrap();
+    assert_eq!(state(&world, id), EXECUTED);
+    assert_eq!(world.view(MANAGER, IManager::guardianCall {})._0, CAROL);
+}
+
+#[test]
+fn quorum_and_majority_decide() {
+    let world = World::new();
+    deploy(&world);
+    let quiet = propose(&world, ALICE, BOB).unwrap();
+    let abstained = propose(&world, ALICE, BOB).unwrap();
+    let opposed = propose(&world, ALICE, BOB).unwrap();
+    mine(&world, VOTING_DELAY + 1);
+    let snapshot = U256::from(world.chain().block().0 - 1);
+    assert_eq!(world.view(GOVERNOR, IGovernor::quorumCall { block: snapshot })._0, ether(40));
+
+    // Votes count as held at the snapshot, moving tokens afterwards changes nothing
+    world.call(ALICE, GOV_TOKEN, IGovToken::transferCall { to: CAROL, value: ether(60) }).unwrap();
+    vote(&world, CAROL, quiet, FOR).unwrap();
+    vote(&world, CAROL, abstained, FOR).unwrap();
+    vote(&world, BOB, abstained, ABSTAIN).unwrap();
+    vote(&world, BOB, opposed, FOR).unwrap();
+    vote(&world, ALICE, opposed, AGAINST).unwrap();
+    let tally = world.view(GOVERNOR, IGovernor::proposalVotesCall { id: opposed });
+    assert_eq!((tally._0, tally._1, tally._2), (ether(60), ether(30), U256::ZERO));
+
+    mine(&world, VOTING_PERIOD);
+    // 10 for misses the quorum, abstaining counts towards it, a majority against defeats
+    assert_eq!(state(&world, quiet), DEFEATED);
+    assert_eq!(state(&world, abstained), SUCCEEDED);
+    assert_eq!(state(&world, opposed), DEFEATED);
+    assert_eq!(world.call(ALICE, GOVERNOR, IGovernor::queueCall { id: opposed }).unwrap_err(), unexpected(DEFEATED));
+}
+
+#[test]
+fn overflowing_delays_are_rejected() {
+    let world = World::new();
+    deploy(&world);
+    let delay = IGovernor::setVotingDelayCall { voting_delay: u64::MAX };
+    assert_eq!(world.call(ALICE, GOVERNOR, delay.clone()).unwrap_err(), OnlyTimelock {}.abi_encode());
+    world.call(TIMELOCK, GOVERNOR, delay).unwrap();
+    assert_eq!(propose(&world, ALICE, BOB).unwrap_err(), CouldNotAdd {}.abi_encode());
+}
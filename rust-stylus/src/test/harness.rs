@@ -0,0 +1,359 @@
+//! In-process chain for tests.
+//!
+//! Every contract gets its own [`TestVM`] for storage, and calls between them
+//! are routed through the contracts' routers like they would be on a node, so
+//! `calls::*` reaches the real `ShUSD`, `TestWeth` and `TestOracle`. A call
+//! that reverts rolls back the storage of every contract, and calling back into
+//! a contract already on the stack reverts as Stylus contracts aren't reentrant.
+//! Delegate calls run the target's router on the caller's storage. Of the
+//! precompiles only ecrecover is there.
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::borrow::BorrowMut;
+use core::cell::RefCell;
+use std::collections::HashMap;
+use alloy_primitives::{address, Address, B256, U256};
+use ethers::types::{Signature, H256, U256 as EthersU256};
+use stylus_sdk::abi::{router_entrypoint, Router};
+use stylus_sdk::host::VM;
+use stylus_sdk::storage::StorageType;
+use stylus_sdk::stylus_core::calls::errors::Error;
+use stylus_sdk::stylus_core::calls::{CallAccess, MutatingCallContext, StaticCallContext, ValueTransfer};
+use stylus_sdk::stylus_core::deploy::DeploymentAccess;
+use stylus_sdk::stylus_core::host::*;
+use stylus_sdk::stylus_core::storage::TopLevelStorage;
+use stylus_sdk::stylus_core::ValueDenier;
+use stylus_sdk::testing::TestVM;
+use stylus_sdk::ArbResult;
+
+pub type Dispatch = fn(Vec<u8>, VM) -> ArbResult;
+
+const ECRECOVER: Address = address!("0000000000000000000000000000000000000001");
+
+#[derive(Default)]
+pub struct Chain {
+    contracts: RefCell<HashMap<Address, (TestVM, Dispatch)>>,
+    stack: RefCell<Vec<Address>>,
+    block_number: RefCell<u64>,
+    block_timestamp: RefCell<u64>,
+}
+
+impl Chain {
+    pub fn new() -> Rc<Self> {
+        Rc::new(Self::default())
+    }
+
+    /// Deploys a contract of type `C` at `address`
+    pub fn deploy<C>(&self, address: Address)
+    where
+        C: Router<C, Storage = C> + StorageType + TopLevelStorage + BorrowMut<C> + ValueDenier,
+    {
+        self.deploy_with(address, router_entrypoint::<C, C>);
+    }
+
+    /// Deploys a contract whose entrypoint is `dispatch` rather than its router
+    pub fn deploy_with(&self, address: Address, dispatch: Dispatch) {
+        let vm = TestVM::new();
+        vm.set_contract_address(address);
+        vm.set_block_number(*self.block_number.borrow());
+        vm.set_block_timestamp(*self.block_timestamp.borrow());
+        self.contracts.borrow_mut().insert(address, (vm, dispatch));
+    }
+
+    /// A host acting as the contract at `address`, to read its storage directly
+    pub fn host(self: &Rc<Self>, address: Address) -> ChainHost {
+        let vm = self.contracts.borrow()[&address].0.clone();
+        ChainHost { vm, chain: self.clone() }
+    }
+
+    /// Sends a transaction with `data` from `from` to `to`
+    pub fn call(self: &Rc<Self>, from: Address, to: Address, data: &[u8]) -> Result<Vec<u8>, Vec<u8>> {
+        for (vm, _) in self.contracts.borrow().values() {
+            vm.set_tx_origin(from);
+        }
+        self.route(from, to, data)
+    }
+
+    pub fn set_block(&self, number: u64, timestamp: u64) {
+        *self.block_number.borrow_mut() = number;
+        *self.block_timestamp.borrow_mut() = timestamp;
+        for (vm, _) in self.contracts.borrow().values() {
+            vm.set_block_number(number);
+            vm.set_block_timestamp(timestamp);
+        }
+    }
+
+    pub fn block(&self) -> (u64, u64) {
+        (*self.block_number.borrow(), *self.block_timestamp.borrow())
+    }
+
+    fn route(self: &Rc<Self>, from: Address, to: Address, data: &[u8]) -> Result<Vec<u8>, Vec<u8>> {
+        if to == ECRECOVER {
+            return Ok(ecrecover(data));
+        }
+        let Some((vm, dispatch)) = self.contracts.borrow().get(&to).cloned() else {
+            // Calling an account without code succeeds and returns nothing
+            return Ok(Vec::new());
+        };
+        if self.stack.borrow().contains(&to) {
+            return Err(Vec::new());
+        }
+        let snapshot: Vec<_> = self.contracts.borrow().values().map(|(vm, _)| (vm.clone(), vm.snapshot().storage)).collect();
+        let previous_sender = vm.msg_sender();
+        vm.set_sender(from);
+        self.stack.borrow_mut().push(to);
+        let result = dispatch(data.to_vec(), VM { host: Box::new(ChainHost { vm: vm.clone(), chain: self.clone() }) });
+        self.stack.borrow_mut().pop();
+        vm.set_sender(previous_sender);
+        if result.is_err() {
+            for (vm, storage) in snapshot {
+                restore(&vm, storage);
+            }
+        }
+        result
+    }
+
+    /// Runs the code at `to` as `host`, with its storage, address and sender
+    fn delegate(&self, host: &ChainHost, to: Address, data: &[u8]) -> Result<Vec<u8>, Vec<u8>> {
+        let Some(dispatch) = self.contracts.borrow().get(&to).map(|(_, dispatch)| *dispatch) else {
+            return Ok(Vec::new());
+        };
+        let storage = host.vm.snapshot().storage;
+        let result = dispatch(data.to_vec(), VM { host: Box::new(host.clone()) });
+        if result.is_err() {
+            restore(&host.vm, storage);
+        }
+        result
+    }
+}
+
+fn restore(vm: &TestVM, storage: impl IntoIterator<Item = (U256, B256)>) {
+    vm.clear_storage();
+    for (key, value) in storage {
+        vm.set_storage(key, value);
+    }
+}
+
+/// The ecrecover precompile, the signer as a word or nothing for a bad signature
+fn ecrecover(input: &[u8]) -> Vec<u8> {
+    if input.len() < 128 || input[32..63].iter().any(|byte| *byte != 0) {
+        return Vec::new();
+    }
+    let signature = Signature {
+        r: EthersU256::from_big_endian(&input[64..96]),
+        s: EthersU256::from_big_endian(&input[96..128]),
+        v: u64::from(input[63]),
+    };
+    match signature.recover(H256::from_slice(&input[..32])) {
+        Ok(signer) => B256::left_padding_from(signer.as_bytes()).to_vec(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Host of one contract on a [`Chain`], delegating to its [`TestVM`] apart from calls
+#[derive(Clone)]
+pub struct ChainHost {
+    vm: TestVM,
+    chain: Rc<Chain>,
+}
+
+impl ChainHost {
+    pub fn vm(&self) -> &TestVM {
+        &self.vm
+    }
+}
+
+impl Host for ChainHost {}
+
+impl CallAccess for ChainHost {
+    fn static_call(&self, _context: &dyn StaticCallContext, to: Address, data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.chain.route(self.vm.contract_address(), to, data).map_err(Error::Revert)
+    }
+
+    unsafe fn delegate_call(&self, _context: &dyn MutatingCallContext, to: Address, data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.chain.delegate(self, to, data).map_err(Error::Revert)
+    }
+
+    fn call(&self, _context: &dyn MutatingCallContext, to: Address, data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.chain.route(self.vm.contract_address(), to, data).map_err(Error::Revert)
+    }
+}
+
+impl AccountAccess for ChainHost {
+    fn balance(&self, account: Address) -> U256 {
+        self.vm.balance(account)
+    }
+
+    fn contract_address(&self) -> Address {
+        self.vm.contract_address()
+    }
+
+    fn code(&self, account: Address) -> Vec<u8> {
+        self.vm.code(account)
+    }
+
+    /// Contracts deployed on the chain report a non-zero code size
+    fn code_size(&self, account: Address) -> usize {
+        if self.chain.contracts.borrow().contains_key(&account) { 1 } else { self.vm.code_size(account) }
+    }
+
+    fn code_hash(&self, account: Address) -> B256 {
+        self.vm.code_hash(account)
+    }
+}
+
+impl CryptographyAccess for ChainHost {
+    fn native_keccak256(&self, input: &[u8]) -> B256 {
+        self.vm.native_keccak256(input)
+    }
+}
+
+impl CalldataAccess for ChainHost {
+    fn read_args(&self, len: usize) -> Vec<u8> {
+        self.vm.read_args(len)
+    }
+
+    fn read_return_data(&self, offset: usize, size: Option<usize>) -> Vec<u8> {
+        self.vm.read_return_data(offset, size)
+    }
+
+    fn return_data_size(&self) -> usize {
+        self.vm.return_data_size()
+    }
+
+    fn write_result(&self, data: &[u8]) {
+        self.vm.write_result(data)
+    }
+}
+
+unsafe impl UnsafeDeploymentAccess for ChainHost {
+    unsafe fn create1(&self, code: *const u8, code_len: usize, endowment: *const u8, contract: *mut u8, revert_data_len: *mut usize) {
+        self.vm.create1(code, code_len, endowment, contract, revert_data_len)
+    }
+
+    unsafe fn create2(&self, code: *const u8, code_len: usize, endowment: *const u8, salt: *const u8, contract: *mut u8, revert_data_len: *mut usize) {
+        self.vm.create2(code, code_len, endowment, salt, contract, revert_data_len)
+    }
+}
+
+impl StorageAccess for ChainHost {
+    fn storage_load_bytes32(&self, key: U256) -> B256 {
+        self.vm.storage_load_bytes32(key)
+    }
+
+    unsafe fn storage_cache_bytes32(&self, key: U256, value: B256) {
+        self.vm.storage_cache_bytes32(key, value)
+    }
+
+    fn flush_cache(&self, clear: bool) {
+        self.vm.flush_cache(clear)
+    }
+}
+
+unsafe impl UnsafeCallAccess for ChainHost {
+    unsafe fn call_contract(&self, to: *const u8, data: *const u8, data_len: usize, value: *const u8, gas: u64, outs_len: &mut usize) -> u8 {
+        self.vm.call_contract(to, data, data_len, value, gas, outs_len)
+    }
+
+    unsafe fn static_call_contract(&self, to: *const u8, data: *const u8, data_len: usize, gas: u64, outs_len: &mut usize) -> u8 {
+        self.vm.static_call_contract(to, data, data_len, gas, outs_len)
+    }
+
+    unsafe fn delegate_call_contract(&self, to: *const u8, data: *const u8, data_len: usize, gas: u64, outs_len: &mut usize) -> u8 {
+        self.vm.delegate_call_contract(to, data, data_len, gas, outs_len)
+    }
+}
+
+impl BlockAccess for ChainHost {
+    fn block_basefee(&self) -> U256 {
+        self.vm.block_basefee()
+    }
+
+    fn block_coinbase(&self) -> Address {
+        self.vm.block_coinbase()
+    }
+
+    fn block_number(&self) -> u64 {
+        self.vm.block_number()
+    }
+
+    fn block_timestamp(&self) -> u64 {
+        self.vm.block_timestamp()
+    }
+
+    fn block_gas_limit(&self) -> u64 {
+        self.vm.block_gas_limit()
+    }
+}
+
+impl ChainAccess for ChainHost {
+    fn chain_id(&self) -> u64 {
+        self.vm.chain_id()
+    }
+}
+
+impl MemoryAccess for ChainHost {
+    fn pay_for_memory_grow(&self, pages: u16) {
+        self.vm.pay_for_memory_grow(pages)
+    }
+}
+
+impl MessageAccess for ChainHost {
+    fn msg_sender(&self) -> Address {
+        self.vm.msg_sender()
+    }
+
+    fn msg_reentrant(&self) -> bool {
+        self.vm.msg_reentrant()
+    }
+
+    fn msg_value(&self) -> U256 {
+        self.vm.msg_value()
+    }
+
+    fn tx_origin(&self) -> Address {
+        self.vm.tx_origin()
+    }
+}
+
+impl MeteringAccess for ChainHost {
+    fn evm_gas_left(&self) -> u64 {
+        self.vm.evm_gas_left()
+    }
+
+    fn evm_ink_left(&self) -> u64 {
+        self.vm.evm_ink_left()
+    }
+
+    fn tx_gas_price(&self) -> U256 {
+        self.vm.tx_gas_price()
+    }
+
+    fn tx_ink_price(&self) -> u32 {
+        self.vm.tx_ink_price()
+    }
+}
+
+impl DeploymentAccess for ChainHost {
+    unsafe fn deploy(&self, code: &[u8], endowment: U256, salt: Option<B256>) -> Result<Address, Vec<u8>> {
+        self.vm.deploy(code, endowment, salt)
+    }
+}
+
+impl LogAccess for ChainHost {
+    fn emit_log(&self, input: &[u8], num_topics: usize) {
+        self.vm.emit_log(input, num_topics)
+    }
+
+    fn raw_log(&self, topics: &[B256], data: &[u8]) -> Result<(), &'static str> {
+        self.vm.raw_log(topics, data)
+    }
+}
+
+impl ValueTransfer for ChainHost {
+    fn transfer_eth(&self, to: Address, amount: U256) -> Result<(), Vec<u8>> {
+        self.vm.transfer_eth(to, amount)
+    }
+}
+
@@ -0,0 +1,247 @@
+//! Invariant fuzzing of the `Manager` accounting.
+//!
+//! Random sequences of deposits, mints, burns, withdrawals, liquidations and
+//! price moves run against the contracts on a [`World`], and after every step
+//!
+//! - the shUSD supply equals the debt of all positions plus the bad debt left
+//!   by liquidations and repayments waiting to be burnt,
+//! - the manager holds exactly the WETH deposited into positions,
+//! - a successful mint or withdrawal leaves the position above `MIN_COLLAT_RATIO`.
+//!
+//! A failing sequence is shrunk to a minimal one and printed with its seed.
+//! `INVARIANT_SEED` replays a single seed and `INVARIANT_CASES` sets how many
+//! sequences are run.
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use alloy_primitives::{address, Address, I256, U256};
+use crate::contracts::manager::MIN_COLLAT_RATIO;
+use super::e2e::{ether, usd, IManager, IToken, World, MANAGER, SH_USD, USERS, WETH};
+
+const KEEPER: Address = address!("000000000000000000000000000000000000c0ff");
+
+const CASES: u64 = 64;
+const STEPS: usize = 32;
+
+/// xorshift64*, enough to spread actions around without pulling in a crate
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Action {
+    Deposit(usize, u64),
+    /// Mints up to this percentage of the most the position can borrow
+    Mint(usize, u64),
+    /// Burns this percentage of the position's debt
+    Burn(usize, u64),
+    Withdraw(usize, u64),
+    /// The keeper liquidates this user
+    Liquidate(usize),
+    /// Moves the price to this percentage of where it is
+    Price(u64),
+}
+
+/// Amounts are in hundredths of a token to keep sequences readable
+fn amount(hundredths: u64) -> U256 {
+    ether(hundredths) / U256::from(100)
+}
+
+impl fmt::Debug for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let user = |index: usize| ["alice", "bob", "carol"][index];
+        let tokens = |hundredths: u64| format!("{}.{:02}", hundredths / 100, hundredths % 100);
+        match *self {
+            Action::Deposit(u, a) => write!(f, "{} deposits {} WETH", user(u), tokens(a)),
+            Action::Mint(u, p) => write!(f, "{} mints up to {}% of the limit", user(u), p),
+            Action::Burn(u, p) => write!(f, "{} burns {}% of the debt", user(u), p),
+            Action::Withdraw(u, a) => write!(f, "{} withdraws {} WETH", user(u), tokens(a)),
+            Action::Liquidate(u) => write!(f, "keeper liquidates {}", user(u)),
+            Action::Price(p) => write!(f, "price moves to {}%", p),
+        }
+    }
+}
+
+impl Action {
+    fn random(rng: &mut Rng) -> Self {
+        let user = rng.below(USERS.len() as u64) as usize;
+        match rng.below(13) {
+            0..=2 => Action::Deposit(user, 1 + rng.below(500)),
+            3..=5 => Action::Mint(user, 60 + rng.below(46)),
+            6 | 7 => Action::Burn(user, 1 + rng.below(100)),
+            8 | 9 => Action::Withdraw(user, 1 + rng.below(300)),
+            10 | 11 => Action::Liquidate(user),
+            _ => Action::Price(55 + rng.below(70)),
+        }
+    }
+
+    /// Smaller versions of this action to try while shrinking
+    fn simpler(&self) -> Vec<Action> {
+        let halve = |a: u64| if a > 1 { Some(a / 2) } else { None };
+        match *self {
+            Action::Deposit(u, a) => halve(a).map(|a| Action::Deposit(u, a)).into_iter().collect(),
+            Action::Mint(u, a) => halve(a).map(|a| Action::Mint(u, a)).into_iter().collect(),
+            Action::Burn(u, a) => halve(a).map(|a| Action::Burn(u, a)).into_iter().collect(),
+            Action::Withdraw(u, a) => halve(a).map(|a| Action::Withdraw(u, a)).into_iter().collect(),
+            Action::Liquidate(..) => Vec::new(),
+            Action::Price(p) => Some((p + 100) / 2).filter(|&q| q != p).map(Action::Price).into_iter().collect(),
+        }
+    }
+}
+
+/// Runs `actions` on a fresh world, returns the first broken invariant
+fn run(actions: &[Action]) -> Option<(usize, String)> {
+    let world = World::new();
+    let mut price = usd(1757);
+    world.set_price(price);
+    // The keeper holds enough shUSD to liquidate anyone
+    world.call(MANAGER, WETH, IToken::mintCall { to: KEEPER, amount: ether(1000) }).unwrap();
+    world.call(KEEPER, WETH, IToken::approveCall { spender: MANAGER, value: U256::MAX }).unwrap();
+    world.deposit(KEEPER, ether(1000)).unwrap();
+    world.mint(KEEPER, ether(300_000)).unwrap();
+    for (step, action) in actions.iter().enumerate() {
+        let user = |index: usize| USERS[index];
+        let (acted, result) = match *action {
+            Action::Deposit(u, a) => (None, world.deposit(user(u), amount(a))),
+            Action::Mint(u, p) => {
+                let position = world.view(MANAGER, IManager::positionCall { user: user(u) });
+                // Collateral value at 8 decimals over 1.5, so debt at the limit
+                let limit = position._0 * U256::from(price.into_raw()) / U256::from(150_000_000u64);
+                let amount = (limit * U256::from(p) / U256::from(100)).saturating_sub(position._1).max(U256::from(1));
+                (Some(user(u)), world.mint(user(u), amount))
+            }
+            Action::Burn(u, p) => {
+                let debt = world.view(MANAGER, IManager::positionCall { user: user(u) })._1;
+                (None, world.call(user(u), MANAGER, IManager::burnCall { amount: debt * U256::from(p) / U256::from(100) }))
+            }
+            Action::Withdraw(u, a) => (Some(user(u)), world.call(user(u), MANAGER, IManager::withdrawCall { amount: amount(a) })),
+            Action::Liquidate(u) => (None, world.call(KEEPER, MANAGER, IManager::liquidateCall { user: user(u) })),
+            Action::Price(p) => {
+                price = (price * I256::try_from(p).unwrap() / I256::try_from(100).unwrap()).max(usd(1));
+                world.set_price(price);
+                (None, Ok(Vec::new()))
+            }
+        };
+        if let Err(message) = check(&world, acted.filter(|_| result.is_ok())) {
+            return Some((step, message));
+        }
+    }
+    None
+}
+
+fn check(world: &World, acted: Option<Address>) -> Result<(), String> {
+    let mut deposited = U256::ZERO;
+    let mut minted = U256::ZERO;
+    for user in USERS.into_iter().chain([KEEPER]) {
+        let position = world.view(MANAGER, IManager::positionCall { user });
+        deposited += position._0;
+        minted += position._1;
+    }
+
+    let supply = world.view(SH_USD, IToken::totalSupplyCall {})._0;
+    let bad_debt = world.view(MANAGER, IManager::badDebtCall {})._0;
+    let repaid = world.view(MANAGER, IManager::repaidCall {})._0;
+    if supply != minted + bad_debt + repaid {
+        return Err(format!("shUSD supply {supply} != minted {minted} + bad debt {bad_debt} + repaid {repaid}"));
+    }
+
+    let held = world.balance(WETH, MANAGER);
+    if held != deposited {
+        return Err(format!("manager holds {held} WETH but {deposited} is deposited"));
+    }
+
+    if let Some(user) = acted {
+        let ratio = world.ratio(user);
+        if ratio <= U256::from(MIN_COLLAT_RATIO) {
+            return Err(format!("{user} left at ratio {ratio}"));
+        }
+    }
+    Ok(())
+}
+
+/// Shrinks a failing sequence, first dropping actions then simplifying them,
+/// for as long as `fails` still holds
+fn shrink(mut actions: Vec<Action>, fails: impl Fn(&[Action]) -> bool) -> Vec<Action> {
+    loop {
+        let mut progressed = false;
+        let mut i = 0;
+        while i < actions.len() {
+            let mut candidate = actions.clone();
+            candidate.remove(i);
+            if fails(&candidate) {
+                actions = candidate;
+                progressed = true;
+            } else {
+                i += 1;
+            }
+        }
+        for i in 0..actions.len() {
+            for simpler in actions[i].simpler() {
+                let mut candidate = actions.clone();
+                candidate[i] = simpler;
+                if fails(&candidate) {
+                    actions = candidate;
+                    progressed = true;
+                    break;
+                }
+            }
+        }
+        if !progressed {
+            return actions;
+        }
+    }
+}
+
+fn env(name: &str) -> Option<u64> {
+    std::env::var(name).ok().and_then(|value| value.parse().ok())
+}
+
+#[test]
+fn manager_accounting_invariants() {
+    let seeds: Vec<u64> = match env("INVARIANT_SEED") {
+        Some(seed) => vec![seed],
+        None => (0..env("INVARIANT_CASES").unwrap_or(CASES)).collect(),
+    };
+    for seed in seeds {
+        let mut rng = Rng::new(seed);
+        let actions: Vec<Action> = (0..STEPS).map(|_| Action::random(&mut rng)).collect();
+        if run(&actions).is_some() {
+            let minimal = shrink(actions, |actions| run(actions).is_some());
+            let (step, message) = run(&minimal).unwrap();
+            panic!("invariant broken with seed {seed} at step {step}: {message}\n{minimal:#?}");
+        }
+    }
+}
+
+#[test]
+fn shrinking_keeps_the_failure() {
+    let actions = vec![
+        Action::Deposit(0, 100),
+        Action::Price(70),
+        Action::Mint(1, 4000),
+        Action::Burn(2, 10),
+        Action::Mint(1, 300),
+    ];
+    // Fails whenever bob mints more than 10 shUSD in total
+    let fails = |actions: &[Action]| {
+        actions.iter().map(|action| match action { Action::Mint(1, a) => *a, _ => 0 }).sum::<u64>() > 1000
+    };
+    assert_eq!(shrink(actions, fails), vec![Action::Mint(1, 2000)]);
+}
@@ -0,0 +1,82 @@
+//! The keeper's book and off-chain math against the real `Manager` on a [`World`].
+use alloy_primitives::{address, Address, U256};
+use alloy_sol_types::SolEvent;
+use crate::client::Position;
+use crate::contracts::manager::{PositionUpdated, MIN_COLLAT_RATIO};
+use crate::keeper::book::{self, Book};
+use super::e2e::{ether, usd, IManager, IToken, World, ALICE, BOB, CAROL, MANAGER, SH_USD, WETH};
+
+const KEEPER: Address = address!("000000000000000000000000000000000000c0ff");
+
+/// Replays every `PositionUpdated` the manager emitted so far
+fn book(world: &World) -> Book {
+    let mut book = Book::default();
+    for (topics, data) in world.logs(MANAGER) {
+        if topics.first() == Some(&PositionUpdated::SIGNATURE_HASH) {
+            book.apply(&PositionUpdated::decode_raw_log(topics, &data, true).unwrap());
+        }
+    }
+    book
+}
+
+fn position(world: &World, user: Address) -> Position {
+    let position = world.view(MANAGER, IManager::positionCall { user });
+    Position { deposited: position._0, minted: position._1 }
+}
+
+#[test]
+fn book_and_ratios_match_the_manager() {
+    let world = World::new();
+    world.call(MANAGER, WETH, IToken::mintCall { to: KEEPER, amount: ether(100) }).unwrap();
+    world.call(KEEPER, WETH, IToken::approveCall { spender: MANAGER, value: U256::MAX }).unwrap();
+    world.deposit(KEEPER, ether(100)).unwrap();
+    world.mint(KEEPER, ether(50_000)).unwrap();
+
+    world.deposit(ALICE, ether(2)).unwrap();
+    world.mint(ALICE, ether(2000)).unwrap();
+    world.deposit(BOB, ether(3)).unwrap();
+    world.mint(BOB, ether(1500)).unwrap();
+    world.call(BOB, MANAGER, IManager::burnCall { amount: ether(500) }).unwrap();
+    world.deposit(CAROL, ether(1)).unwrap();
+    world.call(CAROL, MANAGER, IManager::withdrawCall { amount: ether(1) }).unwrap();
+
+    let book = book(&world);
+    assert_eq!(book.get(CAROL), None);
+    for user in [ALICE, BOB, KEEPER] {
+        assert_eq!(book.get(user), Some(position(&world, user)));
+    }
+
+    for price in [1757, 1400, 1200, 900, 600, 300] {
+        world.set_price(usd(price));
+        let at = book::price(usd(price)).unwrap();
+        let liquidatable: Vec<Address> = book.liquidatable(at).iter().map(|candidate| candidate.user).collect();
+        for user in [ALICE, BOB, KEEPER] {
+            let ratio = world.ratio(user);
+            assert_eq!(book::collat_ratio(&book.get(user).unwrap(), at), ratio, "{user} at ${price}");
+            assert_eq!(liquidatable.contains(&user), ratio <= U256::from(MIN_COLLAT_RATIO), "{user} at ${price}");
+        }
+    }
+}
+
+#[test]
+fn proceeds_match_a_liquidation() {
+    let world = World::new();
+    world.call(MANAGER, WETH, IToken::mintCall { to: KEEPER, amount: ether(100) }).unwrap();
+    world.call(KEEPER, WETH, IToken::approveCall { spender: MANAGER, value: U256::MAX }).unwrap();
+    world.deposit(KEEPER, ether(100)).unwrap();
+    world.mint(KEEPER, ether(50_000)).unwrap();
+    world.deposit(ALICE, ether(2)).unwrap();
+    world.mint(ALICE, ether(2000)).unwrap();
+
+    // $1400 puts alice at 1.4, under the minimum but with collateral to spare
+    world.set_price(usd(1400));
+    let at = book::price(usd(1400)).unwrap();
+    let candidate = book(&world).liquidatable(at).into_iter().find(|candidate| candidate.user == ALICE).unwrap();
+
+    let (weth, sh_usd) = (world.balance(WETH, KEEPER), world.balance(SH_USD, KEEPER));
+    world.call(KEEPER, MANAGER, IManager::liquidateCall { user: ALICE }).unwrap();
+    let received = book::collateral_value(world.balance(WETH, KEEPER) - weth, at);
+    let burnt = sh_usd - world.balance(SH_USD, KEEPER);
+    assert_eq!(received - burnt, candidate.proceeds);
+    assert_eq!(book(&world).get(ALICE), None);
+}
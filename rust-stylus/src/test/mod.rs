@@ -1,5 +1,38 @@
-#[cfg(feature = "test-oracle")]
+#[cfg(any(feature = "test-oracle", test))]
 pub mod test_oracle;
 
-#[cfg(feature = "test-weth")]
+#[cfg(any(feature = "test-weth", test))]
 pub mod test_weth;
+
+#[cfg(any(feature = "test-usdc", test))]
+pub mod test_usdc;
+
+#[cfg(any(feature = "test-pool", test))]
+pub mod test_pool;
+
+#[cfg(test)]
+pub mod harness;
+#[cfg(test)]
+mod e2e;
+#[cfg(test)]
+mod forwarder;
+#[cfg(test)]
+mod gov_token;
+#[cfg(test)]
+mod governor;
+#[cfg(test)]
+mod invariants;
+#[cfg(test)]
+mod keeper;
+#[cfg(test)]
+mod proxy;
+#[cfg(test)]
+mod psm;
+#[cfg(test)]
+mod sh_usd;
+#[cfg(test)]
+mod timelock;
+#[cfg(test)]
+mod transfer_and_call;
+#[cfg(test)]
+mod twap;
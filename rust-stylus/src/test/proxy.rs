@@ -0,0 +1,93 @@
+//! A `Manager` behind the `Erc1967Proxy` of a [`World`], upgraded in place.
+use alloy_primitives::{address, Address, U256};
+use alloy_sol_types::{sol, SolCall, SolError};
+use crate::access::access_control::{BURNER_ROLE, MINTER_ROLE};
+use crate::client::sh_usd::IShUSD;
+use crate::contracts::manager::{self, NotUpgradeable, OnlyGovernance, UnauthorizedCallContext};
+use crate::contracts::proxy::{self, Erc1967Proxy, ProxyAlreadyInitialized};
+use super::e2e::{ether, IManager, IToken, World, ALICE, DEPLOYER, GOVERNANCE, ORACLE, SH_USD, WETH};
+
+// Only `#[entrypoint]` marks the proxy as top level storage
+#[cfg(not(feature = "proxy"))]
+unsafe impl stylus_sdk::stylus_core::storage::TopLevelStorage for Erc1967Proxy {}
+
+sol! {
+    interface IProxy {
+        function initProxy(address implementation_address, bytes data) external;
+    }
+}
+
+const PROXY: Address = address!("000000000000000000000000000000000000a009");
+const IMPLEMENTATION: Address = address!("000000000000000000000000000000000000a00a");
+const NEXT: Address = address!("000000000000000000000000000000000000a00b");
+
+/// The world with a second manager behind `PROXY`, ALICE has approved it
+fn world() -> World {
+    let world = World::new();
+    world.chain().deploy::<Erc1967Proxy>(PROXY);
+    world.chain().deploy_with(IMPLEMENTATION, manager::route);
+    world.chain().deploy_with(NEXT, manager::route);
+    let data = IManager::initializeCall { weth: WETH, oracle: ORACLE, sh_usd: SH_USD, governance: GOVERNANCE }.abi_encode();
+    world.call(DEPLOYER, PROXY, IProxy::initProxyCall { implementation_address: IMPLEMENTATION, data: data.into() }).unwrap();
+    for role in [MINTER_ROLE, BURNER_ROLE] {
+        world.call(DEPLOYER, SH_USD, IShUSD::grantRoleCall { role, account: PROXY }).unwrap();
+    }
+    world.call(ALICE, WETH, IToken::approveCall { spender: PROXY, value: U256::MAX }).unwrap();
+    world
+}
+
+fn implementation(world: &World) -> Address {
+    proxy::implementation(world.chain().host(PROXY).vm())
+}
+
+#[test]
+fn initializes_through_the_proxy_once() {
+    let world = world();
+    assert_eq!(implementation(&world), IMPLEMENTATION);
+    let err = world.call(DEPLOYER, PROXY, IProxy::initProxyCall { implementation_address: NEXT, data: Default::default() }).unwrap_err();
+    assert_eq!(err, ProxyAlreadyInitialized {}.abi_encode());
+
+    // The state is the proxy's, the implementation itself was never initialized
+    assert_eq!(world.view(PROXY, IManager::governanceCall {})._0, GOVERNANCE);
+    assert_eq!(world.view(IMPLEMENTATION, IManager::governanceCall {})._0, Address::ZERO);
+}
+
+#[test]
+fn storage_survives_an_upgrade() {
+    let world = world();
+    world.call(ALICE, PROXY, IManager::depositCall { amount: ether(1) }).unwrap();
+    world.call(ALICE, PROXY, IManager::mintCall { amount: ether(1000) }).unwrap();
+
+    world.call(GOVERNANCE, PROXY, IManager::upgradeToCall { new_implementation: NEXT }).unwrap();
+    assert_eq!(implementation(&world), NEXT);
+    let position = world.view(PROXY, IManager::positionCall { user: ALICE });
+    assert_eq!((position._0, position._1), (ether(1), ether(1000)));
+    assert_eq!(world.view(PROXY, IManager::governanceCall {})._0, GOVERNANCE);
+
+    // and the new implementation keeps working on it
+    world.call(ALICE, PROXY, IManager::burnCall { amount: ether(400) }).unwrap();
+    assert_eq!(world.view(PROXY, IManager::positionCall { user: ALICE })._1, ether(600));
+    assert_eq!(world.balance(SH_USD, ALICE), ether(600));
+    assert_eq!(world.balance(WETH, PROXY), ether(1));
+}
+
+#[test]
+fn rejects_bad_upgrades() {
+    let world = world();
+    let upgrade = |from, new_implementation| world.call(from, PROXY, IManager::upgradeToCall { new_implementation });
+
+    assert_eq!(upgrade(ALICE, NEXT).unwrap_err(), OnlyGovernance {}.abi_encode());
+    // Neither a token, an account without code nor another proxy report the UUID
+    for target in [WETH, ALICE, PROXY] {
+        assert_eq!(upgrade(GOVERNANCE, target).unwrap_err(), NotUpgradeable {}.abi_encode());
+    }
+    assert_eq!(implementation(&world), IMPLEMENTATION);
+
+    // The implementation only upgrades and reports its UUID in the right context
+    let err = world.call(GOVERNANCE, IMPLEMENTATION, IManager::upgradeToCall { new_implementation: NEXT }).unwrap_err();
+    assert_eq!(err, UnauthorizedCallContext {}.abi_encode());
+    assert!(proxy::implementation(world.chain().host(IMPLEMENTATION).vm()).is_zero());
+    let err = world.call(ALICE, PROXY, IManager::proxiableUUIDCall {}).unwrap_err();
+    assert_eq!(err, UnauthorizedCallContext {}.abi_encode());
+    assert_eq!(world.view(IMPLEMENTATION, IManager::proxiableUUIDCall {})._0, proxy::proxiable_uuid());
+}
@@ -0,0 +1,100 @@
+//! The `Psm` swapping `TestUsdc` and the real `ShUSD` on a [`World`].
+use alloy_primitives::{Address, U256};
+use alloy_sol_types::{sol, SolCall, SolError};
+use crate::contracts::psm::{AlreadyInitialized, DebtCeilingExceeded, FeeTooHigh, InsufficientReserves, OnlyGovernance};
+use super::e2e::{ether, IToken, World, ALICE, BOB, CAROL, GOVERNANCE, PSM, SH_USD, USDC};
+
+sol! {
+    interface IPsm {
+        function init(address usdc, address sh_usd, address governance) external;
+        function sellUsdc(uint256 amount) external returns (uint256);
+        function buyUsdc(uint256 amount) external returns (uint256);
+        function setFees(uint256 tin, uint256 tout) external;
+        function setDebtCeiling(uint256 debt_ceiling) external;
+        function collectFees(address to) external returns (uint256);
+        function reserves() external view returns (uint256);
+        function debt() external view returns (uint256);
+        function fees() external view returns (uint256);
+    }
+}
+
+/// USDC has 6 decimals
+fn usdc(amount: u64) -> U256 {
+    U256::from(amount) * U256::from(1_000_000)
+}
+
+/// Opens the PSM up to `ceiling` shUSD and gives `user` USDC to swap
+fn open(world: &World, ceiling: U256, user: Address, amount: U256) {
+    world.call(GOVERNANCE, PSM, IPsm::setDebtCeilingCall { debt_ceiling: ceiling }).unwrap();
+    world.call(GOVERNANCE, USDC, IToken::mintCall { to: user, amount }).unwrap();
+    for token in [USDC, SH_USD] {
+        world.call(user, token, IToken::approveCall { spender: PSM, value: U256::MAX }).unwrap();
+    }
+}
+
+fn supply(world: &World) -> U256 {
+    world.view(SH_USD, IToken::totalSupplyCall {})._0
+}
+
+#[test]
+fn swaps_at_par_less_fees() {
+    let world = World::new();
+    open(&world, ether(1000), ALICE, usdc(500));
+    // 0.1% in, 0.2% out
+    let (tin, tout) = (ether(1) / U256::from(1000), ether(1) / U256::from(500));
+    world.call(GOVERNANCE, PSM, IPsm::setFeesCall { tin, tout }).unwrap();
+
+    let out = world.call(ALICE, PSM, IPsm::sellUsdcCall { amount: usdc(500) }).unwrap();
+    assert_eq!(IPsm::sellUsdcCall::abi_decode_returns(&out, true).unwrap()._0, ether(4995) / U256::from(10));
+    assert_eq!(world.balance(SH_USD, ALICE), ether(4995) / U256::from(10));
+    assert_eq!(world.balance(USDC, PSM), usdc(500));
+    assert_eq!(world.view(PSM, IPsm::reservesCall {})._0, usdc(500));
+    assert_eq!(world.view(PSM, IPsm::debtCall {})._0, ether(500));
+    assert_eq!(supply(&world), ether(500));
+
+    let paid = world.call(ALICE, PSM, IPsm::buyUsdcCall { amount: usdc(100) }).unwrap();
+    assert_eq!(IPsm::buyUsdcCall::abi_decode_returns(&paid, true).unwrap()._0, ether(1002) / U256::from(10));
+    assert_eq!(world.balance(USDC, ALICE), usdc(100));
+    assert_eq!(world.view(PSM, IPsm::reservesCall {})._0, usdc(400));
+    // Only the swapped value is burnt, the fees stay in the PSM and back the supply with the reserves
+    assert_eq!(world.view(PSM, IPsm::feesCall {})._0, ether(7) / U256::from(10));
+    assert_eq!(supply(&world), ether(400));
+    assert_eq!(world.view(PSM, IPsm::debtCall {})._0, ether(400));
+
+    world.call(GOVERNANCE, PSM, IPsm::collectFeesCall { to: CAROL }).unwrap();
+    assert_eq!(world.balance(SH_USD, CAROL), ether(7) / U256::from(10));
+    assert_eq!(world.view(PSM, IPsm::feesCall {})._0, U256::ZERO);
+
+    // The smallest unit of USDC is worth 1e12 shUSD
+    world.call(ALICE, PSM, IPsm::sellUsdcCall { amount: U256::from(1) }).unwrap();
+    assert_eq!(world.view(PSM, IPsm::feesCall {})._0, U256::from(1_000_000_000u64));
+}
+
+#[test]
+fn ceiling_reserves_and_governance_bound_the_psm() {
+    let world = World::new();
+    open(&world, ether(100), BOB, usdc(200));
+
+    let err = world.call(BOB, PSM, IPsm::sellUsdcCall { amount: usdc(101) }).unwrap_err();
+    assert_eq!(err, DebtCeilingExceeded { debt_ceiling: ether(100), debt: ether(101) }.abi_encode());
+    world.call(BOB, PSM, IPsm::sellUsdcCall { amount: usdc(100) }).unwrap();
+    assert_eq!(world.balance(SH_USD, BOB), ether(100));
+
+    // shUSD minted by the manager can't drain more USDC than was put in
+    let err = world.call(BOB, PSM, IPsm::buyUsdcCall { amount: usdc(101) }).unwrap_err();
+    assert_eq!(err, InsufficientReserves { reserves: usdc(100), requested: usdc(101) }.abi_encode());
+
+    // A lower ceiling only stops new sales
+    world.call(GOVERNANCE, PSM, IPsm::setDebtCeilingCall { debt_ceiling: U256::ZERO }).unwrap();
+    assert!(world.call(BOB, PSM, IPsm::sellUsdcCall { amount: usdc(1) }).is_err());
+    world.call(BOB, PSM, IPsm::buyUsdcCall { amount: usdc(100) }).unwrap();
+    assert_eq!(world.balance(USDC, BOB), usdc(200));
+    assert_eq!(supply(&world), U256::ZERO);
+
+    let err = world.call(BOB, PSM, IPsm::setFeesCall { tin: U256::ZERO, tout: U256::ZERO }).unwrap_err();
+    assert_eq!(err, OnlyGovernance {}.abi_encode());
+    let err = world.call(GOVERNANCE, PSM, IPsm::setFeesCall { tin: ether(1) + U256::from(1), tout: U256::ZERO }).unwrap_err();
+    assert_eq!(err, FeeTooHigh {}.abi_encode());
+    let err = world.call(BOB, PSM, IPsm::initCall { usdc: USDC, sh_usd: BOB, governance: BOB }).unwrap_err();
+    assert_eq!(err, AlreadyInitialized {}.abi_encode());
+}
@@ -0,0 +1,216 @@
+//! Roles, minter caps, pausing and compliance of the real `ShUSD` on a [`World`].
+use alloc::vec::Vec;
+use alloy_primitives::{Address, B256, U256};
+use alloy_sol_types::{SolError, SolEvent};
+use crate::access::access_control::{
+    AccessControlUnauthorizedAccount, ADMIN_ROLE, BURNER_ROLE, COMPLIANCE_ROLE, GOVERNANCE_ROLE, MINTER_ROLE, PAUSER_ROLE,
+};
+use crate::client::sh_usd::IShUSD;
+use crate::client::manager::IManager;
+use crate::contracts::sh_usd::{
+    AlreadyInitialized, Frozen, InvalidTimelock, IssuancePaused, MinterCapExceeded, NotFrozen, OnlyTimelock, Seized, Unfrozen, Wiped,
+};
+use crate::token::erc20::AccountFrozen;
+use super::e2e::{ether, World, ALICE, BOB, CAROL, DEPLOYER, MANAGER, SH_USD};
+use super::timelock::{self, TIMELOCK};
+
+fn unauthorized(account: Address, needed_role: B256) -> Vec<u8> {
+    AccessControlUnauthorizedAccount { account, needed_role }.abi_encode()
+}
+
+#[test]
+fn init_runs_once() {
+    let world = World::new();
+    let err = world.call(DEPLOYER, SH_USD, IShUSD::initCall { manager: ALICE }).unwrap_err();
+    assert_eq!(err, AlreadyInitialized {}.abi_encode());
+
+    for (role, account) in [(ADMIN_ROLE, DEPLOYER), (GOVERNANCE_ROLE, DEPLOYER), (MINTER_ROLE, MANAGER), (BURNER_ROLE, MANAGER)] {
+        assert!(world.view(SH_USD, IShUSD::hasRoleCall { role, account })._0);
+    }
+    assert!(!world.view(SH_USD, IShUSD::hasRoleCall { role: PAUSER_ROLE, account: DEPLOYER })._0);
+}
+
+#[test]
+fn admin_grants_and_revokes_roles() {
+    let world = World::new();
+    let has_role = |role, account| world.view(SH_USD, IShUSD::hasRoleCall { role, account })._0;
+
+    let err = world.call(ALICE, SH_USD, IShUSD::mintCall { to: ALICE, amount: ether(1) }).unwrap_err();
+    assert_eq!(err, unauthorized(ALICE, MINTER_ROLE));
+    let err = world.call(BOB, SH_USD, IShUSD::grantRoleCall { role: MINTER_ROLE, account: ALICE }).unwrap_err();
+    assert_eq!(err, unauthorized(BOB, ADMIN_ROLE));
+
+    world.call(DEPLOYER, SH_USD, IShUSD::grantRoleCall { role: MINTER_ROLE, account: ALICE }).unwrap();
+    assert!(has_role(MINTER_ROLE, ALICE));
+    world.call(ALICE, SH_USD, IShUSD::mintCall { to: BOB, amount: ether(5) }).unwrap();
+    assert_eq!(world.balance(SH_USD, BOB), ether(5));
+
+    let err = world.call(BOB, SH_USD, IShUSD::revokeRoleCall { role: MINTER_ROLE, account: ALICE }).unwrap_err();
+    assert_eq!(err, unauthorized(BOB, ADMIN_ROLE));
+    world.call(DEPLOYER, SH_USD, IShUSD::revokeRoleCall { role: MINTER_ROLE, account: ALICE }).unwrap();
+    assert!(!has_role(MINTER_ROLE, ALICE));
+    let err = world.call(ALICE, SH_USD, IShUSD::mintCall { to: BOB, amount: ether(5) }).unwrap_err();
+    assert_eq!(err, unauthorized(ALICE, MINTER_ROLE));
+
+    // Governance is a role like any other, handing it over takes the old holder's rights away
+    world.call(DEPLOYER, SH_USD, IShUSD::grantRoleCall { role: GOVERNANCE_ROLE, account: CAROL }).unwrap();
+    world.call(DEPLOYER, SH_USD, IShUSD::renounceRoleCall { role: GOVERNANCE_ROLE }).unwrap();
+    let err = world.call(DEPLOYER, SH_USD, IShUSD::setTrustedForwarderCall { forwarder: BOB }).unwrap_err();
+    assert_eq!(err, unauthorized(DEPLOYER, GOVERNANCE_ROLE));
+    world.call(CAROL, SH_USD, IShUSD::setTrustedForwarderCall { forwarder: BOB }).unwrap();
+    assert_eq!(world.view(SH_USD, IShUSD::trustedForwarderCall {})._0, BOB);
+}
+
+#[test]
+fn minter_caps_bound_what_is_outstanding() {
+    let world = World::new();
+    for role in [MINTER_ROLE, BURNER_ROLE] {
+        world.call(DEPLOYER, SH_USD, IShUSD::grantRoleCall { role, account: ALICE }).unwrap();
+    }
+    let err = world.call(ALICE, SH_USD, IShUSD::setMinterCapCall { minter: ALICE, cap: ether(100) }).unwrap_err();
+    assert_eq!(err, unauthorized(ALICE, ADMIN_ROLE));
+    world.call(DEPLOYER, SH_USD, IShUSD::setMinterCapCall { minter: ALICE, cap: ether(100) }).unwrap();
+    assert_eq!(world.view(SH_USD, IShUSD::minterCapCall { minter: ALICE })._0, ether(100));
+
+    world.call(ALICE, SH_USD, IShUSD::mintCall { to: BOB, amount: ether(60) }).unwrap();
+    let err = world.call(ALICE, SH_USD, IShUSD::mintCall { to: BOB, amount: ether(41) }).unwrap_err();
+    assert_eq!(err, MinterCapExceeded { minter: ALICE, cap: ether(100), requested: ether(41) }.abi_encode());
+    world.call(ALICE, SH_USD, IShUSD::mintCall { to: BOB, amount: ether(40) }).unwrap();
+    assert_eq!(world.view(SH_USD, IShUSD::mintedByCall { minter: ALICE })._0, ether(100));
+
+    // Burning frees the cap up again
+    world.call(ALICE, SH_USD, IShUSD::burnCall { from: BOB, amount: ether(30) }).unwrap();
+    assert_eq!(world.view(SH_USD, IShUSD::mintedByCall { minter: ALICE })._0, ether(70));
+    world.call(ALICE, SH_USD, IShUSD::mintCall { to: BOB, amount: ether(30) }).unwrap();
+    assert_eq!(world.balance(SH_USD, BOB), ether(100));
+
+    // Other minters aren't affected, zero is no cap at all
+    world.deposit(CAROL, ether(10)).unwrap();
+    world.mint(CAROL, ether(10000)).unwrap();
+    assert_eq!(world.view(SH_USD, IShUSD::minterCapCall { minter: MANAGER })._0, U256::ZERO);
+}
+
+#[test]
+fn pause_stops_issuance_but_not_repayments() {
+    let world = World::new();
+    world.deposit(ALICE, ether(1)).unwrap();
+    world.mint(ALICE, ether(1000)).unwrap();
+
+    let err = world.call(DEPLOYER, SH_USD, IShUSD::pauseCall {}).unwrap_err();
+    assert_eq!(err, unauthorized(DEPLOYER, PAUSER_ROLE));
+    world.call(DEPLOYER, SH_USD, IShUSD::grantRoleCall { role: PAUSER_ROLE, account: BOB }).unwrap();
+    world.call(BOB, SH_USD, IShUSD::pauseCall {}).unwrap();
+    assert!(world.view(SH_USD, IShUSD::pausedCall {})._0);
+
+    assert_eq!(world.mint(ALICE, ether(1)).unwrap_err(), IssuancePaused {}.abi_encode());
+    // Repaying, and so liquidating, still burns
+    world.call(ALICE, MANAGER, IManager::burnCall { amount: ether(1) }).unwrap();
+    world.call(ALICE, SH_USD, IShUSD::transferCall { to: CAROL, value: ether(100) }).unwrap();
+    assert_eq!(world.balance(SH_USD, CAROL), ether(100));
+
+    world.call(BOB, SH_USD, IShUSD::unpauseCall {}).unwrap();
+    world.mint(ALICE, ether(1)).unwrap();
+    assert_eq!(world.balance(SH_USD, ALICE), ether(900));
+}
+
+/// ALICE has 1000 shUSD and is frozen
+fn frozen_alice() -> World {
+    let world = World::new();
+    world.deposit(ALICE, ether(1)).unwrap();
+    world.mint(ALICE, ether(1000)).unwrap();
+    world.call(DEPLOYER, SH_USD, IShUSD::freezeCall { account: ALICE }).unwrap();
+    world
+}
+
+/// Hands governance over to the timelock, which shUSD knows as its timelock
+fn govern_by_timelock(world: &World) {
+    timelock::deploy(world);
+    world.call(DEPLOYER, SH_USD, IShUSD::setTimelockCall { timelock: TIMELOCK }).unwrap();
+    world.call(DEPLOYER, SH_USD, IShUSD::grantRoleCall { role: GOVERNANCE_ROLE, account: TIMELOCK }).unwrap();
+}
+
+#[test]
+fn frozen_accounts_cannot_move_shusd() {
+    let world = World::new();
+    world.deposit(ALICE, ether(1)).unwrap();
+    world.mint(ALICE, ether(1000)).unwrap();
+    let err = world.call(BOB, SH_USD, IShUSD::freezeCall { account: ALICE }).unwrap_err();
+    assert_eq!(err, unauthorized(BOB, COMPLIANCE_ROLE));
+    world.call(DEPLOYER, SH_USD, IShUSD::freezeCall { account: ALICE }).unwrap();
+    assert!(world.view(SH_USD, IShUSD::isFrozenCall { account: ALICE })._0);
+    let frozen = AccountFrozen { account: ALICE }.abi_encode();
+
+    // Neither sending, receiving, minting nor repaying
+    assert_eq!(world.call(ALICE, SH_USD, IShUSD::transferCall { to: BOB, value: ether(1) }).unwrap_err(), frozen);
+    world.deposit(BOB, ether(1)).unwrap();
+    world.mint(BOB, ether(10)).unwrap();
+    assert_eq!(world.call(BOB, SH_USD, IShUSD::transferCall { to: ALICE, value: ether(1) }).unwrap_err(), frozen);
+    world.call(ALICE, SH_USD, IShUSD::approveCall { spender: BOB, value: ether(1) }).unwrap();
+    let err = world.call(BOB, SH_USD, IShUSD::transferFromCall { from: ALICE, to: BOB, value: ether(1) }).unwrap_err();
+    assert_eq!(err, frozen);
+    assert_eq!(world.mint(ALICE, ether(1)).unwrap_err(), frozen);
+    assert_eq!(world.call(ALICE, MANAGER, IManager::burnCall { amount: ether(1) }).unwrap_err(), frozen);
+
+    world.call(DEPLOYER, SH_USD, IShUSD::unfreezeCall { account: ALICE }).unwrap();
+    world.call(ALICE, SH_USD, IShUSD::transferCall { to: BOB, value: ether(1) }).unwrap();
+    assert_eq!(world.balance(SH_USD, BOB), ether(11));
+
+    let events: Vec<_> = world.logs(SH_USD).into_iter().map(|(topics, _)| topics[0]).filter(|topic| *topic == Frozen::SIGNATURE_HASH || *topic == Unfrozen::SIGNATURE_HASH).collect();
+    assert_eq!(events, [Frozen::SIGNATURE_HASH, Unfrozen::SIGNATURE_HASH]);
+}
+
+#[test]
+fn only_a_governing_timelock_seizes() {
+    let world = frozen_alice();
+    // Holding the governance role isn't enough
+    let err = world.call(DEPLOYER, SH_USD, IShUSD::seizeCall { from: ALICE, to: CAROL }).unwrap_err();
+    assert_eq!(err, OnlyTimelock {}.abi_encode());
+    let err = world.call(DEPLOYER, SH_USD, IShUSD::wipeFrozenCall { account: ALICE }).unwrap_err();
+    assert_eq!(err, OnlyTimelock {}.abi_encode());
+
+    // Nor is an account without the timelock's delay the timelock
+    for timelock in [BOB, MANAGER] {
+        let err = world.call(DEPLOYER, SH_USD, IShUSD::setTimelockCall { timelock }).unwrap_err();
+        assert_eq!(err, InvalidTimelock { timelock }.abi_encode());
+    }
+    timelock::deploy(&world);
+    let err = world.call(BOB, SH_USD, IShUSD::setTimelockCall { timelock: TIMELOCK }).unwrap_err();
+    assert_eq!(err, unauthorized(BOB, ADMIN_ROLE));
+    world.call(DEPLOYER, SH_USD, IShUSD::setTimelockCall { timelock: TIMELOCK }).unwrap();
+    assert_eq!(world.view(SH_USD, IShUSD::timelockCall {})._0, TIMELOCK);
+
+    // and the timelock needs the governance role too
+    let id = timelock::queue(&world, SH_USD, IShUSD::seizeCall { from: ALICE, to: CAROL });
+    world.advance(crate::contracts::timelock::MIN_DELAY);
+    let err = timelock::execute(&world, id).unwrap_err();
+    assert_eq!(err, unauthorized(TIMELOCK, GOVERNANCE_ROLE));
+    assert_eq!(world.balance(SH_USD, ALICE), ether(1000));
+}
+
+#[test]
+fn timelock_seizes_and_wipes_frozen_balances() {
+    let world = frozen_alice();
+    govern_by_timelock(&world);
+    world.deposit(BOB, ether(1)).unwrap();
+    world.mint(BOB, ether(500)).unwrap();
+    let seize = timelock::queue(&world, SH_USD, IShUSD::seizeCall { from: ALICE, to: CAROL });
+    let not_frozen = timelock::queue(&world, SH_USD, IShUSD::wipeFrozenCall { account: BOB });
+    world.call(DEPLOYER, SH_USD, IShUSD::freezeCall { account: CAROL }).unwrap();
+    let wipe = timelock::queue(&world, SH_USD, IShUSD::wipeFrozenCall { account: CAROL });
+    world.advance(crate::contracts::timelock::MIN_DELAY);
+    let execute = |id| timelock::execute(&world, id);
+
+    assert_eq!(execute(not_frozen).unwrap_err(), NotFrozen { account: BOB }.abi_encode());
+    // Seizing moves the balance even though the receiver is frozen
+    execute(seize).unwrap();
+    assert_eq!(world.balance(SH_USD, ALICE), U256::ZERO);
+    assert_eq!(world.balance(SH_USD, CAROL), ether(1000));
+    execute(wipe).unwrap();
+    assert_eq!(world.balance(SH_USD, CAROL), U256::ZERO);
+    assert_eq!(world.view(SH_USD, IShUSD::totalSupplyCall {})._0, ether(500));
+
+    let logs = world.logs(SH_USD);
+    let (_, data) = logs.iter().find(|(topics, _)| topics[0] == Seized::SIGNATURE_HASH).unwrap();
+    assert_eq!(U256::from_be_slice(data), ether(1000));
+    assert!(logs.iter().any(|(topics, _)| topics[0] == Wiped::SIGNATURE_HASH));
+}
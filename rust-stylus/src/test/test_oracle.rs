@@ -1,28 +1,247 @@
+//! Chainlink style mock oracle for local testing.
+//!
+//! Until `init` is called it behaves like the old fixed price mock, returning
+//! `PRICE` (halved after `rekt`) with 8 decimals. After that the owner can set
+//! the price, decimals, round id and `updatedAt`, script a path of prices that
+//! `advance` steps through, and switch on failure modes.
 use alloc::vec;
 use alloc::vec::Vec;
-use stylus_sdk::storage::StorageBool;
-use stylus_sdk::{alloy_primitives::I256, prelude::*};
+use alloy_primitives::{aliases::U80, Address, U64, U8};
+use alloy_sol_types::sol;
+use stylus_sdk::storage::{StorageAddress, StorageBool, StorageI256, StorageU64, StorageU8, StorageVec};
+use stylus_sdk::{alloy_primitives::{I256, U256}, prelude::*};
 
 const PRICE: I256 = I256::from_limbs([175_765_550_000, 0, 0, 0]);
+const DECIMALS: u8 = 8;
+
+/// How far back `updatedAt` is put in stale mode
+const STALE_AGE: u64 = 7 * 24 * 60 * 60;
+
+/// Failure modes, see `set_failure_mode`
+pub const HEALTHY: u8 = 0;
+pub const REVERT: u8 = 1;
+pub const ZERO: u8 = 2;
+pub const NEGATIVE: u8 = 3;
+pub const STALE: u8 = 4;
+
+sol! {
+    error AlreadyInitialized();
+    error OnlyOwner();
+    error OracleReverted();
+    error UnknownFailureMode();
+    error PricePathExhausted();
+}
+
+#[derive(SolidityError)]
+pub enum TestOracleErrors {
+    AlreadyInitialized(AlreadyInitialized),
+    OnlyOwner(OnlyOwner),
+    OracleReverted(OracleReverted),
+    UnknownFailureMode(UnknownFailureMode),
+    PricePathExhausted(PricePathExhausted),
+}
 
 #[cfg_attr(feature = "test-oracle", stylus_sdk::prelude::entrypoint)]
 #[storage]
 pub struct TestOracle {
-    is_rekt: StorageBool
+    is_rekt: StorageBool,
+    is_initialized: StorageBool,
+    owner: StorageAddress,
+    price: StorageI256,
+    decimals: StorageU8,
+    round_id: StorageU64,
+    updated_at: StorageU64,
+    failure_mode: StorageU8,
+    price_path: StorageVec<StorageI256>,
+    path_index: StorageU64,
+    /// Whether `price` was ever set, the round id can be set back to zero
+    has_price: StorageBool,
 }
 
-#[cfg_attr(feature = "test-oracle", stylus_sdk::prelude::public)]
-#[cfg(feature = "test-oracle")]
+#[cfg_attr(any(feature = "test-oracle", test), stylus_sdk::prelude::public)]
+#[cfg(any(feature = "test-oracle", test))]
 impl TestOracle {
-    pub fn latest_answer(&mut self) -> Result<I256, Vec<u8>> { 
-        if self.is_rekt.get() {
-            Ok(PRICE / I256::from_limbs([2, 0, 0, 0]))
-        } else {
-            Ok(PRICE)
+    /// Makes the caller owner, starting from the fixed `PRICE` at round 1
+    pub fn init(&mut self) -> Result<(), Vec<u8>> {
+        if self.is_initialized.get() {
+            return Err(TestOracleErrors::AlreadyInitialized(AlreadyInitialized {}).into());
+        }
+        self.is_initialized.set(true);
+        self.owner.set(self.vm().msg_sender());
+        self.decimals.set(U8::from(DECIMALS));
+        let price = self.current_price();
+        self.update(price);
+        Ok(())
+    }
+
+    pub fn latest_answer(&mut self) -> Result<I256, Vec<u8>> {
+        Ok(self.round_data()?.1)
+    }
+
+    /// Returns round id, answer, startedAt, updatedAt and answeredInRound
+    pub fn latest_round_data(&self) -> Result<(U80, I256, U256, U256, U80), Vec<u8>> {
+        self.round_data()
+    }
+
+    pub fn decimals(&self) -> u8 {
+        if self.is_initialized.get() { self.decimals.get().to() } else { DECIMALS }
+    }
+
+    /// Sets a new price in a new round updated now
+    pub fn set_price(&mut self, price: I256) -> Result<(), Vec<u8>> {
+        self.only_owner()?;
+        self.update(price);
+        Ok(())
+    }
+
+    pub fn set_decimals(&mut self, decimals: u8) -> Result<(), Vec<u8>> {
+        self.only_owner()?;
+        self.decimals.set(U8::from(decimals));
+        Ok(())
+    }
+
+    pub fn set_round_id(&mut self, round_id: u64) -> Result<(), Vec<u8>> {
+        self.only_owner()?;
+        self.round_id.set(U64::from(round_id));
+        Ok(())
+    }
+
+    pub fn set_updated_at(&mut self, updated_at: u64) -> Result<(), Vec<u8>> {
+        self.only_owner()?;
+        self.updated_at.set(U64::from(updated_at));
+        Ok(())
+    }
+
+    /// One of healthy (0), revert, zero, negative or stale (4)
+    pub fn set_failure_mode(&mut self, mode: u8) -> Result<(), Vec<u8>> {
+        self.only_owner()?;
+        if mode > STALE {
+            return Err(TestOracleErrors::UnknownFailureMode(UnknownFailureMode {}).into());
+        }
+        self.failure_mode.set(U8::from(mode));
+        Ok(())
+    }
+
+    /// Replaces the scripted price path, `advance` then walks through it
+    pub fn set_price_path(&mut self, prices: Vec<I256>) -> Result<(), Vec<u8>> {
+        self.only_owner()?;
+        while self.price_path.pop().is_some() {}
+        for price in prices {
+            self.price_path.push(price);
         }
+        self.path_index.set(U64::ZERO);
+        Ok(())
     }
 
+    /// Moves to the next price of the path in a new round, returns it
+    pub fn advance(&mut self) -> Result<I256, Vec<u8>> {
+        self.only_owner()?;
+        let index: u64 = self.path_index.get().to();
+        let price = self.price_path.get(index as usize)
+            .ok_or(TestOracleErrors::PricePathExhausted(PricePathExhausted {}))?;
+        self.path_index.set(U64::from(index + 1));
+        self.update(price);
+        Ok(price)
+    }
+
+    /// Halves the price
     pub fn rekt(&mut self) {
-        self.is_rekt.set(true)
+        if self.is_initialized.get() {
+            let price = self.price.get() / I256::from_limbs([2, 0, 0, 0]);
+            self.update(price);
+        } else {
+            self.is_rekt.set(true)
+        }
+    }
+
+    pub fn owner(&self) -> Address {
+        self.owner.get()
+    }
+}
+
+#[cfg(any(feature = "test-oracle", test))]
+impl TestOracle {
+    fn only_owner(&self) -> Result<(), Vec<u8>> {
+        if self.vm().msg_sender() != self.owner.get() {
+            return Err(TestOracleErrors::OnlyOwner(OnlyOwner {}).into());
+        }
+        Ok(())
+    }
+
+    fn current_price(&self) -> I256 {
+        if self.has_price.get() {
+            self.price.get()
+        } else if self.is_rekt.get() {
+            PRICE / I256::from_limbs([2, 0, 0, 0])
+        } else {
+            PRICE
+        }
+    }
+
+    fn update(&mut self, price: I256) {
+        self.price.set(price);
+        self.has_price.set(true);
+        self.round_id.set(self.round_id.get() + U64::from(1));
+        self.updated_at.set(U64::from(self.vm().block_timestamp()));
+    }
+
+    fn round_data(&self) -> Result<(U80, I256, U256, U256, U80), Vec<u8>> {
+        let now = self.vm().block_timestamp();
+        let price = self.current_price();
+        let round_id = U80::from(self.round_id.get().to::<u64>());
+        let mut updated_at = if self.is_initialized.get() { self.updated_at.get().to() } else { now };
+        let answer = match self.failure_mode.get().to::<u8>() {
+            REVERT => return Err(TestOracleErrors::OracleReverted(OracleReverted {}).into()),
+            ZERO => I256::ZERO,
+            NEGATIVE => -price.abs(),
+            STALE => {
+                updated_at = now.saturating_sub(STALE_AGE);
+                price
+            }
+            _ => price,
+        };
+        Ok((round_id, answer, U256::from(updated_at), U256::from(updated_at), round_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stylus_sdk::testing::TestVM;
+
+    #[test]
+    fn failure_modes() {
+        let vm = TestVM::new();
+        let mut oracle = TestOracle::from(&vm);
+        assert_eq!(oracle.latest_answer().ok(), Some(PRICE));
+
+        vm.set_block_timestamp(STALE_AGE * 2);
+        assert!(oracle.init().is_ok());
+        assert!(oracle.set_price_path(vec![I256::from_limbs([1, 0, 0, 0]), I256::from_limbs([2, 0, 0, 0])]).is_ok());
+        assert!(oracle.advance().is_ok());
+        assert!(oracle.advance().is_ok());
+        assert!(oracle.advance().is_err());
+        let (round_id, answer, _, updated_at, _) = oracle.latest_round_data().ok().unwrap();
+        assert_eq!((round_id, answer, updated_at), (U80::from(3), I256::from_limbs([2, 0, 0, 0]), U256::from(STALE_AGE * 2)));
+
+        oracle.set_failure_mode(NEGATIVE).ok().unwrap();
+        assert!(oracle.latest_answer().ok().unwrap().is_negative());
+        oracle.set_failure_mode(ZERO).ok().unwrap();
+        assert!(oracle.latest_answer().ok().unwrap().is_zero());
+        oracle.set_failure_mode(STALE).ok().unwrap();
+        assert_eq!(oracle.latest_round_data().ok().unwrap().3, U256::from(STALE_AGE));
+        oracle.set_failure_mode(REVERT).ok().unwrap();
+        assert!(oracle.latest_answer().is_err());
+    }
+
+    #[test]
+    fn price_survives_a_zero_round_id() {
+        let vm = TestVM::new();
+        let mut oracle = TestOracle::from(&vm);
+        oracle.init().ok().unwrap();
+        oracle.set_price(I256::from_limbs([1, 0, 0, 0])).ok().unwrap();
+        oracle.set_round_id(0).ok().unwrap();
+        let (round_id, answer, ..) = oracle.latest_round_data().ok().unwrap();
+        assert_eq!((round_id, answer), (U80::ZERO, I256::from_limbs([1, 0, 0, 0])));
     }
 }
@@ -0,0 +1,101 @@
+//! Uniswap V3 style mock pool for local testing.
+//!
+//! Only keeps what `observe` needs: every `setTick` writes an observation of
+//! the tick cumulative so far and the tick from then on. Unlike a real pool it
+//! never forgets an observation.
+use alloc::vec;
+use alloc::vec::Vec;
+use alloy_primitives::aliases::{I32, I56, I64, U160};
+use alloy_primitives::U64;
+use alloy_sol_types::{sol, SolCall};
+use stylus_sdk::storage::{StorageI32, StorageI64, StorageU64, StorageVec};
+use stylus_sdk::prelude::*;
+use crate::contracts::calls::observeCall;
+
+sol! {
+    error NotInitialized();
+    error ObservationTooOld();
+}
+
+#[derive(SolidityError)]
+pub enum TestPoolErrors {
+    NotInitialized(NotInitialized),
+    ObservationTooOld(ObservationTooOld),
+}
+
+#[cfg_attr(feature = "test-pool", stylus_sdk::prelude::entrypoint)]
+#[storage]
+pub struct TestPool {
+    times: StorageVec<StorageU64>,
+    tick_cumulatives: StorageVec<StorageI64>,
+    ticks: StorageVec<StorageI32>,
+}
+
+#[cfg_attr(any(feature = "test-pool", test), stylus_sdk::prelude::public)]
+#[cfg(any(feature = "test-pool", test))]
+impl TestPool {
+    /// Moves the pool to `tick` from now on
+    pub fn set_tick(&mut self, tick: i32) {
+        let now = self.vm().block_timestamp();
+        let count = self.times.len();
+        if count > 0 && self.time(count - 1) == now {
+            self.ticks.setter(count - 1).unwrap().set(I32::unchecked_from(tick));
+            return;
+        }
+        let tick_cumulative = if count == 0 { 0 } else { self.cumulative_at(now).unwrap_or_default() };
+        self.times.push(U64::from(now));
+        self.tick_cumulatives.push(I64::unchecked_from(tick_cumulative));
+        self.ticks.push(I32::unchecked_from(tick));
+    }
+
+    pub fn tick(&self) -> Result<i32, Vec<u8>> {
+        let count = self.times.len();
+        if count == 0 {
+            return Err(TestPoolErrors::NotInitialized(NotInitialized {}).into());
+        }
+        Ok(self.tick_of(count - 1))
+    }
+
+    /// `observe(uint32[])`, the tick cumulatives `secondsAgos` back from now.
+    /// Seconds per liquidity aren't tracked and come back as zero. Served here
+    /// as `#[public]` would wrap the two arrays in a tuple a real pool doesn't.
+    #[fallback]
+    pub fn fallback(&mut self, calldata: &[u8]) -> stylus_sdk::ArbResult {
+        let call = observeCall::abi_decode(calldata, true).map_err(|_| Vec::<u8>::new())?;
+        let now = self.vm().block_timestamp();
+        let mut tick_cumulatives = Vec::with_capacity(call.secondsAgos.len());
+        for seconds_ago in &call.secondsAgos {
+            let target = now.checked_sub(u64::from(*seconds_ago))
+                .ok_or(TestPoolErrors::ObservationTooOld(ObservationTooOld {}))?;
+            tick_cumulatives.push(I56::unchecked_from(self.cumulative_at(target)?));
+        }
+        let seconds_per_liquidity = vec![U160::ZERO; tick_cumulatives.len()];
+        Ok(observeCall::abi_encode_returns(&(tick_cumulatives, seconds_per_liquidity)))
+    }
+}
+
+#[cfg(any(feature = "test-pool", test))]
+impl TestPool {
+    /// The tick cumulative at `target`, from the last observation before it
+    fn cumulative_at(&self, target: u64) -> Result<i64, Vec<u8>> {
+        if self.times.is_empty() {
+            return Err(TestPoolErrors::NotInitialized(NotInitialized {}).into());
+        }
+        for index in (0..self.times.len()).rev() {
+            let time = self.time(index);
+            if time <= target {
+                let tick_cumulative = self.tick_cumulatives.get(index).unwrap().as_i64();
+                return Ok(tick_cumulative + i64::from(self.tick_of(index)) * (target - time) as i64);
+            }
+        }
+        Err(TestPoolErrors::ObservationTooOld(ObservationTooOld {}).into())
+    }
+
+    fn time(&self, index: usize) -> u64 {
+        self.times.get(index).unwrap().to()
+    }
+
+    fn tick_of(&self, index: usize) -> i32 {
+        self.ticks.get(index).unwrap().as_i32()
+    }
+}
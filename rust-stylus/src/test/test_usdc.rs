@@ -0,0 +1,60 @@
+//! A 6 decimals stand-in for USDC, for the PSM.
+use alloc::vec;
+extern crate alloc;
+use alloc::vec::Vec;
+use crate::token::erc20;
+use alloy_sol_types::sol;
+use alloy_primitives::Address;
+use stylus_sdk::{alloy_primitives::U256, prelude::*};
+
+pub struct UsdcParams;
+
+impl erc20::Erc20Params for UsdcParams {
+    const NAME: &'static str = "USD Coin";
+    const SYMBOL: &'static str = "USDC";
+    const DECIMALS: u8 = 6;
+}
+
+sol_storage! {
+    #[cfg_attr(any(feature = "test-usdc"), stylus_sdk::prelude::entrypoint)]
+    pub struct TestUsdc {
+        #[borrow]
+        erc20::Erc20<UsdcParams> erc20;
+        address minter;
+    }
+}
+
+sol! {
+    error OnlyMinterCanCall();
+}
+
+#[derive(SolidityError)]
+pub enum TestUsdcErrors {
+    OnlyMinterCanCall(OnlyMinterCanCall),
+}
+
+#[cfg_attr(any(feature = "test-usdc", test), stylus_sdk::prelude::public, inherit(erc20::Erc20::<UsdcParams>))]
+impl TestUsdc {
+    pub fn init(&mut self, minter: Address) {
+        self.minter.set(minter);
+    }
+
+    pub fn mint(&mut self, to: Address, amount: U256) -> Result<(), TestUsdcErrors> {
+        if self.vm().msg_sender() == self.minter.get() {
+            let _ = self.erc20.mint(to, amount);
+            Ok(())
+        } else {
+            Err(TestUsdcErrors::OnlyMinterCanCall(OnlyMinterCanCall {}))
+        }
+    }
+
+    pub fn burn(&mut self, from: Address, amount: U256) -> Result<(), TestUsdcErrors> {
+        if self.vm().msg_sender() == self.minter.get() {
+            let _ = self.erc20.burn(from, amount);
+            Ok(())
+        } else {
+            Err(TestUsdcErrors::OnlyMinterCanCall(OnlyMinterCanCall {}))
+        }
+    }
+}
+
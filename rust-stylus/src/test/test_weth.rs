@@ -32,7 +32,7 @@ pub enum TestWethErrors {
     OnlyManagerCanCall(OnlyManagerCanCall),
 }
 
-#[cfg_attr(feature = "test-weth", stylus_sdk::prelude::public, inherit(erc20::Erc20::<MicroParams>))]
+#[cfg_attr(any(feature = "test-weth", test), stylus_sdk::prelude::public, inherit(erc20::Erc20::<MicroParams>))]
 impl TestWeth {
     pub fn init(&mut self, manager_address: Address) {
         self.manager.set(manager_address);
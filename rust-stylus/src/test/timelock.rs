@@ -0,0 +1,135 @@
+//! The `Timelock` owning the `Manager` of a [`World`].
+use alloy_primitives::{address, Address, U256};
+use alloy_sol_types::{sol, SolCall, SolError};
+use crate::contracts::manager::OnlyGovernance;
+use crate::contracts::timelock::{
+    AlreadyInitialized, DelayTooShort, Expired, NotQueued, NotReady, OnlyCanceller, OnlyExecutor, OnlyProposer,
+    Timelock, GRACE_PERIOD, MIN_DELAY,
+};
+use super::e2e::{IManager, World, ALICE, BOB, CAROL, DEPLOYER, GOVERNANCE, MANAGER};
+
+// Only `#[entrypoint]` marks the timelock as top level storage
+#[cfg(not(feature = "timelock"))]
+unsafe impl stylus_sdk::stylus_core::storage::TopLevelStorage for Timelock {}
+
+sol! {
+    interface ITimelock {
+        function init(uint64 min_delay, address proposer, address executor, address guardian) external;
+        function queue(address target, bytes data, uint64 eta) external returns (uint256);
+        function execute(uint256 id) external returns (bytes);
+        function cancel(uint256 id) external;
+        function setMinDelay(uint64 min_delay) external;
+        function minDelay() external view returns (uint64);
+        // Stylus 0.8 returns tuples holding dynamic types as a single tuple
+        function operation(uint256 id) external view returns ((address, bytes, uint64, uint8));
+    }
+}
+
+pub(super) const TIMELOCK: Address = address!("000000000000000000000000000000000000a00c");
+/// Queues and executes, CAROL is the guardian
+pub(super) const PROPOSER: Address = ALICE;
+pub(super) const EXECUTOR: Address = BOB;
+
+/// Deploys the timelock with the shortest delay and hands it the manager
+pub(super) fn deploy(world: &World) {
+    world.chain().deploy::<Timelock>(TIMELOCK);
+    world.call(DEPLOYER, TIMELOCK, ITimelock::initCall { min_delay: MIN_DELAY, proposer: PROPOSER, executor: EXECUTOR, guardian: CAROL }).unwrap();
+    world.call(GOVERNANCE, MANAGER, IManager::setGovernanceCall { new_governance: TIMELOCK }).unwrap();
+}
+
+fn now(world: &World) -> u64 {
+    world.chain().block().1
+}
+
+/// Queues `call` on `target` for the earliest eta, returns its id
+pub(super) fn queue<C: SolCall>(world: &World, target: Address, call: C) -> U256 {
+    let eta = now(world) + MIN_DELAY;
+    let id = world.call(PROPOSER, TIMELOCK, ITimelock::queueCall { target, data: call.abi_encode().into(), eta }).unwrap();
+    ITimelock::queueCall::abi_decode_returns(&id, true).unwrap()._0
+}
+
+pub(super) fn execute(world: &World, id: U256) -> Result<Vec<u8>, Vec<u8>> {
+    world.call(EXECUTOR, TIMELOCK, ITimelock::executeCall { id })
+}
+
+fn status(world: &World, id: U256) -> u8 {
+    world.view(TIMELOCK, ITimelock::operationCall { id })._0.3
+}
+
+#[test]
+fn init_enforces_the_delay_floor() {
+    let world = World::new();
+    deploy(&world);
+    let init = ITimelock::initCall { min_delay: MIN_DELAY, proposer: ALICE, executor: ALICE, guardian: ALICE };
+    assert_eq!(world.call(DEPLOYER, TIMELOCK, init).unwrap_err(), AlreadyInitialized {}.abi_encode());
+    assert_eq!(world.view(TIMELOCK, ITimelock::minDelayCall {})._0, MIN_DELAY);
+
+    let other = address!("000000000000000000000000000000000000a00d");
+    world.chain().deploy::<Timelock>(other);
+    for min_delay in [0, MIN_DELAY - 1] {
+        let init = ITimelock::initCall { min_delay, proposer: ALICE, executor: ALICE, guardian: ALICE };
+        assert_eq!(world.call(DEPLOYER, other, init).unwrap_err(), DelayTooShort {}.abi_encode());
+    }
+}
+
+#[test]
+fn queued_calls_run_once_ready() {
+    let world = World::new();
+    deploy(&world);
+    let call = IManager::setGuardianCall { guardian: CAROL };
+    assert_eq!(world.call(GOVERNANCE, MANAGER, call.clone()).unwrap_err(), OnlyGovernance {}.abi_encode());
+
+    let early = ITimelock::queueCall { target: MANAGER, data: call.abi_encode().into(), eta: now(&world) + MIN_DELAY - 1 };
+    assert_eq!(world.call(PROPOSER, TIMELOCK, early).unwrap_err(), DelayTooShort {}.abi_encode());
+    let queue_call = ITimelock::queueCall { target: MANAGER, data: call.abi_encode().into(), eta: now(&world) + MIN_DELAY };
+    assert_eq!(world.call(EXECUTOR, TIMELOCK, queue_call).unwrap_err(), OnlyProposer {}.abi_encode());
+    let id = queue(&world, MANAGER, call);
+    assert_eq!(status(&world, id), 1);
+
+    assert_eq!(execute(&world, id).unwrap_err(), NotReady {}.abi_encode());
+    world.advance(MIN_DELAY - 1);
+    assert_eq!(execute(&world, id).unwrap_err(), NotReady {}.abi_encode());
+    world.advance(1);
+    assert_eq!(world.call(PROPOSER, TIMELOCK, ITimelock::executeCall { id }).unwrap_err(), OnlyExecutor {}.abi_encode());
+    execute(&world, id).unwrap();
+    assert_eq!(world.view(MANAGER, IManager::guardianCall {})._0, CAROL);
+    assert_eq!(status(&world, id), 2);
+    assert_eq!(execute(&world, id).unwrap_err(), NotQueued {}.abi_encode());
+}
+
+#[test]
+fn cancelled_and_expired_calls_never_run() {
+    let world = World::new();
+    deploy(&world);
+    let cancelled = queue(&world, MANAGER, IManager::setGuardianCall { guardian: BOB });
+    assert_eq!(world.call(EXECUTOR, TIMELOCK, ITimelock::cancelCall { id: cancelled }).unwrap_err(), OnlyCanceller {}.abi_encode());
+    world.call(CAROL, TIMELOCK, ITimelock::cancelCall { id: cancelled }).unwrap();
+    assert_eq!(status(&world, cancelled), 3);
+
+    let expired = queue(&world, MANAGER, IManager::setGuardianCall { guardian: BOB });
+    world.advance(MIN_DELAY);
+    assert_eq!(execute(&world, cancelled).unwrap_err(), NotQueued {}.abi_encode());
+    world.advance(GRACE_PERIOD + 1);
+    assert_eq!(execute(&world, expired).unwrap_err(), Expired {}.abi_encode());
+    assert_eq!(status(&world, expired), 1);
+    assert_eq!(world.view(MANAGER, IManager::guardianCall {})._0, Address::ZERO);
+}
+
+#[test]
+fn min_delay_changes_go_through_the_queue() {
+    let world = World::new();
+    deploy(&world);
+    // Not part of the public ABI, even the timelock itself can't call it
+    assert!(world.call(TIMELOCK, TIMELOCK, ITimelock::setMinDelayCall { min_delay: 2 * MIN_DELAY }).is_err());
+
+    let too_short = queue(&world, TIMELOCK, ITimelock::setMinDelayCall { min_delay: MIN_DELAY - 1 });
+    let longer = queue(&world, TIMELOCK, ITimelock::setMinDelayCall { min_delay: 2 * MIN_DELAY });
+    world.advance(MIN_DELAY);
+    assert_eq!(execute(&world, too_short).unwrap_err(), DelayTooShort {}.abi_encode());
+    execute(&world, longer).unwrap();
+    assert_eq!(world.view(TIMELOCK, ITimelock::minDelayCall {})._0, 2 * MIN_DELAY);
+
+    let data = IManager::setGuardianCall { guardian: CAROL }.abi_encode().into();
+    let early = ITimelock::queueCall { target: MANAGER, data, eta: now(&world) + MIN_DELAY };
+    assert_eq!(world.call(PROPOSER, TIMELOCK, early).unwrap_err(), DelayTooShort {}.abi_encode());
+}
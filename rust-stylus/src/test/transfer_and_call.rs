@@ -0,0 +1,106 @@
+//! ERC-1363 `transferAndCall` and `approveAndCall` of the real `ShUSD`, and the
+//! `Manager` taking repayments through them, on a [`World`].
+use alloc::vec;
+use alloc::vec::Vec;
+use alloy_primitives::{address, Address, U256};
+use alloy_sol_types::{SolError, SolValue};
+use stylus_sdk::host::VM;
+use stylus_sdk::ArbResult;
+use crate::client::manager::IManager;
+use crate::client::sh_usd::IShUSD;
+use crate::contracts::manager::OnlyShUSD;
+use crate::token::erc20::{ERC1363InvalidReceiver, ERC1363InvalidSpender};
+use super::e2e::{ether, World, ALICE, BOB, CAROL, MANAGER, SH_USD};
+
+/// Answers both hooks with their own selector, as a receiver or spender should
+const ACCEPTING: Address = address!("000000000000000000000000000000000000a010");
+/// Answers both hooks with a zero word
+const REJECTING: Address = address!("000000000000000000000000000000000000a011");
+
+fn accepting(input: Vec<u8>, _: VM) -> ArbResult {
+    let mut magic = vec![0; 32];
+    magic[..4].copy_from_slice(&input[..4]);
+    Ok(magic)
+}
+
+fn rejecting(_: Vec<u8>, _: VM) -> ArbResult {
+    Ok(vec![0; 32])
+}
+
+/// ALICE holds 1000 shUSD against 10 WETH
+fn world() -> World {
+    let world = World::new();
+    world.chain().deploy_with(ACCEPTING, accepting);
+    world.chain().deploy_with(REJECTING, rejecting);
+    world.deposit(ALICE, ether(10)).unwrap();
+    world.mint(ALICE, ether(1000)).unwrap();
+    world
+}
+
+fn debt(world: &World, user: Address) -> U256 {
+    world.view(MANAGER, IManager::positionCall { user })._1
+}
+
+#[test]
+fn transfer_and_call_needs_the_receivers_magic_value() {
+    let world = world();
+    let transfer = |to| world.call(ALICE, SH_USD, IShUSD::transferAndCallCall { to, value: ether(100), data: Default::default() });
+    transfer(ACCEPTING).unwrap();
+    assert_eq!(world.balance(SH_USD, ACCEPTING), ether(100));
+
+    // A wrong answer and an account without code both revert the transfer
+    for to in [REJECTING, CAROL] {
+        assert_eq!(transfer(to).unwrap_err(), ERC1363InvalidReceiver { receiver: to }.abi_encode());
+        assert_eq!(world.balance(SH_USD, to), U256::ZERO);
+    }
+    assert_eq!(world.balance(SH_USD, ALICE), ether(900));
+
+    world.call(ALICE, SH_USD, IShUSD::approveCall { spender: BOB, value: ether(200) }).unwrap();
+    let transfer_from = |to| world.call(BOB, SH_USD, IShUSD::transferFromAndCallCall { from: ALICE, to, value: ether(100), data: Default::default() });
+    assert_eq!(transfer_from(REJECTING).unwrap_err(), ERC1363InvalidReceiver { receiver: REJECTING }.abi_encode());
+    transfer_from(ACCEPTING).unwrap();
+    assert_eq!(world.balance(SH_USD, ACCEPTING), ether(200));
+    assert_eq!(world.view(SH_USD, IShUSD::allowanceCall { owner: ALICE, spender: BOB })._0, ether(100));
+}
+
+#[test]
+fn approve_and_call_needs_the_spenders_magic_value() {
+    let world = world();
+    let approve = |spender| world.call(ALICE, SH_USD, IShUSD::approveAndCallCall { spender, value: ether(100), data: Default::default() });
+    let allowance = |spender| world.view(SH_USD, IShUSD::allowanceCall { owner: ALICE, spender })._0;
+    approve(ACCEPTING).unwrap();
+    assert_eq!(allowance(ACCEPTING), ether(100));
+    for spender in [REJECTING, CAROL] {
+        assert_eq!(approve(spender).unwrap_err(), ERC1363InvalidSpender { spender }.abi_encode());
+        assert_eq!(allowance(spender), U256::ZERO);
+    }
+}
+
+#[test]
+fn manager_takes_repayments_burnt_later() {
+    let world = world();
+    world.deposit(BOB, ether(1)).unwrap();
+    world.mint(BOB, ether(500)).unwrap();
+    let repay = |value, data: Vec<u8>| world.call(ALICE, SH_USD, IShUSD::transferAndCallCall { to: MANAGER, value, data: data.into() });
+
+    // Empty data repays the sender, an encoded address that user
+    repay(ether(400), Vec::new()).unwrap();
+    repay(ether(100), BOB.abi_encode()).unwrap();
+    assert_eq!((debt(&world, ALICE), debt(&world, BOB)), (ether(600), ether(400)));
+    assert_eq!(world.view(MANAGER, IManager::repaidCall {})._0, ether(500));
+    assert_eq!(world.balance(SH_USD, MANAGER), ether(500));
+
+    // Anything else, or more than the debt, is refused and the shUSD stays put
+    assert_eq!(repay(ether(1), vec![1; 20]).unwrap_err(), ERC1363InvalidReceiver { receiver: MANAGER }.abi_encode());
+    assert_eq!(repay(ether(401), BOB.abi_encode()).unwrap_err(), ERC1363InvalidReceiver { receiver: MANAGER }.abi_encode());
+    assert_eq!(world.balance(SH_USD, ALICE), ether(500));
+    // Only shUSD itself can report a repayment
+    let hook = IManager::onTransferReceivedCall { operator: ALICE, from: ALICE, value: ether(600), data: Default::default() };
+    assert_eq!(world.call(ALICE, MANAGER, hook).unwrap_err(), OnlyShUSD {}.abi_encode());
+
+    let supply = world.view(SH_USD, IShUSD::totalSupplyCall {})._0;
+    world.call(CAROL, MANAGER, IManager::burnRepaidCall {}).unwrap();
+    assert_eq!(world.balance(SH_USD, MANAGER), U256::ZERO);
+    assert_eq!(world.view(SH_USD, IShUSD::totalSupplyCall {})._0, supply - ether(500));
+    assert_eq!(world.view(MANAGER, IManager::repaidCall {})._0, U256::ZERO);
+}
@@ -0,0 +1,102 @@
+//! `TwapOracle` averaging a `TestPool` between `TestWeth` and `TestUsdc`.
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use alloy_primitives::{address, Address, I256};
+use alloy_sol_types::{sol, SolCall, SolError};
+use stylus_sdk::stylus_core::storage::TopLevelStorage;
+use crate::contracts::calls::latestAnswerCall;
+use crate::contracts::twap_oracle::{InvalidWindow, OnlyGovernance, TwapOracle};
+use crate::test::harness::Chain;
+use crate::test::test_pool::{ObservationTooOld, TestPool};
+use crate::test::test_usdc::TestUsdc;
+use crate::test::test_weth::TestWeth;
+use super::e2e::DEPLOYER;
+
+#[cfg(not(feature = "twap-oracle"))]
+unsafe impl TopLevelStorage for TwapOracle {}
+#[cfg(not(feature = "test-pool"))]
+unsafe impl TopLevelStorage for TestPool {}
+
+sol! {
+    interface ITwapOracle {
+        function init(address pool, address base, address quote, uint32 window, address governance) external;
+        function meanTick() external view returns (int32);
+        function setWindow(uint32 window) external;
+        function decimals() external view returns (uint8);
+    }
+
+    interface ITestPool {
+        function setTick(int32 tick) external;
+    }
+}
+
+const POOL: Address = address!("000000000000000000000000000000000000a101");
+const WETH_USD: Address = address!("000000000000000000000000000000000000a102");
+const USD_WETH: Address = address!("000000000000000000000000000000000000a103");
+// WETH sorts first, the pool prices it in USDC
+const WETH: Address = address!("000000000000000000000000000000000000a104");
+const USDC: Address = address!("000000000000000000000000000000000000a105");
+const GOVERNANCE: Address = address!("000000000000000000000000000000000000b001");
+
+/// Ticks of ~$2000 and ~$2200 per WETH, with 18 and 6 decimals
+const TICK_2000: i32 = -200312;
+const TICK_2200: i32 = -199359;
+
+fn call<C: SolCall>(chain: &Rc<Chain>, from: Address, to: Address, call: C) -> Result<Vec<u8>, Vec<u8>> {
+    chain.call(from, to, &call.abi_encode())
+}
+
+fn answer(chain: &Rc<Chain>, oracle: Address) -> Result<I256, Vec<u8>> {
+    let data = call(chain, Address::ZERO, oracle, latestAnswerCall {})?;
+    Ok(latestAnswerCall::abi_decode_returns(&data, true).unwrap()._0)
+}
+
+/// `answer` is within a billionth of `expected`, 8 decimals
+fn assert_close(answer: I256, expected: f64) {
+    let answer: f64 = answer.to_string().parse().unwrap();
+    assert!((answer / (expected * 1e8) - 1.0).abs() < 1e-9, "{answer} is not {expected}");
+}
+
+/// Dollars per WETH at `tick`
+fn price(tick: i32) -> f64 {
+    1.0001f64.powi(tick) * 1e12
+}
+
+#[test]
+fn averages_the_pool_tick_over_the_window() {
+    let chain = Chain::new();
+    chain.set_block(1, 1_700_000_000);
+    chain.deploy::<TestPool>(POOL);
+    chain.deploy::<TwapOracle>(WETH_USD);
+    chain.deploy::<TwapOracle>(USD_WETH);
+    chain.deploy::<TestWeth>(WETH);
+    chain.deploy::<TestUsdc>(USDC);
+    call(&chain, DEPLOYER, WETH_USD, ITwapOracle::initCall { pool: POOL, base: WETH, quote: USDC, window: 3600, governance: GOVERNANCE }).unwrap();
+    call(&chain, DEPLOYER, USD_WETH, ITwapOracle::initCall { pool: POOL, base: USDC, quote: WETH, window: 3600, governance: GOVERNANCE }).unwrap();
+
+    // Half an hour at each price
+    call(&chain, GOVERNANCE, POOL, ITestPool::setTickCall { tick: TICK_2000 }).unwrap();
+    // The pool doesn't go back a whole window yet
+    assert_eq!(answer(&chain, WETH_USD).unwrap_err(), ObservationTooOld {}.abi_encode());
+    chain.set_block(2, 1_700_001_800);
+    call(&chain, GOVERNANCE, POOL, ITestPool::setTickCall { tick: TICK_2200 }).unwrap();
+    chain.set_block(3, 1_700_003_600);
+
+    // The mean of -200312 and -199359 rounds down
+    let mean_tick = call(&chain, Address::ZERO, WETH_USD, ITwapOracle::meanTickCall {}).unwrap();
+    assert_eq!(ITwapOracle::meanTickCall::abi_decode_returns(&mean_tick, true).unwrap()._0, -199836);
+    assert_close(answer(&chain, WETH_USD).unwrap(), price(-199836));
+    // 0.00047679676 WETH per dollar, 8 decimals leave little of it
+    assert_eq!(answer(&chain, USD_WETH).unwrap(), I256::try_from(47679).unwrap());
+    let decimals = call(&chain, Address::ZERO, WETH_USD, ITwapOracle::decimalsCall {}).unwrap();
+    assert_eq!(ITwapOracle::decimalsCall::abi_decode_returns(&decimals, true).unwrap()._0, 8);
+
+    // A shorter window only sees the last price
+    call(&chain, GOVERNANCE, WETH_USD, ITwapOracle::setWindowCall { window: 600 }).unwrap();
+    assert_close(answer(&chain, WETH_USD).unwrap(), price(TICK_2200));
+
+    let err = call(&chain, GOVERNANCE, WETH_USD, ITwapOracle::setWindowCall { window: 0 }).unwrap_err();
+    assert_eq!(err, InvalidWindow {}.abi_encode());
+    let err = call(&chain, WETH, WETH_USD, ITwapOracle::setWindowCall { window: 60 }).unwrap_err();
+    assert_eq!(err, OnlyGovernance {}.abi_encode());
+}
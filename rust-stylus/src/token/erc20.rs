@@ -4,15 +4,22 @@
 //! and is intended to be inherited by other contract types.
 //!
 //! You can configure the behavior of [`Erc20`] via the [`Erc20Params`] trait,
-//! which allows specifying the name, symbol, and decimals of the token.
+//! which allows specifying the name, symbol, and decimals of the token,
+//! and optionally hooks that run before and after every balance change.
+//!
+//! ERC-1363 `transferAndCall`, `transferFromAndCall` and `approveAndCall` notify
+//! the recipient afterwards. Stylus contracts are not reentrant by default, so a
+//! receiver can't call back into the token from within the hook.
 //!
 //! Note that this code is unaudited and not fit for production use.
 
 // Imported packages
-use alloy_primitives::{Address, U256};
-use alloy_sol_types::sol;
+use alloy_primitives::{keccak256, uint, Address, B256, U256};
+use alloy_sol_types::{sol, SolCall};
 use core::marker::PhantomData;
-use stylus_sdk::prelude::*;
+use crate::context;
+use stylus_sdk::{abi::Bytes, prelude::*};
+use stylus_sdk::stylus_core::calls::context::Call;
 use alloc::{vec::Vec, string::String};
 use alloc::vec;
 
@@ -25,8 +32,31 @@ pub trait Erc20Params {
 
     /// Immutable token decimals
     const DECIMALS: u8;
+
+    /// Upper bound on the total supply enforced by `mint`, `None` only stops U256 overflows
+    const MAX_SUPPLY: Option<U256> = None;
+
+    /// Runs before every transfer, mint (`from` is zero) and burn (`to` is zero)
+    fn before_transfer(_token: &Erc20<Self>, _from: Address, _to: Address, _value: U256) -> Result<(), Erc20Error>
+    where
+        Self: Sized,
+    {
+        Ok(())
+    }
+
+    /// Runs after every balance change like `before_transfer`, forced ones included
+    fn after_update(_token: &mut Erc20<Self>, _from: Address, _to: Address, _value: U256) -> Result<(), Erc20Error>
+    where
+        Self: Sized,
+    {
+        Ok(())
+    }
 }
 
+/// keccak256("microstable.erc20.frozen") - 1, the frozen flags live outside the
+/// sequential layout so tokens embedding [`Erc20`] keep their slots
+const FROZEN_SLOT: U256 = uint!(0x2c2cbc651481f7c725df0c35162a88a781a1dd7a1f4e720829f6573ab23d7a8c_U256);
+
 sol_storage! {
     /// Erc20 implements all ERC-20 methods.
     pub struct Erc20<T> {
@@ -48,6 +78,21 @@ sol! {
 
     error InsufficientBalance(address from, uint256 have, uint256 want);
     error InsufficientAllowance(address owner, address spender, uint256 have, uint256 want);
+    error AccountFrozen(address account);
+    error BalanceOverflow(address account);
+    error SupplyOverflow();
+    error SupplyUnderflow();
+    error MaxSupplyExceeded(uint256 max_supply, uint256 new_supply);
+    error ERC1363InvalidReceiver(address receiver);
+    error ERC1363InvalidSpender(address spender);
+
+    interface IERC1363Receiver {
+        function onTransferReceived(address operator, address from, uint256 value, bytes calldata data) external returns (bytes4);
+    }
+
+    interface IERC1363Spender {
+        function onApprovalReceived(address owner, uint256 value, bytes calldata data) external returns (bytes4);
+    }
 }
 
 /// Represents the ways methods may fail.
@@ -55,6 +100,13 @@ sol! {
 pub enum Erc20Error {
     InsufficientBalance(InsufficientBalance),
     InsufficientAllowance(InsufficientAllowance),
+    AccountFrozen(AccountFrozen),
+    BalanceOverflow(BalanceOverflow),
+    SupplyOverflow(SupplyOverflow),
+    SupplyUnderflow(SupplyUnderflow),
+    MaxSupplyExceeded(MaxSupplyExceeded),
+    ERC1363InvalidReceiver(ERC1363InvalidReceiver),
+    ERC1363InvalidSpender(ERC1363InvalidSpender),
 }
 
 // These methods aren't exposed to other contracts
@@ -64,6 +116,12 @@ impl<T: Erc20Params> Erc20<T> {
     /// Movement of funds between 2 accounts
     /// (invoked by the public transfer() and transfer_from() functions )
     pub fn _transfer(&mut self, from: Address, to: Address, value: U256) -> Result<(), Erc20Error> {
+        T::before_transfer(self, from, to, value)?;
+        self.force_transfer(from, to, value)
+    }
+
+    /// Same as [`Erc20::_transfer`] without running [`Erc20Params::before_transfer`]
+    pub fn force_transfer(&mut self, from: Address, to: Address, value: U256) -> Result<(), Erc20Error> {
         // Decreasing sender balance
         let mut sender_balance = self.balances.setter(from);
         let old_sender_balance = sender_balance.get();
@@ -78,23 +136,36 @@ impl<T: Erc20Params> Erc20<T> {
 
         // Increasing receiver balance
         let mut to_balance = self.balances.setter(to);
-        let new_to_balance = to_balance.get() + value;
+        let new_to_balance = to_balance.get().checked_add(value)
+            .ok_or(Erc20Error::BalanceOverflow(BalanceOverflow { account: to }))?;
         to_balance.set(new_to_balance);
 
         // Emitting the transfer event
         log(self.vm(), Transfer { from, to, value });
-        Ok(())
+        T::after_update(self, from, to, value)
     }
 
     /// Mints `value` tokens to `address`
     pub fn mint(&mut self, address: Address, value: U256) -> Result<(), Erc20Error> {
+        T::before_transfer(self, Address::ZERO, address, value)?;
+
+        // Checking the new total supply against the cap
+        let new_supply = self.total_supply.get().checked_add(value)
+            .ok_or(Erc20Error::SupplyOverflow(SupplyOverflow {}))?;
+        if let Some(max_supply) = T::MAX_SUPPLY {
+            if new_supply > max_supply {
+                return Err(Erc20Error::MaxSupplyExceeded(MaxSupplyExceeded { max_supply, new_supply }));
+            }
+        }
+
         // Increasing balance
         let mut balance = self.balances.setter(address);
-        let new_balance = balance.get() + value;
+        let new_balance = balance.get().checked_add(value)
+            .ok_or(Erc20Error::BalanceOverflow(BalanceOverflow { account: address }))?;
         balance.set(new_balance);
 
         // Increasing total supply
-        self.total_supply.set(self.total_supply.get() + value);
+        self.total_supply.set(new_supply);
 
         // Emitting the transfer event
         log(self.vm(), Transfer {
@@ -103,14 +174,18 @@ impl<T: Erc20Params> Erc20<T> {
             value,
         });
 
-        Ok(())
+        T::after_update(self, Address::ZERO, address, value)
     }
 
     /// Burns `value` tokens from `address`
     pub fn burn(&mut self, address: Address, value: U256) -> Result<(), Erc20Error> {
-        // Decreasing balance
-        let mut balance = self.balances.setter(address);
-        let old_balance = balance.get();
+        T::before_transfer(self, address, Address::ZERO, value)?;
+        self.force_burn(address, value)
+    }
+
+    /// Same as [`Erc20::burn`] without running [`Erc20Params::before_transfer`]
+    pub fn force_burn(&mut self, address: Address, value: U256) -> Result<(), Erc20Error> {
+        let old_balance = self.balances.get(address);
         if old_balance < value {
             return Err(Erc20Error::InsufficientBalance(InsufficientBalance {
                 from: address,
@@ -118,10 +193,14 @@ impl<T: Erc20Params> Erc20<T> {
                 want: value,
             }));
         }
-        balance.set(old_balance - value);
+        let new_supply = self.total_supply.get().checked_sub(value)
+            .ok_or(Erc20Error::SupplyUnderflow(SupplyUnderflow {}))?;
+
+        // Decreasing balance
+        self.balances.insert(address, old_balance - value);
 
         // Decreasing the total supply
-        self.total_supply.set(self.total_supply.get() - value);
+        self.total_supply.set(new_supply);
 
         // Emitting the transfer event
         log(self.vm(),Transfer {
@@ -130,7 +209,67 @@ impl<T: Erc20Params> Erc20<T> {
             value,
         });
 
-        Ok(())
+        T::after_update(self, address, Address::ZERO, value)
+    }
+
+    /// Calls `onTransferReceived` on `to`, which must be a contract returning its selector
+    pub fn check_on_transfer_received(&mut self, from: Address, to: Address, value: U256, data: Bytes) -> Result<(), Erc20Error> {
+        let invalid = || Erc20Error::ERC1363InvalidReceiver(ERC1363InvalidReceiver { receiver: to });
+        if self.vm().code_size(to) == 0 {
+            return Err(invalid());
+        }
+        let operator = context::msg_sender(self.vm());
+        let calldata = IERC1363Receiver::onTransferReceivedCall { operator, from, value, data: data.0.into() }.abi_encode();
+        let returned = self.vm().call(&Call::new(), to, &calldata).map_err(|_| invalid())?;
+        match IERC1363Receiver::onTransferReceivedCall::abi_decode_returns(&returned, true) {
+            Ok(magic) if magic._0 == IERC1363Receiver::onTransferReceivedCall::SELECTOR => Ok(()),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Calls `onApprovalReceived` on `spender`, which must be a contract returning its selector
+    pub fn check_on_approval_received(&mut self, spender: Address, value: U256, data: Bytes) -> Result<(), Erc20Error> {
+        let invalid = || Erc20Error::ERC1363InvalidSpender(ERC1363InvalidSpender { spender });
+        if self.vm().code_size(spender) == 0 {
+            return Err(invalid());
+        }
+        let owner = context::msg_sender(self.vm());
+        let calldata = IERC1363Spender::onApprovalReceivedCall { owner, value, data: data.0.into() }.abi_encode();
+        let returned = self.vm().call(&Call::new(), spender, &calldata).map_err(|_| invalid())?;
+        match IERC1363Spender::onApprovalReceivedCall::abi_decode_returns(&returned, true) {
+            Ok(magic) if magic._0 == IERC1363Spender::onApprovalReceivedCall::SELECTOR => Ok(()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl<T> Erc20<T> {
+    /// Whether `account` has been frozen
+    pub fn is_frozen(&self, account: Address) -> bool {
+        !self.vm().storage_load_bytes32(Self::frozen_slot(account)).is_zero()
+    }
+
+    /// Freezes or unfreezes `account`, it is up to [`Erc20Params::before_transfer`] to enforce it
+    pub fn set_frozen(&mut self, account: Address, frozen: bool) {
+        unsafe { self.vm().storage_cache_bytes32(Self::frozen_slot(account), B256::with_last_byte(frozen as u8)) };
+        self.vm().flush_cache(false);
+    }
+
+    /// Storage of type `S` at `slot` of the token's contract, for hooks keeping
+    /// state outside the sequential layout.
+    ///
+    /// # Safety
+    ///
+    /// Nothing else of the contract may use the slots `S` takes from `slot` on.
+    pub unsafe fn storage_at<S: StorageType>(&self, slot: U256) -> S {
+        S::new(slot, 0, self.__stylus_host.clone())
+    }
+
+    fn frozen_slot(account: Address) -> U256 {
+        let mut preimage = [0u8; 64];
+        preimage[12..32].copy_from_slice(account.as_slice());
+        preimage[32..].copy_from_slice(&FROZEN_SLOT.to_be_bytes::<32>());
+        U256::from_be_bytes(keccak256(preimage).0)
     }
 }
 
@@ -165,7 +304,7 @@ impl<T: Erc20Params> Erc20<T> {
 
     /// Transfers `value` tokens from msg::sender() to `to`
     pub fn transfer(&mut self, to: Address, value: U256) -> Result<bool, Erc20Error> {
-        self._transfer(self.vm().msg_sender(), to, value)?;
+        self._transfer(context::msg_sender(self.vm()), to, value)?;
         Ok(true)
     }
 
@@ -178,14 +317,14 @@ impl<T: Erc20Params> Erc20<T> {
         value: U256,
     ) -> Result<bool, Erc20Error> {
         // Check msg::sender() allowance
-        let sender = self.vm().msg_sender();
+        let sender = context::msg_sender(self.vm());
         let mut sender_allowances = self.allowances.setter(from);
         let mut allowance = sender_allowances.setter(sender);
         let old_allowance = allowance.get();
         if old_allowance < value {
             return Err(Erc20Error::InsufficientAllowance(InsufficientAllowance {
                 owner: from,
-                spender: self.vm().msg_sender(),
+                spender: context::msg_sender(self.vm()),
                 have: old_allowance,
                 want: value,
             }));
@@ -202,9 +341,9 @@ impl<T: Erc20Params> Erc20<T> {
 
     /// Approves the spenditure of `value` tokens of msg::sender() to `spender`
     pub fn approve(&mut self, spender: Address, value: U256) -> bool {
-        self.allowances.setter(self.vm().msg_sender()).insert(spender, value);
+        self.allowances.setter(context::msg_sender(self.vm())).insert(spender, value);
         log(self.vm(), Approval {
-            owner: self.vm().msg_sender(),
+            owner: context::msg_sender(self.vm()),
             spender,
             value,
         });
@@ -215,4 +354,85 @@ impl<T: Erc20Params> Erc20<T> {
     pub fn allowance(&self, owner: Address, spender: Address) -> U256 {
         self.allowances.getter(owner).get(spender)
     }
-}
\ No newline at end of file
+
+    /// Transfers `value` tokens to `to` and calls `onTransferReceived` on it
+    pub fn transfer_and_call(&mut self, to: Address, value: U256, data: Bytes) -> Result<bool, Erc20Error> {
+        self.transfer(to, value)?;
+        self.check_on_transfer_received(context::msg_sender(self.vm()), to, value, data)?;
+        Ok(true)
+    }
+
+    /// Transfers `value` tokens from `from` to `to` and calls `onTransferReceived` on `to`
+    pub fn transfer_from_and_call(&mut self, from: Address, to: Address, value: U256, data: Bytes) -> Result<bool, Erc20Error> {
+        self.transfer_from(from, to, value)?;
+        self.check_on_transfer_received(from, to, value, data)?;
+        Ok(true)
+    }
+
+    /// Approves `spender` for `value` tokens and calls `onApprovalReceived` on it
+    pub fn approve_and_call(&mut self, spender: Address, value: U256, data: Bytes) -> Result<bool, Erc20Error> {
+        self.approve(spender, value);
+        self.check_on_approval_received(spender, value, data)?;
+        Ok(true)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, keccak256};
+    use stylus_sdk::testing::TestVM;
+
+    struct TestParams;
+
+    impl Erc20Params for TestParams {
+        const NAME: &'static str = "Test";
+        const SYMBOL: &'static str = "TST";
+        const DECIMALS: u8 = 18;
+        const MAX_SUPPLY: Option<U256> = Some(U256::from_limbs([1000, 0, 0, 0]));
+    }
+
+    fn map_slot(key: &[u8], slot: U256) -> U256 {
+        let mut preimage = [0u8; 64];
+        preimage[32 - key.len()..32].copy_from_slice(key);
+        preimage[32..].copy_from_slice(&slot.to_be_bytes::<32>());
+        U256::from_be_bytes(keccak256(preimage).0)
+    }
+
+    /// `Erc20` is embedded at the start of every token, so its fields may only
+    /// ever be appended to.
+    #[test]
+    fn storage_layout_is_append_only() {
+        let vm = TestVM::new();
+        let mut token = Erc20::<TestParams>::from(&vm);
+        let owner = address!("00000000000000000000000000000000000000aa");
+        let spender = address!("00000000000000000000000000000000000000bb");
+
+        token.balances.insert(owner, U256::from(1));
+        token.allowances.setter(owner).insert(spender, U256::from(2));
+        token.total_supply.set(U256::from(3));
+
+        let word = |slot: U256| U256::from_be_bytes(vm.get_storage(slot).0);
+        assert_eq!(word(map_slot(owner.as_slice(), U256::from(0))), U256::from(1));
+        let allowances = map_slot(owner.as_slice(), U256::from(1));
+        assert_eq!(word(map_slot(spender.as_slice(), allowances)), U256::from(2));
+        assert_eq!(word(U256::from(2)), U256::from(3));
+    }
+
+    #[test]
+    fn mint_is_capped_and_checked() {
+        let vm = TestVM::new();
+        let mut token = Erc20::<TestParams>::from(&vm);
+        let owner = address!("00000000000000000000000000000000000000aa");
+
+        assert!(token.mint(owner, U256::from(1000)).is_ok());
+        assert!(matches!(token.mint(owner, U256::from(1)), Err(Erc20Error::MaxSupplyExceeded(_))));
+        assert!(matches!(token.burn(owner, U256::from(1001)), Err(Erc20Error::InsufficientBalance(_))));
+
+        // a corrupted supply can't wrap around either
+        token.total_supply.set(U256::ZERO);
+        assert!(matches!(token.burn(owner, U256::from(1)), Err(Erc20Error::SupplyUnderflow(_))));
+        token.total_supply.set(U256::MAX);
+        assert!(matches!(token.mint(owner, U256::from(1)), Err(Erc20Error::SupplyOverflow(_))));
+        assert_eq!(token.balance_of(owner), U256::from(1000));
+    }
+}
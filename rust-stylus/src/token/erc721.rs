@@ -0,0 +1,270 @@
+//! Implementation of the ERC-721 standard
+//!
+//! Like [`crate::token::erc20::Erc20`], [`Erc721`] is meant to be inherited by
+//! a contract that decides who may mint and burn, configured through
+//! [`Erc721Params`]. `safeTransferFrom` calls `onERC721Received` on contract
+//! recipients and reverts unless they return its selector.
+use alloy_primitives::{Address, FixedBytes, U256};
+use alloy_sol_types::{sol, SolCall};
+use core::marker::PhantomData;
+use stylus_sdk::{abi::Bytes, prelude::*};
+use stylus_sdk::stylus_core::calls::context::Call;
+use alloc::{vec::Vec, string::String};
+use alloc::vec;
+
+pub trait Erc721Params {
+    /// Immutable collection name
+    const NAME: &'static str;
+
+    /// Immutable collection symbol
+    const SYMBOL: &'static str;
+}
+
+sol_storage! {
+    /// Erc721 implements all ERC-721 methods.
+    pub struct Erc721<T> {
+        /// Maps token ids to their owner
+        mapping(uint256 => address) owners;
+        /// Maps owners to how many tokens they hold
+        mapping(address => uint256) balances;
+        /// Maps token ids to the one account approved to move them
+        mapping(uint256 => address) token_approvals;
+        /// Maps owners to the operators allowed to move all their tokens
+        mapping(address => mapping(address => bool)) operator_approvals;
+        /// Used to allow [`Erc721Params`]
+        PhantomData<T> phantom;
+    }
+}
+
+sol! {
+    event Transfer(address indexed from, address indexed to, uint256 indexed token_id);
+    event Approval(address indexed owner, address indexed approved, uint256 indexed token_id);
+    event ApprovalForAll(address indexed owner, address indexed operator, bool approved);
+
+    error ERC721InvalidOwner(address owner);
+    error ERC721NonexistentToken(uint256 token_id);
+    error ERC721IncorrectOwner(address sender, uint256 token_id, address owner);
+    error ERC721InsufficientApproval(address operator, uint256 token_id);
+    error ERC721InvalidReceiver(address receiver);
+    error ERC721InvalidApprover(address approver);
+    error ERC721InvalidSender(address sender);
+
+    interface IERC721Receiver {
+        function onERC721Received(address operator, address from, uint256 token_id, bytes calldata data) external returns (bytes4);
+    }
+}
+
+/// Represents the ways methods may fail.
+#[derive(SolidityError)]
+pub enum Erc721Error {
+    ERC721InvalidOwner(ERC721InvalidOwner),
+    ERC721NonexistentToken(ERC721NonexistentToken),
+    ERC721IncorrectOwner(ERC721IncorrectOwner),
+    ERC721InsufficientApproval(ERC721InsufficientApproval),
+    ERC721InvalidReceiver(ERC721InvalidReceiver),
+    ERC721InvalidApprover(ERC721InvalidApprover),
+    ERC721InvalidSender(ERC721InvalidSender),
+}
+
+/// `supportsInterface` ids of ERC-165 and ERC-721. `name` and `symbol` are there but
+/// without `tokenURI` the ERC-721 metadata id isn't claimed.
+const INTERFACE_IDS: [[u8; 4]; 2] = [[0x01, 0xff, 0xc9, 0xa7], [0x80, 0xac, 0x58, 0xcd]];
+
+impl<T: Erc721Params> Erc721<T> {
+    /// Owner of `token_id`, which must exist
+    pub fn require_owned(&self, token_id: U256) -> Result<Address, Erc721Error> {
+        let owner = self.owners.get(token_id);
+        if owner.is_zero() {
+            return Err(Erc721Error::ERC721NonexistentToken(ERC721NonexistentToken { token_id }));
+        }
+        Ok(owner)
+    }
+
+    /// Mints `token_id` to `to`, it must not exist yet
+    pub fn mint(&mut self, to: Address, token_id: U256) -> Result<(), Erc721Error> {
+        if to.is_zero() {
+            return Err(Erc721Error::ERC721InvalidReceiver(ERC721InvalidReceiver { receiver: to }));
+        }
+        if !self.owners.get(token_id).is_zero() {
+            return Err(Erc721Error::ERC721InvalidSender(ERC721InvalidSender { sender: Address::ZERO }));
+        }
+        self.owners.insert(token_id, to);
+        let balance = self.balances.get(to);
+        self.balances.insert(to, balance + U256::from(1));
+        log(self.vm(), Transfer { from: Address::ZERO, to, token_id });
+        Ok(())
+    }
+
+    /// Burns `token_id`, whoever owns it
+    pub fn burn(&mut self, token_id: U256) -> Result<(), Erc721Error> {
+        let owner = self.require_owned(token_id)?;
+        self.token_approvals.delete(token_id);
+        self.owners.delete(token_id);
+        let balance = self.balances.get(owner);
+        self.balances.insert(owner, balance - U256::from(1));
+        log(self.vm(), Transfer { from: owner, to: Address::ZERO, token_id });
+        Ok(())
+    }
+
+    /// Whether `spender` may move `owner`'s `token_id`
+    pub fn is_authorized(&self, owner: Address, spender: Address, token_id: U256) -> bool {
+        spender == owner
+            || self.operator_approvals.getter(owner).get(spender)
+            || self.token_approvals.get(token_id) == spender
+    }
+
+    /// Calls `onERC721Received` on `to` when it is a contract, which must return its selector
+    pub fn check_on_erc721_received(&mut self, from: Address, to: Address, token_id: U256, data: Bytes) -> Result<(), Erc721Error> {
+        if self.vm().code_size(to) == 0 {
+            return Ok(());
+        }
+        let invalid = || Erc721Error::ERC721InvalidReceiver(ERC721InvalidReceiver { receiver: to });
+        let operator = self.vm().msg_sender();
+        let calldata = IERC721Receiver::onERC721ReceivedCall { operator, from, token_id, data: data.0.into() }.abi_encode();
+        let returned = self.vm().call(&Call::new(), to, &calldata).map_err(|_| invalid())?;
+        match IERC721Receiver::onERC721ReceivedCall::abi_decode_returns(&returned, true) {
+            Ok(magic) if magic._0 == IERC721Receiver::onERC721ReceivedCall::SELECTOR => Ok(()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[public]
+impl<T: Erc721Params> Erc721<T> {
+    /// Immutable collection name
+    pub fn name() -> String {
+        T::NAME.into()
+    }
+
+    /// Immutable collection symbol
+    pub fn symbol() -> String {
+        T::SYMBOL.into()
+    }
+
+    pub fn balance_of(&self, owner: Address) -> Result<U256, Erc721Error> {
+        if owner.is_zero() {
+            return Err(Erc721Error::ERC721InvalidOwner(ERC721InvalidOwner { owner }));
+        }
+        Ok(self.balances.get(owner))
+    }
+
+    pub fn owner_of(&self, token_id: U256) -> Result<Address, Erc721Error> {
+        self.require_owned(token_id)
+    }
+
+    /// Lets `approved` move `token_id`, the sender must own it or be an operator of its owner
+    pub fn approve(&mut self, approved: Address, token_id: U256) -> Result<(), Erc721Error> {
+        let owner = self.require_owned(token_id)?;
+        let sender = self.vm().msg_sender();
+        if sender != owner && !self.operator_approvals.getter(owner).get(sender) {
+            return Err(Erc721Error::ERC721InvalidApprover(ERC721InvalidApprover { approver: sender }));
+        }
+        self.token_approvals.insert(token_id, approved);
+        log(self.vm(), Approval { owner, approved, token_id });
+        Ok(())
+    }
+
+    pub fn get_approved(&self, token_id: U256) -> Result<Address, Erc721Error> {
+        self.require_owned(token_id)?;
+        Ok(self.token_approvals.get(token_id))
+    }
+
+    pub fn set_approval_for_all(&mut self, operator: Address, approved: bool) {
+        let owner = self.vm().msg_sender();
+        self.operator_approvals.setter(owner).insert(operator, approved);
+        log(self.vm(), ApprovalForAll { owner, operator, approved });
+    }
+
+    pub fn is_approved_for_all(&self, owner: Address, operator: Address) -> bool {
+        self.operator_approvals.getter(owner).get(operator)
+    }
+
+    /// Moves `token_id` from `from` to `to`, the sender must own it or be approved
+    pub fn transfer_from(&mut self, from: Address, to: Address, token_id: U256) -> Result<(), Erc721Error> {
+        if to.is_zero() {
+            return Err(Erc721Error::ERC721InvalidReceiver(ERC721InvalidReceiver { receiver: to }));
+        }
+        let owner = self.require_owned(token_id)?;
+        if owner != from {
+            return Err(Erc721Error::ERC721IncorrectOwner(ERC721IncorrectOwner { sender: from, token_id, owner }));
+        }
+        let sender = self.vm().msg_sender();
+        if !self.is_authorized(owner, sender, token_id) {
+            return Err(Erc721Error::ERC721InsufficientApproval(ERC721InsufficientApproval { operator: sender, token_id }));
+        }
+        self.token_approvals.delete(token_id);
+        let from_balance = self.balances.get(from);
+        self.balances.insert(from, from_balance - U256::from(1));
+        let to_balance = self.balances.get(to);
+        self.balances.insert(to, to_balance + U256::from(1));
+        self.owners.insert(token_id, to);
+        log(self.vm(), Transfer { from, to, token_id });
+        Ok(())
+    }
+
+    /// Same as `transfer_from`, then checks a contract `to` accepts the token
+    #[selector(name = "safeTransferFrom")]
+    pub fn safe_transfer_from(&mut self, from: Address, to: Address, token_id: U256) -> Result<(), Erc721Error> {
+        self.safe_transfer_from_with_data(from, to, token_id, Bytes(Vec::new()))
+    }
+
+    #[selector(name = "safeTransferFrom")]
+    pub fn safe_transfer_from_with_data(&mut self, from: Address, to: Address, token_id: U256, data: Bytes) -> Result<(), Erc721Error> {
+        self.transfer_from(from, to, token_id)?;
+        self.check_on_erc721_received(from, to, token_id, data)
+    }
+
+    pub fn supports_interface(interface_id: FixedBytes<4>) -> bool {
+        INTERFACE_IDS.contains(&interface_id.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stylus_sdk::testing::TestVM;
+
+    struct TestParams;
+
+    impl Erc721Params for TestParams {
+        const NAME: &'static str = "Test";
+        const SYMBOL: &'static str = "TST";
+    }
+
+    #[test]
+    fn transfers_need_the_owner_or_an_approval() {
+        let vm = TestVM::new();
+        let mut token = Erc721::<TestParams>::from(&vm);
+        let [owner, spender, operator] = [0xaa, 0xbb, 0xcc].map(Address::with_last_byte);
+        let id = U256::from(7);
+
+        assert!(token.mint(owner, id).is_ok());
+        assert!(matches!(token.mint(spender, id), Err(Erc721Error::ERC721InvalidSender(_))));
+        assert_eq!(token.balance_of(owner).ok(), Some(U256::from(1)));
+
+        vm.set_sender(spender);
+        assert!(matches!(token.transfer_from(owner, spender, id), Err(Erc721Error::ERC721InsufficientApproval(_))));
+        assert!(matches!(token.approve(spender, id), Err(Erc721Error::ERC721InvalidApprover(_))));
+
+        // A token approval is used up by the transfer
+        vm.set_sender(owner);
+        assert!(token.approve(spender, id).is_ok());
+        vm.set_sender(spender);
+        assert!(matches!(token.transfer_from(spender, owner, id), Err(Erc721Error::ERC721IncorrectOwner(_))));
+        assert!(token.transfer_from(owner, spender, id).is_ok());
+        assert_eq!(token.owner_of(id).ok(), Some(spender));
+        assert_eq!(token.get_approved(id).ok(), Some(Address::ZERO));
+        assert_eq!((token.balance_of(owner).ok(), token.balance_of(spender).ok()), (Some(U256::ZERO), Some(U256::from(1))));
+
+        // Operators move every token of the owner
+        token.set_approval_for_all(operator, true);
+        vm.set_sender(operator);
+        assert!(token.safe_transfer_from(spender, owner, id).is_ok());
+        assert_eq!(token.owner_of(id).ok(), Some(owner));
+
+        assert!(token.burn(id).is_ok());
+        assert!(matches!(token.owner_of(id), Err(Erc721Error::ERC721NonexistentToken(_))));
+        assert!(matches!(token.balance_of(Address::ZERO), Err(Erc721Error::ERC721InvalidOwner(_))));
+        assert!(Erc721::<TestParams>::supports_interface(FixedBytes([0x80, 0xac, 0x58, 0xcd])));
+    }
+}
@@ -1 +1,3 @@
-pub mod erc20;
\ No newline at end of file
+pub mod erc20;
+pub mod erc721;
+pub mod votes;
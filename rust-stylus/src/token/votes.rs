@@ -0,0 +1,284 @@
+//! Checkpointed voting power, as in ERC20Votes
+//!
+//! [`Votes`] doesn't hold any balances itself, the token using it reports
+//! every balance change through [`Votes::transfer_voting_units`], usually from
+//! [`Erc20Params::after_update`](crate::token::erc20::Erc20Params::after_update)
+//! with the votes kept at [`VOTES_SLOT`]. Voting power
+//! follows delegation: tokens only count once their holder delegated them,
+//! possibly to themselves.
+//!
+//! Every change is recorded as a checkpoint keyed by block number, checkpoints
+//! are only ever pushed in increasing block order so past values can be looked
+//! up with a binary search.
+//!
+//! Note that this code is unaudited and not fit for production use.
+use alloc::vec;
+use alloc::vec::Vec;
+use alloy_primitives::{address, uint, Address, B256, U256, U64};
+use alloy_sol_types::{sol, Eip712Domain, SolStruct, SolValue};
+use stylus_sdk::prelude::*;
+use stylus_sdk::storage::{StorageAddress, StorageMap, StorageU256, StorageU64, StorageVec};
+use stylus_sdk::stylus_core::calls::context::Call;
+use crate::token::erc20::{BalanceOverflow, Erc20Error, InsufficientBalance, SupplyOverflow, SupplyUnderflow};
+
+/// keccak256("microstable.votes") - 1, where a token hooking [`Votes`] into its
+/// balance changes keeps them
+pub const VOTES_SLOT: U256 = uint!(0x5566848e684ba03b07c4d34d1aeb56615f845041d09e3ca709fb5b80cee39edb_U256);
+
+/// The ecrecover precompile
+const ECRECOVER: Address = address!("0000000000000000000000000000000000000001");
+
+sol! {
+    event DelegateChanged(address indexed delegator, address indexed from_delegate, address indexed to_delegate);
+    event DelegateVotesChanged(address indexed delegate, uint256 previous_votes, uint256 new_votes);
+
+    error ERC5805FutureLookup(uint256 timepoint, uint64 clock);
+    error VotesExpiredSignature(uint256 expiry);
+    error InvalidAccountNonce(address account, uint256 current_nonce);
+    error InvalidSignature();
+
+    struct Delegation {
+        address delegatee;
+        uint256 nonce;
+        uint256 expiry;
+    }
+}
+
+#[derive(SolidityError)]
+pub enum VotesError {
+    ERC5805FutureLookup(ERC5805FutureLookup),
+    VotesExpiredSignature(VotesExpiredSignature),
+    InvalidAccountNonce(InvalidAccountNonce),
+    InvalidSignature(InvalidSignature),
+}
+
+#[storage]
+pub struct Checkpoint {
+    from_block: StorageU64,
+    votes: StorageU256,
+}
+
+#[storage]
+pub struct Votes {
+    delegates: StorageMap<Address, StorageAddress>,
+    checkpoints: StorageMap<Address, StorageVec<Checkpoint>>,
+    total_checkpoints: StorageVec<Checkpoint>,
+    nonces: StorageMap<Address, StorageU256>,
+}
+
+impl Votes {
+    pub fn delegates(&self, account: Address) -> Address {
+        self.delegates.get(account)
+    }
+
+    pub fn nonces(&self, account: Address) -> U256 {
+        self.nonces.get(account)
+    }
+
+    /// Current voting power of `account`
+    pub fn get_votes(&self, account: Address) -> U256 {
+        latest(&self.checkpoints.getter(account))
+    }
+
+    /// Voting power of `account` at the end of `block`, which must be in the past
+    pub fn get_past_votes(&self, account: Address, block: U256) -> Result<U256, VotesError> {
+        let block = self.past_block(block)?;
+        Ok(upper_lookup(&self.checkpoints.getter(account), block))
+    }
+
+    /// Total supply at the end of `block`, which must be in the past
+    pub fn get_past_total_supply(&self, block: U256) -> Result<U256, VotesError> {
+        let block = self.past_block(block)?;
+        Ok(upper_lookup(&self.total_checkpoints, block))
+    }
+
+    pub fn num_checkpoints(&self, account: Address) -> u32 {
+        self.checkpoints.getter(account).len() as u32
+    }
+
+    /// Delegates the `balance` of `account` to `delegatee`
+    pub fn delegate(&mut self, account: Address, delegatee: Address, balance: U256) -> Result<(), Erc20Error> {
+        let previous = self.delegates.get(account);
+        self.delegates.insert(account, delegatee);
+        log(self.vm(), DelegateChanged { delegator: account, from_delegate: previous, to_delegate: delegatee });
+        self.move_delegate_votes(previous, delegatee, balance)
+    }
+
+    /// Checks an EIP-712 `Delegation` signature and returns the signer, whose nonce is used up
+    pub fn recover_delegation(&mut self, domain: &Eip712Domain, delegation: &Delegation, v: u8, r: B256, s: B256) -> Result<Address, VotesError> {
+        let Delegation { nonce, expiry, .. } = *delegation;
+        if U256::from(self.vm().block_timestamp()) > expiry {
+            return Err(VotesError::VotesExpiredSignature(VotesExpiredSignature { expiry }));
+        }
+        let digest = delegation.eip712_signing_hash(domain);
+        let input = (digest, U256::from(v), r, s).abi_encode();
+        let output = self.vm().static_call(&Call::new(), ECRECOVER, &input)
+            .map_err(|_| VotesError::InvalidSignature(InvalidSignature {}))?;
+        if output.len() != 32 {
+            return Err(VotesError::InvalidSignature(InvalidSignature {}));
+        }
+        let signer = Address::from_word(B256::from_slice(&output));
+        let current_nonce = self.nonces.get(signer);
+        if signer == Address::ZERO || current_nonce != nonce {
+            return Err(VotesError::InvalidAccountNonce(InvalidAccountNonce { account: signer, current_nonce }));
+        }
+        self.nonces.insert(signer, current_nonce + U256::from(1));
+        Ok(signer)
+    }
+
+    /// Moves voting power along with `amount` tokens, `from` is zero on mints and `to` on burns.
+    /// Votes are counted like balances and fail with the same errors.
+    pub fn transfer_voting_units(&mut self, from: Address, to: Address, amount: U256) -> Result<(), Erc20Error> {
+        if from == Address::ZERO {
+            let supply = latest(&self.total_checkpoints).checked_add(amount)
+                .ok_or(Erc20Error::SupplyOverflow(SupplyOverflow {}))?;
+            self.push_total(supply);
+        }
+        if to == Address::ZERO {
+            let supply = latest(&self.total_checkpoints).checked_sub(amount)
+                .ok_or(Erc20Error::SupplyUnderflow(SupplyUnderflow {}))?;
+            self.push_total(supply);
+        }
+        let (from_delegate, to_delegate) = (self.delegates.get(from), self.delegates.get(to));
+        self.move_delegate_votes(from_delegate, to_delegate, amount)
+    }
+
+    fn move_delegate_votes(&mut self, from: Address, to: Address, amount: U256) -> Result<(), Erc20Error> {
+        if from == to || amount.is_zero() {
+            return Ok(());
+        }
+        if from != Address::ZERO {
+            let previous_votes = latest(&self.checkpoints.getter(from));
+            let new_votes = previous_votes.checked_sub(amount)
+                .ok_or(Erc20Error::InsufficientBalance(InsufficientBalance { from, have: previous_votes, want: amount }))?;
+            self.push_account(from, new_votes);
+            log(self.vm(), DelegateVotesChanged { delegate: from, previous_votes, new_votes });
+        }
+        if to != Address::ZERO {
+            let previous_votes = latest(&self.checkpoints.getter(to));
+            let new_votes = previous_votes.checked_add(amount)
+                .ok_or(Erc20Error::BalanceOverflow(BalanceOverflow { account: to }))?;
+            self.push_account(to, new_votes);
+            log(self.vm(), DelegateVotesChanged { delegate: to, previous_votes, new_votes });
+        }
+        Ok(())
+    }
+
+    fn push_account(&mut self, account: Address, votes: U256) {
+        let block = self.vm().block_number();
+        push(&mut self.checkpoints.setter(account), block, votes);
+    }
+
+    fn push_total(&mut self, votes: U256) {
+        let block = self.vm().block_number();
+        push(&mut self.total_checkpoints, block, votes);
+    }
+
+    fn past_block(&self, block: U256) -> Result<u64, VotesError> {
+        let clock = self.vm().block_number();
+        if block >= U256::from(clock) {
+            return Err(VotesError::ERC5805FutureLookup(ERC5805FutureLookup { timepoint: block, clock }));
+        }
+        Ok(block.to())
+    }
+}
+
+/// Value of the last checkpoint, zero without any
+fn latest(checkpoints: &StorageVec<Checkpoint>) -> U256 {
+    match checkpoints.len() {
+        0 => U256::ZERO,
+        len => checkpoints.getter(len - 1).map(|c| c.votes.get()).unwrap_or_default(),
+    }
+}
+
+/// Overwrites the last checkpoint if it is from `block`, appends a new one otherwise
+fn push(checkpoints: &mut StorageVec<Checkpoint>, block: u64, votes: U256) {
+    let len = checkpoints.len();
+    if len > 0 {
+        if let Some(mut last) = checkpoints.setter(len - 1) {
+            if last.from_block.get() == U64::from(block) {
+                last.votes.set(votes);
+                return;
+            }
+        }
+    }
+    let mut checkpoint = checkpoints.grow();
+    checkpoint.from_block.set(U64::from(block));
+    checkpoint.votes.set(votes);
+}
+
+/// Value of the last checkpoint at or before `block`, in O(log n)
+fn upper_lookup(checkpoints: &StorageVec<Checkpoint>, block: u64) -> U256 {
+    let block_at = |i: usize| checkpoints.getter(i).map(|c| c.from_block.get().to::<u64>()).unwrap_or_default();
+    let (mut low, mut high) = (0, checkpoints.len());
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if block_at(mid) > block {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    match high {
+        0 => U256::ZERO,
+        pos => checkpoints.getter(pos - 1).map(|c| c.votes.get()).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stylus_sdk::testing::TestVM;
+
+    #[test]
+    fn past_votes_follow_checkpoints() {
+        let vm = TestVM::new();
+        let mut votes = Votes::from(&vm);
+        let alice = address!("00000000000000000000000000000000000000aa");
+        let bob = address!("00000000000000000000000000000000000000bb");
+
+        vm.set_block_number(10);
+        votes.delegate(alice, alice, U256::ZERO).ok().unwrap();
+        votes.transfer_voting_units(Address::ZERO, alice, U256::from(100)).ok().unwrap();
+        vm.set_block_number(20);
+        votes.delegate(bob, bob, U256::ZERO).ok().unwrap();
+        votes.transfer_voting_units(alice, bob, U256::from(40)).ok().unwrap();
+        // a second change in the same block overwrites the checkpoint
+        votes.transfer_voting_units(alice, bob, U256::from(10)).ok().unwrap();
+        vm.set_block_number(30);
+        votes.transfer_voting_units(bob, Address::ZERO, U256::from(50)).ok().unwrap();
+        vm.set_block_number(31);
+
+        assert_eq!(votes.num_checkpoints(alice), 2);
+        let past = |account, block: u64| votes.get_past_votes(account, U256::from(block)).ok().unwrap();
+        assert_eq!(past(alice, 9), U256::ZERO);
+        assert_eq!(past(alice, 10), U256::from(100));
+        assert_eq!(past(alice, 19), U256::from(100));
+        assert_eq!(past(alice, 20), U256::from(50));
+        assert_eq!(past(bob, 25), U256::from(50));
+        assert_eq!(past(bob, 30), U256::ZERO);
+        let supply = |block: u64| votes.get_past_total_supply(U256::from(block)).ok().unwrap();
+        assert_eq!(supply(29), U256::from(100));
+        assert_eq!(supply(30), U256::from(50));
+        assert!(votes.get_past_votes(alice, U256::from(31)).is_err());
+    }
+
+    #[test]
+    fn votes_never_underflow() {
+        let vm = TestVM::new();
+        let mut votes = Votes::from(&vm);
+        let alice = address!("00000000000000000000000000000000000000aa");
+        votes.delegate(alice, alice, U256::ZERO).ok().unwrap();
+        votes.transfer_voting_units(Address::ZERO, alice, U256::from(10)).ok().unwrap();
+        let err = votes.transfer_voting_units(alice, Address::ZERO, U256::from(11)).err().unwrap();
+        assert!(matches!(err, Erc20Error::SupplyUnderflow(_)));
+        let err = votes.transfer_voting_units(alice, address!("00000000000000000000000000000000000000bb"), U256::from(11)).err().unwrap();
+        assert!(matches!(err, Erc20Error::InsufficientBalance(InsufficientBalance { have, want, .. }) if have == U256::from(10) && want == U256::from(11)));
+    }
+
+    #[test]
+    fn votes_slot_is_the_hashed_name() {
+        let hash = U256::from_be_bytes(alloy_primitives::keccak256("microstable.votes").0);
+        assert_eq!(VOTES_SLOT, hash - U256::from(1));
+    }
+}
@@ -138,7 +138,7 @@ echo "✅ DONE"
 
 # Manager Setup
 echo "🏃 Setting up Manager contract..."
-cast send $MANAGER_ADDRESS "init(address,address,address)" $WETH_ADDRESS $TEST_ORACLE_ADDRESS $SH_USD_ADDRESS --rpc-url $RPC_URL --private-key $PRIVATE_KEY
+cast send $MANAGER_ADDRESS "initialize(address,address,address,address)" $WETH_ADDRESS $TEST_ORACLE_ADDRESS $SH_USD_ADDRESS $PUB_KEY --rpc-url $RPC_URL --private-key $PRIVATE_KEY
 echo "✅ DONE"
 
 # Approve weth for spending
@@ -256,6 +256,8 @@ BOB_WETH_BALANCE_BEFORE=$(cast call $WETH_ADDRESS "balanceOf(address)(uint256)"
 echo "Bobs balance before: $BOB_WETH_BALANCE_BEFORE"
 MY_SH_USD_BALANCE_BEFORE_LIQ=$(cast call $SH_USD_ADDRESS "balanceOf(address)(uint256)" $PUB_KEY --rpc-url $RPC_URL -- --to-dec)
 echo "My ShUSD balance before liquidation: $MY_SH_USD_BALANCE_BEFORE_LIQ"
+# The liquidator repays the debt out of their own shUSD
+cast send $SH_USD_ADDRESS "transfer(address,uint256)" $BOB_PUBKEY $MY_SH_USD_BALANCE_BEFORE_LIQ --rpc-url $RPC_URL --private-key $PRIVATE_KEY
 # Performing actual liquidation
 cast send $MANAGER_ADDRESS "liquidate(address)" $PUB_KEY --rpc-url $RPC_URL --private-key $BOB_PKEY
 BOB_WETH_BALANCE_AFTER=$(cast call $WETH_ADDRESS "balanceOf(address)(uint256)" $BOB_PUBKEY --rpc-url $RPC_URL -- --to-dec)